@@ -155,22 +155,47 @@ async function queryApp() {
     }
 
     const url = `${API_BASE}/submit`;
+    const headers = {
+        'Content-Type': 'application/json',
+    };
+    // 服务端没有对匿名开放投稿时, 需要在 localStorage 的 apiKey 里放一个带 submit 权限的 key
+    const apiKey = localStorage.getItem('apiKey');
+    if (apiKey) {
+        headers['x-api-key'] = apiKey;
+    }
     try {
         const response = await fetch(url, {
             method: 'POST',
-            headers: {
-                'Content-Type': 'application/json',
-            },
+            headers,
             body: JSON.stringify(submit_body)
         });
+        if (response.status === 401 || response.status === 403) {
+            alert("投稿需要带有 submit 权限的 API key");
+            resultArea.classList.add("hidden");
+            return;
+        }
+        if (response.status === 429) {
+            const retryAfter = response.headers.get('Retry-After') || '几';
+            alert(`投稿太频繁了, 请 ${retryAfter} 秒后再试`);
+            resultArea.classList.add("hidden");
+            return;
+        }
         if (!response.ok) {
             throw new Error("查询失败");
         }
         const data = await response.json();
-        if (data.success) {
-            renderResult(data.data);
+        if (!data.success) {
+            alert("查询失败，请检查输入");
+            resultArea.classList.add("hidden");
+            return;
+        }
+        // 投稿进入队列, 等任务执行完再展示结果
+        const job = await waitForSubmission(data.data.job.id, headers);
+        if (job.status === 'succeeded') {
+            renderResult(job.result);
             resultArea.classList.remove("hidden");
         } else {
+            console.error("投稿任务失败:", job.error);
             alert("查询失败，请检查输入");
             resultArea.classList.add("hidden");
         }
@@ -181,6 +206,28 @@ async function queryApp() {
     }
 }
 
+/**
+ * 轮询投稿任务直到执行完成
+ * @param {number} jobId - 投稿接口返回的任务 ID
+ * @param {Object} headers - 请求头 (带上投稿用的 API key)
+ * @returns {Promise<Object>} 状态为 succeeded 或 failed 的任务
+ */
+async function waitForSubmission(jobId, headers) {
+    const url = `${API_BASE}/submissions/${jobId}`;
+    for (let attempt = 0; attempt < 120; attempt++) {
+        const response = await fetch(url, { headers });
+        if (!response.ok) {
+            throw new Error("查询投稿任务失败");
+        }
+        const job = (await response.json()).data;
+        if (job.status === 'succeeded' || job.status === 'failed') {
+            return job;
+        }
+        await new Promise(resolve => setTimeout(resolve, 1000));
+    }
+    throw new Error("投稿任务超时");
+}
+
 /**
  * 渲染查询结果
  * @param {Object} data - API 返回数据
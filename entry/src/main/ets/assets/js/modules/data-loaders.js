@@ -129,6 +129,12 @@ var DashboardDataLoaders = (function () {
         return duration.secs * 1000 + Math.floor(duration.nanos / 1000000);
     }
 
+    const SYNC_PHASE_LABELS = {
+        apps: '同步应用',
+        substances: '同步专题',
+        discovery: '发现新应用'
+    };
+
     /**
      * 更新同步状态显示
      * @param {Object} syncStatus - 同步状态对象
@@ -146,6 +152,7 @@ var DashboardDataLoaders = (function () {
         let elapsedTimeMs = syncStatus.elapsed_time ? durationToMs(syncStatus.elapsed_time) : 0;
         let estimatedTotalTimeMs = syncStatus.estimated_total_time ? durationToMs(syncStatus.estimated_total_time) : 0;
         let nextSyncCountdownMs = syncStatus.next_sync_countdown ? durationToMs(syncStatus.next_sync_countdown) : 0;
+        let remainingTimeMs = syncStatus.estimated_remaining_time ? durationToMs(syncStatus.estimated_remaining_time) : 0;
 
         if (!syncStatus) {
             container.classList.add('hidden');
@@ -153,27 +160,39 @@ var DashboardDataLoaders = (function () {
             return;
         }
 
-        if (syncStatus.is_syncing_all) {
+        const phase = syncStatus.phase || 'idle';
+        if (syncStatus.is_syncing_all || phase !== 'idle') {
             container.classList.remove('hidden');
             nextSyncContainer.classList.add('hidden');
-            status.textContent = '同步中';
+            status.textContent = SYNC_PHASE_LABELS[phase] ? `同步中 (${SYNC_PHASE_LABELS[phase]})` : '同步中';
             status.className = 'font-medium text-blue-600';
 
             // 进度
             const [current, total] = syncStatus.progress;
             const percentage = total > 0 ? ((current / total) * 100).toFixed(1) : '0.0';
             progress.textContent = `${current}/${total} (${percentage}%)`;
+            if (syncStatus.batch && syncStatus.batch[1] > 0) {
+                progress.textContent += ` 批次 ${syncStatus.batch[0]}/${syncStatus.batch[1]}`;
+            }
 
             // 统计信息
             const statsText = `处理:${syncStatus.total_processed} 新增:${syncStatus.total_inserted} 跳过:${syncStatus.total_skipped} 失败:${syncStatus.total_failed}`;
             stats.textContent = statsText;
+            // 最近失败放在 title 里, 鼠标悬停查看
+            const failures = syncStatus.recent_failures || [];
+            stats.title = failures.slice(0, 10).map(f => `${f.target}: ${f.error}`).join('\n');
 
             // 时间信息
             if (elapsedTimeMs) {
                 const elapsed = formatDuration(elapsedTimeMs);
                 time.textContent = `已用时: ${elapsed}`;
 
-                if (estimatedTotalTimeMs) {
+                if (remainingTimeMs) {
+                    time.textContent += ` 预计剩余: ${formatDuration(remainingTimeMs)}`;
+                    if (syncStatus.throughput) {
+                        time.textContent += ` (${syncStatus.throughput.toFixed(1)} 个/秒)`;
+                    }
+                } else if (estimatedTotalTimeMs) {
                     const estimated = formatDuration(estimatedTotalTimeMs);
                     const remainingMs = estimatedTotalTimeMs - elapsedTimeMs;
                     const remaining = formatDuration(remainingMs);
@@ -43,6 +43,8 @@ CREATE TABLE app_info (
     listed_at               TIMESTAMPTZ NOT NULL, -- 应用上架时间
     release_countries       TEXT[] NOT NULL DEFAULT '{}', -- 应用发布的国家/地区列表
     main_device_codes       TEXT[] NOT NULL DEFAULT '{}', -- 应用支持的主要设备类型
+    name_pinyin             TEXT,                 -- 应用名全拼 (程序写入, 搜索用)
+    name_initials           TEXT,                 -- 应用名拼音首字母 (程序写入, 搜索用)
 
     created_at              TIMESTAMPTZ NOT NULL DEFAULT now() -- 创建时间 (爬取时间)
 );
@@ -92,6 +94,8 @@ CREATE TABLE app_full_info (
     listed_at               TIMESTAMPTZ NOT NULL, -- 应用上架时间
     release_countries       TEXT[] NOT NULL DEFAULT '{}', -- 应用发布的国家/地区列表
     main_device_codes       TEXT[] NOT NULL DEFAULT '{}', -- 应用支持的主要设备类型
+    name_pinyin             TEXT,                 -- 应用名全拼 (程序写入, 搜索用)
+    name_initials           TEXT,                 -- 应用名拼音首字母 (程序写入, 搜索用)
 
     -- latest metrics
     version                 TEXT,                             -- 来自 app_metrics 表的版本号
@@ -177,11 +181,20 @@ CREATE TABLE app_rating (
     created_at                  TIMESTAMPTZ NOT NULL DEFAULT now()          -- 创建时间
 );
 
+-- 按内容寻址的 JSON 存储 (见 020_dedup_json_history)
+-- 原始数据快照的每个顶层字段各存一份, 快照本身是 {字段: hash} 的清单
+CREATE TABLE json_blob (
+    hash            TEXT PRIMARY KEY,                          -- sha256(content::text) 的十六进制
+    content         JSONB NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
 CREATE TABLE app_data_history (
     id              BIGSERIAL PRIMARY KEY,                     -- 主键ID
     app_id          TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE, -- 对应 app_info 的 app_id
     pkg_name        TEXT NOT NULL REFERENCES app_info(pkg_name) ON DELETE CASCADE, -- 对应 app_info 的 pkg_name
-    raw_json_data   JSONB NOT NULL DEFAULT '{}'::JSONB,        -- 原始应用数据JSON
+    raw_json_data   JSONB,                                     -- 写入口, 触发器拆分进 json_blob 后置空
+    raw_json_hash   TEXT NOT NULL,                             -- 原始应用数据快照的清单 hash
     created_at      TIMESTAMPTZ NOT NULL DEFAULT now()         -- 数据创建或记录时间
 );
 
@@ -207,7 +220,8 @@ CREATE TABLE substance_info (
 CREATE TABLE substance_history (
     id                 BIGSERIAL PRIMARY KEY,
     substance_id       TEXT NOT NULL REFERENCES substance_info(substance_id) ON DELETE CASCADE,
-    raw_json_substance JSONB NOT NULL DEFAULT '{}'::JSONB,
+    raw_json_substance JSONB,                             -- 写入口, 触发器拆分进 json_blob 后置空
+    raw_json_hash      TEXT NOT NULL,                     -- 原始专题数据快照的清单 hash
     created_at         TIMESTAMPTZ NOT NULL DEFAULT now()
 );
 
@@ -216,3 +230,200 @@ CREATE TABLE substance_app_map (
     app_id         TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE,
     PRIMARY KEY (substance_id, app_id)
 );
+
+-- app_metrics 降采样后的历史数据 (见 017_add_metrics_rollup)
+CREATE TABLE app_metrics_rollup (
+    id                  BIGSERIAL PRIMARY KEY,                      -- 主键ID
+    app_id              TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE,  -- 对应 app_info 的 app_id
+    pkg_name            TEXT NOT NULL REFERENCES app_info(pkg_name) ON DELETE CASCADE, -- 对应 app_info 的 pkg_name
+    granularity         TEXT NOT NULL CHECK (granularity IN ('day', 'week')), -- 聚合粒度
+    bucket_start        DATE NOT NULL,                              -- 桶起始日期（Asia/Shanghai，周以周一开始）
+    sample_count        INTEGER NOT NULL,                           -- 桶内原始记录数
+    -- 以下为桶内最后一条记录的快照
+    version             TEXT NOT NULL,
+    version_code        BIGINT NOT NULL,
+    size_bytes          BIGINT NOT NULL,
+    sha256              TEXT NOT NULL,
+    info_score          NUMERIC(3,1) NOT NULL,
+    info_rate_count     BIGINT NOT NULL,
+    download_count      BIGINT NOT NULL,
+    price               NUMERIC(10,2) NOT NULL,
+    release_date        BIGINT NOT NULL,
+    target_sdk          INTEGER NOT NULL,
+    minsdk              INTEGER NOT NULL,
+    compile_sdk_version INTEGER NOT NULL,
+    min_hmos_api_level  INTEGER NOT NULL,
+    api_release_type    TEXT NOT NULL,
+    -- 桶内聚合
+    download_count_min  BIGINT NOT NULL,                            -- 桶内最小下载量
+    download_count_max  BIGINT NOT NULL,                            -- 桶内最大下载量
+    info_score_avg      NUMERIC(3,1) NOT NULL,                      -- 桶内平均评分
+    first_created_at    TIMESTAMPTZ NOT NULL,                       -- 桶内最早记录时间
+    last_created_at     TIMESTAMPTZ NOT NULL,                       -- 桶内最晚记录时间（快照对应的时间）
+    UNIQUE (app_id, granularity, bucket_start)
+);
+
+-- 文本字段只在变化时记录一条, 从 valid_from 开始生效
+CREATE TABLE app_metrics_text (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    app_id          TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE, -- 对应 app_info 的 app_id
+    new_features    TEXT NOT NULL,                                  -- 新功能描述
+    upgrade_msg     TEXT NOT NULL,                                  -- 升级提示
+    valid_from      TIMESTAMPTZ NOT NULL,                           -- 生效时间（对应原始记录的 created_at）
+    UNIQUE (app_id, valid_from)
+);
+
+-- app_rating 降采样后的历史数据 (见 027_add_rating_rollup)
+CREATE TABLE app_rating_rollup (
+    id                          BIGSERIAL PRIMARY KEY,                      -- 主键ID
+    app_id                      TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE,  -- 对应 app_info 的 app_id
+    pkg_name                    TEXT NOT NULL REFERENCES app_info(pkg_name) ON DELETE CASCADE, -- 对应 app_info 的 pkg_name
+    granularity                 TEXT NOT NULL CHECK (granularity IN ('day', 'week')), -- 聚合粒度
+    bucket_start                DATE NOT NULL,                              -- 桶起始日期（Asia/Shanghai，周以周一开始）
+    sample_count                INTEGER NOT NULL,                           -- 桶内原始记录数
+    -- 以下为桶内最后一条记录的快照
+    average_rating              NUMERIC(3,1) NOT NULL,
+    star_1_rating_count         INTEGER NOT NULL,
+    star_2_rating_count         INTEGER NOT NULL,
+    star_3_rating_count         INTEGER NOT NULL,
+    star_4_rating_count         INTEGER NOT NULL,
+    star_5_rating_count         INTEGER NOT NULL,
+    my_star_rating              INTEGER NOT NULL,
+    total_star_rating_count     INTEGER NOT NULL,
+    only_star_count             INTEGER NOT NULL,
+    full_average_rating         NUMERIC(3,1) NOT NULL,
+    source_type                 TEXT NOT NULL,
+    -- 桶内聚合
+    total_star_rating_count_min INTEGER NOT NULL,                           -- 桶内最小评分数量
+    total_star_rating_count_max INTEGER NOT NULL,                           -- 桶内最大评分数量
+    average_rating_avg          NUMERIC(3,1) NOT NULL,                      -- 桶内平均评分
+    first_created_at            TIMESTAMPTZ NOT NULL,                       -- 桶内最早记录时间
+    last_created_at             TIMESTAMPTZ NOT NULL,                       -- 桶内最晚记录时间（快照对应的时间）
+    UNIQUE (app_id, granularity, bucket_start)
+);
+
+-- 原始快照的变更记录 (见 018_add_changelog)
+CREATE TABLE app_changelog (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    app_id          TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE, -- 对应 app_info 的 app_id
+    history_id      BIGINT NOT NULL UNIQUE REFERENCES app_data_history(id) ON DELETE CASCADE, -- 新快照
+    prev_history_id BIGINT,                                         -- 上一个快照, 首个快照为 NULL
+    changes         JSONB NOT NULL DEFAULT '[]'::JSONB,             -- 差异列表 [{path, op, old, new}]
+    fields          TEXT[] NOT NULL DEFAULT '{}',                   -- 发生变化的顶层字段, 用于按字段过滤
+    change_count    INTEGER NOT NULL DEFAULT 0,                     -- 差异条数
+    created_at      TIMESTAMPTZ NOT NULL                            -- 新快照的时间
+);
+
+CREATE TABLE substance_changelog (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    substance_id    TEXT NOT NULL REFERENCES substance_info(substance_id) ON DELETE CASCADE, -- 对应 substance_info
+    history_id      BIGINT NOT NULL UNIQUE REFERENCES substance_history(id) ON DELETE CASCADE, -- 新快照
+    prev_history_id BIGINT,                                         -- 上一个快照, 首个快照为 NULL
+    changes         JSONB NOT NULL DEFAULT '[]'::JSONB,             -- 差异列表 [{path, op, old, new}]
+    fields          TEXT[] NOT NULL DEFAULT '{}',                   -- 发生变化的顶层字段, 用于按字段过滤
+    change_count    INTEGER NOT NULL DEFAULT 0,                     -- 差异条数
+    created_at      TIMESTAMPTZ NOT NULL                            -- 新快照的时间
+);
+
+-- 投稿队列 (见 023_add_submission_jobs)
+CREATE TABLE submission_jobs (
+    id              BIGSERIAL PRIMARY KEY,                          -- 任务ID, 投稿时返回
+    kind            TEXT NOT NULL,                                  -- app / substance
+    target          TEXT NOT NULL,                                  -- 应用: pkg_name:<包名> / app_id:<应用ID>, 专题: 专题ID
+    status          TEXT NOT NULL DEFAULT 'queued',                 -- queued / running / succeeded / failed
+    comment         JSONB,                                          -- 投稿时附带的备注
+    listed_at       TIMESTAMPTZ,                                    -- 投稿时指定的上架时间
+    api_key_id      BIGINT,                                         -- 投稿使用的 API key, 匿名为 NULL
+    attempts        INTEGER NOT NULL DEFAULT 0,                     -- 执行次数
+    result          JSONB,                                          -- 执行结果
+    error           TEXT,                                           -- 失败原因
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),             -- 入队时间
+    started_at      TIMESTAMPTZ,                                    -- 开始执行时间
+    finished_at     TIMESTAMPTZ,                                    -- 结束时间
+    worker_id       TEXT,                                           -- 执行中任务所属的 worker (见 025_add_submission_job_lease)
+    heartbeat_at    TIMESTAMPTZ                                     -- 执行中任务最后一次续约的时间
+);
+
+-- 投稿审核 (见 024_add_submission_reviews)
+CREATE TABLE submission_reviews (
+    id              BIGSERIAL PRIMARY KEY,                          -- 审核ID
+    job_id          BIGINT REFERENCES submission_jobs(id) ON DELETE SET NULL, -- 对应的投稿任务
+    kind            TEXT NOT NULL,                                  -- app / substance
+    target          TEXT NOT NULL,                                  -- 同 submission_jobs.target
+    status          TEXT NOT NULL DEFAULT 'pending',                -- pending / approved / rejected
+    comment         JSONB,                                          -- 待写入的备注 (管理员可以修改)
+    listed_at       TIMESTAMPTZ,                                    -- 待写入的上架时间 (管理员可以修改)
+    submitter_ip    INET NOT NULL,                                  -- 投稿人 IP
+    submitter_ua    TEXT,                                           -- 投稿人 User-Agent
+    api_key_id      BIGINT,                                         -- 投稿使用的 API key, 匿名为 NULL
+    reviewed_by     BIGINT,                                         -- 审核人的 API key
+    review_note     TEXT,                                           -- 审核说明
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),             -- 投稿时间
+    reviewed_at     TIMESTAMPTZ                                     -- 通过 / 驳回时间
+);
+
+-- 审核操作记录 (见 024_add_submission_reviews), 只追加不修改
+CREATE TABLE moderation_log (
+    id              BIGSERIAL PRIMARY KEY,
+    review_id       BIGINT NOT NULL REFERENCES submission_reviews(id), -- 审核ID
+    action          TEXT NOT NULL,                                  -- edit / approve / reject
+    actor_key_id    BIGINT,                                         -- 操作人的 API key
+    actor_ip        INET,                                           -- 操作人 IP
+    old_value       JSONB NOT NULL,                                 -- 操作前的 status / comment / listed_at
+    new_value       JSONB NOT NULL,                                 -- 操作后的 status / comment / listed_at
+    note            TEXT,                                           -- 操作说明
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()              -- 操作时间
+);
+
+-- 原始数据与降采样数据的统一读取视图
+-- 与 app_metrics 字段一致, 聚合层的 id 为 NULL, 文本取当时生效的版本
+CREATE OR REPLACE VIEW app_metrics_timeline AS
+SELECT
+    am.id, am.app_id, am.pkg_name, am.version, am.version_code, am.size_bytes, am.sha256,
+    am.info_score, am.info_rate_count, am.download_count, am.price, am.release_date,
+    am.new_features, am.upgrade_msg, am.target_sdk, am.minsdk, am.compile_sdk_version,
+    am.min_hmos_api_level, am.api_release_type, am.created_at,
+    'raw'::text AS tier
+FROM app_metrics am
+UNION ALL
+SELECT
+    NULL::bigint AS id, r.app_id, r.pkg_name, r.version, r.version_code, r.size_bytes, r.sha256,
+    r.info_score, r.info_rate_count, r.download_count, r.price, r.release_date,
+    COALESCE(t.new_features, '') AS new_features, COALESCE(t.upgrade_msg, '') AS upgrade_msg,
+    r.target_sdk, r.minsdk, r.compile_sdk_version,
+    r.min_hmos_api_level, r.api_release_type, r.last_created_at AS created_at,
+    r.granularity AS tier
+FROM app_metrics_rollup r
+LEFT JOIN LATERAL (
+    SELECT new_features, upgrade_msg
+    FROM app_metrics_text
+    WHERE app_id = r.app_id AND valid_from <= r.last_created_at
+    ORDER BY valid_from DESC
+    LIMIT 1
+) t ON TRUE;
+
+-- 与 app_rating 字段一致, 聚合层的 id 为 NULL
+CREATE OR REPLACE VIEW app_rating_timeline AS
+SELECT
+    ar.id, ar.app_id, ar.pkg_name, ar.average_rating,
+    ar.star_1_rating_count, ar.star_2_rating_count, ar.star_3_rating_count,
+    ar.star_4_rating_count, ar.star_5_rating_count, ar.my_star_rating,
+    ar.total_star_rating_count, ar.only_star_count, ar.full_average_rating,
+    ar.source_type, ar.created_at,
+    'raw'::text AS tier
+FROM app_rating ar
+UNION ALL
+SELECT
+    NULL::bigint AS id, r.app_id, r.pkg_name, r.average_rating,
+    r.star_1_rating_count, r.star_2_rating_count, r.star_3_rating_count,
+    r.star_4_rating_count, r.star_5_rating_count, r.my_star_rating,
+    r.total_star_rating_count, r.only_star_count, r.full_average_rating,
+    r.source_type, r.last_created_at AS created_at,
+    r.granularity AS tier
+FROM app_rating_rollup r;
+
+-- 只有下载量, 给下载增量统计用, 不需要查文本
+CREATE OR REPLACE VIEW app_download_timeline AS
+SELECT app_id, pkg_name, download_count, created_at FROM app_metrics
+UNION ALL
+SELECT app_id, pkg_name, download_count, last_created_at AS created_at FROM app_metrics_rollup;
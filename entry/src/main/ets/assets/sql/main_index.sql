@@ -79,7 +79,7 @@ CREATE INDEX IF NOT EXISTS idx_app_rating_pkg_name ON app_rating (pkg_name);
 
 
 -- app_data_history 表索引
-CREATE INDEX IF NOT EXISTS idx_app_data_history_app_pkg_data ON app_data_history (app_id, pkg_name, raw_json_data);
+CREATE INDEX IF NOT EXISTS idx_app_data_history_app_id_created_at ON app_data_history (app_id, created_at DESC);
 CREATE INDEX IF NOT EXISTS idx_app_data_history_created_at ON app_data_history (created_at);
 
 -- app_record 表索引
@@ -100,8 +100,7 @@ CREATE INDEX IF NOT EXISTS idx_substance_info_created_at ON substance_info (crea
 -- 针对 substance_id 和时间范围查询进行了优化。
 CREATE INDEX IF NOT EXISTS idx_substance_history_substance_id ON substance_history (substance_id);
 CREATE INDEX IF NOT EXISTS idx_substance_history_created_at ON substance_history (created_at);
--- 如果需要基于 JSONB 内容查询（例如 raw_json_substance ->> 'field'），则建立 GIN 索引。
-CREATE INDEX IF NOT EXISTS idx_substance_history_raw_json_gin ON substance_history USING GIN (raw_json_substance);
+-- 快照内容在 json_blob 里 (见 020_dedup_json_history), 不再对 raw_json_substance 建 GIN 索引。
 
 -- 3) substance_app_map 表索引
 -- 主键 (substance_id, app_id) 已存在；补充索引：
@@ -136,3 +135,83 @@ CREATE INDEX IF NOT EXISTS idx_access_logs_timestamp ON access_logs(timestamp DE
 CREATE INDEX IF NOT EXISTS idx_access_logs_ip_address ON access_logs(ip_address);
 CREATE INDEX IF NOT EXISTS idx_access_logs_user_agent ON access_logs(user_agent);
 CREATE INDEX IF NOT EXISTS idx_access_logs_request_path ON access_logs(request_path);
+
+-- ----------------------------------------------------------------------
+-- 016迁移添加的搜索索引
+-- ----------------------------------------------------------------------
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- 应用搜索用的 tsvector, 权重: 名称和名称拼音 A, 开发者/分类 B, 简介 C, 描述 D (026 加了拼音)
+CREATE OR REPLACE FUNCTION app_search_vector(
+    p_name TEXT,
+    p_brief_desc TEXT,
+    p_description TEXT,
+    p_developer_name TEXT,
+    p_kind_name TEXT,
+    p_name_pinyin TEXT,
+    p_name_initials TEXT
+) RETURNS tsvector AS $$
+    SELECT
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_name, '')), 'A') ||
+        setweight(to_tsvector('simple'::regconfig,
+            coalesce(p_name_pinyin, '') || ' ' || coalesce(p_name_initials, '')), 'A') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_developer_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_kind_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_brief_desc, '')), 'C') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_description, '')), 'D')
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+CREATE INDEX IF NOT EXISTS idx_app_full_info_search_vector ON app_full_info
+    USING GIN (app_search_vector(
+        name, brief_desc, description, developer_name, kind_name, name_pinyin, name_initials
+    ));
+
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_trgm ON app_full_info USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_brief_desc_trgm ON app_full_info USING GIN (brief_desc gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_description_trgm ON app_full_info USING GIN (description gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_developer_name_trgm ON app_full_info USING GIN (developer_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_kind_name_trgm ON app_full_info USING GIN (kind_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_pinyin_trgm ON app_full_info USING GIN (name_pinyin gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_initials_trgm ON app_full_info USING GIN (name_initials gin_trgm_ops);
+
+-- app_metrics 降采样
+CREATE INDEX IF NOT EXISTS idx_app_metrics_rollup_app_id_last_created_at
+    ON app_metrics_rollup(app_id, last_created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_metrics_rollup_pkg_name
+    ON app_metrics_rollup(pkg_name);
+
+-- app_rating 降采样
+CREATE INDEX IF NOT EXISTS idx_app_rating_rollup_app_id_last_created_at
+    ON app_rating_rollup(app_id, last_created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_rating_rollup_pkg_name
+    ON app_rating_rollup(pkg_name);
+
+-- 快照变更记录
+CREATE INDEX IF NOT EXISTS idx_app_changelog_app_id_created_at
+    ON app_changelog(app_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_changelog_created_at
+    ON app_changelog(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_changelog_fields
+    ON app_changelog USING GIN (fields);
+
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_substance_id_created_at
+    ON substance_changelog(substance_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_created_at
+    ON substance_changelog(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_fields
+    ON substance_changelog USING GIN (fields);
+
+-- 投稿队列: 同一个对象同时只能有一个未完成的任务
+CREATE UNIQUE INDEX IF NOT EXISTS idx_submission_jobs_active
+    ON submission_jobs(kind, target) WHERE status IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS idx_submission_jobs_queued
+    ON submission_jobs(id) WHERE status = 'queued';
+
+-- 投稿审核
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_status
+    ON submission_reviews(status, id DESC);
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_target
+    ON submission_reviews(kind, target);
+CREATE INDEX IF NOT EXISTS idx_moderation_log_review_id
+    ON moderation_log(review_id, id);
@@ -35,7 +35,7 @@ BEGIN
         is_disciplined, is_shelves, submit_type, delete_archive,
         charging, button_grey, app_gift, free_days,
         pay_install_type, comment, listed_at, release_countries,
-        main_device_codes, created_at, updated_at
+        main_device_codes, name_pinyin, name_initials, created_at, updated_at
     ) VALUES (
         NEW.app_id, NEW.alliance_app_id, NEW.name, NEW.pkg_name,
         NEW.dev_id, NEW.developer_name, NEW.dev_en_name,
@@ -48,7 +48,7 @@ BEGIN
         NEW.is_disciplined, NEW.is_shelves, NEW.submit_type, NEW.delete_archive,
         NEW.charging, NEW.button_grey, NEW.app_gift, NEW.free_days,
         NEW.pay_install_type, NEW.comment, NEW.listed_at, NEW.release_countries,
-        NEW.main_device_codes, NEW.created_at, now()
+        NEW.main_device_codes, NEW.name_pinyin, NEW.name_initials, NEW.created_at, now()
     )
     ON CONFLICT (app_id) DO UPDATE SET
         alliance_app_id = EXCLUDED.alliance_app_id,
@@ -94,6 +94,8 @@ BEGIN
         listed_at = EXCLUDED.listed_at,
         release_countries = EXCLUDED.release_countries,
         main_device_codes = EXCLUDED.main_device_codes,
+        name_pinyin = EXCLUDED.name_pinyin,
+        name_initials = EXCLUDED.name_initials,
         created_at = EXCLUDED.created_at,
         updated_at = now();
     
@@ -117,6 +119,14 @@ DECLARE
 BEGIN
     IF TG_OP = 'DELETE' THEN
         target_app_id := OLD.app_id;
+
+        -- 还有更新的记录, app_full_info 不受影响
+        IF EXISTS (
+            SELECT 1 FROM app_metrics
+            WHERE app_id = OLD.app_id AND created_at > OLD.created_at
+        ) THEN
+            RETURN OLD;
+        END IF;
     ELSE
         target_app_id := NEW.app_id;
     END IF;
@@ -218,6 +228,14 @@ DECLARE
 BEGIN
     IF TG_OP = 'DELETE' THEN
         target_app_id := OLD.app_id;
+
+        -- 还有更新的记录, app_full_info 不受影响
+        IF EXISTS (
+            SELECT 1 FROM app_rating
+            WHERE app_id = OLD.app_id AND created_at > OLD.created_at
+        ) THEN
+            RETURN OLD;
+        END IF;
     ELSE
         target_app_id := NEW.app_id;
     END IF;
@@ -367,4 +385,409 @@ DROP TRIGGER IF EXISTS trg_update_app_full_info_from_record ON app_record;
 CREATE TRIGGER trg_update_app_full_info_from_record
 AFTER INSERT OR UPDATE OR DELETE ON app_record
 FOR EACH ROW
-EXECUTE FUNCTION update_app_full_info_from_record();
\ No newline at end of file
+EXECUTE FUNCTION update_app_full_info_from_record();
+
+-- ============================================================================
+-- 函数：rollup_app_metrics
+-- 功能：把早于 raw_days 天的原始记录压缩为按天聚合, 早于 daily_days 天的按天聚合再压缩为按周聚合
+-- 说明：每个应用最新的一条原始记录永远保留 (app_full_info 依赖它)
+-- ============================================================================
+CREATE OR REPLACE FUNCTION rollup_app_metrics(raw_days INTEGER DEFAULT 30, daily_days INTEGER DEFAULT 180)
+RETURNS TABLE(raw_rolled INTEGER, daily_rolled INTEGER, text_saved INTEGER) AS $$
+DECLARE
+    raw_cutoff TIMESTAMPTZ := NOW() - INTERVAL '1 day' * raw_days;
+    daily_cutoff DATE := ((NOW() - INTERVAL '1 day' * daily_days) AT TIME ZONE 'Asia/Shanghai')::date;
+BEGIN
+    -- 1. 待压缩的原始记录
+    DROP TABLE IF EXISTS _rollup_raw;
+    CREATE TEMP TABLE _rollup_raw ON COMMIT DROP AS
+    SELECT am.*, (am.created_at AT TIME ZONE 'Asia/Shanghai')::date AS bucket_start
+    FROM app_metrics am
+    WHERE am.created_at < raw_cutoff
+      AND am.id <> (
+          SELECT l.id FROM app_metrics l
+          WHERE l.app_id = am.app_id
+          ORDER BY l.created_at DESC, l.id DESC
+          LIMIT 1
+      );
+
+    -- 2. 文本只保留变化点
+    INSERT INTO app_metrics_text (app_id, new_features, upgrade_msg, valid_from)
+    SELECT c.app_id, c.new_features, c.upgrade_msg, c.created_at
+    FROM (
+        SELECT
+            r.app_id, r.new_features, r.upgrade_msg, r.created_at,
+            LAG(r.new_features) OVER w AS prev_features,
+            LAG(r.upgrade_msg) OVER w AS prev_msg,
+            ROW_NUMBER() OVER w AS rn
+        FROM _rollup_raw r
+        WINDOW w AS (PARTITION BY r.app_id ORDER BY r.created_at, r.id)
+    ) c
+    LEFT JOIN LATERAL (
+        SELECT t.new_features, t.upgrade_msg
+        FROM app_metrics_text t
+        WHERE t.app_id = c.app_id AND t.valid_from <= c.created_at
+        ORDER BY t.valid_from DESC
+        LIMIT 1
+    ) last_text ON c.rn = 1
+    WHERE (c.rn = 1 AND (
+            last_text.new_features IS DISTINCT FROM c.new_features
+            OR last_text.upgrade_msg IS DISTINCT FROM c.upgrade_msg
+        ))
+       OR (c.rn > 1 AND (
+            c.new_features IS DISTINCT FROM c.prev_features
+            OR c.upgrade_msg IS DISTINCT FROM c.prev_msg
+        ))
+    ON CONFLICT (app_id, valid_from) DO NOTHING;
+    GET DIAGNOSTICS text_saved = ROW_COUNT;
+
+    -- 3. 原始记录 -> 按天聚合
+    INSERT INTO app_metrics_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        version, version_code, size_bytes, sha256, info_score, info_rate_count,
+        download_count, price, release_date, target_sdk, minsdk, compile_sdk_version,
+        min_hmos_api_level, api_release_type,
+        download_count_min, download_count_max, info_score_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (r.app_id, r.bucket_start)
+        r.app_id, r.pkg_name, 'day', r.bucket_start, COUNT(*) OVER p,
+        r.version, r.version_code, r.size_bytes, r.sha256, r.info_score, r.info_rate_count,
+        r.download_count, r.price, r.release_date, r.target_sdk, r.minsdk, r.compile_sdk_version,
+        r.min_hmos_api_level, r.api_release_type,
+        MIN(r.download_count) OVER p, MAX(r.download_count) OVER p, ROUND(AVG(r.info_score) OVER p, 1),
+        MIN(r.created_at) OVER p, r.created_at
+    FROM _rollup_raw r
+    WINDOW p AS (PARTITION BY r.app_id, r.bucket_start)
+    ORDER BY r.app_id, r.bucket_start, r.created_at DESC, r.id DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_metrics_rollup.sample_count + EXCLUDED.sample_count,
+        download_count_min = LEAST(app_metrics_rollup.download_count_min, EXCLUDED.download_count_min),
+        download_count_max = GREATEST(app_metrics_rollup.download_count_max, EXCLUDED.download_count_max),
+        info_score_avg = ROUND(
+            (app_metrics_rollup.info_score_avg * app_metrics_rollup.sample_count
+                + EXCLUDED.info_score_avg * EXCLUDED.sample_count)
+            / (app_metrics_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_metrics_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        version = EXCLUDED.version,
+        version_code = EXCLUDED.version_code,
+        size_bytes = EXCLUDED.size_bytes,
+        sha256 = EXCLUDED.sha256,
+        info_score = EXCLUDED.info_score,
+        info_rate_count = EXCLUDED.info_rate_count,
+        download_count = EXCLUDED.download_count,
+        price = EXCLUDED.price,
+        release_date = EXCLUDED.release_date,
+        target_sdk = EXCLUDED.target_sdk,
+        minsdk = EXCLUDED.minsdk,
+        compile_sdk_version = EXCLUDED.compile_sdk_version,
+        min_hmos_api_level = EXCLUDED.min_hmos_api_level,
+        api_release_type = EXCLUDED.api_release_type;
+
+    DELETE FROM app_metrics WHERE id IN (SELECT id FROM _rollup_raw);
+    GET DIAGNOSTICS raw_rolled = ROW_COUNT;
+
+    -- 4. 按天聚合 -> 按周聚合
+    DROP TABLE IF EXISTS _rollup_daily;
+    CREATE TEMP TABLE _rollup_daily ON COMMIT DROP AS
+    SELECT d.*, date_trunc('week', d.bucket_start)::date AS week_start
+    FROM app_metrics_rollup d
+    WHERE d.granularity = 'day' AND d.bucket_start < daily_cutoff;
+
+    INSERT INTO app_metrics_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        version, version_code, size_bytes, sha256, info_score, info_rate_count,
+        download_count, price, release_date, target_sdk, minsdk, compile_sdk_version,
+        min_hmos_api_level, api_release_type,
+        download_count_min, download_count_max, info_score_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (d.app_id, d.week_start)
+        d.app_id, d.pkg_name, 'week', d.week_start, (SUM(d.sample_count) OVER p)::integer,
+        d.version, d.version_code, d.size_bytes, d.sha256, d.info_score, d.info_rate_count,
+        d.download_count, d.price, d.release_date, d.target_sdk, d.minsdk, d.compile_sdk_version,
+        d.min_hmos_api_level, d.api_release_type,
+        MIN(d.download_count_min) OVER p, MAX(d.download_count_max) OVER p,
+        ROUND(SUM(d.info_score_avg * d.sample_count) OVER p / SUM(d.sample_count) OVER p, 1),
+        MIN(d.first_created_at) OVER p, d.last_created_at
+    FROM _rollup_daily d
+    WINDOW p AS (PARTITION BY d.app_id, d.week_start)
+    ORDER BY d.app_id, d.week_start, d.last_created_at DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_metrics_rollup.sample_count + EXCLUDED.sample_count,
+        download_count_min = LEAST(app_metrics_rollup.download_count_min, EXCLUDED.download_count_min),
+        download_count_max = GREATEST(app_metrics_rollup.download_count_max, EXCLUDED.download_count_max),
+        info_score_avg = ROUND(
+            (app_metrics_rollup.info_score_avg * app_metrics_rollup.sample_count
+                + EXCLUDED.info_score_avg * EXCLUDED.sample_count)
+            / (app_metrics_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_metrics_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        version = EXCLUDED.version,
+        version_code = EXCLUDED.version_code,
+        size_bytes = EXCLUDED.size_bytes,
+        sha256 = EXCLUDED.sha256,
+        info_score = EXCLUDED.info_score,
+        info_rate_count = EXCLUDED.info_rate_count,
+        download_count = EXCLUDED.download_count,
+        price = EXCLUDED.price,
+        release_date = EXCLUDED.release_date,
+        target_sdk = EXCLUDED.target_sdk,
+        minsdk = EXCLUDED.minsdk,
+        compile_sdk_version = EXCLUDED.compile_sdk_version,
+        min_hmos_api_level = EXCLUDED.min_hmos_api_level,
+        api_release_type = EXCLUDED.api_release_type;
+
+    DELETE FROM app_metrics_rollup WHERE id IN (SELECT id FROM _rollup_daily);
+    GET DIAGNOSTICS daily_rolled = ROW_COUNT;
+
+    RETURN NEXT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================================
+-- 函数：rollup_app_rating
+-- 功能：和 rollup_app_metrics 一样, 把较早的 app_rating 压缩为按天 / 按周聚合
+-- 说明：每个应用最新的一条原始记录永远保留 (app_full_info 依赖它)
+-- ============================================================================
+CREATE OR REPLACE FUNCTION rollup_app_rating(raw_days INTEGER DEFAULT 30, daily_days INTEGER DEFAULT 180)
+RETURNS TABLE(raw_rolled INTEGER, daily_rolled INTEGER) AS $$
+DECLARE
+    raw_cutoff TIMESTAMPTZ := NOW() - INTERVAL '1 day' * raw_days;
+    daily_cutoff DATE := ((NOW() - INTERVAL '1 day' * daily_days) AT TIME ZONE 'Asia/Shanghai')::date;
+BEGIN
+    -- 1. 待压缩的原始记录
+    DROP TABLE IF EXISTS _rollup_rating_raw;
+    CREATE TEMP TABLE _rollup_rating_raw ON COMMIT DROP AS
+    SELECT ar.*, (ar.created_at AT TIME ZONE 'Asia/Shanghai')::date AS bucket_start
+    FROM app_rating ar
+    WHERE ar.created_at < raw_cutoff
+      AND ar.id <> (
+          SELECT l.id FROM app_rating l
+          WHERE l.app_id = ar.app_id
+          ORDER BY l.created_at DESC, l.id DESC
+          LIMIT 1
+      );
+
+    -- 2. 原始记录 -> 按天聚合
+    INSERT INTO app_rating_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        average_rating, star_1_rating_count, star_2_rating_count, star_3_rating_count,
+        star_4_rating_count, star_5_rating_count, my_star_rating, total_star_rating_count,
+        only_star_count, full_average_rating, source_type,
+        total_star_rating_count_min, total_star_rating_count_max, average_rating_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (r.app_id, r.bucket_start)
+        r.app_id, r.pkg_name, 'day', r.bucket_start, COUNT(*) OVER p,
+        r.average_rating, r.star_1_rating_count, r.star_2_rating_count, r.star_3_rating_count,
+        r.star_4_rating_count, r.star_5_rating_count, r.my_star_rating, r.total_star_rating_count,
+        r.only_star_count, r.full_average_rating, r.source_type,
+        MIN(r.total_star_rating_count) OVER p, MAX(r.total_star_rating_count) OVER p,
+        ROUND(AVG(r.average_rating) OVER p, 1),
+        MIN(r.created_at) OVER p, r.created_at
+    FROM _rollup_rating_raw r
+    WINDOW p AS (PARTITION BY r.app_id, r.bucket_start)
+    ORDER BY r.app_id, r.bucket_start, r.created_at DESC, r.id DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_rating_rollup.sample_count + EXCLUDED.sample_count,
+        total_star_rating_count_min = LEAST(app_rating_rollup.total_star_rating_count_min, EXCLUDED.total_star_rating_count_min),
+        total_star_rating_count_max = GREATEST(app_rating_rollup.total_star_rating_count_max, EXCLUDED.total_star_rating_count_max),
+        average_rating_avg = ROUND(
+            (app_rating_rollup.average_rating_avg * app_rating_rollup.sample_count
+                + EXCLUDED.average_rating_avg * EXCLUDED.sample_count)
+            / (app_rating_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_rating_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        average_rating = EXCLUDED.average_rating,
+        star_1_rating_count = EXCLUDED.star_1_rating_count,
+        star_2_rating_count = EXCLUDED.star_2_rating_count,
+        star_3_rating_count = EXCLUDED.star_3_rating_count,
+        star_4_rating_count = EXCLUDED.star_4_rating_count,
+        star_5_rating_count = EXCLUDED.star_5_rating_count,
+        my_star_rating = EXCLUDED.my_star_rating,
+        total_star_rating_count = EXCLUDED.total_star_rating_count,
+        only_star_count = EXCLUDED.only_star_count,
+        full_average_rating = EXCLUDED.full_average_rating,
+        source_type = EXCLUDED.source_type;
+
+    DELETE FROM app_rating WHERE id IN (SELECT id FROM _rollup_rating_raw);
+    GET DIAGNOSTICS raw_rolled = ROW_COUNT;
+
+    -- 3. 按天聚合 -> 按周聚合
+    DROP TABLE IF EXISTS _rollup_rating_daily;
+    CREATE TEMP TABLE _rollup_rating_daily ON COMMIT DROP AS
+    SELECT d.*, date_trunc('week', d.bucket_start)::date AS week_start
+    FROM app_rating_rollup d
+    WHERE d.granularity = 'day' AND d.bucket_start < daily_cutoff;
+
+    INSERT INTO app_rating_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        average_rating, star_1_rating_count, star_2_rating_count, star_3_rating_count,
+        star_4_rating_count, star_5_rating_count, my_star_rating, total_star_rating_count,
+        only_star_count, full_average_rating, source_type,
+        total_star_rating_count_min, total_star_rating_count_max, average_rating_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (d.app_id, d.week_start)
+        d.app_id, d.pkg_name, 'week', d.week_start, (SUM(d.sample_count) OVER p)::integer,
+        d.average_rating, d.star_1_rating_count, d.star_2_rating_count, d.star_3_rating_count,
+        d.star_4_rating_count, d.star_5_rating_count, d.my_star_rating, d.total_star_rating_count,
+        d.only_star_count, d.full_average_rating, d.source_type,
+        MIN(d.total_star_rating_count_min) OVER p, MAX(d.total_star_rating_count_max) OVER p,
+        ROUND(SUM(d.average_rating_avg * d.sample_count) OVER p / SUM(d.sample_count) OVER p, 1),
+        MIN(d.first_created_at) OVER p, d.last_created_at
+    FROM _rollup_rating_daily d
+    WINDOW p AS (PARTITION BY d.app_id, d.week_start)
+    ORDER BY d.app_id, d.week_start, d.last_created_at DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_rating_rollup.sample_count + EXCLUDED.sample_count,
+        total_star_rating_count_min = LEAST(app_rating_rollup.total_star_rating_count_min, EXCLUDED.total_star_rating_count_min),
+        total_star_rating_count_max = GREATEST(app_rating_rollup.total_star_rating_count_max, EXCLUDED.total_star_rating_count_max),
+        average_rating_avg = ROUND(
+            (app_rating_rollup.average_rating_avg * app_rating_rollup.sample_count
+                + EXCLUDED.average_rating_avg * EXCLUDED.sample_count)
+            / (app_rating_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_rating_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        average_rating = EXCLUDED.average_rating,
+        star_1_rating_count = EXCLUDED.star_1_rating_count,
+        star_2_rating_count = EXCLUDED.star_2_rating_count,
+        star_3_rating_count = EXCLUDED.star_3_rating_count,
+        star_4_rating_count = EXCLUDED.star_4_rating_count,
+        star_5_rating_count = EXCLUDED.star_5_rating_count,
+        my_star_rating = EXCLUDED.my_star_rating,
+        total_star_rating_count = EXCLUDED.total_star_rating_count,
+        only_star_count = EXCLUDED.only_star_count,
+        full_average_rating = EXCLUDED.full_average_rating,
+        source_type = EXCLUDED.source_type;
+
+    DELETE FROM app_rating_rollup WHERE id IN (SELECT id FROM _rollup_rating_daily);
+    GET DIAGNOSTICS daily_rolled = ROW_COUNT;
+
+    RETURN NEXT;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================================
+-- 原始数据历史去重 (见 020_dedup_json_history)
+-- app_data_history / substance_history 写入的 JSON 由触发器拆分进 json_blob,
+-- 行里只保留清单 hash, 读取时用 json_blob_load 还原
+-- ============================================================================
+-- 存一个 blob, 返回 hash (jsonb 的文本形式是规范化的, 相同内容的 hash 一定相同)
+CREATE OR REPLACE FUNCTION json_blob_put(doc JSONB)
+RETURNS TEXT AS $$
+DECLARE
+    h TEXT := encode(sha256(convert_to(doc::text, 'UTF8')), 'hex');
+BEGIN
+    INSERT INTO json_blob (hash, content) VALUES (h, doc) ON CONFLICT (hash) DO NOTHING;
+    RETURN h;
+END;
+$$ LANGUAGE plpgsql;
+
+-- 拆分保存一个快照, 返回清单的 hash
+-- 对象: 清单为 {字段: 字段值的 hash}; 其他类型: 清单为整个值的 hash (JSON 字符串)
+CREATE OR REPLACE FUNCTION json_blob_store(doc JSONB)
+RETURNS TEXT AS $$
+BEGIN
+    IF doc IS NULL THEN
+        RETURN NULL;
+    END IF;
+    IF jsonb_typeof(doc) <> 'object' THEN
+        RETURN json_blob_put(to_jsonb(json_blob_put(doc)));
+    END IF;
+    RETURN json_blob_put(COALESCE(
+        (SELECT jsonb_object_agg(key, json_blob_put(value)) FROM jsonb_each(doc)),
+        '{}'::JSONB
+    ));
+END;
+$$ LANGUAGE plpgsql;
+
+-- 按清单 hash 还原快照
+CREATE OR REPLACE FUNCTION json_blob_load(manifest_hash TEXT)
+RETURNS JSONB AS $$
+    SELECT CASE jsonb_typeof(m.content)
+        WHEN 'string' THEN (SELECT b.content FROM json_blob b WHERE b.hash = m.content #>> '{}')
+        ELSE (
+            SELECT COALESCE(jsonb_object_agg(f.key, b.content), '{}'::JSONB)
+            FROM jsonb_each_text(m.content) f
+            JOIN json_blob b ON b.hash = f.value
+        )
+    END
+    FROM json_blob m
+    WHERE m.hash = manifest_hash
+$$ LANGUAGE sql STABLE;
+
+-- 删除不再被任何快照引用的 blob, 返回删除数量
+-- 期间会锁住两张历史表的写入, 避免删掉正在被新快照复用的 blob
+CREATE OR REPLACE FUNCTION cleanup_orphan_json_blobs()
+RETURNS INTEGER AS $$
+DECLARE
+    deleted_count INTEGER;
+BEGIN
+    LOCK TABLE app_data_history, substance_history IN SHARE MODE;
+
+    WITH manifests AS (
+        SELECT raw_json_hash AS hash FROM app_data_history
+        UNION
+        SELECT raw_json_hash FROM substance_history
+    ),
+    live AS (
+        SELECT hash FROM manifests
+        UNION
+        SELECT f.value
+        FROM manifests m
+        JOIN json_blob b ON b.hash = m.hash
+        CROSS JOIN LATERAL jsonb_each_text(
+            CASE jsonb_typeof(b.content)
+                WHEN 'object' THEN b.content
+                ELSE jsonb_build_object('', b.content)
+            END
+        ) f
+    )
+    DELETE FROM json_blob j
+    WHERE NOT EXISTS (SELECT 1 FROM live l WHERE l.hash = j.hash);
+
+    GET DIAGNOSTICS deleted_count = ROW_COUNT;
+    RETURN deleted_count;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION app_data_history_store_json()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.raw_json_data IS NOT NULL THEN
+        NEW.raw_json_hash := json_blob_store(NEW.raw_json_data);
+        NEW.raw_json_data := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION substance_history_store_json()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.raw_json_substance IS NOT NULL THEN
+        NEW.raw_json_hash := json_blob_store(NEW.raw_json_substance);
+        NEW.raw_json_substance := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trigger_app_data_history_store_json ON app_data_history;
+CREATE TRIGGER trigger_app_data_history_store_json
+    BEFORE INSERT OR UPDATE OF raw_json_data ON app_data_history
+    FOR EACH ROW
+    EXECUTE FUNCTION app_data_history_store_json();
+
+DROP TRIGGER IF EXISTS trigger_substance_history_store_json ON substance_history;
+CREATE TRIGGER trigger_substance_history_store_json
+    BEFORE INSERT OR UPDATE OF raw_json_substance ON substance_history
+    FOR EACH ROW
+    EXECUTE FUNCTION substance_history_store_json();
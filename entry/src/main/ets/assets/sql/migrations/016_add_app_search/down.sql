@@ -0,0 +1,14 @@
+-- 应用搜索索引回滚脚本
+
+DROP INDEX IF EXISTS idx_app_full_info_kind_name_trgm;
+DROP INDEX IF EXISTS idx_app_full_info_developer_name_trgm;
+DROP INDEX IF EXISTS idx_app_full_info_description_trgm;
+DROP INDEX IF EXISTS idx_app_full_info_brief_desc_trgm;
+DROP INDEX IF EXISTS idx_app_full_info_name_trgm;
+DROP INDEX IF EXISTS idx_app_full_info_search_vector;
+
+DROP FUNCTION IF EXISTS app_search_vector(TEXT, TEXT, TEXT, TEXT, TEXT);
+
+-- pg_trgm 可能被其他对象使用, 不在这里删除扩展
+
+DELETE FROM schema_version WHERE version = 16;
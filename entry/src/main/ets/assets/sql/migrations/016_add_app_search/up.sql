@@ -0,0 +1,41 @@
+-- 应用搜索索引
+-- 用途: 为 name / brief_desc / description / developer_name / kind_name 提供模糊搜索与全文检索
+
+-- ============================================================
+-- 1. 扩展
+-- ============================================================
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- ============================================================
+-- 2. 全文检索向量函数
+-- ============================================================
+-- 使用 simple 配置, 中文按整段处理, 主要照顾英文/拼音缩写等按空格分词的内容
+-- 中文的部分匹配由下面的 trigram 索引负责
+CREATE OR REPLACE FUNCTION app_search_vector(
+    p_name TEXT,
+    p_brief_desc TEXT,
+    p_description TEXT,
+    p_developer_name TEXT,
+    p_kind_name TEXT
+) RETURNS tsvector AS $$
+    SELECT
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_name, '')), 'A') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_developer_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_kind_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_brief_desc, '')), 'C') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_description, '')), 'D')
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+COMMENT ON FUNCTION app_search_vector(TEXT, TEXT, TEXT, TEXT, TEXT) IS '应用搜索用的 tsvector, 权重: 名称 A, 开发者/分类 B, 简介 C, 描述 D';
+
+-- ============================================================
+-- 3. 索引
+-- ============================================================
+CREATE INDEX IF NOT EXISTS idx_app_full_info_search_vector ON app_full_info
+    USING GIN (app_search_vector(name, brief_desc, description, developer_name, kind_name));
+
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_trgm ON app_full_info USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_brief_desc_trgm ON app_full_info USING GIN (brief_desc gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_description_trgm ON app_full_info USING GIN (description gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_developer_name_trgm ON app_full_info USING GIN (developer_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_kind_name_trgm ON app_full_info USING GIN (kind_name gin_trgm_ops);
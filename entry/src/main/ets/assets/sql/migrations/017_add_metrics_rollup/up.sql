@@ -0,0 +1,357 @@
+-- app_metrics 降采样
+-- 近期保留原始数据, 较早的数据压缩为按天 / 按周的聚合, 文本字段只在变化时保存
+
+-- ============================================================================
+-- 聚合表
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS app_metrics_rollup (
+    id                  BIGSERIAL PRIMARY KEY,                      -- 主键ID
+    app_id              TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE,  -- 对应 app_info 的 app_id
+    pkg_name            TEXT NOT NULL REFERENCES app_info(pkg_name) ON DELETE CASCADE, -- 对应 app_info 的 pkg_name
+    granularity         TEXT NOT NULL CHECK (granularity IN ('day', 'week')), -- 聚合粒度
+    bucket_start        DATE NOT NULL,                              -- 桶起始日期（Asia/Shanghai，周以周一开始）
+    sample_count        INTEGER NOT NULL,                           -- 桶内原始记录数
+    -- 以下为桶内最后一条记录的快照
+    version             TEXT NOT NULL,
+    version_code        BIGINT NOT NULL,
+    size_bytes          BIGINT NOT NULL,
+    sha256              TEXT NOT NULL,
+    info_score          NUMERIC(3,1) NOT NULL,
+    info_rate_count     BIGINT NOT NULL,
+    download_count      BIGINT NOT NULL,
+    price               NUMERIC(10,2) NOT NULL,
+    release_date        BIGINT NOT NULL,
+    target_sdk          INTEGER NOT NULL,
+    minsdk              INTEGER NOT NULL,
+    compile_sdk_version INTEGER NOT NULL,
+    min_hmos_api_level  INTEGER NOT NULL,
+    api_release_type    TEXT NOT NULL,
+    -- 桶内聚合
+    download_count_min  BIGINT NOT NULL,                            -- 桶内最小下载量
+    download_count_max  BIGINT NOT NULL,                            -- 桶内最大下载量
+    info_score_avg      NUMERIC(3,1) NOT NULL,                      -- 桶内平均评分
+    first_created_at    TIMESTAMPTZ NOT NULL,                       -- 桶内最早记录时间
+    last_created_at     TIMESTAMPTZ NOT NULL,                       -- 桶内最晚记录时间（快照对应的时间）
+    UNIQUE (app_id, granularity, bucket_start)
+);
+
+-- 文本字段只在变化时记录一条, 从 valid_from 开始生效
+CREATE TABLE IF NOT EXISTS app_metrics_text (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    app_id          TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE, -- 对应 app_info 的 app_id
+    new_features    TEXT NOT NULL,                                  -- 新功能描述
+    upgrade_msg     TEXT NOT NULL,                                  -- 升级提示
+    valid_from      TIMESTAMPTZ NOT NULL,                           -- 生效时间（对应原始记录的 created_at）
+    UNIQUE (app_id, valid_from)
+);
+
+CREATE INDEX IF NOT EXISTS idx_app_metrics_rollup_app_id_last_created_at
+    ON app_metrics_rollup(app_id, last_created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_metrics_rollup_pkg_name
+    ON app_metrics_rollup(pkg_name);
+
+-- ============================================================================
+-- 跨层读取视图
+-- ============================================================================
+
+-- 与 app_metrics 字段一致, 聚合层的 id 为 NULL, 文本取当时生效的版本
+CREATE OR REPLACE VIEW app_metrics_timeline AS
+SELECT
+    am.id, am.app_id, am.pkg_name, am.version, am.version_code, am.size_bytes, am.sha256,
+    am.info_score, am.info_rate_count, am.download_count, am.price, am.release_date,
+    am.new_features, am.upgrade_msg, am.target_sdk, am.minsdk, am.compile_sdk_version,
+    am.min_hmos_api_level, am.api_release_type, am.created_at,
+    'raw'::text AS tier
+FROM app_metrics am
+UNION ALL
+SELECT
+    NULL::bigint AS id, r.app_id, r.pkg_name, r.version, r.version_code, r.size_bytes, r.sha256,
+    r.info_score, r.info_rate_count, r.download_count, r.price, r.release_date,
+    COALESCE(t.new_features, '') AS new_features, COALESCE(t.upgrade_msg, '') AS upgrade_msg,
+    r.target_sdk, r.minsdk, r.compile_sdk_version,
+    r.min_hmos_api_level, r.api_release_type, r.last_created_at AS created_at,
+    r.granularity AS tier
+FROM app_metrics_rollup r
+LEFT JOIN LATERAL (
+    SELECT new_features, upgrade_msg
+    FROM app_metrics_text
+    WHERE app_id = r.app_id AND valid_from <= r.last_created_at
+    ORDER BY valid_from DESC
+    LIMIT 1
+) t ON TRUE;
+
+-- 只有下载量, 给下载增量统计用, 不需要查文本
+CREATE OR REPLACE VIEW app_download_timeline AS
+SELECT app_id, pkg_name, download_count, created_at FROM app_metrics
+UNION ALL
+SELECT app_id, pkg_name, download_count, last_created_at AS created_at FROM app_metrics_rollup;
+
+-- ============================================================================
+-- 触发器函数：update_app_full_info_from_metrics
+-- 变更：删除的不是该应用最新的一条时直接返回, 压缩历史数据时不必逐行刷新 app_full_info
+-- ============================================================================
+CREATE OR REPLACE FUNCTION update_app_full_info_from_metrics()
+RETURNS TRIGGER AS $$
+DECLARE
+    target_app_id TEXT;
+    latest_metric_id BIGINT;
+    current_listed_at TIMESTAMPTZ;
+    new_release_date TIMESTAMPTZ;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        target_app_id := OLD.app_id;
+
+        -- 还有更新的记录, app_full_info 不受影响
+        IF EXISTS (
+            SELECT 1 FROM app_metrics
+            WHERE app_id = OLD.app_id AND created_at > OLD.created_at
+        ) THEN
+            RETURN OLD;
+        END IF;
+    ELSE
+        target_app_id := NEW.app_id;
+    END IF;
+
+    SELECT id
+    INTO latest_metric_id
+    FROM app_metrics
+    WHERE app_id = target_app_id
+      AND (TG_OP != 'DELETE' OR id != OLD.id)
+    ORDER BY created_at DESC NULLS LAST
+    LIMIT 1;
+
+    IF latest_metric_id IS NOT NULL THEN
+        -- 有最新记录，更新 metrics 字段
+        UPDATE app_full_info
+        SET
+            version = am.version,
+            version_code = am.version_code,
+            size_bytes = am.size_bytes,
+            sha256 = am.sha256,
+            info_score = am.info_score,
+            info_rate_count = am.info_rate_count,
+            download_count = am.download_count,
+            price = am.price,
+            release_date = am.release_date,
+            new_features = am.new_features,
+            upgrade_msg = am.upgrade_msg,
+            target_sdk = am.target_sdk,
+            minsdk = am.minsdk,
+            compile_sdk_version = am.compile_sdk_version,
+            min_hmos_api_level = am.min_hmos_api_level,
+            api_release_type = am.api_release_type,
+            metrics_created_at = am.created_at,
+            updated_at = now()
+        FROM app_metrics am
+        WHERE app_full_info.app_id = am.app_id
+          AND am.id = latest_metric_id;
+    ELSE
+        -- 没有记录，清空 metrics 字段
+        UPDATE app_full_info
+        SET
+            version = NULL,
+            version_code = NULL,
+            size_bytes = NULL,
+            sha256 = NULL,
+            info_score = NULL,
+            info_rate_count = NULL,
+            download_count = NULL,
+            price = NULL,
+            release_date = NULL,
+            new_features = NULL,
+            upgrade_msg = NULL,
+            target_sdk = NULL,
+            minsdk = NULL,
+            compile_sdk_version = NULL,
+            min_hmos_api_level = NULL,
+            api_release_type = NULL,
+            metrics_created_at = NULL,
+            updated_at = now()
+        WHERE app_id = target_app_id;
+    END IF;
+
+    -- 在 INSERT 时，更新 listed_at 为历史最早时间
+    IF TG_OP = 'INSERT' THEN
+        SELECT listed_at
+        INTO current_listed_at
+        FROM app_full_info
+        WHERE app_id = NEW.app_id;
+
+        new_release_date := TO_TIMESTAMP(NEW.release_date / 1000);
+
+        IF current_listed_at IS NULL OR new_release_date < current_listed_at THEN
+            UPDATE app_full_info
+            SET listed_at = new_release_date,
+                updated_at = now()
+            WHERE app_id = NEW.app_id;
+        END IF;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    ELSE
+        RETURN NEW;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================================
+-- 函数：rollup_app_metrics
+-- 功能：把早于 raw_days 天的原始记录压缩为按天聚合, 早于 daily_days 天的按天聚合再压缩为按周聚合
+-- 说明：每个应用最新的一条原始记录永远保留 (app_full_info 依赖它)
+-- ============================================================================
+CREATE OR REPLACE FUNCTION rollup_app_metrics(raw_days INTEGER DEFAULT 30, daily_days INTEGER DEFAULT 180)
+RETURNS TABLE(raw_rolled INTEGER, daily_rolled INTEGER, text_saved INTEGER) AS $$
+DECLARE
+    raw_cutoff TIMESTAMPTZ := NOW() - INTERVAL '1 day' * raw_days;
+    daily_cutoff DATE := ((NOW() - INTERVAL '1 day' * daily_days) AT TIME ZONE 'Asia/Shanghai')::date;
+BEGIN
+    -- 1. 待压缩的原始记录
+    DROP TABLE IF EXISTS _rollup_raw;
+    CREATE TEMP TABLE _rollup_raw ON COMMIT DROP AS
+    SELECT am.*, (am.created_at AT TIME ZONE 'Asia/Shanghai')::date AS bucket_start
+    FROM app_metrics am
+    WHERE am.created_at < raw_cutoff
+      AND am.id <> (
+          SELECT l.id FROM app_metrics l
+          WHERE l.app_id = am.app_id
+          ORDER BY l.created_at DESC, l.id DESC
+          LIMIT 1
+      );
+
+    -- 2. 文本只保留变化点
+    INSERT INTO app_metrics_text (app_id, new_features, upgrade_msg, valid_from)
+    SELECT c.app_id, c.new_features, c.upgrade_msg, c.created_at
+    FROM (
+        SELECT
+            r.app_id, r.new_features, r.upgrade_msg, r.created_at,
+            LAG(r.new_features) OVER w AS prev_features,
+            LAG(r.upgrade_msg) OVER w AS prev_msg,
+            ROW_NUMBER() OVER w AS rn
+        FROM _rollup_raw r
+        WINDOW w AS (PARTITION BY r.app_id ORDER BY r.created_at, r.id)
+    ) c
+    LEFT JOIN LATERAL (
+        SELECT t.new_features, t.upgrade_msg
+        FROM app_metrics_text t
+        WHERE t.app_id = c.app_id AND t.valid_from <= c.created_at
+        ORDER BY t.valid_from DESC
+        LIMIT 1
+    ) last_text ON c.rn = 1
+    WHERE (c.rn = 1 AND (
+            last_text.new_features IS DISTINCT FROM c.new_features
+            OR last_text.upgrade_msg IS DISTINCT FROM c.upgrade_msg
+        ))
+       OR (c.rn > 1 AND (
+            c.new_features IS DISTINCT FROM c.prev_features
+            OR c.upgrade_msg IS DISTINCT FROM c.prev_msg
+        ))
+    ON CONFLICT (app_id, valid_from) DO NOTHING;
+    GET DIAGNOSTICS text_saved = ROW_COUNT;
+
+    -- 3. 原始记录 -> 按天聚合
+    INSERT INTO app_metrics_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        version, version_code, size_bytes, sha256, info_score, info_rate_count,
+        download_count, price, release_date, target_sdk, minsdk, compile_sdk_version,
+        min_hmos_api_level, api_release_type,
+        download_count_min, download_count_max, info_score_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (r.app_id, r.bucket_start)
+        r.app_id, r.pkg_name, 'day', r.bucket_start, COUNT(*) OVER p,
+        r.version, r.version_code, r.size_bytes, r.sha256, r.info_score, r.info_rate_count,
+        r.download_count, r.price, r.release_date, r.target_sdk, r.minsdk, r.compile_sdk_version,
+        r.min_hmos_api_level, r.api_release_type,
+        MIN(r.download_count) OVER p, MAX(r.download_count) OVER p, ROUND(AVG(r.info_score) OVER p, 1),
+        MIN(r.created_at) OVER p, r.created_at
+    FROM _rollup_raw r
+    WINDOW p AS (PARTITION BY r.app_id, r.bucket_start)
+    ORDER BY r.app_id, r.bucket_start, r.created_at DESC, r.id DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_metrics_rollup.sample_count + EXCLUDED.sample_count,
+        download_count_min = LEAST(app_metrics_rollup.download_count_min, EXCLUDED.download_count_min),
+        download_count_max = GREATEST(app_metrics_rollup.download_count_max, EXCLUDED.download_count_max),
+        info_score_avg = ROUND(
+            (app_metrics_rollup.info_score_avg * app_metrics_rollup.sample_count
+                + EXCLUDED.info_score_avg * EXCLUDED.sample_count)
+            / (app_metrics_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_metrics_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        version = EXCLUDED.version,
+        version_code = EXCLUDED.version_code,
+        size_bytes = EXCLUDED.size_bytes,
+        sha256 = EXCLUDED.sha256,
+        info_score = EXCLUDED.info_score,
+        info_rate_count = EXCLUDED.info_rate_count,
+        download_count = EXCLUDED.download_count,
+        price = EXCLUDED.price,
+        release_date = EXCLUDED.release_date,
+        target_sdk = EXCLUDED.target_sdk,
+        minsdk = EXCLUDED.minsdk,
+        compile_sdk_version = EXCLUDED.compile_sdk_version,
+        min_hmos_api_level = EXCLUDED.min_hmos_api_level,
+        api_release_type = EXCLUDED.api_release_type;
+
+    DELETE FROM app_metrics WHERE id IN (SELECT id FROM _rollup_raw);
+    GET DIAGNOSTICS raw_rolled = ROW_COUNT;
+
+    -- 4. 按天聚合 -> 按周聚合
+    DROP TABLE IF EXISTS _rollup_daily;
+    CREATE TEMP TABLE _rollup_daily ON COMMIT DROP AS
+    SELECT d.*, date_trunc('week', d.bucket_start)::date AS week_start
+    FROM app_metrics_rollup d
+    WHERE d.granularity = 'day' AND d.bucket_start < daily_cutoff;
+
+    INSERT INTO app_metrics_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        version, version_code, size_bytes, sha256, info_score, info_rate_count,
+        download_count, price, release_date, target_sdk, minsdk, compile_sdk_version,
+        min_hmos_api_level, api_release_type,
+        download_count_min, download_count_max, info_score_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (d.app_id, d.week_start)
+        d.app_id, d.pkg_name, 'week', d.week_start, (SUM(d.sample_count) OVER p)::integer,
+        d.version, d.version_code, d.size_bytes, d.sha256, d.info_score, d.info_rate_count,
+        d.download_count, d.price, d.release_date, d.target_sdk, d.minsdk, d.compile_sdk_version,
+        d.min_hmos_api_level, d.api_release_type,
+        MIN(d.download_count_min) OVER p, MAX(d.download_count_max) OVER p,
+        ROUND(SUM(d.info_score_avg * d.sample_count) OVER p / SUM(d.sample_count) OVER p, 1),
+        MIN(d.first_created_at) OVER p, d.last_created_at
+    FROM _rollup_daily d
+    WINDOW p AS (PARTITION BY d.app_id, d.week_start)
+    ORDER BY d.app_id, d.week_start, d.last_created_at DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_metrics_rollup.sample_count + EXCLUDED.sample_count,
+        download_count_min = LEAST(app_metrics_rollup.download_count_min, EXCLUDED.download_count_min),
+        download_count_max = GREATEST(app_metrics_rollup.download_count_max, EXCLUDED.download_count_max),
+        info_score_avg = ROUND(
+            (app_metrics_rollup.info_score_avg * app_metrics_rollup.sample_count
+                + EXCLUDED.info_score_avg * EXCLUDED.sample_count)
+            / (app_metrics_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_metrics_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        version = EXCLUDED.version,
+        version_code = EXCLUDED.version_code,
+        size_bytes = EXCLUDED.size_bytes,
+        sha256 = EXCLUDED.sha256,
+        info_score = EXCLUDED.info_score,
+        info_rate_count = EXCLUDED.info_rate_count,
+        download_count = EXCLUDED.download_count,
+        price = EXCLUDED.price,
+        release_date = EXCLUDED.release_date,
+        target_sdk = EXCLUDED.target_sdk,
+        minsdk = EXCLUDED.minsdk,
+        compile_sdk_version = EXCLUDED.compile_sdk_version,
+        min_hmos_api_level = EXCLUDED.min_hmos_api_level,
+        api_release_type = EXCLUDED.api_release_type;
+
+    DELETE FROM app_metrics_rollup WHERE id IN (SELECT id FROM _rollup_daily);
+    GET DIAGNOSTICS daily_rolled = ROW_COUNT;
+
+    RETURN NEXT;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+-- 变更记录回滚脚本
+-- 变更记录可以从历史快照重新计算, 删除不会丢失原始数据
+
+DROP TABLE IF EXISTS substance_changelog;
+DROP TABLE IF EXISTS app_changelog;
+
+DELETE FROM schema_version WHERE version = 18;
@@ -0,0 +1,38 @@
+-- 原始 JSON 快照的变更记录
+-- 每条记录对应一个快照, 保存它和上一个快照之间的结构化差异, 由程序计算后写入
+
+CREATE TABLE IF NOT EXISTS app_changelog (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    app_id          TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE, -- 对应 app_info 的 app_id
+    history_id      BIGINT NOT NULL UNIQUE REFERENCES app_data_history(id) ON DELETE CASCADE, -- 新快照
+    prev_history_id BIGINT,                                         -- 上一个快照, 首个快照为 NULL
+    changes         JSONB NOT NULL DEFAULT '[]'::JSONB,             -- 差异列表 [{path, op, old, new}]
+    fields          TEXT[] NOT NULL DEFAULT '{}',                   -- 发生变化的顶层字段, 用于按字段过滤
+    change_count    INTEGER NOT NULL DEFAULT 0,                     -- 差异条数
+    created_at      TIMESTAMPTZ NOT NULL                            -- 新快照的时间
+);
+
+CREATE TABLE IF NOT EXISTS substance_changelog (
+    id              BIGSERIAL PRIMARY KEY,                          -- 主键ID
+    substance_id    TEXT NOT NULL REFERENCES substance_info(substance_id) ON DELETE CASCADE, -- 对应 substance_info
+    history_id      BIGINT NOT NULL UNIQUE REFERENCES substance_history(id) ON DELETE CASCADE, -- 新快照
+    prev_history_id BIGINT,                                         -- 上一个快照, 首个快照为 NULL
+    changes         JSONB NOT NULL DEFAULT '[]'::JSONB,             -- 差异列表 [{path, op, old, new}]
+    fields          TEXT[] NOT NULL DEFAULT '{}',                   -- 发生变化的顶层字段, 用于按字段过滤
+    change_count    INTEGER NOT NULL DEFAULT 0,                     -- 差异条数
+    created_at      TIMESTAMPTZ NOT NULL                            -- 新快照的时间
+);
+
+CREATE INDEX IF NOT EXISTS idx_app_changelog_app_id_created_at
+    ON app_changelog(app_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_changelog_created_at
+    ON app_changelog(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_changelog_fields
+    ON app_changelog USING GIN (fields);
+
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_substance_id_created_at
+    ON substance_changelog(substance_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_created_at
+    ON substance_changelog(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_substance_changelog_fields
+    ON substance_changelog USING GIN (fields);
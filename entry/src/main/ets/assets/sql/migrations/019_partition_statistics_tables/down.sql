@@ -0,0 +1,145 @@
+-- 统计表分区回滚脚本
+-- 把分区表的数据搬回普通表, 并恢复 015 中的清理函数
+
+-- ============================================================
+-- 1. 分区表改名, 释放表名和约束 / 索引名
+-- ============================================================
+ALTER TABLE access_logs RENAME TO access_logs_partitioned;
+ALTER TABLE access_logs_partitioned DROP CONSTRAINT IF EXISTS access_logs_pkey;
+DROP INDEX IF EXISTS idx_access_logs_timestamp;
+DROP INDEX IF EXISTS idx_access_logs_ip_address;
+DROP INDEX IF EXISTS idx_access_logs_user_agent;
+DROP INDEX IF EXISTS idx_access_logs_request_path;
+-- 序列跟随新表, 删除分区表时保留
+ALTER SEQUENCE access_logs_id_seq OWNED BY NONE;
+
+ALTER TABLE ua_hourly_statistics RENAME TO ua_hourly_statistics_partitioned;
+ALTER TABLE ua_hourly_statistics_partitioned DROP CONSTRAINT IF EXISTS ua_hourly_statistics_pkey;
+DROP TRIGGER IF EXISTS trigger_ua_hourly_statistics_updated_at ON ua_hourly_statistics_partitioned;
+DROP INDEX IF EXISTS idx_ua_hourly_timestamp;
+DROP INDEX IF EXISTS idx_ua_hourly_user_agent;
+DROP INDEX IF EXISTS idx_ua_hourly_access_count;
+
+ALTER TABLE ip_hourly_statistics RENAME TO ip_hourly_statistics_partitioned;
+ALTER TABLE ip_hourly_statistics_partitioned DROP CONSTRAINT IF EXISTS ip_hourly_statistics_pkey;
+DROP TRIGGER IF EXISTS trigger_ip_hourly_statistics_updated_at ON ip_hourly_statistics_partitioned;
+DROP INDEX IF EXISTS idx_ip_hourly_timestamp;
+DROP INDEX IF EXISTS idx_ip_hourly_ip_address;
+DROP INDEX IF EXISTS idx_ip_hourly_access_count;
+
+-- ============================================================
+-- 2. 普通表 (与 015 相同)
+-- ============================================================
+CREATE TABLE access_logs (
+    id BIGINT PRIMARY KEY DEFAULT nextval('access_logs_id_seq'),
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ip_address INET NOT NULL,
+    user_agent TEXT NOT NULL,
+    request_method TEXT NOT NULL,
+    request_path TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER SEQUENCE access_logs_id_seq OWNED BY access_logs.id;
+
+CREATE TABLE ua_hourly_statistics (
+    user_agent TEXT NOT NULL,
+    hour_timestamp TIMESTAMPTZ NOT NULL,
+    access_count BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_agent, hour_timestamp)
+);
+
+CREATE TABLE ip_hourly_statistics (
+    ip_address INET NOT NULL,
+    hour_timestamp TIMESTAMPTZ NOT NULL,
+    access_count BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (ip_address, hour_timestamp)
+);
+
+-- ============================================================
+-- 3. 搬回数据, 删除分区表 (连同所有分区)
+-- ============================================================
+INSERT INTO access_logs (
+    id, timestamp, ip_address, user_agent, request_method, request_path, created_at
+)
+SELECT id, timestamp, ip_address, user_agent, request_method, request_path, created_at
+FROM access_logs_partitioned;
+DROP TABLE access_logs_partitioned;
+
+INSERT INTO ua_hourly_statistics
+SELECT user_agent, hour_timestamp, access_count, created_at, updated_at
+FROM ua_hourly_statistics_partitioned;
+DROP TABLE ua_hourly_statistics_partitioned;
+
+INSERT INTO ip_hourly_statistics
+SELECT ip_address, hour_timestamp, access_count, created_at, updated_at
+FROM ip_hourly_statistics_partitioned;
+DROP TABLE ip_hourly_statistics_partitioned;
+
+-- ============================================================
+-- 4. 索引、触发器和清理函数
+-- ============================================================
+CREATE INDEX IF NOT EXISTS idx_access_logs_timestamp ON access_logs(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_access_logs_ip_address ON access_logs(ip_address);
+CREATE INDEX IF NOT EXISTS idx_access_logs_user_agent ON access_logs(user_agent);
+CREATE INDEX IF NOT EXISTS idx_access_logs_request_path ON access_logs(request_path);
+
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_timestamp ON ua_hourly_statistics(hour_timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_user_agent ON ua_hourly_statistics(user_agent);
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_access_count ON ua_hourly_statistics(access_count DESC);
+
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_timestamp ON ip_hourly_statistics(hour_timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_ip_address ON ip_hourly_statistics(ip_address);
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_access_count ON ip_hourly_statistics(access_count DESC);
+
+CREATE TRIGGER trigger_ua_hourly_statistics_updated_at
+    BEFORE UPDATE ON ua_hourly_statistics
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER trigger_ip_hourly_statistics_updated_at
+    BEFORE UPDATE ON ip_hourly_statistics
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+COMMENT ON TABLE access_logs IS '访问详细日志表';
+COMMENT ON TABLE ua_hourly_statistics IS 'User-Agent 每小时访问统计表';
+COMMENT ON TABLE ip_hourly_statistics IS 'IP 地址每小时访问统计表';
+
+CREATE OR REPLACE FUNCTION cleanup_old_access_logs(days_to_keep INTEGER DEFAULT 90)
+RETURNS INTEGER AS $$
+DECLARE
+    deleted_count INTEGER;
+BEGIN
+    DELETE FROM access_logs
+    WHERE timestamp < NOW() - INTERVAL '1 day' * days_to_keep;
+
+    GET DIAGNOSTICS deleted_count = ROW_COUNT;
+    RETURN deleted_count;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION cleanup_old_hourly_statistics(days_to_keep INTEGER DEFAULT 180)
+RETURNS INTEGER AS $$
+DECLARE
+    deleted_count INTEGER;
+    total_deleted INTEGER := 0;
+BEGIN
+    DELETE FROM ua_hourly_statistics
+    WHERE hour_timestamp < NOW() - INTERVAL '1 day' * days_to_keep;
+    GET DIAGNOSTICS deleted_count = ROW_COUNT;
+    total_deleted := total_deleted + deleted_count;
+
+    DELETE FROM ip_hourly_statistics
+    WHERE hour_timestamp < NOW() - INTERVAL '1 day' * days_to_keep;
+    GET DIAGNOSTICS deleted_count = ROW_COUNT;
+    total_deleted := total_deleted + deleted_count;
+
+    RETURN total_deleted;
+END;
+$$ LANGUAGE plpgsql;
+
+DELETE FROM schema_version WHERE version = 19;
@@ -0,0 +1,163 @@
+-- 访问日志 / 每小时统计改为按时间分区
+-- access_logs 按天分区, ua_hourly_statistics / ip_hourly_statistics 按月分区 (分区边界为 UTC 零点),
+-- 另有 DEFAULT 分区兜底. 之后分区的预建和过期删除由程序的维护任务负责 (src/db/partition.rs),
+-- 这里只建出已有数据和最近几天需要的分区
+
+-- ============================================================
+-- 1. 旧表改名, 约束 / 索引 / 触发器的名字留给新表
+-- ============================================================
+ALTER TABLE access_logs RENAME TO access_logs_legacy;
+ALTER TABLE access_logs_legacy DROP CONSTRAINT IF EXISTS access_logs_pkey;
+DROP INDEX IF EXISTS idx_access_logs_timestamp;
+DROP INDEX IF EXISTS idx_access_logs_ip_address;
+DROP INDEX IF EXISTS idx_access_logs_user_agent;
+DROP INDEX IF EXISTS idx_access_logs_request_path;
+
+ALTER TABLE ua_hourly_statistics RENAME TO ua_hourly_statistics_legacy;
+ALTER TABLE ua_hourly_statistics_legacy DROP CONSTRAINT IF EXISTS ua_hourly_statistics_pkey;
+DROP TRIGGER IF EXISTS trigger_ua_hourly_statistics_updated_at ON ua_hourly_statistics_legacy;
+DROP INDEX IF EXISTS idx_ua_hourly_timestamp;
+DROP INDEX IF EXISTS idx_ua_hourly_user_agent;
+DROP INDEX IF EXISTS idx_ua_hourly_access_count;
+
+ALTER TABLE ip_hourly_statistics RENAME TO ip_hourly_statistics_legacy;
+ALTER TABLE ip_hourly_statistics_legacy DROP CONSTRAINT IF EXISTS ip_hourly_statistics_pkey;
+DROP TRIGGER IF EXISTS trigger_ip_hourly_statistics_updated_at ON ip_hourly_statistics_legacy;
+DROP INDEX IF EXISTS idx_ip_hourly_timestamp;
+DROP INDEX IF EXISTS idx_ip_hourly_ip_address;
+DROP INDEX IF EXISTS idx_ip_hourly_access_count;
+
+-- ============================================================
+-- 2. 分区表 (主键必须包含分区键)
+-- ============================================================
+CREATE TABLE access_logs (
+    id BIGINT NOT NULL DEFAULT nextval('access_logs_id_seq'),
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ip_address INET NOT NULL,
+    user_agent TEXT NOT NULL,
+    request_method TEXT NOT NULL,
+    request_path TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id, timestamp)
+) PARTITION BY RANGE (timestamp);
+-- 沿用旧表的序列, 删除旧表时不会被一起删掉
+ALTER SEQUENCE access_logs_id_seq OWNED BY access_logs.id;
+CREATE TABLE access_logs_default PARTITION OF access_logs DEFAULT;
+
+CREATE TABLE ua_hourly_statistics (
+    user_agent TEXT NOT NULL,
+    hour_timestamp TIMESTAMPTZ NOT NULL,
+    access_count BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_agent, hour_timestamp)
+) PARTITION BY RANGE (hour_timestamp);
+CREATE TABLE ua_hourly_statistics_default PARTITION OF ua_hourly_statistics DEFAULT;
+
+CREATE TABLE ip_hourly_statistics (
+    ip_address INET NOT NULL,
+    hour_timestamp TIMESTAMPTZ NOT NULL,
+    access_count BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (ip_address, hour_timestamp)
+) PARTITION BY RANGE (hour_timestamp);
+CREATE TABLE ip_hourly_statistics_default PARTITION OF ip_hourly_statistics DEFAULT;
+
+-- ============================================================
+-- 3. 为已有数据和接下来 7 天建分区
+--    命名: <表名>_pYYYYMMDD (按天) / <表名>_pYYYYMM (按月)
+-- ============================================================
+DO $$
+DECLARE
+    today DATE := (NOW() AT TIME ZONE 'UTC')::DATE;
+    day DATE;
+    month DATE;
+    tbl TEXT;
+BEGIN
+    SELECT COALESCE(MIN((timestamp AT TIME ZONE 'UTC')::DATE), today)
+        INTO day FROM access_logs_legacy;
+    WHILE day <= today + 7 LOOP
+        EXECUTE format(
+            'CREATE TABLE %I PARTITION OF access_logs FOR VALUES FROM (%L) TO (%L)',
+            'access_logs_p' || to_char(day, 'YYYYMMDD'),
+            day::TIMESTAMP AT TIME ZONE 'UTC',
+            (day + 1)::TIMESTAMP AT TIME ZONE 'UTC'
+        );
+        day := day + 1;
+    END LOOP;
+
+    FOREACH tbl IN ARRAY ARRAY['ua_hourly_statistics', 'ip_hourly_statistics'] LOOP
+        EXECUTE format(
+            'SELECT COALESCE(MIN(date_trunc(''month'', hour_timestamp AT TIME ZONE ''UTC''))::DATE, $1)
+             FROM %I',
+            tbl || '_legacy'
+        ) INTO month USING date_trunc('month', today)::DATE;
+        WHILE month <= today + 7 LOOP
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF %I FOR VALUES FROM (%L) TO (%L)',
+                tbl || '_p' || to_char(month, 'YYYYMM'),
+                tbl,
+                month::TIMESTAMP AT TIME ZONE 'UTC',
+                (month + INTERVAL '1 month')::TIMESTAMP AT TIME ZONE 'UTC'
+            );
+            month := (month + INTERVAL '1 month')::DATE;
+        END LOOP;
+    END LOOP;
+END $$;
+
+-- ============================================================
+-- 4. 迁移数据, 删除旧表
+-- ============================================================
+INSERT INTO access_logs (
+    id, timestamp, ip_address, user_agent, request_method, request_path, created_at
+)
+SELECT id, timestamp, ip_address, user_agent, request_method, request_path, created_at
+FROM access_logs_legacy;
+DROP TABLE access_logs_legacy;
+
+INSERT INTO ua_hourly_statistics
+SELECT user_agent, hour_timestamp, access_count, created_at, updated_at
+FROM ua_hourly_statistics_legacy;
+DROP TABLE ua_hourly_statistics_legacy;
+
+INSERT INTO ip_hourly_statistics
+SELECT ip_address, hour_timestamp, access_count, created_at, updated_at
+FROM ip_hourly_statistics_legacy;
+DROP TABLE ip_hourly_statistics_legacy;
+
+-- ============================================================
+-- 5. 索引和触发器 (建在父表上, 自动应用到所有分区)
+-- ============================================================
+CREATE INDEX IF NOT EXISTS idx_access_logs_timestamp ON access_logs(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_access_logs_ip_address ON access_logs(ip_address);
+CREATE INDEX IF NOT EXISTS idx_access_logs_user_agent ON access_logs(user_agent);
+CREATE INDEX IF NOT EXISTS idx_access_logs_request_path ON access_logs(request_path);
+
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_timestamp ON ua_hourly_statistics(hour_timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_user_agent ON ua_hourly_statistics(user_agent);
+CREATE INDEX IF NOT EXISTS idx_ua_hourly_access_count ON ua_hourly_statistics(access_count DESC);
+
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_timestamp ON ip_hourly_statistics(hour_timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_ip_address ON ip_hourly_statistics(ip_address);
+CREATE INDEX IF NOT EXISTS idx_ip_hourly_access_count ON ip_hourly_statistics(access_count DESC);
+
+CREATE TRIGGER trigger_ua_hourly_statistics_updated_at
+    BEFORE UPDATE ON ua_hourly_statistics
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER trigger_ip_hourly_statistics_updated_at
+    BEFORE UPDATE ON ip_hourly_statistics
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+COMMENT ON TABLE access_logs IS '访问详细日志表 (按天分区)';
+COMMENT ON TABLE ua_hourly_statistics IS 'User-Agent 每小时访问统计表 (按月分区)';
+COMMENT ON TABLE ip_hourly_statistics IS 'IP 地址每小时访问统计表 (按月分区)';
+
+-- ============================================================
+-- 6. 逐行 DELETE 的清理函数由分区维护任务代替
+-- ============================================================
+DROP FUNCTION IF EXISTS cleanup_old_access_logs(INTEGER);
+DROP FUNCTION IF EXISTS cleanup_old_hourly_statistics(INTEGER);
@@ -0,0 +1,35 @@
+-- 原始 JSON 历史去重回滚脚本
+-- 把快照还原回历史表的 JSON 列, 然后删除 json_blob
+
+DROP TRIGGER IF EXISTS trigger_app_data_history_store_json ON app_data_history;
+DROP TRIGGER IF EXISTS trigger_substance_history_store_json ON substance_history;
+DROP FUNCTION IF EXISTS app_data_history_store_json();
+DROP FUNCTION IF EXISTS substance_history_store_json();
+
+UPDATE app_data_history SET raw_json_data = json_blob_load(raw_json_hash);
+UPDATE substance_history SET raw_json_substance = json_blob_load(raw_json_hash);
+
+ALTER TABLE app_data_history ALTER COLUMN raw_json_data SET DEFAULT '{}'::JSONB;
+ALTER TABLE app_data_history ALTER COLUMN raw_json_data SET NOT NULL;
+ALTER TABLE app_data_history DROP COLUMN raw_json_hash;
+
+ALTER TABLE substance_history ALTER COLUMN raw_json_substance SET DEFAULT '{}'::JSONB;
+ALTER TABLE substance_history ALTER COLUMN raw_json_substance SET NOT NULL;
+ALTER TABLE substance_history DROP COLUMN raw_json_hash;
+
+COMMENT ON COLUMN app_data_history.raw_json_data IS NULL;
+COMMENT ON COLUMN substance_history.raw_json_substance IS NULL;
+
+DROP INDEX IF EXISTS idx_app_data_history_app_id_created_at;
+CREATE INDEX IF NOT EXISTS idx_app_data_history_app_pkg_data
+    ON app_data_history (app_id, pkg_name, raw_json_data);
+CREATE INDEX IF NOT EXISTS idx_substance_history_raw_json_gin
+    ON substance_history USING GIN (raw_json_substance);
+
+DROP FUNCTION IF EXISTS cleanup_orphan_json_blobs();
+DROP FUNCTION IF EXISTS json_blob_load(TEXT);
+DROP FUNCTION IF EXISTS json_blob_store(JSONB);
+DROP FUNCTION IF EXISTS json_blob_put(JSONB);
+DROP TABLE IF EXISTS json_blob;
+
+DELETE FROM schema_version WHERE version = 20;
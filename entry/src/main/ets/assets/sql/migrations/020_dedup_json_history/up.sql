@@ -0,0 +1,160 @@
+-- 原始 JSON 历史按内容寻址去重
+-- app_data_history / substance_history 不再每行保存完整 JSONB, 顶层字段各自按 sha256 存进 json_blob,
+-- 快照本身是一个 {字段: hash} 的清单 (同样存在 json_blob 里), 历史表只保存清单的 hash.
+-- 只改了一个字段的相邻快照共享其余所有字段, 完全相同的快照共享同一个清单
+
+-- ============================================================
+-- 1. blob 表和读写函数
+-- ============================================================
+CREATE TABLE IF NOT EXISTS json_blob (
+    hash        TEXT PRIMARY KEY,                       -- sha256(content::text) 的十六进制
+    content     JSONB NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE json_blob IS '按内容寻址的 JSON 存储, 供原始数据历史去重';
+
+-- 存一个 blob, 返回 hash (jsonb 的文本形式是规范化的, 相同内容的 hash 一定相同)
+CREATE OR REPLACE FUNCTION json_blob_put(doc JSONB)
+RETURNS TEXT AS $$
+DECLARE
+    h TEXT := encode(sha256(convert_to(doc::text, 'UTF8')), 'hex');
+BEGIN
+    INSERT INTO json_blob (hash, content) VALUES (h, doc) ON CONFLICT (hash) DO NOTHING;
+    RETURN h;
+END;
+$$ LANGUAGE plpgsql;
+
+-- 拆分保存一个快照, 返回清单的 hash
+-- 对象: 清单为 {字段: 字段值的 hash}; 其他类型: 清单为整个值的 hash (JSON 字符串)
+CREATE OR REPLACE FUNCTION json_blob_store(doc JSONB)
+RETURNS TEXT AS $$
+BEGIN
+    IF doc IS NULL THEN
+        RETURN NULL;
+    END IF;
+    IF jsonb_typeof(doc) <> 'object' THEN
+        RETURN json_blob_put(to_jsonb(json_blob_put(doc)));
+    END IF;
+    RETURN json_blob_put(COALESCE(
+        (SELECT jsonb_object_agg(key, json_blob_put(value)) FROM jsonb_each(doc)),
+        '{}'::JSONB
+    ));
+END;
+$$ LANGUAGE plpgsql;
+
+-- 按清单 hash 还原快照
+CREATE OR REPLACE FUNCTION json_blob_load(manifest_hash TEXT)
+RETURNS JSONB AS $$
+    SELECT CASE jsonb_typeof(m.content)
+        WHEN 'string' THEN (SELECT b.content FROM json_blob b WHERE b.hash = m.content #>> '{}')
+        ELSE (
+            SELECT COALESCE(jsonb_object_agg(f.key, b.content), '{}'::JSONB)
+            FROM jsonb_each_text(m.content) f
+            JOIN json_blob b ON b.hash = f.value
+        )
+    END
+    FROM json_blob m
+    WHERE m.hash = manifest_hash
+$$ LANGUAGE sql STABLE;
+
+-- 删除不再被任何快照引用的 blob, 返回删除数量
+-- 期间会锁住两张历史表的写入, 避免删掉正在被新快照复用的 blob
+CREATE OR REPLACE FUNCTION cleanup_orphan_json_blobs()
+RETURNS INTEGER AS $$
+DECLARE
+    deleted_count INTEGER;
+BEGIN
+    LOCK TABLE app_data_history, substance_history IN SHARE MODE;
+
+    WITH manifests AS (
+        SELECT raw_json_hash AS hash FROM app_data_history
+        UNION
+        SELECT raw_json_hash FROM substance_history
+    ),
+    live AS (
+        SELECT hash FROM manifests
+        UNION
+        SELECT f.value
+        FROM manifests m
+        JOIN json_blob b ON b.hash = m.hash
+        CROSS JOIN LATERAL jsonb_each_text(
+            CASE jsonb_typeof(b.content)
+                WHEN 'object' THEN b.content
+                ELSE jsonb_build_object('', b.content)
+            END
+        ) f
+    )
+    DELETE FROM json_blob j
+    WHERE NOT EXISTS (SELECT 1 FROM live l WHERE l.hash = j.hash);
+
+    GET DIAGNOSTICS deleted_count = ROW_COUNT;
+    RETURN deleted_count;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================
+-- 2. 历史表改为保存清单 hash
+--    原来的 JSON 列保留为写入口, 由触发器拆分后置空, 写入方不需要改动
+-- ============================================================
+ALTER TABLE app_data_history ADD COLUMN IF NOT EXISTS raw_json_hash TEXT;
+ALTER TABLE app_data_history ALTER COLUMN raw_json_data DROP NOT NULL;
+ALTER TABLE app_data_history ALTER COLUMN raw_json_data DROP DEFAULT;
+
+ALTER TABLE substance_history ADD COLUMN IF NOT EXISTS raw_json_hash TEXT;
+ALTER TABLE substance_history ALTER COLUMN raw_json_substance DROP NOT NULL;
+ALTER TABLE substance_history ALTER COLUMN raw_json_substance DROP DEFAULT;
+
+-- 这两个索引直接索引了完整 JSON, 列置空后没有意义
+DROP INDEX IF EXISTS idx_app_data_history_app_pkg_data;
+DROP INDEX IF EXISTS idx_substance_history_raw_json_gin;
+
+-- 已有的行不在迁移里转换 (一条 UPDATE 改写整张表, 锁太久), 由 `blobs backfill` 分批转换,
+-- 转换完之前读取时用 COALESCE(json_blob_load(raw_json_hash), 原 JSON 列) 兼容两种布局,
+-- 全部转换完后 `blobs backfill` 会把 raw_json_hash 设为 NOT NULL
+
+COMMENT ON COLUMN app_data_history.raw_json_data IS '写入口, 触发器拆分进 json_blob 后置空';
+COMMENT ON COLUMN app_data_history.raw_json_hash IS '原始数据快照的清单 hash, 用 json_blob_load 还原';
+COMMENT ON COLUMN substance_history.raw_json_substance IS '写入口, 触发器拆分进 json_blob 后置空';
+COMMENT ON COLUMN substance_history.raw_json_hash IS '原始数据快照的清单 hash, 用 json_blob_load 还原';
+
+-- 读取最新快照按 (app_id, created_at) 走索引
+CREATE INDEX IF NOT EXISTS idx_app_data_history_app_id_created_at
+    ON app_data_history (app_id, created_at DESC);
+
+-- ============================================================
+-- 3. 写入触发器
+-- ============================================================
+CREATE OR REPLACE FUNCTION app_data_history_store_json()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.raw_json_data IS NOT NULL THEN
+        NEW.raw_json_hash := json_blob_store(NEW.raw_json_data);
+        NEW.raw_json_data := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION substance_history_store_json()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.raw_json_substance IS NOT NULL THEN
+        NEW.raw_json_hash := json_blob_store(NEW.raw_json_substance);
+        NEW.raw_json_substance := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trigger_app_data_history_store_json ON app_data_history;
+CREATE TRIGGER trigger_app_data_history_store_json
+    BEFORE INSERT OR UPDATE OF raw_json_data ON app_data_history
+    FOR EACH ROW
+    EXECUTE FUNCTION app_data_history_store_json();
+
+DROP TRIGGER IF EXISTS trigger_substance_history_store_json ON substance_history;
+CREATE TRIGGER trigger_substance_history_store_json
+    BEFORE INSERT OR UPDATE OF raw_json_substance ON substance_history
+    FOR EACH ROW
+    EXECUTE FUNCTION substance_history_store_json();
@@ -0,0 +1,7 @@
+-- API key 回滚脚本
+
+DROP INDEX IF EXISTS idx_access_logs_api_key_id;
+ALTER TABLE access_logs DROP COLUMN IF EXISTS api_key_id;
+DROP TABLE IF EXISTS api_keys;
+
+DELETE FROM schema_version WHERE version = 21;
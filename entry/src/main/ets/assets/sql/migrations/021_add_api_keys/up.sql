@@ -0,0 +1,22 @@
+-- API key
+-- 明文只在创建时返回一次, 库里只保存 sha256; key_prefix 是明文的前几位, 方便在列表里辨认
+CREATE TABLE IF NOT EXISTS api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+COMMENT ON TABLE api_keys IS 'API key, 按 scope 授权写入 / 管理 / 统计接口';
+COMMENT ON COLUMN api_keys.key_hash IS 'key 明文的 sha256 (十六进制)';
+COMMENT ON COLUMN api_keys.scopes IS '授权范围: submit, admin, stats:read';
+COMMENT ON COLUMN api_keys.revoked_at IS '吊销时间, 非空即失效';
+
+-- 访问日志记录使用的 key, 匿名请求为 NULL (不加外键, 日志按分区整块删除)
+ALTER TABLE access_logs ADD COLUMN IF NOT EXISTS api_key_id BIGINT;
+CREATE INDEX IF NOT EXISTS idx_access_logs_api_key_id
+    ON access_logs (api_key_id, timestamp DESC) WHERE api_key_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- 限流统计回滚脚本
+
+ALTER TABLE ip_hourly_statistics DROP COLUMN IF EXISTS throttled_count;
+
+DELETE FROM schema_version WHERE version = 22;
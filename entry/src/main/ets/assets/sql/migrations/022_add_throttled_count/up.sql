@@ -0,0 +1,5 @@
+-- 限流统计
+-- 被限流 (429) 的请求按 IP + 小时计数, 和 access_count 放在同一行; access_count 仍然包含这些请求
+ALTER TABLE ip_hourly_statistics ADD COLUMN IF NOT EXISTS throttled_count BIGINT NOT NULL DEFAULT 0;
+
+COMMENT ON COLUMN ip_hourly_statistics.throttled_count IS '该小时内被限流的请求数';
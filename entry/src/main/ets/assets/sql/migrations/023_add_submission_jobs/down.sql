@@ -0,0 +1,6 @@
+-- 投稿队列回滚脚本
+-- 回滚前确认队列里没有排队中的任务, 否则这些投稿会丢失
+
+DROP TABLE IF EXISTS submission_jobs;
+
+DELETE FROM schema_version WHERE version = 23;
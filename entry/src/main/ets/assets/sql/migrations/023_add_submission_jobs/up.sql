@@ -0,0 +1,23 @@
+-- 投稿队列
+-- 投稿接口只负责入队, 由程序里的 worker 取出执行; 同一个应用 / 专题同时只能有一个未完成的任务
+
+CREATE TABLE IF NOT EXISTS submission_jobs (
+    id              BIGSERIAL PRIMARY KEY,                          -- 任务ID, 投稿时返回
+    kind            TEXT NOT NULL,                                  -- app / substance
+    target          TEXT NOT NULL,                                  -- 应用: pkg_name:<包名> / app_id:<应用ID>, 专题: 专题ID
+    status          TEXT NOT NULL DEFAULT 'queued',                 -- queued / running / succeeded / failed
+    comment         JSONB,                                          -- 投稿时附带的备注
+    listed_at       TIMESTAMPTZ,                                    -- 投稿时指定的上架时间
+    api_key_id      BIGINT,                                         -- 投稿使用的 API key, 匿名为 NULL
+    attempts        INTEGER NOT NULL DEFAULT 0,                     -- 执行次数
+    result          JSONB,                                          -- 执行结果
+    error           TEXT,                                           -- 失败原因
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),             -- 入队时间
+    started_at      TIMESTAMPTZ,                                    -- 开始执行时间
+    finished_at     TIMESTAMPTZ                                     -- 结束时间
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_submission_jobs_active
+    ON submission_jobs(kind, target) WHERE status IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS idx_submission_jobs_queued
+    ON submission_jobs(id) WHERE status = 'queued';
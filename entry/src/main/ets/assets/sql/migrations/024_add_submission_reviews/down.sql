@@ -0,0 +1,7 @@
+-- 投稿审核回滚脚本
+-- 回滚后待审核的备注 / 上架时间和审核记录都会丢失
+
+DROP TABLE IF EXISTS moderation_log;
+DROP TABLE IF EXISTS submission_reviews;
+
+DELETE FROM schema_version WHERE version = 24;
@@ -0,0 +1,39 @@
+-- 投稿审核
+-- 投稿附带的 comment / listed_at 先进入待审核状态, 管理员通过后才写入 app_info / substance_info
+
+CREATE TABLE IF NOT EXISTS submission_reviews (
+    id              BIGSERIAL PRIMARY KEY,                          -- 审核ID
+    job_id          BIGINT REFERENCES submission_jobs(id) ON DELETE SET NULL, -- 对应的投稿任务
+    kind            TEXT NOT NULL,                                  -- app / substance
+    target          TEXT NOT NULL,                                  -- 同 submission_jobs.target
+    status          TEXT NOT NULL DEFAULT 'pending',                -- pending / approved / rejected
+    comment         JSONB,                                          -- 待写入的备注 (管理员可以修改)
+    listed_at       TIMESTAMPTZ,                                    -- 待写入的上架时间 (管理员可以修改)
+    submitter_ip    INET NOT NULL,                                  -- 投稿人 IP
+    submitter_ua    TEXT,                                           -- 投稿人 User-Agent
+    api_key_id      BIGINT,                                         -- 投稿使用的 API key, 匿名为 NULL
+    reviewed_by     BIGINT,                                         -- 审核人的 API key
+    review_note     TEXT,                                           -- 审核说明
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),             -- 投稿时间
+    reviewed_at     TIMESTAMPTZ                                     -- 通过 / 驳回时间
+);
+
+-- 审核操作记录, 只追加不修改
+CREATE TABLE IF NOT EXISTS moderation_log (
+    id              BIGSERIAL PRIMARY KEY,
+    review_id       BIGINT NOT NULL REFERENCES submission_reviews(id), -- 审核ID
+    action          TEXT NOT NULL,                                  -- edit / approve / reject
+    actor_key_id    BIGINT,                                         -- 操作人的 API key
+    actor_ip        INET,                                           -- 操作人 IP
+    old_value       JSONB NOT NULL,                                 -- 操作前的 status / comment / listed_at
+    new_value       JSONB NOT NULL,                                 -- 操作后的 status / comment / listed_at
+    note            TEXT,                                           -- 操作说明
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()              -- 操作时间
+);
+
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_status
+    ON submission_reviews(status, id DESC);
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_target
+    ON submission_reviews(kind, target);
+CREATE INDEX IF NOT EXISTS idx_moderation_log_review_id
+    ON moderation_log(review_id, id);
@@ -0,0 +1,7 @@
+-- 投稿任务租约回滚脚本
+-- 回滚后需要换回启动时放回所有执行中任务的旧版本程序
+
+ALTER TABLE submission_jobs DROP COLUMN IF EXISTS heartbeat_at;
+ALTER TABLE submission_jobs DROP COLUMN IF EXISTS worker_id;
+
+DELETE FROM schema_version WHERE version = 25;
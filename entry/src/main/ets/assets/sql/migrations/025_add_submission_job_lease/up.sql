@@ -0,0 +1,10 @@
+-- 投稿任务租约
+-- 执行中的任务记录取走它的 worker 和最后一次续约时间, 只有租约过期的任务才会重新排队
+ALTER TABLE submission_jobs ADD COLUMN IF NOT EXISTS worker_id TEXT;
+ALTER TABLE submission_jobs ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMPTZ;
+
+COMMENT ON COLUMN submission_jobs.worker_id IS '执行中任务所属的 worker (实例ID)';
+COMMENT ON COLUMN submission_jobs.heartbeat_at IS '执行中任务最后一次续约的时间';
+
+-- 升级前正在执行的任务没有续约时间, 按开始时间算租约
+UPDATE submission_jobs SET heartbeat_at = started_at WHERE status = 'running' AND heartbeat_at IS NULL;
@@ -0,0 +1,140 @@
+-- 应用名拼音搜索
+-- 用途: 保存应用名的全拼 / 首字母, 让 `weixin`、`wx` 这样的输入也能搜到中文名称
+-- 拼音由程序在写入 app_info 时计算, 已有数据需要执行 `search reindex` 回填
+
+-- ============================================================
+-- 1. 新增列
+-- ============================================================
+ALTER TABLE app_info ADD COLUMN IF NOT EXISTS name_pinyin TEXT;
+ALTER TABLE app_info ADD COLUMN IF NOT EXISTS name_initials TEXT;
+ALTER TABLE app_full_info ADD COLUMN IF NOT EXISTS name_pinyin TEXT;
+ALTER TABLE app_full_info ADD COLUMN IF NOT EXISTS name_initials TEXT;
+
+COMMENT ON COLUMN app_info.name_pinyin IS '应用名全拼, 小写, 不含空格';
+COMMENT ON COLUMN app_info.name_initials IS '应用名拼音首字母, 小写';
+
+-- ============================================================
+-- 2. app_info -> app_full_info 同步带上拼音列
+-- ============================================================
+CREATE OR REPLACE FUNCTION update_app_full_info_from_app_info()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- 使用 INSERT ... ON CONFLICT ... DO UPDATE 语法
+    -- 只同步基本信息，不包含冗余字段（metrics、rating）
+    INSERT INTO app_full_info (
+        app_id, alliance_app_id, name, pkg_name,
+        dev_id, developer_name, dev_en_name,
+        supplier, kind_id, kind_name,
+        tag_name, kind_type_id, kind_type_name, icon_url,
+        brief_desc, description, privacy_url, ctype,
+        detail_id, app_level, jocat_id, iap, hms,
+        tariff_type, packing_type, order_app, denpend_gms,
+        denpend_hms, force_update, img_tag, is_pay,
+        is_disciplined, is_shelves, submit_type, delete_archive,
+        charging, button_grey, app_gift, free_days,
+        pay_install_type, comment, listed_at, release_countries,
+        main_device_codes, name_pinyin, name_initials, created_at, updated_at
+    ) VALUES (
+        NEW.app_id, NEW.alliance_app_id, NEW.name, NEW.pkg_name,
+        NEW.dev_id, NEW.developer_name, NEW.dev_en_name,
+        NEW.supplier, NEW.kind_id, NEW.kind_name,
+        NEW.tag_name, NEW.kind_type_id, NEW.kind_type_name, NEW.icon_url,
+        NEW.brief_desc, NEW.description, NEW.privacy_url, NEW.ctype,
+        NEW.detail_id, NEW.app_level, NEW.jocat_id, NEW.iap, NEW.hms,
+        NEW.tariff_type, NEW.packing_type, NEW.order_app, NEW.denpend_gms,
+        NEW.denpend_hms, NEW.force_update, NEW.img_tag, NEW.is_pay,
+        NEW.is_disciplined, NEW.is_shelves, NEW.submit_type, NEW.delete_archive,
+        NEW.charging, NEW.button_grey, NEW.app_gift, NEW.free_days,
+        NEW.pay_install_type, NEW.comment, NEW.listed_at, NEW.release_countries,
+        NEW.main_device_codes, NEW.name_pinyin, NEW.name_initials, NEW.created_at, now()
+    )
+    ON CONFLICT (app_id) DO UPDATE SET
+        alliance_app_id = EXCLUDED.alliance_app_id,
+        name = EXCLUDED.name,
+        pkg_name = EXCLUDED.pkg_name,
+        dev_id = EXCLUDED.dev_id,
+        developer_name = EXCLUDED.developer_name,
+        dev_en_name = EXCLUDED.dev_en_name,
+        supplier = EXCLUDED.supplier,
+        kind_id = EXCLUDED.kind_id,
+        kind_name = EXCLUDED.kind_name,
+        tag_name = EXCLUDED.tag_name,
+        kind_type_id = EXCLUDED.kind_type_id,
+        kind_type_name = EXCLUDED.kind_type_name,
+        icon_url = EXCLUDED.icon_url,
+        brief_desc = EXCLUDED.brief_desc,
+        description = EXCLUDED.description,
+        privacy_url = EXCLUDED.privacy_url,
+        ctype = EXCLUDED.ctype,
+        detail_id = EXCLUDED.detail_id,
+        app_level = EXCLUDED.app_level,
+        jocat_id = EXCLUDED.jocat_id,
+        iap = EXCLUDED.iap,
+        hms = EXCLUDED.hms,
+        tariff_type = EXCLUDED.tariff_type,
+        packing_type = EXCLUDED.packing_type,
+        order_app = EXCLUDED.order_app,
+        denpend_gms = EXCLUDED.denpend_gms,
+        denpend_hms = EXCLUDED.denpend_hms,
+        force_update = EXCLUDED.force_update,
+        img_tag = EXCLUDED.img_tag,
+        is_pay = EXCLUDED.is_pay,
+        is_disciplined = EXCLUDED.is_disciplined,
+        is_shelves = EXCLUDED.is_shelves,
+        submit_type = EXCLUDED.submit_type,
+        delete_archive = EXCLUDED.delete_archive,
+        charging = EXCLUDED.charging,
+        button_grey = EXCLUDED.button_grey,
+        app_gift = EXCLUDED.app_gift,
+        free_days = EXCLUDED.free_days,
+        pay_install_type = EXCLUDED.pay_install_type,
+        comment = EXCLUDED.comment,
+        listed_at = EXCLUDED.listed_at,
+        release_countries = EXCLUDED.release_countries,
+        main_device_codes = EXCLUDED.main_device_codes,
+        name_pinyin = EXCLUDED.name_pinyin,
+        name_initials = EXCLUDED.name_initials,
+        created_at = EXCLUDED.created_at,
+        updated_at = now();
+    
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================
+-- 3. 全文检索向量加上拼音
+-- ============================================================
+DROP INDEX IF EXISTS idx_app_full_info_search_vector;
+DROP FUNCTION IF EXISTS app_search_vector(TEXT, TEXT, TEXT, TEXT, TEXT);
+
+CREATE OR REPLACE FUNCTION app_search_vector(
+    p_name TEXT,
+    p_brief_desc TEXT,
+    p_description TEXT,
+    p_developer_name TEXT,
+    p_kind_name TEXT,
+    p_name_pinyin TEXT,
+    p_name_initials TEXT
+) RETURNS tsvector AS $$
+    SELECT
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_name, '')), 'A') ||
+        setweight(to_tsvector('simple'::regconfig,
+            coalesce(p_name_pinyin, '') || ' ' || coalesce(p_name_initials, '')), 'A') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_developer_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_kind_name, '')), 'B') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_brief_desc, '')), 'C') ||
+        setweight(to_tsvector('simple'::regconfig, coalesce(p_description, '')), 'D')
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE;
+
+COMMENT ON FUNCTION app_search_vector(TEXT, TEXT, TEXT, TEXT, TEXT, TEXT, TEXT) IS '应用搜索用的 tsvector, 权重: 名称和名称拼音 A, 开发者/分类 B, 简介 C, 描述 D';
+
+-- ============================================================
+-- 4. 索引
+-- ============================================================
+CREATE INDEX IF NOT EXISTS idx_app_full_info_search_vector ON app_full_info
+    USING GIN (app_search_vector(
+        name, brief_desc, description, developer_name, kind_name, name_pinyin, name_initials
+    ));
+
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_pinyin_trgm ON app_full_info USING GIN (name_pinyin gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_app_full_info_name_initials_trgm ON app_full_info USING GIN (name_initials gin_trgm_ops);
@@ -0,0 +1,12 @@
+-- app_rating 降采样回滚脚本
+-- 注意: 已经压缩的数据无法还原回 app_rating, 回滚会丢弃聚合数据
+
+DROP FUNCTION IF EXISTS rollup_app_rating(INTEGER, INTEGER);
+
+DROP VIEW IF EXISTS app_rating_timeline;
+
+DROP TABLE IF EXISTS app_rating_rollup;
+
+-- update_app_full_info_from_rating 新增的提前返回对旧结构没有影响, 不回滚
+
+DELETE FROM schema_version WHERE version = 27;
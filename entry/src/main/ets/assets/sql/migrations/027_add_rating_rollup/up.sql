@@ -0,0 +1,268 @@
+-- app_rating 降采样
+-- 和 017 的 app_metrics 一样, 近期保留原始数据, 较早的数据压缩为按天 / 按周的聚合
+
+-- ============================================================================
+-- 聚合表
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS app_rating_rollup (
+    id                          BIGSERIAL PRIMARY KEY,                      -- 主键ID
+    app_id                      TEXT NOT NULL REFERENCES app_info(app_id) ON DELETE CASCADE,  -- 对应 app_info 的 app_id
+    pkg_name                    TEXT NOT NULL REFERENCES app_info(pkg_name) ON DELETE CASCADE, -- 对应 app_info 的 pkg_name
+    granularity                 TEXT NOT NULL CHECK (granularity IN ('day', 'week')), -- 聚合粒度
+    bucket_start                DATE NOT NULL,                              -- 桶起始日期（Asia/Shanghai，周以周一开始）
+    sample_count                INTEGER NOT NULL,                           -- 桶内原始记录数
+    -- 以下为桶内最后一条记录的快照
+    average_rating              NUMERIC(3,1) NOT NULL,
+    star_1_rating_count         INTEGER NOT NULL,
+    star_2_rating_count         INTEGER NOT NULL,
+    star_3_rating_count         INTEGER NOT NULL,
+    star_4_rating_count         INTEGER NOT NULL,
+    star_5_rating_count         INTEGER NOT NULL,
+    my_star_rating              INTEGER NOT NULL,
+    total_star_rating_count     INTEGER NOT NULL,
+    only_star_count             INTEGER NOT NULL,
+    full_average_rating         NUMERIC(3,1) NOT NULL,
+    source_type                 TEXT NOT NULL,
+    -- 桶内聚合
+    total_star_rating_count_min INTEGER NOT NULL,                           -- 桶内最小评分数量
+    total_star_rating_count_max INTEGER NOT NULL,                           -- 桶内最大评分数量
+    average_rating_avg          NUMERIC(3,1) NOT NULL,                      -- 桶内平均评分
+    first_created_at            TIMESTAMPTZ NOT NULL,                       -- 桶内最早记录时间
+    last_created_at             TIMESTAMPTZ NOT NULL,                       -- 桶内最晚记录时间（快照对应的时间）
+    UNIQUE (app_id, granularity, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_app_rating_rollup_app_id_last_created_at
+    ON app_rating_rollup(app_id, last_created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_app_rating_rollup_pkg_name
+    ON app_rating_rollup(pkg_name);
+
+-- ============================================================================
+-- 跨层读取视图
+-- ============================================================================
+
+-- 与 app_rating 字段一致, 聚合层的 id 为 NULL
+CREATE OR REPLACE VIEW app_rating_timeline AS
+SELECT
+    ar.id, ar.app_id, ar.pkg_name, ar.average_rating,
+    ar.star_1_rating_count, ar.star_2_rating_count, ar.star_3_rating_count,
+    ar.star_4_rating_count, ar.star_5_rating_count, ar.my_star_rating,
+    ar.total_star_rating_count, ar.only_star_count, ar.full_average_rating,
+    ar.source_type, ar.created_at,
+    'raw'::text AS tier
+FROM app_rating ar
+UNION ALL
+SELECT
+    NULL::bigint AS id, r.app_id, r.pkg_name, r.average_rating,
+    r.star_1_rating_count, r.star_2_rating_count, r.star_3_rating_count,
+    r.star_4_rating_count, r.star_5_rating_count, r.my_star_rating,
+    r.total_star_rating_count, r.only_star_count, r.full_average_rating,
+    r.source_type, r.last_created_at AS created_at,
+    r.granularity AS tier
+FROM app_rating_rollup r;
+
+-- ============================================================================
+-- 触发器函数：update_app_full_info_from_rating
+-- 变更：删除的不是该应用最新的一条时直接返回, 压缩历史数据时不必逐行刷新 app_full_info
+-- ============================================================================
+CREATE OR REPLACE FUNCTION update_app_full_info_from_rating()
+RETURNS TRIGGER AS $$
+DECLARE
+    target_app_id TEXT;
+    latest_rating_id BIGINT;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        target_app_id := OLD.app_id;
+
+        -- 还有更新的记录, app_full_info 不受影响
+        IF EXISTS (
+            SELECT 1 FROM app_rating
+            WHERE app_id = OLD.app_id AND created_at > OLD.created_at
+        ) THEN
+            RETURN OLD;
+        END IF;
+    ELSE
+        target_app_id := NEW.app_id;
+    END IF;
+
+    SELECT id
+    INTO latest_rating_id
+    FROM app_rating
+    WHERE app_id = target_app_id
+      AND (TG_OP != 'DELETE' OR id != OLD.id)
+    ORDER BY created_at DESC NULLS LAST
+    LIMIT 1;
+
+    IF latest_rating_id IS NOT NULL THEN
+        -- 有最新记录，更新 rating 字段
+        UPDATE app_full_info
+        SET
+            average_rating = ar.average_rating,
+            star_1_rating_count = ar.star_1_rating_count,
+            star_2_rating_count = ar.star_2_rating_count,
+            star_3_rating_count = ar.star_3_rating_count,
+            star_4_rating_count = ar.star_4_rating_count,
+            star_5_rating_count = ar.star_5_rating_count,
+            my_star_rating = ar.my_star_rating,
+            total_star_rating_count = ar.total_star_rating_count,
+            only_star_count = ar.only_star_count,
+            full_average_rating = ar.full_average_rating,
+            source_type = ar.source_type,
+            rating_created_at = ar.created_at,
+            updated_at = now()
+        FROM app_rating ar
+        WHERE app_full_info.app_id = ar.app_id
+          AND ar.id = latest_rating_id;
+    ELSE
+        -- 没有记录，清空 rating 字段
+        UPDATE app_full_info
+        SET
+            average_rating = NULL,
+            star_1_rating_count = NULL,
+            star_2_rating_count = NULL,
+            star_3_rating_count = NULL,
+            star_4_rating_count = NULL,
+            star_5_rating_count = NULL,
+            my_star_rating = NULL,
+            total_star_rating_count = NULL,
+            only_star_count = NULL,
+            full_average_rating = NULL,
+            source_type = NULL,
+            rating_created_at = NULL,
+            updated_at = now()
+        WHERE app_id = target_app_id;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    ELSE
+        RETURN NEW;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+-- ============================================================================
+-- 函数：rollup_app_rating
+-- 功能：把早于 raw_days 天的原始记录压缩为按天聚合, 早于 daily_days 天的按天聚合再压缩为按周聚合
+-- 说明：每个应用最新的一条原始记录永远保留 (app_full_info 依赖它)
+-- ============================================================================
+CREATE OR REPLACE FUNCTION rollup_app_rating(raw_days INTEGER DEFAULT 30, daily_days INTEGER DEFAULT 180)
+RETURNS TABLE(raw_rolled INTEGER, daily_rolled INTEGER) AS $$
+DECLARE
+    raw_cutoff TIMESTAMPTZ := NOW() - INTERVAL '1 day' * raw_days;
+    daily_cutoff DATE := ((NOW() - INTERVAL '1 day' * daily_days) AT TIME ZONE 'Asia/Shanghai')::date;
+BEGIN
+    -- 1. 待压缩的原始记录
+    DROP TABLE IF EXISTS _rollup_rating_raw;
+    CREATE TEMP TABLE _rollup_rating_raw ON COMMIT DROP AS
+    SELECT ar.*, (ar.created_at AT TIME ZONE 'Asia/Shanghai')::date AS bucket_start
+    FROM app_rating ar
+    WHERE ar.created_at < raw_cutoff
+      AND ar.id <> (
+          SELECT l.id FROM app_rating l
+          WHERE l.app_id = ar.app_id
+          ORDER BY l.created_at DESC, l.id DESC
+          LIMIT 1
+      );
+
+    -- 2. 原始记录 -> 按天聚合
+    INSERT INTO app_rating_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        average_rating, star_1_rating_count, star_2_rating_count, star_3_rating_count,
+        star_4_rating_count, star_5_rating_count, my_star_rating, total_star_rating_count,
+        only_star_count, full_average_rating, source_type,
+        total_star_rating_count_min, total_star_rating_count_max, average_rating_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (r.app_id, r.bucket_start)
+        r.app_id, r.pkg_name, 'day', r.bucket_start, COUNT(*) OVER p,
+        r.average_rating, r.star_1_rating_count, r.star_2_rating_count, r.star_3_rating_count,
+        r.star_4_rating_count, r.star_5_rating_count, r.my_star_rating, r.total_star_rating_count,
+        r.only_star_count, r.full_average_rating, r.source_type,
+        MIN(r.total_star_rating_count) OVER p, MAX(r.total_star_rating_count) OVER p,
+        ROUND(AVG(r.average_rating) OVER p, 1),
+        MIN(r.created_at) OVER p, r.created_at
+    FROM _rollup_rating_raw r
+    WINDOW p AS (PARTITION BY r.app_id, r.bucket_start)
+    ORDER BY r.app_id, r.bucket_start, r.created_at DESC, r.id DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_rating_rollup.sample_count + EXCLUDED.sample_count,
+        total_star_rating_count_min = LEAST(app_rating_rollup.total_star_rating_count_min, EXCLUDED.total_star_rating_count_min),
+        total_star_rating_count_max = GREATEST(app_rating_rollup.total_star_rating_count_max, EXCLUDED.total_star_rating_count_max),
+        average_rating_avg = ROUND(
+            (app_rating_rollup.average_rating_avg * app_rating_rollup.sample_count
+                + EXCLUDED.average_rating_avg * EXCLUDED.sample_count)
+            / (app_rating_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_rating_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        average_rating = EXCLUDED.average_rating,
+        star_1_rating_count = EXCLUDED.star_1_rating_count,
+        star_2_rating_count = EXCLUDED.star_2_rating_count,
+        star_3_rating_count = EXCLUDED.star_3_rating_count,
+        star_4_rating_count = EXCLUDED.star_4_rating_count,
+        star_5_rating_count = EXCLUDED.star_5_rating_count,
+        my_star_rating = EXCLUDED.my_star_rating,
+        total_star_rating_count = EXCLUDED.total_star_rating_count,
+        only_star_count = EXCLUDED.only_star_count,
+        full_average_rating = EXCLUDED.full_average_rating,
+        source_type = EXCLUDED.source_type;
+
+    DELETE FROM app_rating WHERE id IN (SELECT id FROM _rollup_rating_raw);
+    GET DIAGNOSTICS raw_rolled = ROW_COUNT;
+
+    -- 3. 按天聚合 -> 按周聚合
+    DROP TABLE IF EXISTS _rollup_rating_daily;
+    CREATE TEMP TABLE _rollup_rating_daily ON COMMIT DROP AS
+    SELECT d.*, date_trunc('week', d.bucket_start)::date AS week_start
+    FROM app_rating_rollup d
+    WHERE d.granularity = 'day' AND d.bucket_start < daily_cutoff;
+
+    INSERT INTO app_rating_rollup (
+        app_id, pkg_name, granularity, bucket_start, sample_count,
+        average_rating, star_1_rating_count, star_2_rating_count, star_3_rating_count,
+        star_4_rating_count, star_5_rating_count, my_star_rating, total_star_rating_count,
+        only_star_count, full_average_rating, source_type,
+        total_star_rating_count_min, total_star_rating_count_max, average_rating_avg,
+        first_created_at, last_created_at
+    )
+    SELECT DISTINCT ON (d.app_id, d.week_start)
+        d.app_id, d.pkg_name, 'week', d.week_start, (SUM(d.sample_count) OVER p)::integer,
+        d.average_rating, d.star_1_rating_count, d.star_2_rating_count, d.star_3_rating_count,
+        d.star_4_rating_count, d.star_5_rating_count, d.my_star_rating, d.total_star_rating_count,
+        d.only_star_count, d.full_average_rating, d.source_type,
+        MIN(d.total_star_rating_count_min) OVER p, MAX(d.total_star_rating_count_max) OVER p,
+        ROUND(SUM(d.average_rating_avg * d.sample_count) OVER p / SUM(d.sample_count) OVER p, 1),
+        MIN(d.first_created_at) OVER p, d.last_created_at
+    FROM _rollup_rating_daily d
+    WINDOW p AS (PARTITION BY d.app_id, d.week_start)
+    ORDER BY d.app_id, d.week_start, d.last_created_at DESC
+    ON CONFLICT (app_id, granularity, bucket_start) DO UPDATE SET
+        sample_count = app_rating_rollup.sample_count + EXCLUDED.sample_count,
+        total_star_rating_count_min = LEAST(app_rating_rollup.total_star_rating_count_min, EXCLUDED.total_star_rating_count_min),
+        total_star_rating_count_max = GREATEST(app_rating_rollup.total_star_rating_count_max, EXCLUDED.total_star_rating_count_max),
+        average_rating_avg = ROUND(
+            (app_rating_rollup.average_rating_avg * app_rating_rollup.sample_count
+                + EXCLUDED.average_rating_avg * EXCLUDED.sample_count)
+            / (app_rating_rollup.sample_count + EXCLUDED.sample_count), 1),
+        first_created_at = LEAST(app_rating_rollup.first_created_at, EXCLUDED.first_created_at),
+        -- 后压缩进来的记录总是更晚, 快照直接取新的
+        last_created_at = EXCLUDED.last_created_at,
+        average_rating = EXCLUDED.average_rating,
+        star_1_rating_count = EXCLUDED.star_1_rating_count,
+        star_2_rating_count = EXCLUDED.star_2_rating_count,
+        star_3_rating_count = EXCLUDED.star_3_rating_count,
+        star_4_rating_count = EXCLUDED.star_4_rating_count,
+        star_5_rating_count = EXCLUDED.star_5_rating_count,
+        my_star_rating = EXCLUDED.my_star_rating,
+        total_star_rating_count = EXCLUDED.total_star_rating_count,
+        only_star_count = EXCLUDED.only_star_count,
+        full_average_rating = EXCLUDED.full_average_rating,
+        source_type = EXCLUDED.source_type;
+
+    DELETE FROM app_rating_rollup WHERE id IN (SELECT id FROM _rollup_rating_daily);
+    GET DIAGNOSTICS daily_rolled = ROW_COUNT;
+
+    RETURN NEXT;
+END;
+$$ LANGUAGE plpgsql;
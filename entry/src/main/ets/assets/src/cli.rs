@@ -0,0 +1,556 @@
+//! 命令行子命令
+//!
+//! 不带子命令时照常启动服务, `-v` / `-vv` / `-d` 这类日志参数由 [`crate::utils::init_log`] 处理
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use tracing::{Level, event};
+
+use crate::{
+    config::Config,
+    db::{
+        Database,
+        api_key::ApiScope,
+        changelog::ChangelogKind,
+        export::{Dataset, ExportParams},
+        import::ImportOptions,
+        sqlite::SqliteStorage,
+        storage::{Storage, open_storage},
+    },
+};
+
+/// 子命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 启动服务 (默认)
+    Serve,
+    /// 执行数据库迁移
+    ///
+    /// `migrate check` 只检查不执行
+    Migrate { check: bool },
+    /// 检查 app_full_info 和源表是否一致
+    ///
+    /// `consistency repair` 检查后分批修复
+    Consistency { repair: bool },
+    /// 导出数据集
+    ///
+    /// `export <dataset> [format=..] [columns=a,b] [since=..] [until=..] [filter=..] [out=..]`
+    Export {
+        params: ExportParams,
+        output: Option<String>,
+    },
+    /// 从 export 导出的快照导入
+    ///
+    /// `import <目录或文件> [dataset=..] [overwrite=true] [batch_size=..]`
+    Import {
+        path: String,
+        dataset: Option<Dataset>,
+        overwrite: bool,
+        batch_size: Option<usize>,
+    },
+    /// 管理 API key
+    ApiKey(ApiKeyCommand),
+    /// 回填历史快照的变更记录
+    ///
+    /// `changelog backfill [kind=app|substance]`
+    ChangelogBackfill { kind: Option<ChangelogKind> },
+    /// 删除不再被历史快照引用的 json_blob
+    ///
+    /// `blobs cleanup`
+    BlobCleanup,
+    /// 把 020 迁移之前的历史快照分批转换成 json_blob 布局
+    ///
+    /// `blobs backfill`
+    BlobBackfill,
+    /// 给 026 迁移之前入库的应用补上名称拼音
+    ///
+    /// `search reindex`
+    SearchReindex,
+}
+
+/// `apikey` 子命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeyCommand {
+    /// `apikey create <名称> scopes=submit,stats:read`
+    Create { name: String, scopes: Vec<ApiScope> },
+    /// `apikey list`
+    List,
+    /// `apikey revoke <id>`
+    Revoke { id: i64 },
+}
+
+impl Command {
+    /// 从命令行参数解析子命令
+    pub fn from_args() -> Result<Self> {
+        let args: Vec<String> = std::env::args()
+            .skip(1)
+            .filter(|arg| !arg.starts_with('-'))
+            .collect();
+        Self::parse(&args)
+    }
+
+    pub fn parse(args: &[String]) -> Result<Self> {
+        match args.first().map(|s| s.as_str()) {
+            None | Some("serve") => Ok(Self::Serve),
+            Some("migrate") => match args.get(1).map(|s| s.as_str()) {
+                None | Some("up") => Ok(Self::Migrate { check: false }),
+                Some("check") => Ok(Self::Migrate { check: true }),
+                Some(other) => anyhow::bail!("未知的 migrate 参数: {other}, 可选: up, check"),
+            },
+            Some("consistency") => match args.get(1).map(|s| s.as_str()) {
+                None | Some("check") => Ok(Self::Consistency { repair: false }),
+                Some("repair") => Ok(Self::Consistency { repair: true }),
+                Some(other) => {
+                    anyhow::bail!("未知的 consistency 参数: {other}, 可选: check, repair")
+                }
+            },
+            Some("export") => Self::parse_export(&args[1..]),
+            Some("import") => Self::parse_import(&args[1..]),
+            Some("apikey") => Self::parse_apikey(&args[1..]),
+            Some("changelog") => Self::parse_changelog(&args[1..]),
+            Some("blobs") => match args.get(1).map(|s| s.as_str()) {
+                Some("cleanup") => Ok(Self::BlobCleanup),
+                Some("backfill") => Ok(Self::BlobBackfill),
+                Some(other) => {
+                    anyhow::bail!("未知的 blobs 参数: {other}, 可选: cleanup, backfill")
+                }
+                None => anyhow::bail!("缺少参数: blobs cleanup / blobs backfill"),
+            },
+            Some("search") => match args.get(1).map(|s| s.as_str()) {
+                Some("reindex") => Ok(Self::SearchReindex),
+                Some(other) => anyhow::bail!("未知的 search 参数: {other}, 可选: reindex"),
+                None => anyhow::bail!("缺少参数: search reindex"),
+            },
+            Some(other) => anyhow::bail!(
+                "未知的子命令: {other}, 可选: serve, migrate, consistency, export, import, apikey, changelog, blobs, search"
+            ),
+        }
+    }
+
+    fn parse_export(args: &[String]) -> Result<Self> {
+        let mut params = serde_json::Map::new();
+        let mut output = None;
+        for arg in args {
+            let Some((key, value)) = arg.split_once('=') else {
+                params.insert("dataset".to_string(), arg.clone().into());
+                continue;
+            };
+            match key {
+                "out" => output = Some(value.to_string()),
+                "dataset" | "format" | "columns" | "since" | "until" | "filter" => {
+                    params.insert(key.to_string(), value.into());
+                }
+                _ => anyhow::bail!(
+                    "未知的 export 参数: {key}, 可选: format, columns, since, until, filter, out"
+                ),
+            }
+        }
+        let params: ExportParams = serde_json::from_value(params.into())
+            .with_context(|| "export 参数不合法, format 可选: parquet, csv, ndjson")?;
+        Ok(Self::Export { params, output })
+    }
+
+    fn parse_import(args: &[String]) -> Result<Self> {
+        let mut path = None;
+        let mut dataset = None;
+        let mut overwrite = false;
+        let mut batch_size = None;
+        for arg in args {
+            let Some((key, value)) = arg.split_once('=') else {
+                path = Some(arg.clone());
+                continue;
+            };
+            match key {
+                "dataset" => {
+                    dataset = Some(
+                        Dataset::from_name(value)
+                            .ok_or_else(|| anyhow::anyhow!("未知的数据集: {value}"))?,
+                    )
+                }
+                "overwrite" => {
+                    overwrite = value
+                        .parse()
+                        .with_context(|| "overwrite 只能是 true / false")?
+                }
+                "batch_size" => {
+                    batch_size = Some(
+                        value
+                            .parse()
+                            .with_context(|| "batch_size 必须是正整数")?,
+                    )
+                }
+                _ => anyhow::bail!("未知的 import 参数: {key}, 可选: dataset, overwrite, batch_size"),
+            }
+        }
+        let path = path.ok_or_else(|| anyhow::anyhow!("缺少快照路径: import <目录或文件>"))?;
+        Ok(Self::Import {
+            path,
+            dataset,
+            overwrite,
+            batch_size,
+        })
+    }
+
+    fn parse_changelog(args: &[String]) -> Result<Self> {
+        match args.first().map(|s| s.as_str()) {
+            Some("backfill") => {}
+            Some(other) => anyhow::bail!("未知的 changelog 参数: {other}, 可选: backfill"),
+            None => anyhow::bail!("缺少参数: changelog backfill [kind=app|substance]"),
+        }
+        let mut kind = None;
+        for arg in &args[1..] {
+            kind = match arg.split_once('=') {
+                Some(("kind", "app")) => Some(ChangelogKind::App),
+                Some(("kind", "substance")) => Some(ChangelogKind::Substance),
+                _ => anyhow::bail!("未知的 changelog backfill 参数: {arg}, 可选: kind=app|substance"),
+            };
+        }
+        Ok(Self::ChangelogBackfill { kind })
+    }
+
+    fn parse_apikey(args: &[String]) -> Result<Self> {
+        let command = match args.first().map(|s| s.as_str()) {
+            None | Some("list") => ApiKeyCommand::List,
+            Some("create") => {
+                let mut name = None;
+                let mut scopes = Vec::new();
+                for arg in &args[1..] {
+                    match arg.split_once('=') {
+                        Some(("scopes", value)) => scopes = ApiScope::parse_list(value)?,
+                        Some((key, _)) => {
+                            anyhow::bail!("未知的 apikey create 参数: {key}, 可选: scopes")
+                        }
+                        None => name = Some(arg.clone()),
+                    }
+                }
+                let name = name.ok_or_else(|| {
+                    anyhow::anyhow!("缺少名称: apikey create <名称> scopes=submit,stats:read")
+                })?;
+                if scopes.is_empty() {
+                    anyhow::bail!("至少需要一个 scope, 可选: submit, admin, stats:read");
+                }
+                ApiKeyCommand::Create { name, scopes }
+            }
+            Some("revoke") => {
+                let id = args
+                    .get(1)
+                    .ok_or_else(|| anyhow::anyhow!("缺少 id: apikey revoke <id>"))?;
+                let id = id.parse().with_context(|| format!("id 必须是整数: {id}"))?;
+                ApiKeyCommand::Revoke { id }
+            }
+            Some(other) => {
+                anyhow::bail!("未知的 apikey 参数: {other}, 可选: create, list, revoke")
+            }
+        };
+        Ok(Self::ApiKey(command))
+    }
+}
+
+/// 连接数据库, 子命令共用
+pub async fn connect_db(config: &Config) -> Result<Database> {
+    event!(Level::INFO, "connecting to db");
+    let db = Database::new(config.database_url(), config.db_max_connect())
+        .await
+        .with_context(|| "无法连接数据库")?;
+    event!(Level::INFO, "connected to db");
+    Ok(db)
+}
+
+/// `migrate` 子命令
+pub async fn run_migrate(config: &Config, check: bool) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        // SQLite 用 user_version 记录版本, 不走迁移表
+        let db = SqliteStorage::new(config.database_url(), config.db_max_connect()).await?;
+        db.migrate(!check).await?;
+        println!("{}", "SQLite 数据库 schema 已是最新版本".green());
+        return Ok(());
+    }
+
+    let db = connect_db(config).await?;
+    let status = db.migrate(!check).await?;
+    if status.applied.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "数据库 schema 已是最新版本 {:?} (程序需要 {})",
+                status.current, status.latest
+            )
+            .green()
+        );
+    } else {
+        println!(
+            "{}",
+            format!(
+                "已执行迁移 {:?}, 当前版本 {:?}",
+                status.applied, status.current
+            )
+            .green()
+        );
+    }
+    Ok(())
+}
+
+/// `consistency` 子命令
+pub async fn run_consistency(config: &Config, repair: bool) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端没有 app_full_info 触发器, 不需要检查");
+    }
+
+    let db = connect_db(config).await?;
+    let report = if repair {
+        db.repair_full_info(config.sync_batch_size()).await?
+    } else {
+        db.check_full_info_consistency().await?
+    };
+
+    println!("检查了 {} 个应用", report.checked_apps);
+    if report.is_clean() {
+        println!("{}", "app_full_info 与源表一致".green());
+        return Ok(());
+    }
+    for (kind, count) in &report.summary {
+        println!("{}", format!("{kind:?}: {count}").yellow());
+    }
+    for issue in report.issues.iter().take(50) {
+        match &issue.substance_id {
+            Some(substance_id) => {
+                println!("  {:?} {substance_id} -> {}", issue.kind, issue.app_id)
+            }
+            None => println!("  {:?} {} {}", issue.kind, issue.app_id, issue.fields.join(",")),
+        }
+    }
+    if report.issues.len() > 50 {
+        println!("  ... 共 {} 条", report.issues.len());
+    }
+    if repair {
+        println!(
+            "{}",
+            format!(
+                "已重建 {} 个应用的 app_full_info, 删除 {} 条孤立的专题映射",
+                report.repaired_apps, report.removed_substance_maps
+            )
+            .green()
+        );
+    } else {
+        println!("运行 `consistency repair` 修复");
+    }
+    Ok(())
+}
+
+/// `export` 子命令
+///
+/// 边读边写文件, 不指定 `out` 时写到当前目录的 `<dataset>.<format>`
+pub async fn run_export(
+    config: &Config,
+    params: ExportParams,
+    output: Option<String>,
+) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端暂不支持导出");
+    }
+    let options = params.into_options()?;
+    let path = output.unwrap_or_else(|| options.file_name());
+
+    let db = connect_db(config).await?;
+    let mut file =
+        std::fs::File::create(&path).with_context(|| format!("无法创建导出文件 {path}"))?;
+    let rows = db
+        .export(&options, |chunk| {
+            let result = file.write_all(&chunk).map_err(anyhow::Error::from);
+            async move { result }
+        })
+        .await?;
+    file.flush()?;
+
+    println!("{}", format!("已导出 {rows} 行到 {path}").green());
+    Ok(())
+}
+
+/// `import` 子命令
+pub async fn run_import(
+    config: &Config,
+    path: &str,
+    dataset: Option<Dataset>,
+    options: ImportOptions,
+) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端暂不支持导入快照");
+    }
+
+    let db = connect_db(config).await?;
+    let reports = db
+        .import_snapshot(std::path::Path::new(path), dataset, &options)
+        .await?;
+
+    for report in &reports {
+        println!(
+            "{}",
+            format!(
+                "{} ({}): {} 行, 新增 {}, 覆盖 {}, 重复 {}, 冲突 {}, 跳过 {}",
+                report.dataset.name(),
+                report.file.display(),
+                report.rows,
+                report.inserted,
+                report.updated,
+                report.duplicates,
+                report.conflicts,
+                report.missing
+            )
+            .green()
+        );
+        for conflict in report.conflict_samples.iter().take(20) {
+            let at = conflict
+                .created_at
+                .map(|time| format!(" @ {time}"))
+                .unwrap_or_default();
+            println!(
+                "{}",
+                format!("  冲突 {}{at}: {}", conflict.id, conflict.fields.join(",")).yellow()
+            );
+        }
+        if report.conflicts > 20 {
+            println!("  ... 共 {} 条冲突", report.conflicts);
+        }
+    }
+    if !options.overwrite && reports.iter().any(|report| report.conflicts > 0) {
+        println!("冲突的行保留了库里的数据, 加 `overwrite=true` 用快照覆盖应用信息和专题");
+    }
+    Ok(())
+}
+
+/// `changelog backfill` 子命令
+///
+/// 计算升级前已有的历史快照的差异, 已经算过的跳过, 可以重复执行
+pub async fn run_changelog_backfill(config: &Config, kind: Option<ChangelogKind>) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端没有变更记录");
+    }
+
+    let db = connect_db(config).await?;
+    let kinds = match kind {
+        Some(kind) => vec![kind],
+        None => vec![ChangelogKind::App, ChangelogKind::Substance],
+    };
+    for kind in kinds {
+        let (targets, inserted) = db
+            .backfill_changelog(kind, |done, total| {
+                if done % 1000 == 0 || done == total {
+                    event!(Level::INFO, "{kind:?} 变更记录回填进度: {done}/{total}");
+                }
+            })
+            .await?;
+        println!(
+            "{}",
+            format!("{kind:?}: 回填了 {targets} 个对象, 新增 {inserted} 条变更记录").green()
+        );
+    }
+    Ok(())
+}
+
+/// `blobs cleanup` 子命令
+///
+/// 删除应用 / 专题之后执行, 期间会阻塞同步写入历史表, 最好在同步间隙运行
+pub async fn run_blob_cleanup(config: &Config) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端的历史快照不走 json_blob, 不需要清理");
+    }
+
+    let db = connect_db(config).await?;
+    let deleted = db.cleanup_orphan_json_blobs().await?;
+    println!("{}", format!("删除了 {deleted} 个没有被引用的 json_blob").green());
+    Ok(())
+}
+
+/// `blobs backfill` 子命令
+///
+/// 每批一个短事务, 中途中断后重新执行即可; 转换完后需要手动回收空间 (见 020 迁移的 README)
+pub async fn run_blob_backfill(config: &Config) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端的历史快照不走 json_blob, 不需要转换");
+    }
+
+    let db = connect_db(config).await?;
+    let converted = db.backfill_json_blobs(config.sync_batch_size()).await?;
+    println!("{}", format!("转换了 {converted} 条历史快照").green());
+    Ok(())
+}
+
+/// `search reindex` 子命令
+///
+/// 已经有拼音的应用不会重复计算, 中途中断后重新执行即可
+pub async fn run_search_reindex(config: &Config) -> Result<()> {
+    if SqliteStorage::is_sqlite_url(config.database_url()) {
+        anyhow::bail!("SQLite 后端没有应用搜索");
+    }
+
+    let db = connect_db(config).await?;
+    let updated = db.backfill_name_pinyin(config.sync_batch_size()).await?;
+    println!("{}", format!("补上了 {updated} 个应用的名称拼音").green());
+    Ok(())
+}
+
+/// `apikey` 子命令
+///
+/// 两种后端都支持, 需要先跑过 `migrate`
+pub async fn run_apikey(config: &Config, command: ApiKeyCommand) -> Result<()> {
+    let db = open_storage(config).await?;
+    match command {
+        ApiKeyCommand::Create { name, scopes } => {
+            let new_key = db.create_api_key(&name, &scopes).await?;
+            println!(
+                "{}",
+                format!("已创建 API key #{} ({name})", new_key.key.id).green()
+            );
+            println!("{}", new_key.secret.green().bold());
+            println!("{}", "明文只显示这一次, 请立即保存".yellow());
+        }
+        ApiKeyCommand::List => {
+            let keys = db.list_api_keys().await?;
+            if keys.is_empty() {
+                println!("还没有 API key, 用 `apikey create <名称> scopes=..` 创建");
+                return Ok(());
+            }
+            let requests: std::collections::HashMap<i64, i64> =
+                db.count_api_key_requests().await?.into_iter().collect();
+            for key in keys {
+                let scopes: Vec<&str> = key.scopes.iter().map(ApiScope::name).collect();
+                let last_used = key
+                    .last_used_at
+                    .map(|time| time.to_rfc3339())
+                    .unwrap_or_else(|| "从未使用".to_string());
+                let line = format!(
+                    "#{} {} {}... [{}] 创建于 {} 最后使用 {} 请求 {} 次",
+                    key.id,
+                    key.name,
+                    key.key_prefix,
+                    scopes.join(","),
+                    key.created_at.to_rfc3339(),
+                    last_used,
+                    requests.get(&key.id).copied().unwrap_or(0)
+                );
+                match key.revoked_at {
+                    Some(revoked_at) => {
+                        println!("{}", format!("{line} (已于 {revoked_at} 吊销)").dimmed())
+                    }
+                    None => println!("{line}"),
+                }
+            }
+        }
+        ApiKeyCommand::Revoke { id } => {
+            if db.revoke_api_key(id).await? {
+                // Postgres 会通知运行中的服务清掉缓存, SQLite 只能等缓存过期
+                let effective = if SqliteStorage::is_sqlite_url(config.database_url()) {
+                    "运行中的服务最多 30 秒后生效"
+                } else {
+                    "运行中的服务立即生效"
+                };
+                println!("{}", format!("已吊销 API key #{id}, {effective}").green());
+            } else {
+                anyhow::bail!("API key #{id} 不存在或已经吊销");
+            }
+        }
+    }
+    Ok(())
+}
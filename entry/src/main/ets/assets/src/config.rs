@@ -3,6 +3,8 @@ use serde::Deserialize;
 use std::{fs, sync::OnceLock};
 use tracing::{Level, event};
 
+use crate::db::api_key::ApiScope;
+
 pub static GLOBAL_CONFIG: OnceLock<Config> = OnceLock::new();
 
 pub fn get_config() -> &'static Config {
@@ -13,6 +15,33 @@ fn default_db_max_limit() -> u32 {
     100
 }
 
+fn default_auto_migrate() -> bool {
+    true
+}
+
+/// 原始 app_metrics 最少保留 7 天, 下载增量统计要用到
+const MIN_METRICS_RAW_DAYS: u32 = 7;
+
+fn default_metrics_raw_days() -> u32 {
+    30
+}
+
+fn default_metrics_daily_days() -> u32 {
+    180
+}
+
+fn default_metrics_rollup_interval() -> u64 {
+    86400
+}
+
+fn default_replica_max_lag() -> u64 {
+    30
+}
+
+fn default_replica_check_interval() -> u64 {
+    10
+}
+
 fn default_sync_batch_size() -> usize {
     100
 }
@@ -33,12 +62,183 @@ fn default_statistics_enable_detailed_logs() -> bool {
     true
 }
 
+fn default_query_cache_ttl() -> u64 {
+    60
+}
+
+fn default_query_cache_max_entries() -> usize {
+    1000
+}
+
+fn default_access_log_retention_days() -> u32 {
+    90
+}
+
+fn default_hourly_statistics_retention_days() -> u32 {
+    180
+}
+
+fn default_statistics_maintenance_interval() -> u64 {
+    3600
+}
+
+fn default_submission_workers() -> usize {
+    2
+}
+
+fn default_submission_poll_interval() -> u64 {
+    5
+}
+
+fn default_submission_lease() -> u64 {
+    120
+}
+
+fn default_graphql_max_complexity() -> usize {
+    1000
+}
+
+fn default_graphql_max_depth() -> usize {
+    10
+}
+
+fn default_feishu_signature_max_age() -> u64 {
+    300
+}
+
+fn default_rate_limit_enabled() -> bool {
+    true
+}
+
+fn default_rate_limit_by_api_key() -> bool {
+    true
+}
+
+fn default_rate_limit_max_clients() -> usize {
+    100000
+}
+
+/// 令牌桶预算: 最多攒 `burst` 个令牌, 每分钟补 `per_minute` 个
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub struct RateBudget {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+/// 单独计数的接口, 按路径前缀匹配
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct RouteRateLimit {
+    /// 预算名, 和内置的同名时覆盖内置预算
+    pub name: String,
+    /// 路径前缀, 例如 `/api/v0/submit`
+    pub paths: Vec<String>,
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RateLimitConfig {
+    /// 是否启用限流
+    #[serde(default = "default_rate_limit_enabled")]
+    pub enabled: bool,
+    /// 带有效 API key 的请求按 key 计数, 否则一律按 IP
+    #[serde(default = "default_rate_limit_by_api_key")]
+    pub by_api_key: bool,
+    /// 其余 `/api` 接口共用的预算
+    #[serde(default = "crate::server::rate_limit::default_budget")]
+    pub default: RateBudget,
+    /// 单独计数的接口, 会和内置的 submit / download_increase / app_list 合并
+    #[serde(default)]
+    pub routes: Vec<RouteRateLimit>,
+    /// 内存里最多保留多少个令牌桶, 超出时先清掉已经补满的
+    #[serde(default = "default_rate_limit_max_clients")]
+    pub max_clients: usize,
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        let budgets = std::iter::once(("default", self.default.burst, self.default.per_minute))
+            .chain(
+                self.routes
+                    .iter()
+                    .map(|route| (route.name.as_str(), route.burst, route.per_minute)),
+            );
+        for (name, burst, per_minute) in budgets {
+            if burst == 0 || per_minute == 0 {
+                anyhow::bail!("限流预算 {name} 的 burst 和 per_minute 必须大于 0");
+            }
+        }
+        if let Some(route) = self.routes.iter().find(|route| route.paths.is_empty()) {
+            anyhow::bail!("限流预算 {} 没有配置 paths", route.name);
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_rate_limit_enabled(),
+            by_api_key: default_rate_limit_by_api_key(),
+            default: crate::server::rate_limit::default_budget(),
+            routes: Vec::new(),
+            max_clients: default_rate_limit_max_clients(),
+        }
+    }
+}
+
+/// 飞书多维表格数据连接器
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeishuConfig {
+    /// 校验请求签名用的密钥, 和飞书开发者后台里配置的一致. 不配置时拒绝取数请求
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// 请求时间戳和服务器时间最多相差多少秒, 超出视为重放
+    #[serde(default = "default_feishu_signature_max_age")]
+    pub signature_max_age_seconds: u64,
+    /// 数据源配置页面地址, 写进 meta.json 的 `extraData.dataSourceConfigUiUri`
+    #[serde(default)]
+    pub config_ui_url: Option<String>,
+}
+
+impl Default for FeishuConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            signature_max_age_seconds: default_feishu_signature_max_age(),
+            config_ui_url: None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DatabaseConfig {
+    /// 数据库连接串, `postgres://...` 或 `sqlite://data.db` (单文件, 部分功能不可用)
     pub url: String,
     pub max_connect: u32,
     #[serde(default = "default_db_max_limit")]
     pub max_limit: u32,
+    /// 启动时是否自动执行待执行的迁移, 关闭后有待执行迁移会拒绝启动
+    #[serde(default = "default_auto_migrate")]
+    pub auto_migrate: bool,
+    /// app_metrics / app_rating 原始记录保留天数, 更早的压缩为按天聚合
+    #[serde(default = "default_metrics_raw_days")]
+    pub metrics_raw_days: u32,
+    /// 按天聚合保留天数, 更早的合并为按周聚合
+    #[serde(default = "default_metrics_daily_days")]
+    pub metrics_daily_days: u32,
+    /// 降采样执行间隔 (秒), 0 表示不执行
+    #[serde(default = "default_metrics_rollup_interval")]
+    pub metrics_rollup_interval_seconds: u64,
+    /// 只读副本连接串, 看板查询会分摊到这些副本上
+    #[serde(default)]
+    pub replica_urls: Vec<String>,
+    /// 副本复制延迟超过这个秒数就暂时不用, 回落到主库
+    #[serde(default = "default_replica_max_lag")]
+    pub replica_max_lag_seconds: u64,
+    /// 副本状态检查间隔 (秒)
+    #[serde(default = "default_replica_check_interval")]
+    pub replica_check_interval_seconds: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -76,6 +276,45 @@ pub struct ServeConfig {
     /// 是否启用详细访问日志
     #[serde(default = "default_statistics_enable_detailed_logs")]
     pub statistics_enable_detailed_logs: bool,
+    /// 看板查询缓存有效期 (秒), 0 表示不缓存
+    #[serde(default = "default_query_cache_ttl")]
+    pub query_cache_ttl_seconds: u64,
+    /// 看板查询缓存条目上限
+    #[serde(default = "default_query_cache_max_entries")]
+    pub query_cache_max_entries: usize,
+    /// 访问日志保留天数, 0 表示永久保留
+    #[serde(default = "default_access_log_retention_days")]
+    pub access_log_retention_days: u32,
+    /// 每小时统计保留天数, 0 表示永久保留
+    #[serde(default = "default_hourly_statistics_retention_days")]
+    pub hourly_statistics_retention_days: u32,
+    /// 统计表分区维护间隔 (秒), 0 表示不维护
+    #[serde(default = "default_statistics_maintenance_interval")]
+    pub statistics_maintenance_interval_seconds: u64,
+    /// 不带 API key 也允许的 scope, 例如 `["submit"]` 保持投稿开放; 不能包含 admin
+    #[serde(default)]
+    pub anonymous_scopes: Vec<String>,
+    /// 按客户端限流
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// 投稿队列 worker 数量, 0 表示这个实例不处理投稿; 多个实例可以同时开启
+    #[serde(default = "default_submission_workers")]
+    pub submission_workers: usize,
+    /// 投稿队列空闲时的轮询间隔 (秒), 同实例入队会立即唤醒 worker
+    #[serde(default = "default_submission_poll_interval")]
+    pub submission_poll_interval_seconds: u64,
+    /// 执行中投稿任务的租约 (秒), 超过这么久没有续约的任务重新排队
+    #[serde(default = "default_submission_lease")]
+    pub submission_lease_seconds: u64,
+    /// 飞书数据连接器
+    #[serde(default)]
+    pub feishu: FeishuConfig,
+    /// GraphQL 查询复杂度上限, 列表字段按请求的数量放大
+    #[serde(default = "default_graphql_max_complexity")]
+    pub graphql_max_complexity: usize,
+    /// GraphQL 查询嵌套深度上限
+    #[serde(default = "default_graphql_max_depth")]
+    pub graphql_max_depth: usize,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -94,10 +333,32 @@ impl Config {
         let config: Config =
             toml::from_str(&config_content).with_context(|| "无法解析 config.toml 配置文件")?;
         event!(Level::INFO, "config.toml parsed");
+        let anonymous = ApiScope::parse_list(&config.serve.anonymous_scopes.join(","))
+            .with_context(|| "serve.anonymous_scopes 配置不合法")?;
+        // 管理接口可以修改 / 导出整个数据集, 不允许对匿名请求开放
+        if anonymous.contains(&ApiScope::Admin) {
+            anyhow::bail!("serve.anonymous_scopes 不能包含 admin");
+        }
+        config
+            .serve
+            .rate_limit
+            .validate()
+            .with_context(|| "serve.rate_limit 配置不合法")?;
         // 用配置文件初始化
         crate::db::query::SELECT_MAX_LIMIT.get_or_init(|| config.database.max_limit);
         crate::server::statistics::MAX_UA_ENTRIES.get_or_init(|| config.serve.max_ua_entries);
         crate::server::statistics::MAX_IP_ENTRIES.get_or_init(|| config.serve.max_ip_entries);
+        crate::server::cache::QUERY_CACHE.get_or_init(|| {
+            crate::server::cache::QueryCache::new(
+                std::time::Duration::from_secs(config.serve.query_cache_ttl_seconds),
+                config.serve.query_cache_max_entries,
+            )
+        });
+        if config.serve.rate_limit.enabled {
+            crate::server::rate_limit::RATE_LIMITER.get_or_init(|| {
+                crate::server::rate_limit::RateLimiter::new(&config.serve.rate_limit)
+            });
+        }
         Ok(GLOBAL_CONFIG.get_or_init(|| config))
     }
 
@@ -109,6 +370,34 @@ impl Config {
         self.database.max_connect
     }
 
+    pub fn db_auto_migrate(&self) -> bool {
+        self.database.auto_migrate
+    }
+
+    pub fn metrics_raw_days(&self) -> u32 {
+        self.database.metrics_raw_days.max(MIN_METRICS_RAW_DAYS)
+    }
+
+    pub fn metrics_daily_days(&self) -> u32 {
+        self.database.metrics_daily_days.max(self.metrics_raw_days())
+    }
+
+    pub fn metrics_rollup_interval(&self) -> u64 {
+        self.database.metrics_rollup_interval_seconds
+    }
+
+    pub fn db_replica_urls(&self) -> &[String] {
+        &self.database.replica_urls
+    }
+
+    pub fn db_replica_max_lag(&self) -> u64 {
+        self.database.replica_max_lag_seconds
+    }
+
+    pub fn db_replica_check_interval(&self) -> u64 {
+        self.database.replica_check_interval_seconds
+    }
+
     pub fn packages(&self) -> &[String] {
         &self.app.packages
     }
@@ -156,4 +445,54 @@ impl Config {
     pub fn statistics_enable_detailed_logs(&self) -> bool {
         self.serve.statistics_enable_detailed_logs
     }
+
+    pub fn query_cache_ttl(&self) -> u64 {
+        self.serve.query_cache_ttl_seconds
+    }
+
+    pub fn query_cache_max_entries(&self) -> usize {
+        self.serve.query_cache_max_entries
+    }
+
+    pub fn access_log_retention_days(&self) -> u32 {
+        self.serve.access_log_retention_days
+    }
+
+    pub fn hourly_statistics_retention_days(&self) -> u32 {
+        self.serve.hourly_statistics_retention_days
+    }
+
+    pub fn statistics_maintenance_interval(&self) -> u64 {
+        self.serve.statistics_maintenance_interval_seconds
+    }
+
+    pub fn submission_workers(&self) -> usize {
+        self.serve.submission_workers
+    }
+
+    pub fn submission_poll_interval(&self) -> u64 {
+        self.serve.submission_poll_interval_seconds.max(1)
+    }
+
+    /// 至少 15 秒, 续约间隔是租约的 1/3
+    pub fn submission_lease(&self) -> u64 {
+        self.serve.submission_lease_seconds.max(15)
+    }
+
+    pub fn graphql_max_complexity(&self) -> usize {
+        self.serve.graphql_max_complexity
+    }
+
+    pub fn graphql_max_depth(&self) -> usize {
+        self.serve.graphql_max_depth
+    }
+
+    pub fn feishu(&self) -> &FeishuConfig {
+        &self.serve.feishu
+    }
+
+    /// 不认识的 scope 在加载配置时就会报错, 这里直接忽略
+    pub fn anonymous_scopes(&self) -> Vec<ApiScope> {
+        ApiScope::from_names(&self.serve.anonymous_scopes)
+    }
 }
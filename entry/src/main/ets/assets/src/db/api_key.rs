@@ -0,0 +1,295 @@
+//! API key
+//!
+//! 明文只在创建时返回一次, 库里只保存 sha256 和明文的前几位 (方便在列表里辨认).
+//! 校验时把请求带来的明文算一遍 sha256 再查表.
+//! Postgres 吊销 key 时在 [`API_KEY_REVOKED_CHANNEL`] 上发 NOTIFY, 运行中的服务收到后清掉缓存
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, Row, postgres::PgListener};
+use utoipa::ToSchema;
+
+use crate::db::Database;
+
+/// 明文前缀, 方便在日志 / 配置里一眼认出是 key
+const KEY_PREFIX: &str = "hmk_";
+
+/// 列表里显示的明文长度 (含前缀)
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// 吊销 key 时 NOTIFY 的频道, payload 是 key 的 id
+pub const API_KEY_REVOKED_CHANNEL: &str = "api_key_revoked";
+
+/// 授权范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum ApiScope {
+    /// 投稿应用 / 专题
+    #[serde(rename = "submit")]
+    Submit,
+    /// 管理接口, 同时拥有其它所有 scope
+    #[serde(rename = "admin")]
+    Admin,
+    /// 读取访问日志
+    #[serde(rename = "stats:read")]
+    StatsRead,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 3] = [Self::Submit, Self::Admin, Self::StatsRead];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Submit => "submit",
+            Self::Admin => "admin",
+            Self::StatsRead => "stats:read",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.name() == name)
+    }
+
+    /// 解析逗号分隔的 scope 列表, 去重
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>> {
+        let mut scopes = Vec::new();
+        for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let scope = Self::from_name(name).ok_or_else(|| {
+                anyhow::anyhow!("未知的 scope: {name}, 可选: submit, admin, stats:read")
+            })?;
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        Ok(scopes)
+    }
+
+    /// 读库时用, 不认识的 scope 直接忽略 (可能是新版本程序写入的)
+    pub(crate) fn from_names<S: AsRef<str>>(names: &[S]) -> Vec<Self> {
+        names
+            .iter()
+            .filter_map(|name| Self::from_name(name.as_ref()))
+            .collect()
+    }
+}
+
+/// API key 记录 (不含明文)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// 明文的前几位
+    pub key_prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    /// 是否拥有某个 scope, admin 拥有全部
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.revoked_at.is_none()
+            && (self.scopes.contains(&scope) || self.scopes.contains(&ApiScope::Admin))
+    }
+}
+
+/// 新建的 key, `secret` 是明文, 只有这一次能拿到
+#[derive(Debug, Clone)]
+pub struct NewApiKey {
+    pub key: ApiKey,
+    pub secret: String,
+}
+
+/// 生成新的 key 明文
+pub fn generate_secret() -> String {
+    format!(
+        "{KEY_PREFIX}{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// key 明文的 sha256 (十六进制)
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// 列表里显示的明文前缀
+pub fn display_prefix(secret: &str) -> String {
+    secret.chars().take(DISPLAY_PREFIX_LEN).collect()
+}
+
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: i64,
+    name: String,
+    key_prefix: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            key_prefix: row.key_prefix,
+            scopes: ApiScope::from_names(&row.scopes),
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+const SELECT_API_KEY_FIELDS: &str =
+    "id, name, key_prefix, scopes, created_at, last_used_at, revoked_at";
+
+impl Database {
+    /// 新建 key
+    pub async fn create_api_key(&self, name: &str, scopes: &[ApiScope]) -> Result<NewApiKey> {
+        let secret = generate_secret();
+        let names: Vec<&str> = scopes.iter().map(ApiScope::name).collect();
+        let row: ApiKeyRow = sqlx::query_as(&format!(
+            "INSERT INTO api_keys (name, key_prefix, key_hash, scopes) VALUES ($1, $2, $3, $4)
+             RETURNING {SELECT_API_KEY_FIELDS}"
+        ))
+        .bind(name)
+        .bind(display_prefix(&secret))
+        .bind(hash_secret(&secret))
+        .bind(&names)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(NewApiKey {
+            key: row.into(),
+            secret,
+        })
+    }
+
+    /// 所有 key, 包括已吊销的
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_API_KEY_FIELDS} FROM api_keys ORDER BY id"
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    /// 吊销 key, 不存在或已经吊销时返回 false
+    ///
+    /// 同一条语句里发 NOTIFY, 提交后监听的服务立即清掉这个 key 的缓存
+    pub async fn revoke_api_key(&self, id: i64) -> Result<bool> {
+        let revoked = sqlx::query(
+            "WITH revoked AS (
+                UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL
+                RETURNING id
+            )
+            SELECT pg_notify($2, id::text) FROM revoked",
+        )
+        .bind(id)
+        .bind(API_KEY_REVOKED_CHANNEL)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(!revoked.is_empty())
+    }
+
+    /// 监听 key 吊销通知, 见 [`API_KEY_REVOKED_CHANNEL`]
+    pub async fn listen_api_key_revocations(&self) -> Result<PgListener> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(API_KEY_REVOKED_CHANNEL).await?;
+        Ok(listener)
+    }
+
+    /// 按明文的 sha256 查找未吊销的 key
+    ///
+    /// 校验请求时调用, 走主库, 吊销立即生效
+    pub async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let row: Option<ApiKeyRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_API_KEY_FIELDS} FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL"
+        ))
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(ApiKey::from))
+    }
+
+    /// 批量更新 key 的最后使用时间, 随统计数据一起定期写入
+    pub async fn touch_api_keys(&self, usages: &[(i64, DateTime<Utc>)]) -> Result<u64> {
+        if usages.is_empty() {
+            return Ok(0);
+        }
+        let (ids, times): (Vec<i64>, Vec<DateTime<Utc>>) = usages.iter().copied().unzip();
+        let result = sqlx::query(
+            "UPDATE api_keys AS k SET last_used_at = GREATEST(k.last_used_at, u.used_at)
+             FROM unnest($1::BIGINT[], $2::TIMESTAMPTZ[]) AS u(id, used_at)
+             WHERE k.id = u.id",
+        )
+        .bind(&ids)
+        .bind(&times)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// 每个 key 的请求数 (访问日志里的记录数)
+    pub async fn count_api_key_requests(&self) -> Result<Vec<(i64, i64)>> {
+        let rows = sqlx::query(
+            "SELECT api_key_id, COUNT(*) AS requests FROM access_logs
+             WHERE api_key_id IS NOT NULL GROUP BY api_key_id",
+        )
+        .fetch_all(self.read_pool())
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("api_key_id"), row.get("requests")))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_list_and_admin() {
+        let scopes = ApiScope::parse_list("submit, stats:read,submit").unwrap();
+        assert_eq!(scopes, vec![ApiScope::Submit, ApiScope::StatsRead]);
+        assert!(ApiScope::parse_list("submit,write").is_err());
+        // serde 名字和命令行里的一致
+        assert_eq!(
+            serde_json::to_value(ApiScope::StatsRead).unwrap(),
+            serde_json::json!("stats:read")
+        );
+
+        let mut key = ApiKey {
+            id: 1,
+            name: "test".to_string(),
+            key_prefix: "hmk_12345678".to_string(),
+            scopes: vec![ApiScope::Admin],
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        // admin 拥有全部 scope
+        assert!(ApiScope::ALL.iter().all(|scope| key.allows(*scope)));
+        key.revoked_at = Some(Utc::now());
+        assert!(!key.allows(ApiScope::Submit));
+    }
+
+    #[test]
+    fn secret_hash_is_stable() {
+        let secret = generate_secret();
+        assert!(secret.starts_with(KEY_PREFIX));
+        assert_eq!(display_prefix(&secret).len(), DISPLAY_PREFIX_LEN);
+        assert_eq!(hash_secret(&secret), hash_secret(&secret));
+        assert_eq!(
+            hash_secret("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! 应用数据的事务化 / 批量保存
+//!
+//! 一批应用先用几条 `= ANY($1)` 查询把各自最新的 app_info / app_metrics /
+//! app_rating / 原始 JSON 读出来, 在内存里比较出要写哪些行,
+//! 然后在同一个事务里写入. 中途失败整批回滚, 不会出现 app_info 更新了但没有历史记录的情况
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local};
+use serde_json::Value as JsonValue;
+use sqlx::{PgConnection, Row};
+use tracing::{Level, event};
+
+use crate::{
+    db::{
+        Database, changelog,
+        insert::MetadataOverwrite,
+        read_data::{SELECT_APP_METRIC_FIELDS, SELECT_APP_RATING_FIELDS},
+    },
+    model::{AppInfo, AppMetric, AppRating, AppRecord, FullAppInfo, raw::RawAppData},
+};
+
+/// app_info 表自己的列 (`SELECT_APP_INFO_FIELDS` 是给 app_full_info 用的, 多了指标和评分)
+pub(crate) const APP_INFO_COLUMNS: &str = r#"
+    app_id, alliance_app_id, name, pkg_name,
+    dev_id, developer_name, dev_en_name,
+    supplier, kind_id, kind_name,
+    tag_name, kind_type_id, kind_type_name, icon_url,
+    brief_desc, description, privacy_url, ctype,
+    detail_id, app_level, jocat_id, iap, hms,
+    tariff_type, packing_type, order_app, denpend_gms,
+    denpend_hms, force_update, img_tag, is_pay,
+    is_disciplined, is_shelves, submit_type, delete_archive,
+    charging, button_grey, app_gift, free_days,
+    pay_install_type, created_at, listed_at, comment,
+    release_countries, main_device_codes
+"#;
+
+/// 待保存的一个应用
+#[derive(Debug, Clone)]
+pub struct PendingApp {
+    pub data: RawAppData,
+    pub listed_at: Option<DateTime<Local>>,
+    pub comment: Option<JsonValue>,
+}
+
+impl PendingApp {
+    pub fn new(
+        data: RawAppData,
+        listed_at: Option<DateTime<Local>>,
+        comment: Option<JsonValue>,
+    ) -> Self {
+        Self {
+            data,
+            listed_at,
+            comment,
+        }
+    }
+}
+
+/// 应用信息是否没有变化, created_at / listed_at / comment 不算数据变化
+///
+/// 下面三个比较函数保存和导入快照共用
+pub(crate) fn same_app_info(new: &AppInfo, last: &AppInfo) -> bool {
+    let mut cmp = new.clone();
+    cmp.update_from_db(last);
+    cmp == *last
+}
+
+/// 指标是否没有变化, 不比较 id / created_at
+pub(crate) fn same_app_metric(new: &AppMetric, last: &AppMetric) -> bool {
+    let mut cmp = new.clone();
+    cmp.update_from_db(last);
+    cmp == *last
+}
+
+/// 评分是否没有变化, 不比较 id / created_at
+pub(crate) fn same_app_rating(new: &AppRating, last: &AppRating) -> bool {
+    let mut cmp = new.clone();
+    cmp.update_from_db(last);
+    cmp == *last
+}
+
+/// 数据库中某个应用当前最新的状态
+#[derive(Debug, Default, Clone)]
+struct LastAppState {
+    raw_json: Option<JsonValue>,
+    info: Option<AppInfo>,
+    metric: Option<AppMetric>,
+    rating: Option<AppRating>,
+}
+
+/// 比较之后需要写入的内容
+#[derive(Debug, Default)]
+struct SavePlan {
+    info: Option<AppInfo>,
+    metric: Option<AppMetric>,
+    rating: Option<AppRating>,
+    record: Option<AppRecord>,
+    /// 写 info 时覆盖哪些人工维护的字段, 只有明确传入的才覆盖
+    overwrite: MetadataOverwrite,
+    /// 有 info 或 metric 更新时记录原始 JSON
+    history: bool,
+}
+
+impl SavePlan {
+    /// 和 `save_app_data` 原来逐条比较的规则一致:
+    /// - 原始 JSON 完全相同时跳过 info 和 metric
+    /// - 其余字段相同但明确传入的 listed_at / comment 不同时也更新 info
+    /// - 评分和备案单独处理
+    fn build(last: &LastAppState, app: &PendingApp) -> Self {
+        let raw_data = &app.data.app_info;
+        let mut plan = SavePlan {
+            overwrite: MetadataOverwrite::explicit(app.listed_at, app.comment.as_ref()),
+            ..Default::default()
+        };
+
+        let same_raw = last.info.is_some()
+            && last
+                .raw_json
+                .as_ref()
+                .is_some_and(|last| *last == app.data.app_info_json);
+        if !same_raw {
+            let mut app_info: AppInfo = raw_data.into();
+            app_info.comment = app.comment.clone();
+            if let Some(listed_at) = app.listed_at {
+                app_info.listed_at = listed_at;
+            }
+            // 明确传了 listed_at / comment 时, 它们变了也要更新
+            let same_info = last.info.as_ref().is_some_and(|last| {
+                same_app_info(&app_info, last)
+                    && app.listed_at.is_none_or(|listed_at| listed_at == last.listed_at)
+                    && app
+                        .comment
+                        .as_ref()
+                        .is_none_or(|comment| last.comment.as_ref() == Some(comment))
+            });
+            if !same_info {
+                plan.info = Some(app_info);
+            }
+
+            let app_metric = AppMetric::from_raw_data(raw_data);
+            let same_metric = last
+                .metric
+                .as_ref()
+                .is_some_and(|last| same_app_metric(&app_metric, last));
+            if !same_metric {
+                plan.metric = Some(app_metric);
+            }
+        }
+
+        if let Some(raw_star) = app.data.app_rating.as_ref() {
+            let app_rating = AppRating::from_raw_star(raw_data, raw_star);
+            let is_new = last
+                .rating
+                .as_ref()
+                .is_none_or(|last| !same_app_rating(&app_rating, last));
+            if is_new {
+                plan.rating = Some(app_rating);
+            }
+        }
+
+        if let Some(record) = app.data.app_record.as_ref() {
+            plan.record = Some(AppRecord::from_raw_record(&app.data.app_id(), record));
+        }
+
+        plan.history = plan.info.is_some() || plan.metric.is_some();
+        plan
+    }
+
+    /// 写入之后这个应用的最新状态, 同一批里出现重复应用时给后面的比较用
+    fn apply(&self, last: &mut LastAppState, raw_json: &JsonValue) {
+        if let Some(info) = &self.info {
+            let mut info = info.clone();
+            if let Some(prev) = &last.info {
+                info.created_at = prev.created_at;
+                self.overwrite.merge(&mut info, prev);
+            }
+            last.info = Some(info);
+        }
+        if let Some(metric) = &self.metric {
+            last.metric = Some(metric.clone());
+        }
+        if let Some(rating) = &self.rating {
+            last.rating = Some(rating.clone());
+        }
+        if self.history {
+            last.raw_json = Some(raw_json.clone());
+        }
+    }
+}
+
+impl Database {
+    /// 一次性读出一批应用的最新状态
+    ///
+    /// app_info 行会加 `FOR UPDATE` 锁 (按 app_id 排序, 避免死锁),
+    /// 同一个应用的并发保存会排队
+    async fn load_last_states(
+        conn: &mut PgConnection,
+        app_ids: &[String],
+    ) -> Result<HashMap<String, LastAppState>> {
+        let mut states: HashMap<String, LastAppState> = HashMap::new();
+
+        let query = format!(
+            "SELECT {APP_INFO_COLUMNS} FROM app_info WHERE app_id = ANY($1) ORDER BY app_id FOR UPDATE"
+        );
+        for row in sqlx::query(&query)
+            .bind(app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            let info = Self::read_app_info_from_row(&row);
+            states.entry(info.app_id.clone()).or_default().info = Some(info);
+        }
+
+        let query = format!(
+            "SELECT DISTINCT ON (app_id) id, {SELECT_APP_METRIC_FIELDS} FROM app_metrics
+            WHERE app_id = ANY($1) ORDER BY app_id, id DESC"
+        );
+        for row in sqlx::query(&query)
+            .bind(app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            let metric = Self::read_app_metric_from_row(&row);
+            states.entry(metric.app_id.clone()).or_default().metric = Some(metric);
+        }
+
+        let query = format!(
+            "SELECT DISTINCT ON (app_id) {SELECT_APP_RATING_FIELDS} FROM app_rating
+            WHERE app_id = ANY($1) ORDER BY app_id, id DESC"
+        );
+        for row in sqlx::query(&query)
+            .bind(app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            if let Some(rating) = Self::read_app_rating_from_row(&row) {
+                states.entry(rating.app_id.clone()).or_default().rating = Some(rating);
+            }
+        }
+
+        for row in sqlx::query(
+            "SELECT app_id, COALESCE(json_blob_load(raw_json_hash), legacy_json) AS raw_json_data
+            FROM (
+                SELECT DISTINCT ON (app_id) app_id, raw_json_hash, raw_json_data AS legacy_json
+                FROM app_data_history
+                WHERE app_id = ANY($1) ORDER BY app_id, created_at DESC
+            ) h",
+        )
+        .bind(app_ids)
+        .fetch_all(&mut *conn)
+        .await?
+        {
+            let app_id: String = row.get("app_id");
+            states.entry(app_id).or_default().raw_json = row.try_get("raw_json_data").ok();
+        }
+
+        Ok(states)
+    }
+
+    /// 在一个事务里保存一批应用
+    ///
+    /// 返回值和输入一一对应: (info_updated, metric_updated, rating_updated, full_app_info)
+    pub async fn save_app_data_batch(
+        &self,
+        apps: Vec<PendingApp>,
+    ) -> Result<Vec<(bool, bool, bool, FullAppInfo)>> {
+        if apps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let app_ids: Vec<String> = apps.iter().map(|app| app.data.app_id()).collect();
+
+        let mut tx = self.pool.begin().await?;
+        let mut states = Self::load_last_states(&mut *tx, &app_ids).await?;
+
+        let mut flags = Vec::with_capacity(apps.len());
+        for (app, app_id) in apps.iter().zip(&app_ids) {
+            let last = states.entry(app_id.clone()).or_default();
+            let plan = SavePlan::build(last, app);
+
+            if let Some(info) = &plan.info {
+                Self::insert_app_info(&mut *tx, info, plan.overwrite).await?;
+            }
+            if let Some(metric) = &plan.metric {
+                Self::insert_app_metric(&mut *tx, metric).await?;
+            }
+            if let Some(rating) = &plan.rating {
+                Self::insert_app_rating(&mut *tx, rating).await?;
+            }
+            if let Some(record) = &plan.record {
+                Self::insert_app_record(&mut *tx, record).await?;
+            }
+            if plan.history {
+                Self::insert_data_history(&mut *tx, app_id, &app.data.app_info_json).await?;
+            }
+
+            plan.apply(last, &app.data.app_info_json);
+            flags.push((
+                plan.info.is_some(),
+                plan.metric.is_some(),
+                plan.rating.is_some(),
+                plan.history,
+            ));
+        }
+        tx.commit().await?;
+
+        // 变更记录算不出来不影响同步, 下次同步这个应用或者 `changelog backfill` 时会补算
+        let mut changed: Vec<&String> = app_ids
+            .iter()
+            .zip(&flags)
+            .filter(|(_, flag)| flag.3)
+            .map(|(app_id, _)| app_id)
+            .collect();
+        changed.sort();
+        changed.dedup();
+        for app_id in changed {
+            if let Err(e) = self
+                .build_changelog(changelog::ChangelogKind::App, app_id)
+                .await
+            {
+                event!(Level::WARN, "计算应用 {app_id} 的变更记录失败: {e}");
+            }
+        }
+
+        // 从 app_full_info 表查询最新的完整数据（trigger 已自动更新）
+        let full_infos: HashMap<String, FullAppInfo> =
+            sqlx::query_as::<_, FullAppInfo>("SELECT * FROM app_full_info WHERE app_id = ANY($1)")
+                .bind(&app_ids)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .map(|info| (info.app_id.clone(), info))
+                .collect();
+
+        app_ids
+            .iter()
+            .zip(flags)
+            .map(|(app_id, (info, metric, rating, _))| {
+                let full_info = full_infos
+                    .get(app_id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("app_full_info 中没有应用 {app_id}"))?;
+                Ok((info, metric, rating, full_info))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::db::insert::UPSERT_APP_INFO;
+
+    fn app_info(description: &str, listed_at: DateTime<Local>) -> AppInfo {
+        AppInfo {
+            app_id: "C1234".to_string(),
+            alliance_app_id: "1234".to_string(),
+            name: "测试应用".to_string(),
+            pkg_name: "com.example.test".to_string(),
+            dev_id: "dev".to_string(),
+            developer_name: "开发者".to_string(),
+            dev_en_name: "developer".to_string(),
+            supplier: String::new(),
+            kind_id: 1,
+            kind_name: "工具".to_string(),
+            tag_name: None,
+            kind_type_id: 13,
+            kind_type_name: "应用".to_string(),
+            icon_url: String::new(),
+            brief_desc: String::new(),
+            description: description.to_string(),
+            privacy_url: String::new(),
+            ctype: 1,
+            detail_id: String::new(),
+            app_level: 2,
+            jocat_id: 0,
+            iap: false,
+            hms: false,
+            tariff_type: String::new(),
+            packing_type: 0,
+            order_app: false,
+            denpend_gms: false,
+            denpend_hms: false,
+            force_update: false,
+            img_tag: String::new(),
+            is_pay: false,
+            is_disciplined: false,
+            is_shelves: true,
+            submit_type: 0,
+            delete_archive: false,
+            charging: false,
+            button_grey: false,
+            app_gift: false,
+            free_days: 0,
+            pay_install_type: 0,
+            created_at: listed_at,
+            listed_at,
+            comment: None,
+            release_countries: Vec::new(),
+            main_device_codes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sync_after_approval_keeps_metadata() {
+        let approved_at = Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut approved = app_info("旧的描述", approved_at);
+        approved.comment = Some(json!({"note": "审核通过"}));
+        let mut last = LastAppState {
+            info: Some(approved.clone()),
+            ..Default::default()
+        };
+
+        // 同步拿到的数据: listed_at 是当前时间, 没有 comment
+        let plan = SavePlan {
+            info: Some(app_info("新的描述", Local::now())),
+            overwrite: MetadataOverwrite::explicit(None, None),
+            history: true,
+            ..Default::default()
+        };
+        plan.apply(&mut last, &json!({}));
+
+        let info = last.info.unwrap();
+        assert_eq!(info.description, "新的描述");
+        assert_eq!(info.listed_at, approved.listed_at);
+        assert_eq!(info.comment, approved.comment);
+        // 数据库里的 upsert 用同样的规则
+        assert!(!UPSERT_APP_INFO.contains("listed_at = EXCLUDED.listed_at"));
+        assert!(UPSERT_APP_INFO.contains("ELSE app_info.listed_at END"));
+        assert!(UPSERT_APP_INFO.contains("ELSE app_info.comment END"));
+    }
+
+    #[test]
+    fn test_explicit_metadata_overwrites() {
+        let old_at = Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let new_at = Local.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+        let comment = json!({"note": "新的备注"});
+        let mut info = app_info("描述", new_at);
+        info.comment = Some(comment.clone());
+
+        MetadataOverwrite::explicit(Some(new_at), Some(&comment))
+            .merge(&mut info, &app_info("描述", old_at));
+        assert_eq!(info.listed_at, new_at);
+        assert_eq!(info.comment, Some(comment));
+    }
+}
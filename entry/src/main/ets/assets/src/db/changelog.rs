@@ -0,0 +1,504 @@
+//! 原始 JSON 快照的变更记录
+//!
+//! 相邻两个快照做结构化 diff, 结果存在 `app_changelog` / `substance_changelog` (018 迁移)
+//! 快照内容按 020 迁移的布局存在 `json_blob` 里, 用 `json_blob_load` 还原
+//!
+//! - 同步写入新快照后立即计算 (同时补上这个应用 / 专题之前没算过的快照)
+//! - 查询只读, 走 [`Database::read_pool`]; 老数据用 `changelog backfill` 子命令回填
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::db::{Database, query::get_max_limit, query::normalize_json_for_comparison};
+
+/// 快照来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangelogKind {
+    /// 应用 (app_data_history)
+    App,
+    /// 专题 (substance_history)
+    Substance,
+}
+
+impl ChangelogKind {
+    fn changelog_table(&self) -> &'static str {
+        match self {
+            Self::App => "app_changelog",
+            Self::Substance => "substance_changelog",
+        }
+    }
+
+    fn history_table(&self) -> &'static str {
+        match self {
+            Self::App => "app_data_history",
+            Self::Substance => "substance_history",
+        }
+    }
+
+    fn target_column(&self) -> &'static str {
+        match self {
+            Self::App => "app_id",
+            Self::Substance => "substance_id",
+        }
+    }
+
+    /// 历史表原来的 JSON 列, `blobs backfill` 转换之前的行还存在这里
+    fn json_column(&self) -> &'static str {
+        match self {
+            Self::App => "raw_json_data",
+            Self::Substance => "raw_json_substance",
+        }
+    }
+
+    /// 查名称用的 (表, 名称字段, 主键)
+    fn name_source(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Self::App => ("app_info", "name", "app_id"),
+            Self::Substance => ("substance_info", "title", "substance_id"),
+        }
+    }
+}
+
+/// 变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOp {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// 单个字段的变化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldChange {
+    /// 字段路径, 如 `versionName`、`labels[0].name`
+    pub path: String,
+    pub op: ChangeOp,
+    /// 旧值, added 时为 None
+    #[schema(value_type = Option<Object>)]
+    pub old: Option<Value>,
+    /// 新值, removed 时为 None
+    #[schema(value_type = Option<Object>)]
+    pub new: Option<Value>,
+}
+
+impl FieldChange {
+    /// 顶层字段名
+    pub fn field(&self) -> &str {
+        top_level_field(&self.path)
+    }
+}
+
+/// 一个快照的变更记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangelogEntry {
+    pub kind: ChangelogKind,
+    /// app_id / substance_id
+    pub target_id: String,
+    /// 应用名 / 专题标题
+    pub name: Option<String>,
+    pub history_id: i64,
+    pub prev_history_id: Option<i64>,
+    /// 是否是第一个快照 (新上架)
+    pub initial: bool,
+    /// 发生变化的顶层字段
+    pub fields: Vec<String>,
+    pub change_count: i32,
+    pub changes: Vec<FieldChange>,
+    pub created_at: DateTime<Local>,
+}
+
+/// 变更记录查询条件
+#[derive(Debug, Clone, Default)]
+pub struct ChangelogFilter {
+    /// 只看这些顶层字段, 空表示全部
+    pub fields: Vec<String>,
+    /// 是否包含第一个快照 (新上架)
+    pub include_initial: bool,
+    /// 只看这个时间之后的
+    pub since: Option<DateTime<Local>>,
+}
+
+impl Database {
+    /// 补算指定应用 / 专题还没有计算过的快照差异
+    ///
+    /// 返回新写入的记录数
+    pub async fn build_changelog(&self, kind: ChangelogKind, target_id: &str) -> Result<usize> {
+        let history = kind.history_table();
+        let target = kind.target_column();
+        let changelog = kind.changelog_table();
+        let json = kind.json_column();
+        let query = format!(
+            r#"
+            SELECT
+                h.id, h.created_at,
+                COALESCE(json_blob_load(h.raw_json_hash), h.{json}) AS new_json,
+                p.id AS prev_id,
+                COALESCE(json_blob_load(p.raw_json_hash), p.{json}) AS prev_json
+            FROM (
+                SELECT id, created_at, raw_json_hash, {json},
+                       LAG(id) OVER (ORDER BY created_at, id) AS prev_id
+                FROM {history}
+                WHERE {target} = $1
+            ) h
+            LEFT JOIN {history} p ON p.id = h.prev_id
+            WHERE NOT EXISTS (SELECT 1 FROM {changelog} c WHERE c.history_id = h.id)
+            ORDER BY h.created_at, h.id
+            "#
+        );
+        let rows = sqlx::query(&query)
+            .bind(target_id)
+            .fetch_all(&self.pool)
+            .await?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let insert = format!(
+            r#"
+            INSERT INTO {changelog}
+                ({target}, history_id, prev_history_id, changes, fields, change_count, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (history_id) DO NOTHING
+            "#
+        );
+        let mut inserted = 0;
+        let mut tx = self.pool.begin().await?;
+        for row in rows.iter() {
+            let new_json: Value = row.get("new_json");
+            let prev_id: Option<i64> = row.get("prev_id");
+            let prev_json: Option<Value> = row.get("prev_json");
+            let changes = match prev_json {
+                Some(prev) => diff_json(&prev, &new_json),
+                None => Vec::new(),
+            };
+            let fields = changed_fields(&changes);
+            let result = sqlx::query(&insert)
+                .bind(target_id)
+                .bind(row.get::<i64, _>("id"))
+                .bind(prev_id)
+                .bind(serde_json::to_value(&changes)?)
+                .bind(&fields)
+                .bind(changes.len() as i32)
+                .bind(row.get::<DateTime<Local>, _>("created_at"))
+                .execute(&mut *tx)
+                .await?;
+            inserted += result.rows_affected() as usize;
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    /// 查询指定应用 / 专题的变更记录, 新的在前
+    ///
+    /// 返回 (记录, 总数)
+    pub async fn get_changelog(
+        &self,
+        kind: ChangelogKind,
+        target_id: &str,
+        filter: &ChangelogFilter,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ChangelogEntry>, i64)> {
+        self.query_changelog(Some(kind), Some(target_id), filter, page, page_size)
+            .await
+    }
+
+    /// 回填所有还没有计算过的快照差异, 每算完一个应用 / 专题调用一次 `progress`
+    ///
+    /// 返回 (处理的应用 / 专题数, 新写入的记录数)
+    pub async fn backfill_changelog(
+        &self,
+        kind: ChangelogKind,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<(usize, usize)> {
+        let history = kind.history_table();
+        let target = kind.target_column();
+        let changelog = kind.changelog_table();
+        let targets: Vec<String> = sqlx::query_scalar(&format!(
+            r#"
+            SELECT DISTINCT h.{target}
+            FROM {history} h
+            WHERE NOT EXISTS (SELECT 1 FROM {changelog} c WHERE c.history_id = h.id)
+            ORDER BY h.{target}
+            "#
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut inserted = 0;
+        for (done, target_id) in targets.iter().enumerate() {
+            inserted += self.build_changelog(kind, target_id).await?;
+            progress(done + 1, targets.len());
+        }
+        Ok((targets.len(), inserted))
+    }
+
+    /// 全市场的最近变更
+    ///
+    /// `kind` 为 None 时同时返回应用和专题
+    pub async fn recent_changes(
+        &self,
+        kind: Option<ChangelogKind>,
+        filter: &ChangelogFilter,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ChangelogEntry>, i64)> {
+        self.query_changelog(kind, None, filter, page, page_size).await
+    }
+
+    async fn query_changelog(
+        &self,
+        kind: Option<ChangelogKind>,
+        target_id: Option<&str>,
+        filter: &ChangelogFilter,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ChangelogEntry>, i64)> {
+        let kinds: Vec<ChangelogKind> = match kind {
+            Some(kind) => vec![kind],
+            None => vec![ChangelogKind::App, ChangelogKind::Substance],
+        };
+        // $1 target_id, $2 fields, $3 include_initial, $4 since
+        let union = kinds
+            .iter()
+            .map(|kind| {
+                let changelog = kind.changelog_table();
+                let target = kind.target_column();
+                let (name_table, name_column, name_key) = kind.name_source();
+                format!(
+                    r#"
+                    SELECT
+                        '{kind_name}'::text AS kind, c.{target} AS target_id, n.{name_column} AS name,
+                        c.history_id, c.prev_history_id, c.changes, c.fields, c.change_count, c.created_at
+                    FROM {changelog} c
+                    LEFT JOIN {name_table} n ON n.{name_key} = c.{target}
+                    WHERE ($1::text IS NULL OR c.{target} = $1)
+                      AND ($2::text[] IS NULL OR c.fields && $2)
+                      AND (c.change_count > 0 OR ($3 AND c.prev_history_id IS NULL))
+                      AND ($4::timestamptz IS NULL OR c.created_at >= $4)
+                    "#,
+                    kind_name = match kind {
+                        ChangelogKind::App => "app",
+                        ChangelogKind::Substance => "substance",
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+
+        let fields = (!filter.fields.is_empty()).then_some(&filter.fields);
+        let limit = page_size.min(get_max_limit()) as i64;
+        let offset = page.saturating_sub(1) as i64 * limit;
+
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM ({union}) AS t"))
+            .bind(target_id)
+            .bind(fields)
+            .bind(filter.include_initial)
+            .bind(filter.since)
+            .fetch_one(self.read_pool())
+            .await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT * FROM ({union}) AS t ORDER BY created_at DESC, history_id DESC LIMIT $5 OFFSET $6"
+        ))
+        .bind(target_id)
+        .bind(fields)
+        .bind(filter.include_initial)
+        .bind(filter.since)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool())
+        .await?;
+
+        let entries = rows
+            .iter()
+            .map(|row| {
+                let kind = if row.get::<String, _>("kind") == "app" {
+                    ChangelogKind::App
+                } else {
+                    ChangelogKind::Substance
+                };
+                let mut changes: Vec<FieldChange> =
+                    serde_json::from_value(row.get("changes")).unwrap_or_default();
+                // 只返回关心的字段
+                if !filter.fields.is_empty() {
+                    changes.retain(|change| filter.fields.iter().any(|f| f == change.field()));
+                }
+                let prev_history_id: Option<i64> = row.get("prev_history_id");
+                ChangelogEntry {
+                    kind,
+                    target_id: row.get("target_id"),
+                    name: row.get("name"),
+                    history_id: row.get("history_id"),
+                    prev_history_id,
+                    initial: prev_history_id.is_none(),
+                    fields: row.get("fields"),
+                    change_count: row.get("change_count"),
+                    changes,
+                    created_at: row.get("created_at"),
+                }
+            })
+            .collect();
+
+        Ok((entries, total))
+    }
+}
+
+/// 路径的顶层字段名
+fn top_level_field(path: &str) -> &str {
+    let end = path.find(['.', '[']).unwrap_or(path.len());
+    &path[..end]
+}
+
+/// 差异中涉及的顶层字段, 去重并保持顺序
+fn changed_fields(changes: &[FieldChange]) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for change in changes {
+        let field = change.field();
+        if !fields.iter().any(|f| f == field) {
+            fields.push(field.to_string());
+        }
+    }
+    fields
+}
+
+/// 比较两个 JSON, 返回结构化差异
+///
+/// - 对象按 key 递归比较
+/// - 等长数组按下标递归比较, 长度不同时整个数组记为一条 changed
+/// - 和去重时一样忽略 trace 之类每次都会变化的字符串
+pub fn diff_json(old: &Value, new: &Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_value("", old, new, &mut changes);
+    changes
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+fn same_value(old: &Value, new: &Value) -> bool {
+    old == new || normalize_json_for_comparison(old) == normalize_json_for_comparison(new)
+}
+
+fn diff_object(
+    prefix: &str,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    out: &mut Vec<FieldChange>,
+) {
+    for (key, old_value) in old {
+        let path = join_key(prefix, key);
+        match new.get(key) {
+            Some(new_value) => diff_value(&path, old_value, new_value, out),
+            None => out.push(FieldChange {
+                path,
+                op: ChangeOp::Removed,
+                old: Some(old_value.clone()),
+                new: None,
+            }),
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            out.push(FieldChange {
+                path: join_key(prefix, key),
+                op: ChangeOp::Added,
+                old: None,
+                new: Some(new_value.clone()),
+            });
+        }
+    }
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, out: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => diff_object(path, old, new, out),
+        (Value::Array(old_items), Value::Array(new_items))
+            if old_items.len() == new_items.len() =>
+        {
+            for (i, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                diff_value(&format!("{path}[{i}]"), old_item, new_item, out);
+            }
+        }
+        _ => {
+            if !same_value(old, new) {
+                out.push(FieldChange {
+                    path: path.to_string(),
+                    op: ChangeOp::Changed,
+                    old: Some(old.clone()),
+                    new: Some(new.clone()),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_json() {
+        let old = json!({
+            "name": "应用",
+            "version": "1.0",
+            "labels": [{"name": "a"}, {"name": "b"}],
+            "removed": 1,
+            "traceId": "trace-1"
+        });
+        let new = json!({
+            "name": "应用",
+            "version": "1.1",
+            "labels": [{"name": "a"}, {"name": "c"}],
+            "added": true,
+            "traceId": "trace-2"
+        });
+        let changes = diff_json(&old, &new);
+        // key 的顺序取决于 serde_json 的 Map 实现, 排序后再比较
+        let mut summary: Vec<(&str, ChangeOp)> =
+            changes.iter().map(|c| (c.path.as_str(), c.op)).collect();
+        summary.sort_by_key(|(path, _)| *path);
+        // trace 的变化被忽略
+        assert_eq!(
+            summary,
+            vec![
+                ("added", ChangeOp::Added),
+                ("labels[1].name", ChangeOp::Changed),
+                ("removed", ChangeOp::Removed),
+                ("version", ChangeOp::Changed),
+            ]
+        );
+        let mut fields = changed_fields(&changes);
+        fields.sort();
+        assert_eq!(fields, vec!["added", "labels", "removed", "version"]);
+    }
+
+    #[test]
+    fn test_diff_array_length_changed() {
+        // 长度不同时整个数组记为一条
+        let changes = diff_json(&json!({"tags": [1, 2]}), &json!({"tags": [1, 2, 3]}));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "tags");
+        assert_eq!(changes[0].new, Some(json!([1, 2, 3])));
+        assert!(diff_json(&json!({"a": [1]}), &json!({"a": [1]})).is_empty());
+    }
+
+    #[test]
+    fn test_top_level_field() {
+        assert_eq!(top_level_field("labels[1].name"), "labels");
+        assert_eq!(top_level_field("a.b"), "a");
+        assert_eq!(top_level_field("version"), "version");
+    }
+}
@@ -0,0 +1,442 @@
+//! app_full_info 一致性检查与修复
+//!
+//! app_full_info 由 `main_triggers.sql` 里的四个触发器维护, 迁移或者手动改库绕过触发器时
+//! 就会和源表对不上. 这里逐段比较:
+//!
+//! - app_info 的基本字段
+//! - 最新一条 app_metrics / app_rating / app_record (和触发器一样按 created_at 取最新)
+//!
+//! 另外检查没有 app_full_info 的应用、没有任何指标的应用和孤立的 substance_app_map
+//!
+//! `listed_at` 由指标触发器维护成历史最早上架时间, 本来就可能和 app_info 不同, 不参与比较
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use tracing::{Level, event};
+use utoipa::ToSchema;
+
+use crate::db::Database;
+
+/// 不一致的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// app_info 有, app_full_info 没有
+    MissingFullInfo,
+    /// 基本字段和 app_info 不一致
+    StaleInfo,
+    /// 指标字段和最新 app_metrics 不一致
+    StaleMetrics,
+    /// 评分字段和最新 app_rating 不一致
+    StaleRating,
+    /// 备案字段和最新 app_record 不一致
+    StaleRecord,
+    /// 应用没有任何 app_metrics 记录 (需要重新同步, 不能自动修复)
+    MissingMetrics,
+    /// substance_app_map 指向不存在的应用或专题
+    OrphanSubstanceMap,
+}
+
+impl IssueKind {
+    /// 重建 app_full_info 能修好的类型
+    pub fn rebuildable(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingFullInfo
+                | Self::StaleInfo
+                | Self::StaleMetrics
+                | Self::StaleRating
+                | Self::StaleRecord
+        )
+    }
+}
+
+/// 一条不一致记录
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConsistencyIssue {
+    pub kind: IssueKind,
+    pub app_id: String,
+    /// 只有 `orphan_substance_map` 有
+    pub substance_id: Option<String>,
+    /// 不一致的 app_full_info 字段
+    pub fields: Vec<String>,
+}
+
+/// 检查 (和修复) 结果
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ConsistencyReport {
+    /// 检查的应用数
+    pub checked_apps: i64,
+    /// 各类型的数量
+    pub summary: BTreeMap<IssueKind, usize>,
+    /// 不一致记录
+    pub issues: Vec<ConsistencyIssue>,
+    /// 重建了 app_full_info 的应用数
+    pub repaired_apps: usize,
+    /// 删除的孤立 substance_app_map 行数
+    pub removed_substance_maps: u64,
+}
+
+/// app_full_info 的一段字段和它的来源
+struct Section {
+    kind: IssueKind,
+    table: &'static str,
+    /// 是否按 created_at 取最新一条 (app_info 每个应用只有一行)
+    latest: bool,
+    /// (app_full_info 列, 源表列)
+    columns: &'static [(&'static str, &'static str)],
+}
+
+const INFO_SECTION: Section = Section {
+    kind: IssueKind::StaleInfo,
+    table: "app_info",
+    latest: false,
+    columns: &[
+        ("alliance_app_id", "alliance_app_id"),
+        ("name", "name"),
+        ("pkg_name", "pkg_name"),
+        ("dev_id", "dev_id"),
+        ("developer_name", "developer_name"),
+        ("dev_en_name", "dev_en_name"),
+        ("supplier", "supplier"),
+        ("kind_id", "kind_id"),
+        ("kind_name", "kind_name"),
+        ("tag_name", "tag_name"),
+        ("kind_type_id", "kind_type_id"),
+        ("kind_type_name", "kind_type_name"),
+        ("icon_url", "icon_url"),
+        ("brief_desc", "brief_desc"),
+        ("description", "description"),
+        ("privacy_url", "privacy_url"),
+        ("ctype", "ctype"),
+        ("detail_id", "detail_id"),
+        ("app_level", "app_level"),
+        ("jocat_id", "jocat_id"),
+        ("iap", "iap"),
+        ("hms", "hms"),
+        ("tariff_type", "tariff_type"),
+        ("packing_type", "packing_type"),
+        ("order_app", "order_app"),
+        ("denpend_gms", "denpend_gms"),
+        ("denpend_hms", "denpend_hms"),
+        ("force_update", "force_update"),
+        ("img_tag", "img_tag"),
+        ("is_pay", "is_pay"),
+        ("is_disciplined", "is_disciplined"),
+        ("is_shelves", "is_shelves"),
+        ("submit_type", "submit_type"),
+        ("delete_archive", "delete_archive"),
+        ("charging", "charging"),
+        ("button_grey", "button_grey"),
+        ("app_gift", "app_gift"),
+        ("free_days", "free_days"),
+        ("pay_install_type", "pay_install_type"),
+        ("comment", "comment"),
+        ("release_countries", "release_countries"),
+        ("main_device_codes", "main_device_codes"),
+        ("name_pinyin", "name_pinyin"),
+        ("name_initials", "name_initials"),
+        ("created_at", "created_at"),
+    ],
+};
+
+const METRICS_SECTION: Section = Section {
+    kind: IssueKind::StaleMetrics,
+    table: "app_metrics",
+    latest: true,
+    columns: &[
+        ("version", "version"),
+        ("version_code", "version_code"),
+        ("size_bytes", "size_bytes"),
+        ("sha256", "sha256"),
+        ("info_score", "info_score"),
+        ("info_rate_count", "info_rate_count"),
+        ("download_count", "download_count"),
+        ("price", "price"),
+        ("release_date", "release_date"),
+        ("new_features", "new_features"),
+        ("upgrade_msg", "upgrade_msg"),
+        ("target_sdk", "target_sdk"),
+        ("minsdk", "minsdk"),
+        ("compile_sdk_version", "compile_sdk_version"),
+        ("min_hmos_api_level", "min_hmos_api_level"),
+        ("api_release_type", "api_release_type"),
+        ("metrics_created_at", "created_at"),
+    ],
+};
+
+const RATING_SECTION: Section = Section {
+    kind: IssueKind::StaleRating,
+    table: "app_rating",
+    latest: true,
+    columns: &[
+        ("average_rating", "average_rating"),
+        ("star_1_rating_count", "star_1_rating_count"),
+        ("star_2_rating_count", "star_2_rating_count"),
+        ("star_3_rating_count", "star_3_rating_count"),
+        ("star_4_rating_count", "star_4_rating_count"),
+        ("star_5_rating_count", "star_5_rating_count"),
+        ("my_star_rating", "my_star_rating"),
+        ("total_star_rating_count", "total_star_rating_count"),
+        ("only_star_count", "only_star_count"),
+        ("full_average_rating", "full_average_rating"),
+        ("source_type", "source_type"),
+        ("rating_created_at", "created_at"),
+    ],
+};
+
+const RECORD_SECTION: Section = Section {
+    kind: IssueKind::StaleRecord,
+    table: "app_record",
+    latest: true,
+    columns: &[
+        ("title", "title"),
+        ("app_recordal_info", "app_recordal_info"),
+        ("recordal_entity_title", "recordal_entity_title"),
+        ("recordal_entity_name", "recordal_entity_name"),
+    ],
+};
+
+const SECTIONS: [&Section; 4] = [
+    &INFO_SECTION,
+    &METRICS_SECTION,
+    &RATING_SECTION,
+    &RECORD_SECTION,
+];
+
+impl Section {
+    /// 取 `outer` 这个应用对应的源数据行, 和触发器取最新记录的规则一致
+    fn source(&self, outer: &str) -> String {
+        if self.latest {
+            format!(
+                "(SELECT * FROM {} s WHERE s.app_id = {outer}.app_id \
+                ORDER BY s.created_at DESC NULLS LAST LIMIT 1)",
+                self.table
+            )
+        } else {
+            format!("(SELECT * FROM {} s WHERE s.app_id = {outer}.app_id)", self.table)
+        }
+    }
+
+    /// 找出这一段不一致的应用, 以及具体哪些字段不一致
+    fn check_sql(&self) -> String {
+        let fields = self
+            .columns
+            .iter()
+            .map(|(full, src)| {
+                format!("CASE WHEN f.{full} IS DISTINCT FROM s.{src} THEN '{full}' END")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let full_row = self
+            .columns
+            .iter()
+            .map(|(full, _)| format!("f.{full}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let src_row = self
+            .columns
+            .iter()
+            .map(|(_, src)| format!("s.{src}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "SELECT f.app_id, ARRAY_REMOVE(ARRAY[{fields}]::text[], NULL) AS fields
+            FROM app_full_info f
+            LEFT JOIN LATERAL {} s ON true
+            WHERE ROW({full_row}) IS DISTINCT FROM ROW({src_row})
+            ORDER BY f.app_id",
+            self.source("f")
+        )
+    }
+}
+
+/// 用源表重建一批应用的 app_full_info, 已有行的 listed_at 保持不变
+fn rebuild_sql() -> String {
+    let mut columns = vec!["app_id".to_string(), "listed_at".to_string()];
+    let mut values = vec!["a.app_id".to_string(), "a.listed_at".to_string()];
+    let mut joins = Vec::new();
+    for (idx, section) in SECTIONS.iter().enumerate() {
+        let alias = if section.latest {
+            let alias = format!("s{idx}");
+            joins.push(format!("LEFT JOIN LATERAL {} {alias} ON true", section.source("a")));
+            alias
+        } else {
+            "a".to_string()
+        };
+        for (full, src) in section.columns {
+            columns.push(full.to_string());
+            values.push(format!("{alias}.{src}"));
+        }
+    }
+    let updates = columns
+        .iter()
+        .filter(|c| *c != "app_id" && *c != "listed_at")
+        .map(|c| format!("{c} = EXCLUDED.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "INSERT INTO app_full_info ({}, updated_at)
+        SELECT {}, now()
+        FROM app_info a
+        {}
+        WHERE a.app_id = ANY($1)
+        ON CONFLICT (app_id) DO UPDATE SET {updates}, updated_at = now()",
+        columns.join(", "),
+        values.join(", "),
+        joins.join("\n        "),
+    )
+}
+
+impl Database {
+    /// 检查 app_full_info 和源表是否一致
+    pub async fn check_full_info_consistency(&self) -> Result<ConsistencyReport> {
+        let mut report = ConsistencyReport {
+            checked_apps: sqlx::query_scalar("SELECT COUNT(*) FROM app_info")
+                .fetch_one(&self.pool)
+                .await?,
+            ..Default::default()
+        };
+
+        let missing: Vec<String> = sqlx::query_scalar(
+            "SELECT a.app_id FROM app_info a
+            WHERE NOT EXISTS (SELECT 1 FROM app_full_info f WHERE f.app_id = a.app_id)
+            ORDER BY a.app_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        report.push_apps(IssueKind::MissingFullInfo, missing);
+
+        for section in SECTIONS {
+            for row in sqlx::query(&section.check_sql())
+                .fetch_all(&self.pool)
+                .await?
+            {
+                report.push(ConsistencyIssue {
+                    kind: section.kind,
+                    app_id: row.get("app_id"),
+                    substance_id: None,
+                    fields: row.get("fields"),
+                });
+            }
+        }
+
+        let no_metrics: Vec<String> = sqlx::query_scalar(
+            "SELECT a.app_id FROM app_info a
+            WHERE NOT EXISTS (SELECT 1 FROM app_metrics m WHERE m.app_id = a.app_id)
+            ORDER BY a.app_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        report.push_apps(IssueKind::MissingMetrics, no_metrics);
+
+        for row in sqlx::query(
+            "SELECT m.substance_id, m.app_id FROM substance_app_map m
+            WHERE NOT EXISTS (SELECT 1 FROM app_info a WHERE a.app_id = m.app_id)
+               OR NOT EXISTS (SELECT 1 FROM substance_info s WHERE s.substance_id = m.substance_id)
+            ORDER BY m.substance_id, m.app_id",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        {
+            report.push(ConsistencyIssue {
+                kind: IssueKind::OrphanSubstanceMap,
+                app_id: row.get("app_id"),
+                substance_id: Some(row.get("substance_id")),
+                fields: Vec::new(),
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// 检查并修复
+    ///
+    /// 能重建的应用按 `batch_size` 分批, 每批一个事务重建 app_full_info;
+    /// 孤立的 substance_app_map 直接删除. 返回修复前的检查结果和修复数量
+    pub async fn repair_full_info(&self, batch_size: usize) -> Result<ConsistencyReport> {
+        let mut report = self.check_full_info_consistency().await?;
+
+        let mut app_ids: Vec<&str> = report
+            .issues
+            .iter()
+            .filter(|issue| issue.kind.rebuildable())
+            .map(|issue| issue.app_id.as_str())
+            .collect();
+        app_ids.sort_unstable();
+        app_ids.dedup();
+
+        let query = rebuild_sql();
+        let mut repaired = 0;
+        for chunk in app_ids.chunks(batch_size.max(1)) {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(&query).bind(chunk).execute(&mut *tx).await?;
+            tx.commit().await?;
+            repaired += chunk.len();
+            event!(Level::INFO, "已重建 {repaired}/{} 个应用的 app_full_info", app_ids.len());
+        }
+        report.repaired_apps = repaired;
+
+        if report.summary.contains_key(&IssueKind::OrphanSubstanceMap) {
+            report.removed_substance_maps = sqlx::query(
+                "DELETE FROM substance_app_map m
+                WHERE NOT EXISTS (SELECT 1 FROM app_info a WHERE a.app_id = m.app_id)
+                   OR NOT EXISTS (SELECT 1 FROM substance_info s WHERE s.substance_id = m.substance_id)",
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+
+        Ok(report)
+    }
+}
+
+impl ConsistencyReport {
+    fn push(&mut self, issue: ConsistencyIssue) {
+        *self.summary.entry(issue.kind).or_default() += 1;
+        self.issues.push(issue);
+    }
+
+    fn push_apps(&mut self, kind: IssueKind, app_ids: Vec<String>) {
+        for app_id in app_ids {
+            self.push(ConsistencyIssue {
+                kind,
+                app_id,
+                substance_id: None,
+                fields: Vec::new(),
+            });
+        }
+    }
+
+    /// 是否完全一致
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_sql_maps_renamed_columns() {
+        let sql = METRICS_SECTION.check_sql();
+        // 指标的 created_at 在 app_full_info 里叫 metrics_created_at
+        assert!(sql.contains("f.metrics_created_at IS DISTINCT FROM s.created_at"));
+        assert!(sql.contains("ORDER BY s.created_at DESC NULLS LAST LIMIT 1"));
+        // app_info 每个应用只有一行, 不需要排序
+        assert!(!INFO_SECTION.check_sql().contains("ORDER BY s.created_at"));
+    }
+
+    #[test]
+    fn test_rebuild_sql_keeps_listed_at() {
+        let sql = rebuild_sql();
+        assert!(sql.contains("s1.created_at"));
+        assert!(sql.contains("s3.recordal_entity_name"));
+        assert!(!sql.contains("listed_at = EXCLUDED.listed_at"));
+    }
+}
@@ -0,0 +1,482 @@
+//! 应用接口的 `fields=` / `include=`
+//!
+//! - `fields`: 只返回指定的列, 列表接口直接在 SQL 里只查这些列 (app_full_info 的列名, 逗号分隔)
+//! - `include`: 把关联数据一起带回来, 省得客户端再挨个请求. 按一页的 app_id 批量查, 每种关联一条 SQL
+//!
+//! 列名走白名单, 不会拼进用户输入
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{Postgres, QueryBuilder, Row};
+use utoipa::ToSchema;
+
+use crate::db::{
+    Database, DbSearch, PageInfo,
+    filter::AppFilter,
+    query::{get_max_limit, push_list_conditions},
+    read_data::{SELECT_APP_METRIC_FIELDS, SELECT_APP_RATING_FIELDS},
+};
+use crate::model::{AppMetric, AppRating, AppRecord, ShortSubstanceInfo};
+
+/// app_full_info 里可以通过 `fields` 选择的列
+pub const APP_FIELDS: &[&str] = &[
+    "app_id",
+    "alliance_app_id",
+    "name",
+    "pkg_name",
+    "dev_id",
+    "developer_name",
+    "dev_en_name",
+    "supplier",
+    "kind_id",
+    "kind_name",
+    "tag_name",
+    "kind_type_id",
+    "kind_type_name",
+    "icon_url",
+    "brief_desc",
+    "description",
+    "privacy_url",
+    "ctype",
+    "detail_id",
+    "app_level",
+    "jocat_id",
+    "iap",
+    "hms",
+    "tariff_type",
+    "packing_type",
+    "order_app",
+    "denpend_gms",
+    "denpend_hms",
+    "force_update",
+    "img_tag",
+    "is_pay",
+    "is_disciplined",
+    "is_shelves",
+    "submit_type",
+    "delete_archive",
+    "charging",
+    "button_grey",
+    "app_gift",
+    "free_days",
+    "pay_install_type",
+    "comment",
+    "listed_at",
+    "release_countries",
+    "main_device_codes",
+    "version",
+    "version_code",
+    "size_bytes",
+    "sha256",
+    "info_score",
+    "info_rate_count",
+    "download_count",
+    "price",
+    "release_date",
+    "new_features",
+    "upgrade_msg",
+    "target_sdk",
+    "minsdk",
+    "compile_sdk_version",
+    "min_hmos_api_level",
+    "api_release_type",
+    "metrics_created_at",
+    "average_rating",
+    "star_1_rating_count",
+    "star_2_rating_count",
+    "star_3_rating_count",
+    "star_4_rating_count",
+    "star_5_rating_count",
+    "my_star_rating",
+    "total_star_rating_count",
+    "only_star_count",
+    "full_average_rating",
+    "source_type",
+    "rating_created_at",
+    "title",
+    "app_recordal_info",
+    "recordal_entity_title",
+    "recordal_entity_name",
+    "created_at",
+    "updated_at",
+];
+
+/// NUMERIC 列, 转成文本输出, 和 `Decimal` 的序列化结果保持一致
+const DECIMAL_FIELDS: &[&str] = &[
+    "info_score",
+    "price",
+    "average_rating",
+    "full_average_rating",
+];
+
+/// `include=metrics` 默认带几条
+pub const DEFAULT_INCLUDE_METRICS: u32 = 10;
+
+/// 解析 `fields`, 返回白名单里的列名
+///
+/// 空字符串视为没传; `app_id` 总是会带上, 关联数据要靠它对回去
+pub fn parse_fields(raw: &str) -> Result<Option<Vec<&'static str>>> {
+    let mut fields = vec!["app_id"];
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let field = APP_FIELDS
+            .iter()
+            .find(|f| **f == name)
+            .ok_or_else(|| anyhow!("不支持的字段: {name}"))?;
+        if !fields.contains(field) {
+            fields.push(field);
+        }
+    }
+    if fields.len() == 1 && !raw.split(',').any(|s| s.trim() == "app_id") {
+        return Ok(None);
+    }
+    Ok(Some(fields))
+}
+
+/// 按 `fields` 裁剪一个已经序列化好的应用, 用于没法在 SQL 里裁剪的地方
+pub fn project_fields(value: JsonValue, fields: &[&str]) -> JsonValue {
+    match value {
+        JsonValue::Object(mut map) => JsonValue::Object(
+            fields
+                .iter()
+                .filter_map(|f| map.remove(*f).map(|v| (f.to_string(), v)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// 可以内嵌的关联数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Include {
+    /// 最近 N 条指标 (版本 / 下载量等)
+    Metrics,
+    /// 最新一条评分, 含各星级数量
+    Rating,
+    /// 收录了这个应用的专题
+    Substances,
+    /// 备案信息
+    Recordal,
+}
+
+impl Include {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "metrics" => Some(Self::Metrics),
+            "rating" => Some(Self::Rating),
+            "substances" => Some(Self::Substances),
+            "recordal" => Some(Self::Recordal),
+            _ => None,
+        }
+    }
+}
+
+/// 解析 `include`, 逗号分隔, 重复的只算一次
+pub fn parse_includes(raw: &str) -> Result<Vec<Include>> {
+    let mut includes = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let include = Include::from_name(name).ok_or_else(|| {
+            anyhow!("不支持的 include: {name}, 可选 metrics / rating / substances / recordal")
+        })?;
+        if !includes.contains(&include) {
+            includes.push(include);
+        }
+    }
+    Ok(includes)
+}
+
+/// 一个应用的关联数据, 只输出请求了的部分
+///
+/// 请求了但没有数据时: 列表为空数组, 单条为 null
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Included {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Vec<AppMetric>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Option<AppRating>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub substances: Option<Vec<ShortSubstanceInfo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recordal: Option<Option<AppRecord>>,
+}
+
+impl Included {
+    fn requested(includes: &[Include]) -> Self {
+        let has = |include| includes.contains(&include);
+        Self {
+            metrics: has(Include::Metrics).then(Vec::new),
+            rating: has(Include::Rating).then_some(None),
+            substances: has(Include::Substances).then(Vec::new),
+            recordal: has(Include::Recordal).then_some(None),
+        }
+    }
+
+    /// 合并进应用对象里, 键名与应用字段重复时覆盖
+    pub fn merge_into(self, target: &mut JsonValue) {
+        if let (JsonValue::Object(target), Ok(JsonValue::Object(extra))) =
+            (target, serde_json::to_value(self))
+        {
+            target.extend(extra);
+        }
+    }
+}
+
+impl Database {
+    /// 分页查询应用, 只查 `fields` 里的列, 每行输出为 JSON 对象
+    ///
+    /// 条件 / 排序 / 分页与 [`Database::get_app_info_paginated_enhanced`] 一致
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_app_list_fields(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        sort_desc: bool,
+        search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+        fields: &[&str],
+    ) -> Result<PageInfo<JsonValue>> {
+        let total_count = self
+            .count_app_list(search.as_ref(), filter, exclude_huawei, exclude_atomic)
+            .await?;
+        let total_pages = if page_size == 0 {
+            0
+        } else {
+            ((total_count as f32 / page_size as f32).ceil()) as u32
+        };
+        let offset = page.saturating_sub(1) * page_size;
+        let limit = page_size.min(get_max_limit()) as i64;
+        let order_clause = if sort_desc { "DESC" } else { "ASC" };
+
+        let columns = fields
+            .iter()
+            .map(|f| {
+                if DECIMAL_FIELDS.contains(f) {
+                    format!("{f}::text AS {f}")
+                } else {
+                    f.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // 先在子查询里排好序分页, 外面转 JSON 时按行号保持顺序
+        let mut qb = QueryBuilder::<Postgres>::new(format!(
+            "SELECT to_jsonb(t) - '_row' FROM (SELECT {columns}, \
+             row_number() OVER (ORDER BY {sort_key} {order_clause}) AS _row \
+             FROM app_full_info WHERE "
+        ));
+        push_list_conditions(
+            &mut qb,
+            sort_key,
+            search.as_ref(),
+            filter,
+            exclude_huawei,
+            exclude_atomic,
+        )?;
+        qb.push(format!(" ORDER BY {sort_key} {order_clause} LIMIT "))
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset as i64)
+            .push(") t ORDER BY t._row");
+
+        let data: Vec<JsonValue> = qb.build_query_scalar().fetch_all(self.read_pool()).await?;
+
+        Ok(PageInfo {
+            data,
+            total_count: total_count as u32,
+            page,
+            page_size,
+            total_pages,
+        })
+    }
+
+    /// 批量查询一组应用的关联数据, 返回 app_id -> 关联数据
+    ///
+    /// 没有关联数据的应用也会有一项 (空数组 / null)
+    pub async fn get_app_includes(
+        &self,
+        app_ids: &[String],
+        includes: &[Include],
+        metrics_limit: u32,
+    ) -> Result<HashMap<String, Included>> {
+        let mut result: HashMap<String, Included> = app_ids
+            .iter()
+            .map(|id| (id.clone(), Included::requested(includes)))
+            .collect();
+        if app_ids.is_empty() {
+            return Ok(result);
+        }
+
+        for include in includes {
+            match include {
+                Include::Metrics => {
+                    let query = format!(
+                        r#"
+                        SELECT id, {SELECT_APP_METRIC_FIELDS}
+                        FROM (
+                            SELECT *, row_number() OVER (
+                                PARTITION BY app_id ORDER BY created_at DESC
+                            ) AS rn
+                            FROM app_metrics_timeline
+                            WHERE app_id = ANY($1)
+                        ) m
+                        WHERE rn <= $2
+                        ORDER BY app_id, created_at DESC
+                        "#
+                    );
+                    let rows = sqlx::query(&query)
+                        .bind(app_ids)
+                        .bind(metrics_limit.min(get_max_limit()) as i64)
+                        .fetch_all(self.read_pool())
+                        .await?;
+                    for row in rows.iter() {
+                        let metric = Self::read_app_metric_from_row(row);
+                        if let Some(metrics) = result
+                            .get_mut(&metric.app_id)
+                            .and_then(|i| i.metrics.as_mut())
+                        {
+                            metrics.push(metric);
+                        }
+                    }
+                }
+                Include::Rating => {
+                    let query = format!(
+                        r#"
+                        SELECT DISTINCT ON (app_id) {SELECT_APP_RATING_FIELDS}
+                        FROM app_rating
+                        WHERE app_id = ANY($1)
+                        ORDER BY app_id, id DESC
+                        "#
+                    );
+                    let rows = sqlx::query(&query)
+                        .bind(app_ids)
+                        .fetch_all(self.read_pool())
+                        .await?;
+                    for rating in rows.iter().filter_map(Self::read_app_rating_from_row) {
+                        if let Some(included) = result.get_mut(&rating.app_id) {
+                            included.rating = Some(Some(rating));
+                        }
+                    }
+                }
+                Include::Substances => {
+                    const QUERY: &str = r#"
+                        SELECT m.app_id, s.substance_id, s.title, s.subtitle, s.created_at
+                        FROM substance_app_map m
+                        JOIN substance_info s ON s.substance_id = m.substance_id
+                        WHERE m.app_id = ANY($1)
+                        ORDER BY s.created_at DESC
+                    "#;
+                    let rows = sqlx::query(QUERY)
+                        .bind(app_ids)
+                        .fetch_all(self.read_pool())
+                        .await?;
+                    for row in rows {
+                        let app_id: String = row.get("app_id");
+                        if let Some(substances) = result
+                            .get_mut(&app_id)
+                            .and_then(|i| i.substances.as_mut())
+                        {
+                            substances.push(ShortSubstanceInfo {
+                                substance_id: row.get("substance_id"),
+                                title: row.get("title"),
+                                subtitle: row.get("subtitle"),
+                                created_at: row.get("created_at"),
+                            });
+                        }
+                    }
+                }
+                Include::Recordal => {
+                    const QUERY: &str = r#"
+                        SELECT id, app_id, title, app_recordal_info,
+                               recordal_entity_title, recordal_entity_name
+                        FROM app_record
+                        WHERE app_id = ANY($1)
+                    "#;
+                    let records = sqlx::query_as::<_, AppRecord>(QUERY)
+                        .bind(app_ids)
+                        .fetch_all(self.read_pool())
+                        .await?;
+                    for record in records {
+                        if let Some(included) = result.get_mut(&record.app_id) {
+                            included.recordal = Some(Some(record));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// 给一组已经序列化好的应用挂上关联数据
+pub async fn attach_includes(
+    db: &Database,
+    items: &mut [JsonValue],
+    includes: &[Include],
+    metrics_limit: u32,
+) -> Result<()> {
+    if includes.is_empty() {
+        return Ok(());
+    }
+    let app_ids = items
+        .iter()
+        .filter_map(|item| item.get("app_id").and_then(JsonValue::as_str))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let mut included = db.get_app_includes(&app_ids, includes, metrics_limit).await?;
+    for item in items.iter_mut() {
+        let extra = item
+            .get("app_id")
+            .and_then(JsonValue::as_str)
+            .and_then(|id| included.remove(id));
+        if let Some(extra) = extra {
+            extra.merge_into(item);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_fields_whitelist() {
+        assert_eq!(parse_fields("").unwrap(), None);
+        assert_eq!(parse_fields(" , ").unwrap(), None);
+        // app_id 总是在最前面, 重复的去掉
+        assert_eq!(
+            parse_fields("name, download_count,name").unwrap(),
+            Some(vec!["app_id", "name", "download_count"])
+        );
+        assert_eq!(parse_fields("app_id").unwrap(), Some(vec!["app_id"]));
+        // 不在白名单里的直接报错
+        assert!(parse_fields("name;DROP TABLE app_info").is_err());
+        assert!(parse_fields("raw_json_data").is_err());
+    }
+
+    #[test]
+    fn includes_merge_into_app() {
+        assert!(parse_includes("metrics,unknown").is_err());
+        let includes = parse_includes("rating, substances,rating").unwrap();
+        assert_eq!(includes, vec![Include::Rating, Include::Substances]);
+
+        // 请求了但没有数据: 列表为 [], 单条为 null, 没请求的不出现
+        let mut app = project_fields(
+            json!({"app_id": "C1", "name": "应用", "description": "很长的描述"}),
+            &["app_id", "name"],
+        );
+        Included::requested(&includes).merge_into(&mut app);
+        assert_eq!(
+            app,
+            json!({"app_id": "C1", "name": "应用", "rating": null, "substances": []})
+        );
+    }
+}
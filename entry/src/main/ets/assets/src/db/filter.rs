@@ -0,0 +1,537 @@
+//! 应用列表的组合过滤条件
+//!
+//! 前端传一棵 JSON 过滤树, 例如 "本月上架的、下载量超过 10 万的付费游戏":
+//!
+//! ```json
+//! {
+//!   "op": "and",
+//!   "filters": [
+//!     { "op": "eq", "field": "is_pay", "value": true },
+//!     { "op": "eq", "field": "kind_type_name", "value": "游戏" },
+//!     { "op": "range", "field": "download_count", "gt": 100000 },
+//!     { "op": "range", "field": "listed_at", "gte": "2025-06-01" }
+//!   ]
+//! }
+//! ```
+//!
+//! 字段名走白名单, 值全部参数化绑定, 不会拼进 SQL
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use utoipa::ToSchema;
+
+/// 过滤树最大深度
+const MAX_FILTER_DEPTH: usize = 8;
+/// 过滤树最多节点数
+const MAX_FILTER_NODES: usize = 64;
+/// IN 列表最多元素数
+const MAX_IN_VALUES: usize = 100;
+
+/// 字段的值类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Int,
+    Decimal,
+    Bool,
+    Time,
+}
+
+/// 可以用来过滤的字段 (app_full_info 的列)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+    AppId,
+    Name,
+    PkgName,
+    DevId,
+    DeveloperName,
+    DevEnName,
+    Supplier,
+    BriefDesc,
+    Description,
+    KindId,
+    KindName,
+    KindTypeId,
+    KindTypeName,
+    TagName,
+    TariffType,
+    Version,
+    ApiReleaseType,
+    IsPay,
+    Iap,
+    Hms,
+    Charging,
+    VersionCode,
+    SizeBytes,
+    DownloadCount,
+    InfoRateCount,
+    ReleaseDate,
+    Minsdk,
+    TargetSdk,
+    CompileSdkVersion,
+    MinHmosApiLevel,
+    TotalStarRatingCount,
+    Price,
+    InfoScore,
+    AverageRating,
+    ListedAt,
+    CreatedAt,
+    UpdatedAt,
+    MetricsCreatedAt,
+    RatingCreatedAt,
+}
+
+impl FilterField {
+    /// 对应的列名
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::AppId => "app_id",
+            Self::Name => "name",
+            Self::PkgName => "pkg_name",
+            Self::DevId => "dev_id",
+            Self::DeveloperName => "developer_name",
+            Self::DevEnName => "dev_en_name",
+            Self::Supplier => "supplier",
+            Self::BriefDesc => "brief_desc",
+            Self::Description => "description",
+            Self::KindId => "kind_id",
+            Self::KindName => "kind_name",
+            Self::KindTypeId => "kind_type_id",
+            Self::KindTypeName => "kind_type_name",
+            Self::TagName => "tag_name",
+            Self::TariffType => "tariff_type",
+            Self::Version => "version",
+            Self::ApiReleaseType => "api_release_type",
+            Self::IsPay => "is_pay",
+            Self::Iap => "iap",
+            Self::Hms => "hms",
+            Self::Charging => "charging",
+            Self::VersionCode => "version_code",
+            Self::SizeBytes => "size_bytes",
+            Self::DownloadCount => "download_count",
+            Self::InfoRateCount => "info_rate_count",
+            Self::ReleaseDate => "release_date",
+            Self::Minsdk => "minsdk",
+            Self::TargetSdk => "target_sdk",
+            Self::CompileSdkVersion => "compile_sdk_version",
+            Self::MinHmosApiLevel => "min_hmos_api_level",
+            Self::TotalStarRatingCount => "total_star_rating_count",
+            Self::Price => "price",
+            Self::InfoScore => "info_score",
+            Self::AverageRating => "average_rating",
+            Self::ListedAt => "listed_at",
+            Self::CreatedAt => "created_at",
+            Self::UpdatedAt => "updated_at",
+            Self::MetricsCreatedAt => "metrics_created_at",
+            Self::RatingCreatedAt => "rating_created_at",
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::AppId
+            | Self::Name
+            | Self::PkgName
+            | Self::DevId
+            | Self::DeveloperName
+            | Self::DevEnName
+            | Self::Supplier
+            | Self::BriefDesc
+            | Self::Description
+            | Self::KindName
+            | Self::KindTypeName
+            | Self::TagName
+            | Self::TariffType
+            | Self::Version
+            | Self::ApiReleaseType => FieldKind::Text,
+            Self::IsPay | Self::Iap | Self::Hms | Self::Charging => FieldKind::Bool,
+            Self::KindId
+            | Self::KindTypeId
+            | Self::VersionCode
+            | Self::SizeBytes
+            | Self::DownloadCount
+            | Self::InfoRateCount
+            | Self::ReleaseDate
+            | Self::Minsdk
+            | Self::TargetSdk
+            | Self::CompileSdkVersion
+            | Self::MinHmosApiLevel
+            | Self::TotalStarRatingCount => FieldKind::Int,
+            Self::Price | Self::InfoScore | Self::AverageRating => FieldKind::Decimal,
+            Self::ListedAt
+            | Self::CreatedAt
+            | Self::UpdatedAt
+            | Self::MetricsCreatedAt
+            | Self::RatingCreatedAt => FieldKind::Time,
+        }
+    }
+}
+
+/// 过滤值, 具体类型按字段转换
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum FilterValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// 过滤条件树
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AppFilter {
+    /// 所有子条件都满足
+    And {
+        #[schema(no_recursion)]
+        filters: Vec<AppFilter>,
+    },
+    /// 任一子条件满足
+    Or {
+        #[schema(no_recursion)]
+        filters: Vec<AppFilter>,
+    },
+    /// 取反
+    Not {
+        #[schema(no_recursion)]
+        filter: Box<AppFilter>,
+    },
+    /// 等于
+    Eq {
+        field: FilterField,
+        value: FilterValue,
+    },
+    /// 不等于
+    Ne {
+        field: FilterField,
+        value: FilterValue,
+    },
+    /// 模糊匹配 (ILIKE %value%), 只能用于文本字段
+    Contains { field: FilterField, value: String },
+    /// 范围, 至少给一个边界, 可用于数值和时间字段
+    ///
+    /// 时间支持 RFC3339 或 `YYYY-MM-DD` (按本地时区零点)
+    Range {
+        field: FilterField,
+        gt: Option<FilterValue>,
+        gte: Option<FilterValue>,
+        lt: Option<FilterValue>,
+        lte: Option<FilterValue>,
+    },
+    /// 在列表中
+    In {
+        field: FilterField,
+        values: Vec<FilterValue>,
+    },
+    /// 为空
+    IsNull { field: FilterField },
+    /// 不为空
+    NotNull { field: FilterField },
+}
+
+/// 转换好类型的绑定值
+enum BindValue {
+    Text(String),
+    Int(i64),
+    Decimal(f64),
+    Bool(bool),
+    Time(DateTime<FixedOffset>),
+}
+
+impl FilterValue {
+    fn describe(&self) -> String {
+        match self {
+            Self::Bool(b) => b.to_string(),
+            Self::Int(i) => i.to_string(),
+            Self::Float(f) => f.to_string(),
+            Self::Text(s) => format!("\"{s}\""),
+        }
+    }
+
+    fn to_bind(&self, field: FilterField) -> Result<BindValue> {
+        let err = || {
+            anyhow!(
+                "字段 {} 不接受值 {}",
+                field.column(),
+                self.describe()
+            )
+        };
+        let value = match (field.kind(), self) {
+            (FieldKind::Text, Self::Text(s)) => BindValue::Text(s.clone()),
+            (FieldKind::Text, Self::Int(i)) => BindValue::Text(i.to_string()),
+            (FieldKind::Int, Self::Int(i)) => BindValue::Int(*i),
+            (FieldKind::Int, Self::Float(f)) if f.fract() == 0.0 => BindValue::Int(*f as i64),
+            (FieldKind::Int, Self::Text(s)) => BindValue::Int(s.trim().parse().map_err(|_| err())?),
+            (FieldKind::Decimal, Self::Int(i)) => BindValue::Decimal(*i as f64),
+            (FieldKind::Decimal, Self::Float(f)) => BindValue::Decimal(*f),
+            (FieldKind::Decimal, Self::Text(s)) => {
+                BindValue::Decimal(s.trim().parse().map_err(|_| err())?)
+            }
+            (FieldKind::Bool, Self::Bool(b)) => BindValue::Bool(*b),
+            (FieldKind::Bool, Self::Text(s)) => BindValue::Bool(s.trim().parse().map_err(|_| err())?),
+            (FieldKind::Time, Self::Text(s)) => BindValue::Time(parse_time(s).ok_or_else(err)?),
+            _ => return Err(err()),
+        };
+        Ok(value)
+    }
+}
+
+/// RFC3339 或者 `YYYY-MM-DD`
+pub(crate) fn parse_time(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time);
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|t| t.fixed_offset())
+}
+
+fn push_value(qb: &mut QueryBuilder<'_, Postgres>, value: BindValue) {
+    match value {
+        BindValue::Text(v) => {
+            qb.push_bind(v);
+        }
+        BindValue::Int(v) => {
+            qb.push_bind(v);
+        }
+        BindValue::Decimal(v) => {
+            qb.push_bind(v).push("::numeric");
+        }
+        BindValue::Bool(v) => {
+            qb.push_bind(v);
+        }
+        BindValue::Time(v) => {
+            qb.push_bind(v);
+        }
+    }
+}
+
+impl AppFilter {
+    /// 多个条件用 AND 合并, 没有条件时返回 None
+    pub fn all(filters: impl IntoIterator<Item = AppFilter>) -> Option<AppFilter> {
+        let mut filters: Vec<AppFilter> = filters.into_iter().collect();
+        match filters.len() {
+            0 => None,
+            1 => filters.pop(),
+            _ => Some(AppFilter::And { filters }),
+        }
+    }
+
+    /// 节点数量
+    fn node_count(&self) -> usize {
+        match self {
+            Self::And { filters } | Self::Or { filters } => {
+                1 + filters.iter().map(|f| f.node_count()).sum::<usize>()
+            }
+            Self::Not { filter } => 1 + filter.node_count(),
+            _ => 1,
+        }
+    }
+
+    /// 检查树的规模, 防止有人传一棵巨大的树上来
+    pub fn validate(&self) -> Result<()> {
+        let nodes = self.node_count();
+        if nodes > MAX_FILTER_NODES {
+            anyhow::bail!("过滤条件太多了: {nodes} > {MAX_FILTER_NODES}");
+        }
+        Ok(())
+    }
+
+    /// 编译成 SQL 条件追加到 `qb` 上, 整体包在括号里
+    pub fn push_sql(&self, qb: &mut QueryBuilder<'_, Postgres>) -> Result<()> {
+        self.validate()?;
+        self.push_sql_inner(qb, 0)
+    }
+
+    fn push_sql_inner(&self, qb: &mut QueryBuilder<'_, Postgres>, depth: usize) -> Result<()> {
+        if depth >= MAX_FILTER_DEPTH {
+            anyhow::bail!("过滤条件嵌套太深了, 最多 {MAX_FILTER_DEPTH} 层");
+        }
+        match self {
+            Self::And { filters } | Self::Or { filters } => {
+                if filters.is_empty() {
+                    // 空 AND 恒真, 空 OR 恒假
+                    qb.push(if matches!(self, Self::And { .. }) {
+                        "TRUE"
+                    } else {
+                        "FALSE"
+                    });
+                    return Ok(());
+                }
+                let joiner = if matches!(self, Self::And { .. }) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                qb.push("(");
+                for (idx, filter) in filters.iter().enumerate() {
+                    if idx > 0 {
+                        qb.push(joiner);
+                    }
+                    filter.push_sql_inner(qb, depth + 1)?;
+                }
+                qb.push(")");
+            }
+            Self::Not { filter } => {
+                qb.push("(NOT ");
+                filter.push_sql_inner(qb, depth + 1)?;
+                qb.push(")");
+            }
+            Self::Eq { field, value } | Self::Ne { field, value } => {
+                let value = value.to_bind(*field)?;
+                let op = if matches!(self, Self::Eq { .. }) {
+                    " = "
+                } else {
+                    " IS DISTINCT FROM "
+                };
+                qb.push("(").push(field.column()).push(op);
+                push_value(qb, value);
+                qb.push(")");
+            }
+            Self::Contains { field, value } => {
+                if field.kind() != FieldKind::Text {
+                    anyhow::bail!("字段 {} 不是文本, 不能模糊匹配", field.column());
+                }
+                qb.push("(")
+                    .push(field.column())
+                    .push(" ILIKE ")
+                    .push_bind(format!("%{}%", escape_like(value)))
+                    .push(")");
+            }
+            Self::Range {
+                field,
+                gt,
+                gte,
+                lt,
+                lte,
+            } => {
+                if matches!(field.kind(), FieldKind::Text | FieldKind::Bool) {
+                    anyhow::bail!("字段 {} 不支持范围查询", field.column());
+                }
+                let bounds = [(" > ", gt), (" >= ", gte), (" < ", lt), (" <= ", lte)];
+                if bounds.iter().all(|(_, v)| v.is_none()) {
+                    anyhow::bail!("字段 {} 的范围查询至少需要一个边界", field.column());
+                }
+                qb.push("(");
+                let mut first = true;
+                for (op, value) in bounds {
+                    let Some(value) = value else {
+                        continue;
+                    };
+                    if !first {
+                        qb.push(" AND ");
+                    }
+                    first = false;
+                    let value = value.to_bind(*field)?;
+                    qb.push(field.column()).push(op);
+                    push_value(qb, value);
+                }
+                qb.push(")");
+            }
+            Self::In { field, values } => {
+                if values.is_empty() {
+                    qb.push("FALSE");
+                    return Ok(());
+                }
+                if values.len() > MAX_IN_VALUES {
+                    anyhow::bail!("IN 列表最多 {MAX_IN_VALUES} 个值");
+                }
+                qb.push("(").push(field.column()).push(" IN (");
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        qb.push(", ");
+                    }
+                    push_value(qb, value.to_bind(*field)?);
+                }
+                qb.push("))");
+            }
+            Self::IsNull { field } => {
+                qb.push("(").push(field.column()).push(" IS NULL)");
+            }
+            Self::NotNull { field } => {
+                qb.push("(").push(field.column()).push(" IS NOT NULL)");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 转义 LIKE 里的通配符
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(filter: &AppFilter) -> Result<String> {
+        let mut qb = QueryBuilder::<Postgres>::new("");
+        filter.push_sql(&mut qb)?;
+        Ok(qb.sql().to_string())
+    }
+
+    #[test]
+    fn test_compile_nested_filter() {
+        let filter: AppFilter = serde_json::from_str(
+            r#"{
+                "op": "and",
+                "filters": [
+                    { "op": "eq", "field": "is_pay", "value": true },
+                    { "op": "range", "field": "download_count", "gt": 100000 },
+                    { "op": "or", "filters": [
+                        { "op": "in", "field": "kind_name", "values": ["游戏", "工具"] },
+                        { "op": "is_null", "field": "tag_name" }
+                    ]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            compile(&filter).unwrap(),
+            "((is_pay = $1) AND (download_count > $2) AND ((kind_name IN ($3, $4)) OR (tag_name IS NULL)))"
+        );
+    }
+
+    #[test]
+    fn test_reject_wrong_type() {
+        // 文本字段不能做范围查询
+        let filter = AppFilter::Range {
+            field: FilterField::Name,
+            gt: Some(FilterValue::Int(1)),
+            gte: None,
+            lt: None,
+            lte: None,
+        };
+        assert!(compile(&filter).is_err());
+
+        // 数值字段不接受非数字
+        let filter = AppFilter::Eq {
+            field: FilterField::DownloadCount,
+            value: FilterValue::Text("很多".to_string()),
+        };
+        assert!(compile(&filter).is_err());
+
+        // 未知字段直接反序列化失败
+        assert!(
+            serde_json::from_str::<AppFilter>(r#"{"op":"is_null","field":"1; DROP TABLE"}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert!(parse_time("2025-06-01").is_some());
+        assert!(parse_time("2025-06-01T00:00:00+08:00").is_some());
+        assert!(parse_time("上个月").is_none());
+    }
+}
@@ -0,0 +1,754 @@
+//! 从快照文件导入
+//!
+//! 读取 [`export`](super::export) 导出的 NDJSON / Parquet 快照给新实例灌数据,
+//! 不用再从市场重新爬一遍. 比较规则和 `save_app_data` 相同 (见 [`batch`](super::batch)),
+//! 和库里已有的数据部分重叠时可以正确合并:
+//! - 应用信息 / 专题: 库里没有就插入, 数据相同算重复, 不同算冲突 (默认保留库里的)
+//! - 指标 / 评分: 按时间线合并, 保留原来的 created_at. 同一时间点已有数据算重复或冲突,
+//!   和前一个时间点相同的跳过 (同步时也不会存这样的数据)
+//! - 专题成员关系: 已存在算重复, 应用或专题不存在的跳过
+//!
+//! 每批一个事务, 中途失败时已提交的批次不会回滚, 重新导入即可 (已导入的行会算作重复).
+//! 快照里没有原始 JSON, 所以不会写 app_data_history, 也不会生成变更记录
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use arrow::json::LineDelimitedWriter;
+use chrono::{DateTime, Local};
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value as JsonValue};
+use sqlx::{PgConnection, Row};
+use tracing::{Level, event};
+
+use crate::{
+    db::{
+        Database,
+        batch::{APP_INFO_COLUMNS, same_app_info, same_app_metric, same_app_rating},
+        export::{ColumnType, Dataset},
+        insert::MetadataOverwrite,
+        read_data::{SELECT_APP_METRIC_FIELDS, SELECT_APP_RATING_FIELDS},
+    },
+    model::{AppInfo, AppMetric, AppRating},
+};
+
+/// 每个数据集最多记录多少条冲突明细
+const MAX_CONFLICT_SAMPLES: usize = 100;
+
+/// 导入参数
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// 每批 (每个事务) 处理多少行
+    pub batch_size: usize,
+    /// 应用信息 / 专题冲突时用快照覆盖库里的数据
+    pub overwrite: bool,
+}
+
+/// 一条冲突: 快照和库里的数据不一致
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportConflict {
+    /// app_id 或 substance_id
+    pub id: String,
+    /// 指标 / 评分冲突的时间点
+    pub created_at: Option<DateTime<Local>>,
+    /// 不一致的字段
+    pub fields: Vec<String>,
+}
+
+/// 一个数据集的导入结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub dataset: Dataset,
+    pub file: PathBuf,
+    /// 快照里的行数
+    pub rows: u64,
+    /// 新插入的行数
+    pub inserted: u64,
+    /// 冲突时用快照覆盖的行数
+    pub updated: u64,
+    /// 和库里相同而跳过的行数
+    pub duplicates: u64,
+    /// 冲突行数
+    pub conflicts: u64,
+    /// 引用的应用 / 专题不存在而跳过的行数
+    pub missing: u64,
+    /// 冲突明细 (最多 [`MAX_CONFLICT_SAMPLES`] 条)
+    pub conflict_samples: Vec<ImportConflict>,
+}
+
+impl ImportReport {
+    fn new(dataset: Dataset, file: &Path) -> Self {
+        Self {
+            dataset,
+            file: file.to_path_buf(),
+            rows: 0,
+            inserted: 0,
+            updated: 0,
+            duplicates: 0,
+            conflicts: 0,
+            missing: 0,
+            conflict_samples: Vec::new(),
+        }
+    }
+
+    fn conflict(&mut self, id: &str, created_at: Option<DateTime<Local>>, fields: Vec<String>) {
+        self.conflicts += 1;
+        if self.conflict_samples.len() < MAX_CONFLICT_SAMPLES {
+            self.conflict_samples.push(ImportConflict {
+                id: id.to_string(),
+                created_at,
+                fields,
+            });
+        }
+    }
+}
+
+/// 快照里的专题
+#[derive(Debug, Deserialize)]
+struct SnapshotSubstance {
+    substance_id: String,
+    title: String,
+    subtitle: Option<String>,
+    name: Option<String>,
+    comment: Option<JsonValue>,
+    created_at: Option<DateTime<Local>>,
+}
+
+/// 快照里的专题成员关系
+#[derive(Debug, Deserialize)]
+struct SnapshotSubstanceApp {
+    substance_id: String,
+    app_id: String,
+}
+
+/// 快照文件读取器, 按块读出 JSON 行
+enum SnapshotReader {
+    Ndjson {
+        lines: Lines<BufReader<File>>,
+        line: usize,
+    },
+    Parquet {
+        batches: ParquetRecordBatchReader,
+        pending: VecDeque<Map<String, JsonValue>>,
+    },
+}
+
+impl SnapshotReader {
+    fn open(path: &Path, batch_size: usize) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("无法打开快照文件 {}", path.display()))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl") => Ok(Self::Ndjson {
+                lines: BufReader::new(file).lines(),
+                line: 0,
+            }),
+            Some("parquet") => Ok(Self::Parquet {
+                batches: ParquetRecordBatchReaderBuilder::try_new(file)?
+                    .with_batch_size(batch_size)
+                    .build()?,
+                pending: VecDeque::new(),
+            }),
+            _ => bail!("不支持的快照格式 {}, 只支持 .ndjson / .parquet", path.display()),
+        }
+    }
+
+    /// 最多读 `size` 行, 读完了返回空
+    fn next_chunk(&mut self, size: usize) -> Result<Vec<Map<String, JsonValue>>> {
+        let mut rows = Vec::with_capacity(size);
+        match self {
+            Self::Ndjson { lines, line } => {
+                while rows.len() < size {
+                    let Some(text) = lines.next() else { break };
+                    *line += 1;
+                    let text = text?;
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let row = serde_json::from_str(&text)
+                        .with_context(|| format!("第 {line} 行不是合法的 JSON 对象"))?;
+                    rows.push(row);
+                }
+            }
+            Self::Parquet { batches, pending } => {
+                while rows.len() < size {
+                    if let Some(row) = pending.pop_front() {
+                        rows.push(row);
+                        continue;
+                    }
+                    let Some(batch) = batches.next() else { break };
+                    let mut writer = LineDelimitedWriter::new(Vec::new());
+                    writer.write(&batch?)?;
+                    writer.finish()?;
+                    for text in writer.into_inner().split(|b| *b == b'\n') {
+                        if !text.is_empty() {
+                            pending.push_back(serde_json::from_slice(text)?);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// 补齐缺失的列 (Parquet 转出来的 JSON 不带 null), 还原存成字符串的 JSON 列
+fn normalize(dataset: Dataset, mut row: Map<String, JsonValue>) -> Map<String, JsonValue> {
+    for (name, kind) in dataset.columns() {
+        let value = row.entry(*name).or_insert(JsonValue::Null);
+        if *kind == ColumnType::Json
+            && let JsonValue::String(text) = value
+            && let Ok(parsed) = serde_json::from_str(text)
+        {
+            *value = parsed;
+        }
+    }
+    let fill = |row: &mut Map<String, JsonValue>, name: &str, default: JsonValue| {
+        if row.get(name).is_none_or(JsonValue::is_null) {
+            row.insert(name.to_string(), default);
+        }
+    };
+    match dataset {
+        // 库里可以是 NULL, 模型里是 String
+        Dataset::Apps => {
+            fill(&mut row, "dev_en_name", "".into());
+            fill(&mut row, "supplier", "".into());
+        }
+        // rollup 出来的指标 / 评分没有 id, 插入时也用不到
+        Dataset::Metrics | Dataset::Ratings => fill(&mut row, "id", 0.into()),
+        _ => {}
+    }
+    row
+}
+
+fn parse_rows<T: DeserializeOwned>(
+    dataset: Dataset,
+    rows: Vec<Map<String, JsonValue>>,
+    offset: u64,
+) -> Result<Vec<T>> {
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            serde_json::from_value(JsonValue::Object(normalize(dataset, row))).with_context(|| {
+                format!(
+                    "{} 第 {} 行数据不合法 (快照需要包含全部列)",
+                    dataset.name(),
+                    offset + i as u64 + 1
+                )
+            })
+        })
+        .collect()
+}
+
+/// 两份数据不一样的字段, 不算 id / created_at 这类元数据
+fn diff_fields<T: Serialize>(new: &T, last: &T) -> Vec<String> {
+    let (Ok(JsonValue::Object(new)), Ok(JsonValue::Object(last))) =
+        (serde_json::to_value(new), serde_json::to_value(last))
+    else {
+        return Vec::new();
+    };
+    new.iter()
+        .filter(|(key, value)| {
+            !matches!(key.as_str(), "id" | "created_at" | "listed_at" | "comment")
+                && last.get(*key) != Some(*value)
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// 把一个时间点合并进时间线, 返回是否需要插入
+fn merge_point<T: Clone + Serialize>(
+    timeline: &mut BTreeMap<DateTime<Local>, T>,
+    id: &str,
+    created_at: DateTime<Local>,
+    row: &T,
+    same: fn(&T, &T) -> bool,
+    report: &mut ImportReport,
+) -> bool {
+    if let Some(existing) = timeline.get(&created_at) {
+        if same(row, existing) {
+            report.duplicates += 1;
+        } else {
+            report.conflict(id, Some(created_at), diff_fields(row, existing));
+        }
+        return false;
+    }
+    if timeline
+        .range(..created_at)
+        .next_back()
+        .is_some_and(|(_, prev)| same(row, prev))
+    {
+        report.duplicates += 1;
+        return false;
+    }
+    timeline.insert(created_at, row.clone());
+    true
+}
+
+fn unique_ids<'a>(ids: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut ids: Vec<String> = ids.cloned().collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+impl Database {
+    /// 导入快照
+    ///
+    /// `path` 是目录时按依赖顺序导入其中的 `<dataset>.parquet` / `<dataset>.ndjson`
+    /// (也就是 export 默认的文件名), `dataset` 可以只导入其中一个;
+    /// 是文件时只导入这个文件, 数据集由 `dataset` 或文件名决定
+    pub async fn import_snapshot(
+        &self,
+        path: &Path,
+        dataset: Option<Dataset>,
+        options: &ImportOptions,
+    ) -> Result<Vec<ImportReport>> {
+        if options.batch_size == 0 {
+            bail!("batch_size 必须大于 0");
+        }
+        let mut reports = Vec::new();
+        if path.is_dir() {
+            for current in Dataset::ALL {
+                if dataset.is_some_and(|only| only != current) {
+                    continue;
+                }
+                let file = ["parquet", "ndjson"]
+                    .iter()
+                    .map(|ext| path.join(format!("{}.{ext}", current.name())))
+                    .find(|file| file.is_file());
+                if let Some(file) = file {
+                    reports.push(self.import_file(current, &file, options).await?);
+                }
+            }
+            if reports.is_empty() {
+                bail!("{} 里没有快照文件", path.display());
+            }
+        } else {
+            let dataset = match dataset {
+                Some(dataset) => dataset,
+                None => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(Dataset::from_name)
+                    .ok_or_else(|| anyhow!("无法从文件名判断数据集, 请指定 dataset"))?,
+            };
+            reports.push(self.import_file(dataset, path, options).await?);
+        }
+        Ok(reports)
+    }
+
+    async fn import_file(
+        &self,
+        dataset: Dataset,
+        file: &Path,
+        options: &ImportOptions,
+    ) -> Result<ImportReport> {
+        let mut reader = SnapshotReader::open(file, options.batch_size)?;
+        let mut report = ImportReport::new(dataset, file);
+        event!(Level::INFO, "开始导入 {} ({})", dataset.name(), file.display());
+
+        loop {
+            let rows = reader.next_chunk(options.batch_size)?;
+            if rows.is_empty() {
+                break;
+            }
+            let offset = report.rows;
+            report.rows += rows.len() as u64;
+
+            let mut tx = self.pool.begin().await?;
+            match dataset {
+                Dataset::Apps => {
+                    let apps = parse_rows(dataset, rows, offset)?;
+                    Self::import_apps(&mut tx, apps, options, &mut report).await?
+                }
+                Dataset::Metrics => {
+                    let metrics = parse_rows(dataset, rows, offset)?;
+                    Self::import_metrics(&mut tx, metrics, &mut report).await?
+                }
+                Dataset::Ratings => {
+                    let ratings = parse_rows(dataset, rows, offset)?;
+                    Self::import_ratings(&mut tx, ratings, &mut report).await?
+                }
+                Dataset::Substances => {
+                    let substances = parse_rows(dataset, rows, offset)?;
+                    Self::import_substances(&mut tx, substances, options, &mut report).await?
+                }
+                Dataset::SubstanceApps => {
+                    let maps = parse_rows(dataset, rows, offset)?;
+                    Self::import_substance_apps(&mut tx, maps, &mut report).await?
+                }
+            }
+            tx.commit().await?;
+
+            event!(
+                Level::INFO,
+                "导入 {}: 已处理 {} 行, 新增 {}, 覆盖 {}, 重复 {}, 冲突 {}, 跳过 {}",
+                dataset.name(),
+                report.rows,
+                report.inserted,
+                report.updated,
+                report.duplicates,
+                report.conflicts,
+                report.missing
+            );
+        }
+        Ok(report)
+    }
+
+    async fn import_apps(
+        conn: &mut PgConnection,
+        apps: Vec<AppInfo>,
+        options: &ImportOptions,
+        report: &mut ImportReport,
+    ) -> Result<()> {
+        // 快照里的 listed_at 是原来库里的值, 覆盖时一起写入; comment 保留库里的
+        const OVERWRITE: MetadataOverwrite = MetadataOverwrite {
+            listed_at: true,
+            comment: false,
+        };
+        let app_ids = unique_ids(apps.iter().map(|app| &app.app_id));
+        let pkg_names = unique_ids(apps.iter().map(|app| &app.pkg_name));
+
+        let query = format!(
+            "SELECT {APP_INFO_COLUMNS} FROM app_info WHERE app_id = ANY($1) ORDER BY app_id FOR UPDATE"
+        );
+        let mut existing: HashMap<String, AppInfo> = sqlx::query(&query)
+            .bind(&app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+            .iter()
+            .map(|row| {
+                let info = Self::read_app_info_from_row(row);
+                (info.app_id.clone(), info)
+            })
+            .collect();
+        // pkg_name 是唯一的, 被别的应用占用时插入会失败
+        let mut pkg_owners: HashMap<String, String> =
+            sqlx::query("SELECT pkg_name, app_id FROM app_info WHERE pkg_name = ANY($1)")
+                .bind(&pkg_names)
+                .fetch_all(&mut *conn)
+                .await?
+                .iter()
+                .map(|row| (row.get("pkg_name"), row.get("app_id")))
+                .collect();
+
+        for app in apps {
+            let diff = existing.get(&app.app_id).map(|last| {
+                if same_app_info(&app, last) {
+                    None
+                } else {
+                    Some(diff_fields(&app, last))
+                }
+            });
+            match diff {
+                Some(None) => report.duplicates += 1,
+                Some(Some(fields)) => {
+                    report.conflict(&app.app_id, None, fields);
+                    if options.overwrite {
+                        Self::insert_app_info(&mut *conn, &app, OVERWRITE).await?;
+                        report.updated += 1;
+                        existing.insert(app.app_id.clone(), app);
+                    }
+                }
+                None => {
+                    if let Some(owner) = pkg_owners.get(&app.pkg_name)
+                        && *owner != app.app_id
+                    {
+                        report.conflict(&app.app_id, None, vec!["pkg_name".to_string()]);
+                        continue;
+                    }
+                    Self::insert_app_info(&mut *conn, &app, OVERWRITE).await?;
+                    report.inserted += 1;
+                    pkg_owners.insert(app.pkg_name.clone(), app.app_id.clone());
+                    existing.insert(app.app_id.clone(), app);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn existing_app_ids(
+        conn: &mut PgConnection,
+        app_ids: &[String],
+    ) -> Result<HashSet<String>> {
+        Ok(
+            sqlx::query_scalar::<_, String>("SELECT app_id FROM app_info WHERE app_id = ANY($1)")
+                .bind(app_ids)
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    async fn import_metrics(
+        conn: &mut PgConnection,
+        metrics: Vec<AppMetric>,
+        report: &mut ImportReport,
+    ) -> Result<()> {
+        let app_ids = unique_ids(metrics.iter().map(|metric| &metric.app_id));
+        let known = Self::existing_app_ids(conn, &app_ids).await?;
+
+        // 和 timeline 视图比较, 已经压缩进 rollup 的时间点也算在内
+        let query = format!(
+            "SELECT id, {SELECT_APP_METRIC_FIELDS} FROM app_metrics_timeline WHERE app_id = ANY($1)"
+        );
+        let mut timelines: HashMap<String, BTreeMap<DateTime<Local>, AppMetric>> = HashMap::new();
+        for row in sqlx::query(&query)
+            .bind(&app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            let metric = Self::read_app_metric_from_row(&row);
+            timelines
+                .entry(metric.app_id.clone())
+                .or_default()
+                .insert(metric.created_at, metric);
+        }
+
+        for metric in metrics {
+            if !known.contains(&metric.app_id) {
+                report.missing += 1;
+                continue;
+            }
+            let timeline = timelines.entry(metric.app_id.clone()).or_default();
+            let created_at = metric.created_at;
+            if merge_point(timeline, &metric.app_id, created_at, &metric, same_app_metric, report)
+            {
+                Self::insert_app_metric_at(&mut *conn, &metric, Some(created_at)).await?;
+                report.inserted += 1;
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_ratings(
+        conn: &mut PgConnection,
+        ratings: Vec<AppRating>,
+        report: &mut ImportReport,
+    ) -> Result<()> {
+        let app_ids = unique_ids(ratings.iter().map(|rating| &rating.app_id));
+        let known = Self::existing_app_ids(conn, &app_ids).await?;
+
+        // 和 metrics 一样跟 timeline 视图比较
+        let query = format!(
+            "SELECT {SELECT_APP_RATING_FIELDS} FROM app_rating_timeline WHERE app_id = ANY($1)"
+        );
+        let mut timelines: HashMap<String, BTreeMap<DateTime<Local>, AppRating>> = HashMap::new();
+        for row in sqlx::query(&query)
+            .bind(&app_ids)
+            .fetch_all(&mut *conn)
+            .await?
+        {
+            if let Some(rating) = Self::read_app_rating_from_row(&row) {
+                timelines
+                    .entry(rating.app_id.clone())
+                    .or_default()
+                    .insert(rating.created_at, rating);
+            }
+        }
+
+        for rating in ratings {
+            if !known.contains(&rating.app_id) {
+                report.missing += 1;
+                continue;
+            }
+            let timeline = timelines.entry(rating.app_id.clone()).or_default();
+            let created_at = rating.created_at;
+            if merge_point(timeline, &rating.app_id, created_at, &rating, same_app_rating, report)
+            {
+                Self::insert_app_rating_at(&mut *conn, &rating, Some(created_at)).await?;
+                report.inserted += 1;
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_substances(
+        conn: &mut PgConnection,
+        substances: Vec<SnapshotSubstance>,
+        options: &ImportOptions,
+        report: &mut ImportReport,
+    ) -> Result<()> {
+        let ids = unique_ids(substances.iter().map(|substance| &substance.substance_id));
+        type Fields = (String, Option<String>, Option<String>);
+        let mut existing: HashMap<String, Fields> = sqlx::query(
+            "SELECT substance_id, title, subtitle, name FROM substance_info
+            WHERE substance_id = ANY($1) ORDER BY substance_id FOR UPDATE",
+        )
+        .bind(&ids)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| {
+            (
+                row.get("substance_id"),
+                (row.get("title"), row.get("subtitle"), row.get("name")),
+            )
+        })
+        .collect();
+
+        for substance in substances {
+            let fields: Fields = (
+                substance.title.clone(),
+                substance.subtitle.clone(),
+                substance.name.clone(),
+            );
+            let diff = existing.get(&substance.substance_id).map(|last| {
+                let mut diff = Vec::new();
+                if last.0 != fields.0 {
+                    diff.push("title".to_string());
+                }
+                if last.1 != fields.1 {
+                    diff.push("subtitle".to_string());
+                }
+                if last.2 != fields.2 {
+                    diff.push("name".to_string());
+                }
+                diff
+            });
+            match diff {
+                Some(diff) if diff.is_empty() => report.duplicates += 1,
+                Some(diff) => {
+                    report.conflict(&substance.substance_id, None, diff);
+                    if options.overwrite {
+                        sqlx::query(
+                            "UPDATE substance_info SET title = $2, subtitle = $3, name = $4
+                            WHERE substance_id = $1",
+                        )
+                        .bind(&substance.substance_id)
+                        .bind(&substance.title)
+                        .bind(&substance.subtitle)
+                        .bind(&substance.name)
+                        .execute(&mut *conn)
+                        .await?;
+                        report.updated += 1;
+                        existing.insert(substance.substance_id, fields);
+                    }
+                }
+                None => {
+                    sqlx::query(
+                        "INSERT INTO substance_info
+                            (substance_id, title, subtitle, name, comment, created_at)
+                        VALUES ($1, $2, $3, $4, $5, COALESCE($6, now()))",
+                    )
+                    .bind(&substance.substance_id)
+                    .bind(&substance.title)
+                    .bind(&substance.subtitle)
+                    .bind(&substance.name)
+                    .bind(&substance.comment)
+                    .bind(substance.created_at)
+                    .execute(&mut *conn)
+                    .await?;
+                    report.inserted += 1;
+                    existing.insert(substance.substance_id, fields);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn import_substance_apps(
+        conn: &mut PgConnection,
+        maps: Vec<SnapshotSubstanceApp>,
+        report: &mut ImportReport,
+    ) -> Result<()> {
+        let app_ids = unique_ids(maps.iter().map(|map| &map.app_id));
+        let substance_ids = unique_ids(maps.iter().map(|map| &map.substance_id));
+        let known_apps = Self::existing_app_ids(conn, &app_ids).await?;
+        let known_substances: HashSet<String> = sqlx::query_scalar::<_, String>(
+            "SELECT substance_id FROM substance_info WHERE substance_id = ANY($1)",
+        )
+        .bind(&substance_ids)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+
+        for map in maps {
+            if !known_apps.contains(&map.app_id) || !known_substances.contains(&map.substance_id) {
+                report.missing += 1;
+                continue;
+            }
+            let inserted = sqlx::query(
+                "INSERT INTO substance_app_map (substance_id, app_id) VALUES ($1, $2)
+                ON CONFLICT (substance_id, app_id) DO NOTHING",
+            )
+            .bind(&map.substance_id)
+            .bind(&map.app_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            if inserted > 0 {
+                report.inserted += 1;
+            } else {
+                report.duplicates += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    struct Point {
+        id: i64,
+        value: i64,
+    }
+
+    fn same(a: &Point, b: &Point) -> bool {
+        a.value == b.value
+    }
+
+    #[test]
+    fn test_merge_timeline() {
+        let t = |h: u32| {
+            use chrono::TimeZone;
+            Local.with_ymd_and_hms(2025, 1, 1, h, 0, 0).unwrap()
+        };
+        let point = |value: i64| Point { id: 0, value };
+        let mut report = ImportReport::new(Dataset::Metrics, Path::new("metrics.ndjson"));
+        let mut timeline = BTreeMap::from([(t(2), point(10)), (t(6), point(30))]);
+
+        // 同一时间点相同 -> 重复, 不同 -> 冲突
+        assert!(!merge_point(&mut timeline, "a", t(2), &point(10), same, &mut report));
+        assert!(!merge_point(&mut timeline, "a", t(6), &point(31), same, &mut report));
+        // 和前一个时间点相同 -> 跳过
+        assert!(!merge_point(&mut timeline, "a", t(3), &point(10), same, &mut report));
+        // 中间插入新的时间点
+        assert!(merge_point(&mut timeline, "a", t(4), &point(20), same, &mut report));
+        // 比已有数据都早
+        assert!(merge_point(&mut timeline, "a", t(1), &point(10), same, &mut report));
+
+        assert_eq!(report.duplicates, 2);
+        assert_eq!(report.conflicts, 1);
+        assert_eq!(report.conflict_samples[0].fields, vec!["value".to_string()]);
+        assert_eq!(timeline.len(), 4);
+    }
+
+    #[test]
+    fn test_normalize_row() {
+        let row: Map<String, JsonValue> = serde_json::from_str(
+            r#"{"substance_id":"s1","title":"t","comment":"{\"a\":1}","created_at":null}"#,
+        )
+        .unwrap();
+        let row = normalize(Dataset::Substances, row);
+        // 缺失的列补 null, 字符串形式的 JSON 列还原
+        assert_eq!(row["subtitle"], JsonValue::Null);
+        assert_eq!(row["comment"]["a"], 1);
+
+        let substances: Vec<SnapshotSubstance> =
+            parse_rows(Dataset::Substances, vec![row], 0).unwrap();
+        assert_eq!(substances[0].substance_id, "s1");
+        assert!(substances[0].created_at.is_none());
+    }
+}
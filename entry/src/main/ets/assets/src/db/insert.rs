@@ -1,76 +1,122 @@
 use anyhow::Result;
+use chrono::{DateTime, Local};
 use serde_json::Value as JsonValue;
+use sqlx::PgExecutor;
+use tracing::{Level, event};
 
-use crate::db::Database;
+use crate::db::{Database, search::name_pinyin};
 use crate::model::{AppInfo, AppMetric, AppRating, AppRecord};
 use crate::sync::substance::SubstanceData;
 
+/// 更新已有应用时要不要覆盖人工维护的 listed_at / comment
+///
+/// 同步拿到的 AppInfo 里 listed_at 是当前时间, comment 为空,
+/// 没有明确传入时保留库里的值, 不然审核通过写进去的元数据下次同步就被冲掉了
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetadataOverwrite {
+    pub listed_at: bool,
+    pub comment: bool,
+}
+
+impl MetadataOverwrite {
+    /// 按是否明确传入决定覆盖哪些字段
+    pub fn explicit(listed_at: Option<DateTime<Local>>, comment: Option<&JsonValue>) -> Self {
+        Self {
+            listed_at: listed_at.is_some(),
+            comment: comment.is_some(),
+        }
+    }
+
+    /// 和 upsert 的规则一致: 不覆盖的字段沿用 `prev` 里的值
+    pub fn merge(self, info: &mut AppInfo, prev: &AppInfo) {
+        if !self.listed_at {
+            info.listed_at = prev.listed_at;
+        }
+        if !self.comment {
+            info.comment = prev.comment.clone();
+        }
+    }
+}
+
+/// app_info 的 upsert, $46 / $47 对应 [`MetadataOverwrite`] 的两个字段,
+/// $48 / $49 是由应用名算出来的拼音 (见 [`name_pinyin`])
+pub(crate) const UPSERT_APP_INFO: &str = r#"
+        INSERT INTO app_info (
+            app_id, alliance_app_id, name, pkg_name, dev_id, developer_name,
+            dev_en_name, supplier, kind_id, kind_name, tag_name,
+            kind_type_id, kind_type_name, icon_url, brief_desc, description,
+            privacy_url, ctype, detail_id, app_level, jocat_id, iap, hms,
+            tariff_type, packing_type, order_app, denpend_gms, denpend_hms,
+            force_update, img_tag, is_pay, is_disciplined, is_shelves,
+            submit_type, delete_archive, charging, button_grey, app_gift,
+            free_days, pay_install_type, created_at, listed_at, comment,
+            release_countries, main_device_codes, name_pinyin, name_initials
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
+            $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28,
+            $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41,
+            $42, $43, $44, $45, $48, $49
+        )
+        ON CONFLICT (app_id) DO UPDATE SET
+            alliance_app_id = EXCLUDED.alliance_app_id,
+            name = EXCLUDED.name,
+            pkg_name = EXCLUDED.pkg_name,
+            dev_id = EXCLUDED.dev_id,
+            developer_name = EXCLUDED.developer_name,
+            dev_en_name = EXCLUDED.dev_en_name,
+            supplier = EXCLUDED.supplier,
+            kind_id = EXCLUDED.kind_id,
+            kind_name = EXCLUDED.kind_name,
+            tag_name = EXCLUDED.tag_name,
+            kind_type_id = EXCLUDED.kind_type_id,
+            kind_type_name = EXCLUDED.kind_type_name,
+            icon_url = EXCLUDED.icon_url,
+            brief_desc = EXCLUDED.brief_desc,
+            description = EXCLUDED.description,
+            privacy_url = EXCLUDED.privacy_url,
+            ctype = EXCLUDED.ctype,
+            detail_id = EXCLUDED.detail_id,
+            app_level = EXCLUDED.app_level,
+            jocat_id = EXCLUDED.jocat_id,
+            iap = EXCLUDED.iap,
+            hms = EXCLUDED.hms,
+            tariff_type = EXCLUDED.tariff_type,
+            packing_type = EXCLUDED.packing_type,
+            order_app = EXCLUDED.order_app,
+            denpend_gms = EXCLUDED.denpend_gms,
+            denpend_hms = EXCLUDED.denpend_hms,
+            force_update = EXCLUDED.force_update,
+            img_tag = EXCLUDED.img_tag,
+            is_pay = EXCLUDED.is_pay,
+            is_disciplined = EXCLUDED.is_disciplined,
+            is_shelves = EXCLUDED.is_shelves,
+            submit_type = EXCLUDED.submit_type,
+            delete_archive = EXCLUDED.delete_archive,
+            charging = EXCLUDED.charging,
+            button_grey = EXCLUDED.button_grey,
+            app_gift = EXCLUDED.app_gift,
+            free_days = EXCLUDED.free_days,
+            pay_install_type = EXCLUDED.pay_install_type,
+            listed_at = CASE WHEN $46 THEN EXCLUDED.listed_at ELSE app_info.listed_at END,
+            comment = CASE WHEN $47 THEN EXCLUDED.comment ELSE app_info.comment END,
+            release_countries = EXCLUDED.release_countries,
+            main_device_codes = EXCLUDED.main_device_codes,
+            name_pinyin = EXCLUDED.name_pinyin,
+            name_initials = EXCLUDED.name_initials
+    "#;
+
 impl Database {
     /// 插入应用信息到 app_info 表
-    pub async fn insert_app_info(&self, app_info: &AppInfo) -> Result<()> {
-        const QUERY: &str = r#"
-            INSERT INTO app_info (
-                app_id, alliance_app_id, name, pkg_name, dev_id, developer_name,
-                dev_en_name, supplier, kind_id, kind_name, tag_name,
-                kind_type_id, kind_type_name, icon_url, brief_desc, description,
-                privacy_url, ctype, detail_id, app_level, jocat_id, iap, hms,
-                tariff_type, packing_type, order_app, denpend_gms, denpend_hms,
-                force_update, img_tag, is_pay, is_disciplined, is_shelves,
-                submit_type, delete_archive, charging, button_grey, app_gift,
-                free_days, pay_install_type, created_at, listed_at, comment,
-                release_countries, main_device_codes
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
-                $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28,
-                $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41,
-                $42, $43, $44, $45
-            )
-            ON CONFLICT (app_id) DO UPDATE SET
-                alliance_app_id = EXCLUDED.alliance_app_id,
-                name = EXCLUDED.name,
-                pkg_name = EXCLUDED.pkg_name,
-                dev_id = EXCLUDED.dev_id,
-                developer_name = EXCLUDED.developer_name,
-                dev_en_name = EXCLUDED.dev_en_name,
-                supplier = EXCLUDED.supplier,
-                kind_id = EXCLUDED.kind_id,
-                kind_name = EXCLUDED.kind_name,
-                tag_name = EXCLUDED.tag_name,
-                kind_type_id = EXCLUDED.kind_type_id,
-                kind_type_name = EXCLUDED.kind_type_name,
-                icon_url = EXCLUDED.icon_url,
-                brief_desc = EXCLUDED.brief_desc,
-                description = EXCLUDED.description,
-                privacy_url = EXCLUDED.privacy_url,
-                ctype = EXCLUDED.ctype,
-                detail_id = EXCLUDED.detail_id,
-                app_level = EXCLUDED.app_level,
-                jocat_id = EXCLUDED.jocat_id,
-                iap = EXCLUDED.iap,
-                hms = EXCLUDED.hms,
-                tariff_type = EXCLUDED.tariff_type,
-                packing_type = EXCLUDED.packing_type,
-                order_app = EXCLUDED.order_app,
-                denpend_gms = EXCLUDED.denpend_gms,
-                denpend_hms = EXCLUDED.denpend_hms,
-                force_update = EXCLUDED.force_update,
-                img_tag = EXCLUDED.img_tag,
-                is_pay = EXCLUDED.is_pay,
-                is_disciplined = EXCLUDED.is_disciplined,
-                is_shelves = EXCLUDED.is_shelves,
-                submit_type = EXCLUDED.submit_type,
-                delete_archive = EXCLUDED.delete_archive,
-                charging = EXCLUDED.charging,
-                button_grey = EXCLUDED.button_grey,
-                app_gift = EXCLUDED.app_gift,
-                free_days = EXCLUDED.free_days,
-                pay_install_type = EXCLUDED.pay_install_type,
-                listed_at = EXCLUDED.listed_at,
-                release_countries = EXCLUDED.release_countries,
-                main_device_codes = EXCLUDED.main_device_codes
-        "#;
-
-        sqlx::query(QUERY)
+    ///
+    /// 应用相关的插入都接受任意 executor, 方便放进同一个事务;
+    /// 已有的应用只在 `overwrite` 指定时才覆盖 listed_at / comment
+    pub async fn insert_app_info<'e>(
+        executor: impl PgExecutor<'e>,
+        app_info: &AppInfo,
+        overwrite: MetadataOverwrite,
+    ) -> Result<()> {
+        let (pinyin, initials) = name_pinyin(&app_info.name);
+        sqlx::query(UPSERT_APP_INFO)
             .bind(&app_info.app_id)
             .bind(&app_info.alliance_app_id)
             .bind(&app_info.name)
@@ -116,24 +162,41 @@ impl Database {
             .bind(&app_info.comment)
             .bind(&app_info.release_countries)
             .bind(&app_info.main_device_codes)
-            .execute(&self.pool)
+            .bind(overwrite.listed_at)
+            .bind(overwrite.comment)
+            .bind(pinyin)
+            .bind(initials)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
     /// 插入应用指标到 app_metrics 表
-    pub async fn insert_app_metric(&self, app_metric: &AppMetric) -> Result<()> {
+    pub async fn insert_app_metric<'e>(
+        executor: impl PgExecutor<'e>,
+        app_metric: &AppMetric,
+    ) -> Result<()> {
+        Self::insert_app_metric_at(executor, app_metric, None).await
+    }
+
+    /// 插入应用指标, 指定 created_at (导入快照时保留原来的时间), None 时用数据库当前时间
+    pub async fn insert_app_metric_at<'e>(
+        executor: impl PgExecutor<'e>,
+        app_metric: &AppMetric,
+        created_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
         const QUERY: &str = r#"
             INSERT INTO app_metrics (
                 app_id, pkg_name, version, version_code, size_bytes, sha256, info_score,
                 info_rate_count, download_count, price, release_date, new_features,
                 upgrade_msg, target_sdk, minsdk, compile_sdk_version,
-                min_hmos_api_level, api_release_type
+                min_hmos_api_level, api_release_type, created_at
             ) VALUES (
                 $1,
                 (SELECT pkg_name FROM app_info WHERE app_id = $1),
-                $2, $3, $4, $5, $6::numeric, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17
+                $2, $3, $4, $5, $6::numeric, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+                COALESCE($18, now())
             )
         "#;
 
@@ -155,23 +218,36 @@ impl Database {
             .bind(app_metric.compile_sdk_version)
             .bind(app_metric.min_hmos_api_level)
             .bind(&app_metric.api_release_type)
-            .execute(&self.pool)
+            .bind(created_at)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
     /// 插入应用评分到 app_rating 表
-    pub async fn insert_app_rating(&self, app_rating: &AppRating) -> Result<()> {
+    pub async fn insert_app_rating<'e>(
+        executor: impl PgExecutor<'e>,
+        app_rating: &AppRating,
+    ) -> Result<()> {
+        Self::insert_app_rating_at(executor, app_rating, None).await
+    }
+
+    /// 插入应用评分, 指定 created_at, 同 [`Database::insert_app_metric_at`]
+    pub async fn insert_app_rating_at<'e>(
+        executor: impl PgExecutor<'e>,
+        app_rating: &AppRating,
+        created_at: Option<DateTime<Local>>,
+    ) -> Result<()> {
         const QUERY: &str = r#"
             INSERT INTO app_rating (
                 app_id, pkg_name, average_rating,
                 star_1_rating_count, star_2_rating_count, star_3_rating_count,
                 star_4_rating_count, star_5_rating_count, my_star_rating,
                 total_star_rating_count, only_star_count, full_average_rating,
-                source_type
+                source_type, created_at
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, COALESCE($14, now())
             )
         "#;
 
@@ -189,14 +265,18 @@ impl Database {
             .bind(app_rating.only_star_count)
             .bind(app_rating.full_average_rating)
             .bind(&app_rating.source_type)
-            .execute(&self.pool)
+            .bind(created_at)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
     /// 插入应用备案信息到 app_record 表
-    pub async fn insert_app_record(&self, app_record: &AppRecord) -> Result<()> {
+    pub async fn insert_app_record<'e>(
+        executor: impl PgExecutor<'e>,
+        app_record: &AppRecord,
+    ) -> Result<()> {
         const QUERY: &str = r#"
             INSERT INTO app_record (
                 app_id, title, app_recordal_info,
@@ -217,14 +297,18 @@ impl Database {
             .bind(&app_record.app_recordal_info)
             .bind(&app_record.recordal_entity_title)
             .bind(&app_record.recordal_entity_name)
-            .execute(&self.pool)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
     /// 插入应用数据到 app_data_history 表
-    pub async fn insert_data_history(&self, app_id: &str, data: &JsonValue) -> Result<()> {
+    pub async fn insert_data_history<'e>(
+        executor: impl PgExecutor<'e>,
+        app_id: &str,
+        data: &JsonValue,
+    ) -> Result<()> {
         let query = r#"
             INSERT INTO app_data_history (app_id, pkg_name, raw_json_data)
             VALUES ($1,
@@ -235,18 +319,86 @@ impl Database {
         sqlx::query(query)
             .bind(app_id)
             .bind(data)
-            .execute(&self.pool)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
+    /// 把 020 迁移之前写入的历史快照分批转换成 json_blob 布局, 返回转换的行数
+    ///
+    /// 按 id 分页, 每批一个短事务, 可以在服务运行时执行, 中断后重新执行即可.
+    /// 两张表都转换完后把 raw_json_hash 设为 NOT NULL
+    pub async fn backfill_json_blobs(&self, batch_size: usize) -> Result<u64> {
+        let mut converted = 0;
+        for (table, json) in [
+            ("app_data_history", "raw_json_data"),
+            ("substance_history", "raw_json_substance"),
+        ] {
+            let query = format!(
+                "UPDATE {table} SET raw_json_hash = json_blob_store({json}), {json} = NULL
+                 WHERE id IN (
+                     SELECT id FROM {table}
+                     WHERE id > $1 AND raw_json_hash IS NULL AND {json} IS NOT NULL
+                     ORDER BY id LIMIT $2
+                 )
+                 RETURNING id"
+            );
+            let mut after = 0i64;
+            loop {
+                let ids: Vec<i64> = sqlx::query_scalar(&query)
+                    .bind(after)
+                    .bind(batch_size.max(1) as i64)
+                    .fetch_all(&self.pool)
+                    .await?;
+                let Some(last) = ids.iter().max() else {
+                    break;
+                };
+                after = *last;
+                converted += ids.len() as u64;
+                event!(Level::INFO, "{table}: 已转换 {converted} 行, 当前 id {after}");
+            }
+
+            let remaining: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE raw_json_hash IS NULL"
+            ))
+            .fetch_one(&self.pool)
+            .await?;
+            if remaining == 0 {
+                sqlx::query(&format!(
+                    "ALTER TABLE {table} ALTER COLUMN raw_json_hash SET NOT NULL"
+                ))
+                .execute(&self.pool)
+                .await?;
+            } else {
+                event!(
+                    Level::WARN,
+                    "{table} 还有 {remaining} 行没有快照内容, raw_json_hash 保持可为空"
+                );
+            }
+        }
+        Ok(converted)
+    }
+
+    /// 删除不再被任何历史快照引用的 json_blob, 返回删除数量
+    ///
+    /// 执行期间会阻塞历史表的写入 (见 020 迁移), 所以只在 `blobs cleanup` 命令里手动调用
+    pub async fn cleanup_orphan_json_blobs(&self) -> Result<i32> {
+        let deleted: i32 = sqlx::query_scalar("SELECT cleanup_orphan_json_blobs()")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(deleted)
+    }
+
     /// 插入 substance 到 substance_info 表
-    pub async fn insert_substance(
-        &self,
+    ///
+    /// 返回是否新增或改写了这一行
+    pub async fn insert_substance<'e>(
+        executor: impl PgExecutor<'e>,
         substance: &SubstanceData,
         comment: Option<JsonValue>,
-    ) -> Result<()> {
+    ) -> Result<bool> {
+        // 标题等没变时不改写, 这样 rows_affected 才能反映是否真的有变化
         const QUERY: &str = r#"
             INSERT INTO substance_info (substance_id, title, subtitle, name, comment)
             VALUES ($1, $2, $3, $4, $5)
@@ -254,23 +406,25 @@ impl Database {
                 title = EXCLUDED.title,
                 subtitle = EXCLUDED.subtitle,
                 name = EXCLUDED.name
+            WHERE (substance_info.title, substance_info.subtitle, substance_info.name)
+                IS DISTINCT FROM (EXCLUDED.title, EXCLUDED.subtitle, EXCLUDED.name)
         "#;
 
-        sqlx::query(QUERY)
+        let result = sqlx::query(QUERY)
             .bind(&substance.id)
             .bind(&substance.title)
             .bind(&substance.sub_title)
             .bind(&substance.name)
             .bind(comment)
-            .execute(&self.pool)
+            .execute(executor)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
     /// 插入 substance history 到 substance_history 表
-    pub async fn insert_substance_history(
-        &self,
+    pub async fn insert_substance_history<'e>(
+        executor: impl PgExecutor<'e>,
         substance_id: &str,
         substance: &JsonValue,
     ) -> Result<()> {
@@ -282,26 +436,32 @@ impl Database {
         sqlx::query(QUERY)
             .bind(substance_id)
             .bind(substance)
-            .execute(&self.pool)
+            .execute(executor)
             .await?;
 
         Ok(())
     }
 
     /// 插入 substance 和 app 的映射关系到 substance_app_map 表
-    pub async fn insert_substance_app_map(&self, substance_id: &str, app_id: &str) -> Result<()> {
+    ///
+    /// 返回是否新增了映射
+    pub async fn insert_substance_app_map<'e>(
+        executor: impl PgExecutor<'e>,
+        substance_id: &str,
+        app_id: &str,
+    ) -> Result<bool> {
         const QUERY: &str = r#"
             INSERT INTO substance_app_map (substance_id, app_id)
             VALUES ($1, $2)
             ON CONFLICT (substance_id, app_id) DO NOTHING
         "#;
 
-        sqlx::query(QUERY)
+        let result = sqlx::query(QUERY)
             .bind(substance_id)
             .bind(app_id)
-            .execute(&self.pool)
+            .execute(executor)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 }
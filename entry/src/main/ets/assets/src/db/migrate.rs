@@ -0,0 +1,387 @@
+//! 内嵌的数据库迁移
+//!
+//! 所有 schema 都在编译期 `include_str!` 进二进制, 版本记录在 `schema_version` 表里
+//!
+//! - 空库: 执行完整 schema (main.sql + 015 统计表 + 019 统计表分区 + 021 API key
+//!   + 022 限流统计 + main_triggers.sql + main_index.sql), 直接记为最新版本
+//! - 手动迁移到 015 的老库 (没有 `schema_version`): 校验通过后直接记为 [`BASELINE_VERSION`],
+//!   校验不通过时拒绝迁移
+//! - 之后新增的迁移放在 `sql/migrations/NNN_xxx/up.sql`, 并登记到 [`MIGRATIONS`]
+
+use anyhow::{Context, Result};
+use sqlx::{Executor, Row};
+use tracing::{Level, event};
+
+use crate::db::{
+    Database,
+    read_data::{SELECT_APP_INFO_FIELDS, SELECT_APP_METRIC_FIELDS, SELECT_APP_RATING_FIELDS},
+};
+
+/// 单个迁移
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// 基线版本, 对应手动迁移时代的最后一个迁移 `015_create_statistics_tables`
+pub const BASELINE_VERSION: i32 = 15;
+
+/// 完整 schema, 按依赖顺序拼接 (和 main.sql 一样始终是最新结构)
+const BASELINE_SQL: &[&str] = &[
+    include_str!("../../sql/main.sql"),
+    include_str!("../../sql/migrations/015_create_statistics_tables/up.sql"),
+    include_str!("../../sql/migrations/019_partition_statistics_tables/up.sql"),
+    include_str!("../../sql/migrations/021_add_api_keys/up.sql"),
+    include_str!("../../sql/migrations/022_add_throttled_count/up.sql"),
+    include_str!("../../sql/main_triggers.sql"),
+    include_str!("../../sql/main_index.sql"),
+];
+
+/// 基线之后的迁移, 版本号必须严格递增
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 16,
+        name: "add_app_search",
+        sql: include_str!("../../sql/migrations/016_add_app_search/up.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "add_metrics_rollup",
+        sql: include_str!("../../sql/migrations/017_add_metrics_rollup/up.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "add_changelog",
+        sql: include_str!("../../sql/migrations/018_add_changelog/up.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "partition_statistics_tables",
+        sql: include_str!("../../sql/migrations/019_partition_statistics_tables/up.sql"),
+    },
+    Migration {
+        version: 20,
+        name: "dedup_json_history",
+        sql: include_str!("../../sql/migrations/020_dedup_json_history/up.sql"),
+    },
+    Migration {
+        version: 21,
+        name: "add_api_keys",
+        sql: include_str!("../../sql/migrations/021_add_api_keys/up.sql"),
+    },
+    Migration {
+        version: 22,
+        name: "add_throttled_count",
+        sql: include_str!("../../sql/migrations/022_add_throttled_count/up.sql"),
+    },
+    Migration {
+        version: 23,
+        name: "add_submission_jobs",
+        sql: include_str!("../../sql/migrations/023_add_submission_jobs/up.sql"),
+    },
+    Migration {
+        version: 24,
+        name: "add_submission_reviews",
+        sql: include_str!("../../sql/migrations/024_add_submission_reviews/up.sql"),
+    },
+    Migration {
+        version: 25,
+        name: "add_submission_job_lease",
+        sql: include_str!("../../sql/migrations/025_add_submission_job_lease/up.sql"),
+    },
+    Migration {
+        version: 26,
+        name: "add_app_name_pinyin",
+        sql: include_str!("../../sql/migrations/026_add_app_name_pinyin/up.sql"),
+    },
+    Migration {
+        version: 27,
+        name: "add_rating_rollup",
+        sql: include_str!("../../sql/migrations/027_add_rating_rollup/up.sql"),
+    },
+];
+
+/// 迁移时使用的 advisory lock key, 防止多个实例同时迁移
+const MIGRATE_LOCK_KEY: i64 = 0x6875_6177_6569;
+
+/// 当前程序需要的 schema 版本
+pub fn latest_version() -> i32 {
+    MIGRATIONS
+        .last()
+        .map(|m| m.version)
+        .unwrap_or(BASELINE_VERSION)
+}
+
+/// schema 检查结果
+#[derive(Debug, Clone)]
+pub struct SchemaStatus {
+    /// 数据库当前版本, None 表示还没初始化
+    pub current: Option<i32>,
+    /// 程序需要的版本
+    pub latest: i32,
+    /// 本次执行的迁移
+    pub applied: Vec<i32>,
+}
+
+impl Database {
+    /// 读取数据库记录的 schema 版本
+    async fn schema_version(&self) -> Result<Option<i32>> {
+        let has_table: bool = sqlx::query_scalar("SELECT to_regclass('schema_version') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        if !has_table {
+            return Ok(None);
+        }
+        let version: Option<i32> = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version)
+    }
+
+    /// 是否是还没有 `schema_version` 的老库
+    async fn is_legacy_schema(&self) -> Result<bool> {
+        let has_app_info: bool = sqlx::query_scalar("SELECT to_regclass('app_info') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(has_app_info)
+    }
+
+    /// 检查 schema 并按需执行迁移
+    ///
+    /// # 参数
+    /// - `apply`: 是否执行待执行的迁移, false 时有待执行迁移直接报错
+    ///
+    /// 数据库版本比程序新, 或者校验不通过时都会报错, 调用方应当拒绝启动
+    pub async fn migrate(&self, apply: bool) -> Result<SchemaStatus> {
+        let latest = latest_version();
+        let mut current = self.schema_version().await?;
+        let mut applied = Vec::new();
+
+        if let Some(version) = current
+            && version > latest
+        {
+            anyhow::bail!(
+                "数据库 schema 版本 {version} 比程序支持的 {latest} 更新, 请升级程序后再启动"
+            );
+        }
+
+        if current.is_none() {
+            if !apply {
+                anyhow::bail!("数据库还没有 schema 版本记录, 请先运行 migrate");
+            }
+            let mut tx = self.pool.begin().await?;
+            lock_migration(&mut tx).await?;
+            create_version_table(&mut tx).await?;
+            // 拿到锁之后再看一眼, 可能别的实例已经初始化了
+            let locked_version: Option<i32> =
+                sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+                    .fetch_one(&mut *tx)
+                    .await?;
+            if locked_version.is_none() {
+                if self.is_legacy_schema().await? {
+                    // 没迁移到 015 的库标成基线后, 之后的迁移会跑在错误的结构上
+                    self.verify_baseline_schema().await.with_context(|| {
+                        format!(
+                            "老库没有手动迁移到基线版本 {BASELINE_VERSION}, 请先执行到 015_create_statistics_tables"
+                        )
+                    })?;
+                    event!(
+                        Level::INFO,
+                        "检测到手动迁移的老库, 标记为基线版本 {BASELINE_VERSION}"
+                    );
+                    record_version(&mut tx, BASELINE_VERSION, "baseline (legacy)").await?;
+                } else {
+                    event!(Level::INFO, "空数据库, 执行基线 schema");
+                    for sql in BASELINE_SQL {
+                        (&mut *tx)
+                            .execute(*sql)
+                            .await
+                            .with_context(|| "执行基线 schema 失败")?;
+                    }
+                    record_version(&mut tx, BASELINE_VERSION, "baseline").await?;
+                    applied.push(BASELINE_VERSION);
+                    // main.sql 等文件始终是最新的完整结构, 之后的迁移不用再执行
+                    for migration in MIGRATIONS {
+                        record_version(&mut tx, migration.version, migration.name).await?;
+                        applied.push(migration.version);
+                    }
+                }
+            }
+            tx.commit().await?;
+            current = self.schema_version().await?;
+        }
+
+        let version = current.unwrap_or(BASELINE_VERSION);
+        let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+        if !pending.is_empty() {
+            if !apply {
+                anyhow::bail!(
+                    "数据库 schema 版本 {version} 落后于程序需要的 {latest}, 有 {} 个迁移待执行, 请运行 migrate",
+                    pending.len()
+                );
+            }
+            for migration in pending {
+                let mut tx = self.pool.begin().await?;
+                lock_migration(&mut tx).await?;
+                let locked_version: Option<i32> =
+                    sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+                        .fetch_one(&mut *tx)
+                        .await?;
+                if locked_version.unwrap_or(0) >= migration.version {
+                    // 别的实例已经执行过了
+                    continue;
+                }
+                event!(
+                    Level::INFO,
+                    "执行迁移 {:03}_{}",
+                    migration.version,
+                    migration.name
+                );
+                (&mut *tx).execute(migration.sql).await.with_context(|| {
+                    format!("执行迁移 {:03}_{} 失败", migration.version, migration.name)
+                })?;
+                record_version(&mut tx, migration.version, migration.name).await?;
+                tx.commit().await?;
+                applied.push(migration.version);
+            }
+        }
+
+        self.verify_schema()
+            .await
+            .with_context(|| "数据库 schema 与程序不兼容")?;
+
+        Ok(SchemaStatus {
+            current: self.schema_version().await?,
+            latest,
+            applied,
+        })
+    }
+
+    /// 校验程序会读写的表和字段都存在
+    ///
+    /// 直接用 read_data 里的字段列表跑一遍 `LIMIT 0`, 缺字段就会报错
+    pub async fn verify_schema(&self) -> Result<()> {
+        let checks = [
+            format!("SELECT {SELECT_APP_INFO_FIELDS} FROM app_full_info LIMIT 0"),
+            format!("SELECT {SELECT_APP_METRIC_FIELDS} FROM app_metrics LIMIT 0"),
+            format!("SELECT {SELECT_APP_RATING_FIELDS} FROM app_rating LIMIT 0"),
+            "SELECT worker_id, heartbeat_at FROM submission_jobs LIMIT 0".to_string(),
+        ];
+        for sql in checks.iter() {
+            sqlx::query(sql)
+                .fetch_all(&self.pool)
+                .await
+                .with_context(|| format!("校验失败: {sql}"))?;
+        }
+
+        const TABLES: &[&str] = &[
+            "app_info",
+            "app_metrics_rollup",
+            "app_metrics_text",
+            "app_metrics_timeline",
+            "app_download_timeline",
+            "app_rating_rollup",
+            "app_rating_timeline",
+            "app_data_history",
+            "app_record",
+            "substance_info",
+            "substance_history",
+            "substance_app_map",
+            "app_changelog",
+            "substance_changelog",
+            "json_blob",
+            "ua_statistics",
+            "ip_statistics",
+            "ua_hourly_statistics",
+            "ip_hourly_statistics",
+            "access_logs",
+            "api_keys",
+            "submission_jobs",
+            "submission_reviews",
+            "moderation_log",
+        ];
+        self.require_tables(TABLES).await
+    }
+
+    /// 校验老库是不是已经手动迁移到了 [`BASELINE_VERSION`]
+    ///
+    /// 只检查到 015 为止的表和 014 加到 app_full_info 上的备案字段, 之后的迁移还没有执行
+    async fn verify_baseline_schema(&self) -> Result<()> {
+        const TABLES: &[&str] = &[
+            "app_info",
+            "app_metrics",
+            "app_rating",
+            "app_full_info",
+            "app_data_history",
+            "app_record",
+            "substance_info",
+            "substance_history",
+            "substance_app_map",
+            "ua_statistics",
+            "ip_statistics",
+            "ua_hourly_statistics",
+            "ip_hourly_statistics",
+            "access_logs",
+        ];
+        self.require_tables(TABLES).await?;
+        let sql = "SELECT title, app_recordal_info, recordal_entity_title, recordal_entity_name \
+                   FROM app_full_info LIMIT 0";
+        sqlx::query(sql)
+            .fetch_all(&self.pool)
+            .await
+            .with_context(|| format!("校验失败: {sql}"))?;
+        Ok(())
+    }
+
+    async fn require_tables(&self, tables: &[&str]) -> Result<()> {
+        let rows = sqlx::query("SELECT t, to_regclass(t) IS NOT NULL AS found FROM unnest($1::text[]) AS t")
+            .bind(tables)
+            .fetch_all(&self.pool)
+            .await?;
+        let missing: Vec<String> = rows
+            .iter()
+            .filter(|row| !row.get::<bool, _>("found"))
+            .map(|row| row.get("t"))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!("缺少数据表: {}", missing.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// 事务级 advisory lock, 事务结束自动释放
+async fn lock_migration(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATE_LOCK_KEY)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+async fn create_version_table(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> Result<()> {
+    const QUERY: &str = r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+    "#;
+    (&mut **tx).execute(QUERY).await?;
+    Ok(())
+}
+
+async fn record_version(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    version: i32,
+    name: &str,
+) -> Result<()> {
+    sqlx::query("INSERT INTO schema_version (version, name) VALUES ($1, $2)")
+        .bind(version)
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -1,20 +1,38 @@
-use crate::model::{AppInfo, AppMetric, AppRating, AppRecord, FullAppInfo, raw::RawAppData};
+use crate::model::{AppQuery, FullAppInfo, raw::RawAppData};
 use crate::sync::substance::SubstanceData;
 
 use anyhow::Result;
 use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use tracing::{Level, event};
 
 use sqlx::{
     FromRow,
     postgres::{PgPool, PgPoolOptions},
 };
 
+pub mod api_key;
+pub mod batch;
+pub mod changelog;
+pub mod consistency;
+pub mod expand;
+pub mod export;
+pub mod filter;
+pub mod import;
 pub mod insert;
+pub mod migrate;
+pub mod moderation;
+pub mod partition;
 pub mod query;
 pub mod read_data;
+pub mod replica;
+pub mod rollup;
+pub mod search;
+pub mod sqlite;
 pub mod statistics;
+pub mod storage;
+pub mod submission;
 
 /// 分页查询结果
 #[derive(Debug, Deserialize, Serialize)]
@@ -26,9 +44,25 @@ pub struct PageInfo<D> {
     pub total_pages: u32,
 }
 
+impl<D> PageInfo<D> {
+    /// 转换每一项, 分页信息不变
+    pub fn map<T>(self, f: impl FnMut(D) -> T) -> PageInfo<T> {
+        PageInfo {
+            data: self.data.into_iter().map(f).collect(),
+            total_count: self.total_count,
+            page: self.page,
+            page_size: self.page_size,
+            total_pages: self.total_pages,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Database {
+    /// 主库, 所有写入和写后读都走这里
     pub pool: PgPool,
+    /// 只读副本, 见 [`Database::read_pool`]
+    pub(crate) replicas: std::sync::Arc<replica::ReplicaSet>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,7 +73,7 @@ pub struct DbSearch {
     pub not_null: bool,
 }
 
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize, FromRow)]
 pub struct DownloadIncrement {
     pub app_id: String,
     pub name: String,
@@ -51,7 +85,7 @@ pub struct DownloadIncrement {
     pub download_increment: i64,
 }
 
-#[derive(Debug, FromRow, Deserialize, Serialize)]
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
 pub struct AppCounts {
     pub total: i64,
     pub apps: i64,
@@ -120,114 +154,105 @@ impl Database {
             .connect(database_url)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            replicas: Default::default(),
+        })
     }
 
     /// 保存应用数据到数据库
     /// 返回布尔值表示是否插入了新数据，以及从 app_full_info 查询到的最新完整数据
     /// 返回: (info_updated, metric_updated, rating_updated, full_app_info)
+    ///
+    /// 所有写入在同一个事务里, 见 [`Database::save_app_data_batch`]
     pub async fn save_app_data(
         &self,
         data: RawAppData,
         listed_at: Option<DateTime<Local>>,
         comment: Option<JsonValue>,
     ) -> Result<(bool, bool, bool, FullAppInfo)> {
-        // 转换原始JSON数据用于比较
-        let query = data.id_query();
-        let app_id = data.app_id();
-
-        let raw_data = data.app_info;
-        let raw_value = data.app_info_json;
-        let exists = self.app_exists(&query).await;
-
-        // 检查并更新 app_info 和 app_metrics
-        let insert_data = if exists && self.is_same_data(&query, &raw_value).await {
-            (false, false)
-        } else {
-            let mut app_info: AppInfo = (&raw_data).into();
-            app_info.comment = comment;
-            if let Some(listed_at) = listed_at {
-                app_info.listed_at = listed_at;
-            }
-
-            // 转换并保存应用信息
-            let info_new = if self.is_same_app_info(&query, &app_info).await {
-                false
-            } else {
-                self.insert_app_info(&app_info).await?;
-                true
-            };
-
-            // 保存指标信息
-            let app_metric = AppMetric::from_raw_data(&raw_data);
-            let metric_new = if self.is_same_app_metric(&query, &app_metric).await {
-                false
-            } else {
-                self.insert_app_metric(&app_metric).await?;
-                true
-            };
-            (info_new, metric_new)
-        };
-
-        // 保存评分信息（如果有）
-        let insert_rate = if let Some(raw_star) = data.app_rating.as_ref() {
-            let app_rating = AppRating::from_raw_star(&raw_data, raw_star);
-            // is_new_app_rating 返回 true 表示与数据库不同，需要插入
-            if self.is_new_app_rating(&query, &app_rating).await {
-                self.insert_app_rating(&app_rating).await?;
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        };
-
-        // 保存/覆盖 备案信息 (如果有的话)
-        if let Some(record) = data.app_record.as_ref() {
-            let app_record = AppRecord::from_raw_record(&app_id, record);
-            self.insert_app_record(&app_record).await?;
-        }
-
-        // 如果有数据更新，记录历史
-        if insert_data.0 || insert_data.1 {
-            self.insert_data_history(&app_id, &raw_value).await?;
-        }
-
-        // 从 app_full_info 表查询最新的完整数据（trigger 已自动更新）
-        let full_info = self.get_full_app_info(&query).await?;
-
-        Ok((insert_data.0, insert_data.1, insert_rate, full_info))
+        self.save_app_data_batch(vec![batch::PendingApp::new(data, listed_at, comment)])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("保存应用数据没有返回结果"))
     }
 
     /// 保存 substance 数据到数据库
+    ///
+    /// 专题信息、历史和应用映射在同一个事务里写入, 和 [`Database::save_app_data_batch`] 一样
+    /// 变更记录在提交之后计算
+    ///
+    /// 返回 (是否是新专题, 专题信息/历史/应用映射是否有变化)
     pub async fn save_substance(
         &self,
         substance: &SubstanceData,
         raw_substance: &JsonValue,
         comment: Option<JsonValue>,
-    ) -> Result<bool> {
-        let is_new = !self.substance_exists(&substance.id).await;
-        self.insert_substance(substance, if is_new { comment } else { None })
-            .await?;
+    ) -> Result<(bool, bool)> {
+        let mut tx = self.pool.begin().await?;
+
+        let is_new: bool = sqlx::query_scalar(
+            "SELECT NOT EXISTS (SELECT 1 FROM substance_info WHERE substance_id = $1)",
+        )
+        .bind(&substance.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        // upsert 会锁住已有的行, 同一个专题的并发保存在这里排队, 下面读到的历史是最新的
+        let mut changed = Self::insert_substance(
+            &mut *tx,
+            substance,
+            if is_new { comment } else { None },
+        )
+        .await?;
 
         // 只在数据发生变化时插入历史记录
-        if !self.is_same_substance_data(&substance.id, raw_substance).await {
-            self.insert_substance_history(&substance.id, raw_substance)
-                .await?;
+        let last: Option<JsonValue> = sqlx::query_scalar(
+            "SELECT COALESCE(json_blob_load(raw_json_hash), raw_json_substance)
+             FROM substance_history
+             WHERE substance_id = $1
+             ORDER BY created_at DESC
+             LIMIT 1",
+        )
+        .bind(&substance.id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        let history_changed = last.is_none_or(|last| {
+            query::normalize_json_for_comparison(&last)
+                != query::normalize_json_for_comparison(raw_substance)
+        });
+        if history_changed {
+            Self::insert_substance_history(&mut *tx, &substance.id, raw_substance).await?;
+            changed = true;
         }
 
         for app_query in &substance.data {
-            let query = self.app_query_to_app_id(app_query).await?;
-            self.insert_substance_app_map(&substance.id, query.name())
-                .await?;
+            let app_id = match app_query {
+                AppQuery::AppId(app_id) => app_id.clone(),
+                AppQuery::PkgName(pkg_name) => {
+                    sqlx::query_scalar("SELECT app_id FROM app_info WHERE pkg_name = $1")
+                        .bind(pkg_name)
+                        .fetch_one(&mut *tx)
+                        .await?
+                }
+            };
+            changed |= Self::insert_substance_app_map(&mut *tx, &substance.id, &app_id).await?;
         }
 
-        // println!(
-        //     "{}",
-        //     format!("插入新的 substance {} ({})", substance.id, substance.title).bright_green()
-        // );
+        tx.commit().await?;
+
+        if history_changed
+            && let Err(e) = self
+                .build_changelog(changelog::ChangelogKind::Substance, &substance.id)
+                .await
+        {
+            event!(
+                Level::WARN,
+                "计算专题 {} 的变更记录失败: {e}",
+                substance.id
+            );
+        }
 
-        Ok(is_new)
+        Ok((is_new, changed))
     }
 }
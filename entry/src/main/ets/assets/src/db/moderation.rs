@@ -0,0 +1,590 @@
+//! 投稿审核
+//!
+//! 投稿带了 `comment` / `listed_at` 这类用户填写的元数据时, 投稿任务只同步应用数据,
+//! 元数据记成一条待审核的 [`SubmissionReview`]. 管理员可以修改、通过或驳回,
+//! 通过时才写入 app_info / substance_info; 每次操作都在 moderation_log 里留一条记录
+
+use std::net::IpAddr;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+use sqlx::{FromRow, PgConnection, types::Json};
+use utoipa::ToSchema;
+
+use crate::{
+    db::{
+        Database,
+        submission::{NewSubmission, SubmissionKind, app_target_query},
+    },
+    model::AppQuery,
+};
+
+/// 审核状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    /// 待审核
+    Pending,
+    /// 已通过, 元数据已写入
+    Approved,
+    /// 已驳回
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub const ALL: [ReviewStatus; 3] = [Self::Pending, Self::Approved, Self::Rejected];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.name() == name)
+    }
+}
+
+/// 审核操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// 修改待写入的元数据, 状态不变
+    Edit,
+    Approve,
+    Reject,
+}
+
+impl ModerationAction {
+    pub const ALL: [ModerationAction; 3] = [Self::Edit, Self::Approve, Self::Reject];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Edit => "edit",
+            Self::Approve => "approve",
+            Self::Reject => "reject",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// 待写入的元数据
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReviewMetadata {
+    pub comment: Option<JsonValue>,
+    pub listed_at: Option<DateTime<Utc>>,
+}
+
+impl ReviewMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.comment.is_none() && self.listed_at.is_none()
+    }
+}
+
+/// 一条投稿审核
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubmissionReview {
+    pub id: i64,
+    /// 对应的投稿任务
+    pub job_id: Option<i64>,
+    pub kind: SubmissionKind,
+    /// 同 [`crate::db::submission::SubmissionJob::target`]
+    pub target: String,
+    pub status: ReviewStatus,
+    /// 待写入的备注
+    pub comment: Option<JsonValue>,
+    /// 待写入的上架时间
+    pub listed_at: Option<DateTime<Utc>>,
+    /// 投稿人 IP
+    #[schema(value_type = String)]
+    pub submitter_ip: IpAddr,
+    /// 投稿人 User-Agent
+    pub submitter_ua: Option<String>,
+    /// 投稿使用的 API key, 匿名投稿为 None
+    pub api_key_id: Option<i64>,
+    /// 审核人的 API key
+    pub reviewed_by: Option<i64>,
+    pub review_note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// 通过 / 驳回时间
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl SubmissionReview {
+    /// 应用投稿对应的查询
+    pub fn app_query(&self) -> Option<AppQuery> {
+        app_target_query(self.kind, &self.target)
+    }
+
+    /// 执行审核操作之后的样子, 调用前需要确认还是待审核状态
+    pub fn moderated(&self, request: &ModerationRequest, now: DateTime<Utc>) -> Self {
+        let mut next = self.clone();
+        match request.action {
+            ModerationAction::Edit => {
+                if let Some(metadata) = &request.metadata {
+                    next.comment = metadata.comment.clone();
+                    next.listed_at = metadata.listed_at;
+                }
+            }
+            ModerationAction::Approve | ModerationAction::Reject => {
+                next.status = if request.action == ModerationAction::Approve {
+                    ReviewStatus::Approved
+                } else {
+                    ReviewStatus::Rejected
+                };
+                next.reviewed_by = request.actor_key_id;
+                next.review_note = request.note.clone();
+                next.reviewed_at = Some(now);
+            }
+        }
+        next
+    }
+
+    /// 审核记录里保存的快照
+    pub fn snapshot(&self) -> JsonValue {
+        json!({
+            "status": self.status,
+            "comment": self.comment,
+            "listed_at": self.listed_at,
+        })
+    }
+}
+
+/// 新的待审核投稿
+#[derive(Debug, Clone)]
+pub struct NewReview {
+    pub job_id: Option<i64>,
+    pub kind: SubmissionKind,
+    pub target: String,
+    pub metadata: ReviewMetadata,
+    pub submitter_ip: IpAddr,
+    pub submitter_ua: Option<String>,
+    pub api_key_id: Option<i64>,
+}
+
+impl NewReview {
+    /// 投稿没有带元数据时不需要审核, 返回 None
+    pub fn from_submission(
+        submission: &NewSubmission,
+        job_id: i64,
+        submitter_ip: IpAddr,
+        submitter_ua: Option<String>,
+    ) -> Option<Self> {
+        let metadata = ReviewMetadata {
+            comment: submission.comment.clone(),
+            listed_at: submission.listed_at,
+        };
+        if metadata.is_empty() {
+            return None;
+        }
+        Some(Self {
+            job_id: Some(job_id),
+            kind: submission.kind,
+            target: submission.target.clone(),
+            metadata,
+            submitter_ip,
+            submitter_ua,
+            api_key_id: submission.api_key_id,
+        })
+    }
+}
+
+/// 一次审核操作
+#[derive(Debug, Clone)]
+pub struct ModerationRequest {
+    pub action: ModerationAction,
+    /// 只有 edit 用到, 整体替换待写入的元数据
+    pub metadata: Option<ReviewMetadata>,
+    pub note: Option<String>,
+    /// 操作人的 API key
+    pub actor_key_id: Option<i64>,
+    pub actor_ip: Option<IpAddr>,
+}
+
+/// 审核操作的结果
+#[derive(Debug, Clone)]
+pub enum ModerationOutcome {
+    /// 操作成功, 返回操作之后的审核
+    Done(SubmissionReview),
+    NotFound,
+    /// 已经通过或驳回过了
+    NotPending(SubmissionReview),
+    /// 通过时对应的应用 / 专题还没有收录
+    TargetMissing,
+}
+
+/// 审核操作记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ModerationLogEntry {
+    pub id: i64,
+    pub review_id: i64,
+    pub action: ModerationAction,
+    /// 操作人的 API key
+    pub actor_key_id: Option<i64>,
+    /// 操作人 IP
+    #[schema(value_type = Option<String>)]
+    pub actor_ip: Option<IpAddr>,
+    /// 操作前的 status / comment / listed_at
+    pub old_value: JsonValue,
+    /// 操作后的 status / comment / listed_at
+    pub new_value: JsonValue,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct ReviewRow {
+    id: i64,
+    job_id: Option<i64>,
+    kind: String,
+    target: String,
+    status: String,
+    comment: Option<Json<JsonValue>>,
+    listed_at: Option<DateTime<Utc>>,
+    submitter_ip: IpAddr,
+    submitter_ua: Option<String>,
+    api_key_id: Option<i64>,
+    reviewed_by: Option<i64>,
+    review_note: Option<String>,
+    created_at: DateTime<Utc>,
+    reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ReviewRow> for SubmissionReview {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ReviewRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            job_id: row.job_id,
+            kind: SubmissionKind::from_name(&row.kind)
+                .ok_or_else(|| anyhow::anyhow!("未知的投稿类型: {}", row.kind))?,
+            target: row.target,
+            status: ReviewStatus::from_name(&row.status)
+                .ok_or_else(|| anyhow::anyhow!("未知的审核状态: {}", row.status))?,
+            comment: row.comment.map(|json| json.0),
+            listed_at: row.listed_at,
+            submitter_ip: row.submitter_ip,
+            submitter_ua: row.submitter_ua,
+            api_key_id: row.api_key_id,
+            reviewed_by: row.reviewed_by,
+            review_note: row.review_note,
+            created_at: row.created_at,
+            reviewed_at: row.reviewed_at,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct LogRow {
+    id: i64,
+    review_id: i64,
+    action: String,
+    actor_key_id: Option<i64>,
+    actor_ip: Option<IpAddr>,
+    old_value: Json<JsonValue>,
+    new_value: Json<JsonValue>,
+    note: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<LogRow> for ModerationLogEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(row: LogRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            review_id: row.review_id,
+            action: ModerationAction::from_name(&row.action)
+                .ok_or_else(|| anyhow::anyhow!("未知的审核操作: {}", row.action))?,
+            actor_key_id: row.actor_key_id,
+            actor_ip: row.actor_ip,
+            old_value: row.old_value.0,
+            new_value: row.new_value.0,
+            note: row.note,
+            created_at: row.created_at,
+        })
+    }
+}
+
+const SELECT_REVIEW_FIELDS: &str = "id, job_id, kind, target, status, comment, listed_at, \
+     submitter_ip, submitter_ua, api_key_id, reviewed_by, review_note, created_at, reviewed_at";
+
+const SELECT_LOG_FIELDS: &str =
+    "id, review_id, action, actor_key_id, actor_ip, old_value, new_value, note, created_at";
+
+impl Database {
+    /// 新建待审核投稿
+    pub async fn create_submission_review(&self, review: &NewReview) -> Result<SubmissionReview> {
+        let row: ReviewRow = sqlx::query_as(&format!(
+            "INSERT INTO submission_reviews
+                 (job_id, kind, target, comment, listed_at, submitter_ip, submitter_ua, api_key_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             RETURNING {SELECT_REVIEW_FIELDS}"
+        ))
+        .bind(review.job_id)
+        .bind(review.kind.name())
+        .bind(&review.target)
+        .bind(review.metadata.comment.as_ref().map(Json))
+        .bind(review.metadata.listed_at)
+        .bind(review.submitter_ip)
+        .bind(&review.submitter_ua)
+        .bind(review.api_key_id)
+        .fetch_one(&self.pool)
+        .await?;
+        row.try_into()
+    }
+
+    pub async fn get_submission_review(&self, id: i64) -> Result<Option<SubmissionReview>> {
+        let row: Option<ReviewRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(SubmissionReview::try_from).transpose()
+    }
+
+    /// 分页列出审核, 新的在前, 返回 `(当前页, 总数)`
+    pub async fn list_submission_reviews(
+        &self,
+        status: Option<ReviewStatus>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<SubmissionReview>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let status = status.map(|status| status.name());
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM submission_reviews WHERE $1::text IS NULL OR status = $1",
+        )
+        .bind(status)
+        .fetch_one(&self.pool)
+        .await?;
+        let rows: Vec<ReviewRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews
+             WHERE $1::text IS NULL OR status = $1
+             ORDER BY id DESC LIMIT $2 OFFSET $3"
+        ))
+        .bind(status)
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        let reviews = rows
+            .into_iter()
+            .map(SubmissionReview::try_from)
+            .collect::<Result<_>>()?;
+        Ok((reviews, total))
+    }
+
+    /// 执行审核操作, 修改审核、写入元数据 (通过时) 和记录操作在同一个事务里
+    pub async fn moderate_submission_review(
+        &self,
+        id: i64,
+        request: &ModerationRequest,
+    ) -> Result<ModerationOutcome> {
+        let mut tx = self.pool.begin().await?;
+        let row: Option<ReviewRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews WHERE id = $1 FOR UPDATE"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(review) = row.map(SubmissionReview::try_from).transpose()? else {
+            return Ok(ModerationOutcome::NotFound);
+        };
+        if review.status != ReviewStatus::Pending {
+            return Ok(ModerationOutcome::NotPending(review));
+        }
+
+        let next = review.moderated(request, Utc::now());
+        let approve = request.action == ModerationAction::Approve;
+        if approve && !Self::apply_review(&mut tx, &next).await? {
+            return Ok(ModerationOutcome::TargetMissing);
+        }
+
+        let row: ReviewRow = sqlx::query_as(&format!(
+            "UPDATE submission_reviews
+             SET status = $2, comment = $3, listed_at = $4,
+                 reviewed_by = $5, review_note = $6, reviewed_at = $7
+             WHERE id = $1
+             RETURNING {SELECT_REVIEW_FIELDS}"
+        ))
+        .bind(id)
+        .bind(next.status.name())
+        .bind(next.comment.as_ref().map(Json))
+        .bind(next.listed_at)
+        .bind(next.reviewed_by)
+        .bind(&next.review_note)
+        .bind(next.reviewed_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO moderation_log
+                 (review_id, action, actor_key_id, actor_ip, old_value, new_value, note)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(id)
+        .bind(request.action.name())
+        .bind(request.actor_key_id)
+        .bind(request.actor_ip)
+        .bind(Json(review.snapshot()))
+        .bind(Json(next.snapshot()))
+        .bind(&request.note)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(ModerationOutcome::Done(row.try_into()?))
+    }
+
+    /// 把通过的元数据写进 app_info / substance_info, 只写不为空的字段
+    ///
+    /// app_full_info 由触发器同步; 应用 / 专题不存在时返回 false
+    async fn apply_review(conn: &mut PgConnection, review: &SubmissionReview) -> Result<bool> {
+        let affected = match review.kind {
+            SubmissionKind::App => {
+                let query = review
+                    .app_query()
+                    .ok_or_else(|| anyhow::anyhow!("无法识别的应用投稿目标: {}", review.target))?;
+                sqlx::query(&format!(
+                    "UPDATE app_info
+                     SET comment = COALESCE($2, comment), listed_at = COALESCE($3, listed_at)
+                     WHERE {} = $1",
+                    query.app_db_name()
+                ))
+                .bind(query.name())
+                .bind(review.comment.as_ref().map(Json))
+                .bind(review.listed_at)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected()
+            }
+            SubmissionKind::Substance => sqlx::query(
+                "UPDATE substance_info SET comment = COALESCE($2, comment) WHERE substance_id = $1",
+            )
+            .bind(&review.target)
+            .bind(review.comment.as_ref().map(Json))
+            .execute(&mut *conn)
+            .await?
+            .rows_affected(),
+        };
+        Ok(affected > 0)
+    }
+
+    /// 分页列出审核操作记录, 新的在前, 返回 `(当前页, 总数)`
+    pub async fn list_moderation_log(
+        &self,
+        review_id: Option<i64>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ModerationLogEntry>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM moderation_log WHERE $1::bigint IS NULL OR review_id = $1",
+        )
+        .bind(review_id)
+        .fetch_one(&self.pool)
+        .await?;
+        let rows: Vec<LogRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_LOG_FIELDS} FROM moderation_log
+             WHERE $1::bigint IS NULL OR review_id = $1
+             ORDER BY id DESC LIMIT $2 OFFSET $3"
+        ))
+        .bind(review_id)
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        let entries = rows
+            .into_iter()
+            .map(ModerationLogEntry::try_from)
+            .collect::<Result<_>>()?;
+        Ok((entries, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_review() -> SubmissionReview {
+        SubmissionReview {
+            id: 1,
+            job_id: Some(1),
+            kind: SubmissionKind::App,
+            target: "pkg_name:com.huawei.music".to_string(),
+            status: ReviewStatus::Pending,
+            comment: Some(json!({"user": "someone"})),
+            listed_at: None,
+            submitter_ip: IpAddr::from([127, 0, 0, 1]),
+            submitter_ua: None,
+            api_key_id: None,
+            reviewed_by: None,
+            review_note: None,
+            created_at: Utc::now(),
+            reviewed_at: None,
+        }
+    }
+
+    fn request(action: ModerationAction, metadata: Option<ReviewMetadata>) -> ModerationRequest {
+        ModerationRequest {
+            action,
+            metadata,
+            note: Some("备注".to_string()),
+            actor_key_id: Some(7),
+            actor_ip: None,
+        }
+    }
+
+    #[test]
+    fn edit_replaces_metadata_and_stays_pending() {
+        let review = pending_review();
+        let metadata = ReviewMetadata {
+            comment: None,
+            listed_at: Some(Utc::now()),
+        };
+        let edit = request(ModerationAction::Edit, Some(metadata.clone()));
+        let next = review.moderated(&edit, Utc::now());
+        assert_eq!(next.status, ReviewStatus::Pending);
+        assert_eq!(next.comment, None);
+        assert_eq!(next.listed_at, metadata.listed_at);
+        // 修改不算审核, 不记审核人
+        assert_eq!(next.reviewed_by, None);
+        assert!(next.reviewed_at.is_none());
+    }
+
+    #[test]
+    fn approve_and_reject_record_reviewer() {
+        let review = pending_review();
+        let now = Utc::now();
+        let approved = review.moderated(&request(ModerationAction::Approve, None), now);
+        assert_eq!(approved.status, ReviewStatus::Approved);
+        assert_eq!(approved.comment, review.comment);
+        assert_eq!(approved.reviewed_by, Some(7));
+        assert_eq!(approved.reviewed_at, Some(now));
+
+        let rejected = review.moderated(&request(ModerationAction::Reject, None), now);
+        assert_eq!(rejected.status, ReviewStatus::Rejected);
+        assert_eq!(rejected.snapshot()["status"], "rejected");
+    }
+
+    #[test]
+    fn submission_without_metadata_needs_no_review() {
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let mut submission = NewSubmission::substance("abc");
+        assert!(NewReview::from_submission(&submission, 1, ip, None).is_none());
+        submission.comment = Some(json!("备注"));
+        let review = NewReview::from_submission(&submission, 1, ip, None).unwrap();
+        assert_eq!(review.kind, SubmissionKind::Substance);
+        assert_eq!(review.job_id, Some(1));
+    }
+}
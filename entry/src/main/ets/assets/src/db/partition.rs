@@ -0,0 +1,374 @@
+//! 统计表分区维护
+//!
+//! 019 迁移把 `access_logs` 改成按天分区, `ua_hourly_statistics` / `ip_hourly_statistics`
+//! 改成按月分区 (边界为 UTC 零点), 另有 `<表名>_default` 分区兜底.
+//! 这里负责定时预建分区、按保留期删除过期分区
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Months, NaiveDate, TimeZone, Utc};
+use sqlx::Row;
+use tracing::{Level, event};
+
+use crate::db::Database;
+
+/// 提前多少天建好分区
+const AHEAD_DAYS: u64 = 7;
+
+/// 分区粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionStep {
+    Day,
+    Month,
+}
+
+impl PartitionStep {
+    /// 包含 `date` 的分区的起点
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// 下一个分区的起点, 也就是 `start` 所在分区的终点
+    pub fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => start.succ_opt().unwrap_or(start),
+            Self::Month => start.checked_add_months(Months::new(1)).unwrap_or(start),
+        }
+    }
+
+    /// 分区名后缀: 按天 `YYYYMMDD`, 按月 `YYYYMM`
+    pub fn suffix(self, start: NaiveDate) -> String {
+        match self {
+            Self::Day => start.format("%Y%m%d").to_string(),
+            Self::Month => start.format("%Y%m").to_string(),
+        }
+    }
+
+    /// 从分区名后缀解析分区起点
+    pub fn parse_suffix(self, suffix: &str) -> Option<NaiveDate> {
+        if !suffix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        match (self, suffix.len()) {
+            (Self::Day, 8) => NaiveDate::parse_from_str(suffix, "%Y%m%d").ok(),
+            (Self::Month, 6) => NaiveDate::parse_from_str(&format!("{suffix}01"), "%Y%m%d").ok(),
+            _ => None,
+        }
+    }
+}
+
+/// 分区表
+#[derive(Debug, Clone, Copy)]
+pub struct PartitionedTable {
+    pub name: &'static str,
+    /// 分区键
+    pub column: &'static str,
+    pub step: PartitionStep,
+}
+
+pub const ACCESS_LOGS: PartitionedTable = PartitionedTable {
+    name: "access_logs",
+    column: "timestamp",
+    step: PartitionStep::Day,
+};
+
+pub const UA_HOURLY_STATISTICS: PartitionedTable = PartitionedTable {
+    name: "ua_hourly_statistics",
+    column: "hour_timestamp",
+    step: PartitionStep::Month,
+};
+
+pub const IP_HOURLY_STATISTICS: PartitionedTable = PartitionedTable {
+    name: "ip_hourly_statistics",
+    column: "hour_timestamp",
+    step: PartitionStep::Month,
+};
+
+impl PartitionedTable {
+    /// `start` 开始的分区名
+    pub fn partition_name(&self, start: NaiveDate) -> String {
+        format!("{}_p{}", self.name, self.step.suffix(start))
+    }
+
+    /// 兜底的 DEFAULT 分区名
+    pub fn default_partition(&self) -> String {
+        format!("{}_default", self.name)
+    }
+
+    /// 从分区名解析分区起点, 不是按规则命名的分区 (比如 DEFAULT 分区) 返回 None
+    pub fn parse_partition(&self, name: &str) -> Option<NaiveDate> {
+        name.strip_prefix(self.name)
+            .and_then(|rest| rest.strip_prefix("_p"))
+            .and_then(|suffix| self.step.parse_suffix(suffix))
+    }
+
+    /// 从包含 `today` 的分区开始, 到包含 `today + ahead_days` 的分区为止的所有分区起点
+    pub fn upcoming(&self, today: NaiveDate, ahead_days: u64) -> Vec<NaiveDate> {
+        let last = today + chrono::Days::new(ahead_days);
+        let mut start = self.step.start_of(today);
+        let mut starts = Vec::new();
+        while start <= last {
+            starts.push(start);
+            start = self.step.next(start);
+        }
+        starts
+    }
+
+    /// 分区里的数据是否全部早于 `cutoff`
+    pub fn is_expired(&self, start: NaiveDate, cutoff: NaiveDate) -> bool {
+        self.step.next(start) <= cutoff
+    }
+}
+
+/// 日期对应的 UTC 零点, 用作分区边界
+fn bound(date: NaiveDate) -> String {
+    format!("{} 00:00:00+00", date.format("%Y-%m-%d"))
+}
+
+/// 一次分区维护的结果
+#[derive(Debug, Clone, Default)]
+pub struct PartitionReport {
+    /// 新建的分区
+    pub created: Vec<String>,
+    /// 删除的过期分区
+    pub dropped: Vec<String>,
+    /// 从 DEFAULT 分区里删除的过期数据行数
+    pub purged_default_rows: u64,
+}
+
+impl Database {
+    /// 列出分区表当前所有按规则命名的分区
+    async fn list_partitions(&self, table: &PartitionedTable) -> Result<Vec<(String, NaiveDate)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.relname::text AS name
+            FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            WHERE i.inhparent = $1::regclass
+            "#,
+        )
+        .bind(table.name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let name: String = row.get("name");
+                table.parse_partition(&name).map(|start| (name, start))
+            })
+            .collect())
+    }
+
+    /// 建好从今天起 `ahead_days` 天内需要的分区, 返回新建的分区名
+    ///
+    /// DEFAULT 分区里已经有这段时间的数据时 (比如维护任务停了一段时间),
+    /// 先建一张独立的表, 把数据搬过去再挂载成分区
+    pub async fn ensure_partitions(
+        &self,
+        table: &PartitionedTable,
+        today: NaiveDate,
+        ahead_days: u64,
+    ) -> Result<Vec<String>> {
+        let existing = self.list_partitions(table).await?;
+        let mut created = Vec::new();
+
+        for start in table.upcoming(today, ahead_days) {
+            if existing.iter().any(|(_, s)| *s == start) {
+                continue;
+            }
+            let name = table.partition_name(start);
+            let (from, to) = (bound(start), bound(table.step.next(start)));
+            let range = format!("FOR VALUES FROM ('{from}') TO ('{to}')");
+            let in_range = format!("{} >= '{from}' AND {} < '{to}'", table.column, table.column);
+
+            let mut tx = self.pool.begin().await?;
+            let has_rows: bool = sqlx::query_scalar(&format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE {in_range})",
+                table.default_partition()
+            ))
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if has_rows {
+                let statements = [
+                    format!("CREATE TABLE {name} (LIKE {} INCLUDING DEFAULTS)", table.name),
+                    format!(
+                        "WITH moved AS (DELETE FROM {} WHERE {in_range} RETURNING *) \
+                         INSERT INTO {name} SELECT * FROM moved",
+                        table.default_partition()
+                    ),
+                    format!("ALTER TABLE {} ATTACH PARTITION {name} {range}", table.name),
+                ];
+                for sql in statements.iter() {
+                    sqlx::query(sql).execute(&mut *tx).await?;
+                }
+            } else {
+                sqlx::query(&format!(
+                    "CREATE TABLE IF NOT EXISTS {name} PARTITION OF {} {range}",
+                    table.name
+                ))
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit()
+                .await
+                .with_context(|| format!("创建分区 {name} 失败"))?;
+            created.push(name);
+        }
+        Ok(created)
+    }
+
+    /// 删除数据全部早于 `cutoff` 的分区, 并清理 DEFAULT 分区里早于 `cutoff` 的数据
+    ///
+    /// 返回 (删除的分区名, DEFAULT 分区删除的行数)
+    pub async fn drop_expired_partitions(
+        &self,
+        table: &PartitionedTable,
+        cutoff: NaiveDate,
+    ) -> Result<(Vec<String>, u64)> {
+        let mut expired: Vec<(String, NaiveDate)> = self
+            .list_partitions(table)
+            .await?
+            .into_iter()
+            .filter(|(_, start)| table.is_expired(*start, cutoff))
+            .collect();
+        expired.sort_by_key(|(_, start)| *start);
+
+        let mut dropped = Vec::with_capacity(expired.len());
+        for (name, _) in expired {
+            sqlx::query(&format!("DROP TABLE IF EXISTS {name}"))
+                .execute(&self.pool)
+                .await
+                .with_context(|| format!("删除分区 {name} 失败"))?;
+            dropped.push(name);
+        }
+
+        let cutoff_time = Utc.from_utc_datetime(&cutoff.and_time(chrono::NaiveTime::MIN));
+        let purged = sqlx::query(&format!(
+            "DELETE FROM {} WHERE {} < $1",
+            table.default_partition(),
+            table.column
+        ))
+        .bind(cutoff_time)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok((dropped, purged))
+    }
+
+    /// 维护所有统计分区表
+    ///
+    /// # 参数
+    /// - `access_log_days`: 访问日志保留天数, 0 表示永久保留
+    /// - `hourly_days`: 每小时统计保留天数, 0 表示永久保留
+    pub async fn maintain_statistics_partitions(
+        &self,
+        access_log_days: u32,
+        hourly_days: u32,
+    ) -> Result<PartitionReport> {
+        let today = Utc::now().date_naive();
+        let mut report = PartitionReport::default();
+
+        for (table, days) in [
+            (ACCESS_LOGS, access_log_days),
+            (UA_HOURLY_STATISTICS, hourly_days),
+            (IP_HOURLY_STATISTICS, hourly_days),
+        ] {
+            report
+                .created
+                .extend(self.ensure_partitions(&table, today, AHEAD_DAYS).await?);
+            if days == 0 {
+                continue;
+            }
+            let cutoff = today - chrono::Days::new(days as u64);
+            let (dropped, purged) = self.drop_expired_partitions(&table, cutoff).await?;
+            report.dropped.extend(dropped);
+            report.purged_default_rows += purged;
+        }
+        Ok(report)
+    }
+}
+
+/// 启动统计表分区维护任务
+pub fn start_partition_maintenance_task(
+    db: Database,
+    access_log_days: u32,
+    hourly_days: u32,
+    interval_seconds: u64,
+) -> tokio::task::JoinHandle<()> {
+    event!(
+        Level::INFO,
+        access_log_days,
+        hourly_days,
+        interval_seconds,
+        "启动统计表分区维护任务"
+    );
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(interval_seconds));
+        loop {
+            interval.tick().await;
+            let start = std::time::Instant::now();
+            match db
+                .maintain_statistics_partitions(access_log_days, hourly_days)
+                .await
+            {
+                Ok(report) => event!(
+                    Level::INFO,
+                    "统计表分区维护完成: 新建分区 {:?}, 删除分区 {:?}, DEFAULT 分区清理 {} 行, 耗时 {:?}",
+                    report.created,
+                    report.dropped,
+                    report.purged_default_rows,
+                    start.elapsed()
+                ),
+                Err(e) => event!(Level::WARN, "统计表分区维护失败: {e}"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn partition_names_round_trip() {
+        let day = ACCESS_LOGS.partition_name(date(2026, 10, 19));
+        assert_eq!(day, "access_logs_p20261019");
+        assert_eq!(ACCESS_LOGS.parse_partition(&day), Some(date(2026, 10, 19)));
+
+        let month = UA_HOURLY_STATISTICS.partition_name(date(2026, 10, 1));
+        assert_eq!(month, "ua_hourly_statistics_p202610");
+        assert_eq!(UA_HOURLY_STATISTICS.parse_partition(&month), Some(date(2026, 10, 1)));
+
+        // DEFAULT 分区和别的表的分区不认
+        assert_eq!(ACCESS_LOGS.parse_partition("access_logs_default"), None);
+        assert_eq!(IP_HOURLY_STATISTICS.parse_partition(&month), None);
+        assert_eq!(UA_HOURLY_STATISTICS.parse_partition("ua_hourly_statistics_p20261019"), None);
+    }
+
+    #[test]
+    fn upcoming_and_expired() {
+        // 按月分区: 月底前 7 天就要建好下个月的分区
+        let months = UA_HOURLY_STATISTICS.upcoming(date(2026, 12, 28), 7);
+        assert_eq!(months, vec![date(2026, 12, 1), date(2027, 1, 1)]);
+        assert_eq!(UA_HOURLY_STATISTICS.upcoming(date(2026, 10, 19), 7).len(), 1);
+
+        let days = ACCESS_LOGS.upcoming(date(2026, 10, 30), 2);
+        assert_eq!(days, vec![date(2026, 10, 30), date(2026, 10, 31), date(2026, 11, 1)]);
+
+        // 分区终点不晚于 cutoff 才算过期, 包含 cutoff 的分区保留
+        assert!(ACCESS_LOGS.is_expired(date(2026, 10, 18), date(2026, 10, 19)));
+        assert!(!ACCESS_LOGS.is_expired(date(2026, 10, 19), date(2026, 10, 19)));
+        assert!(UA_HOURLY_STATISTICS.is_expired(date(2026, 9, 1), date(2026, 10, 1)));
+        assert!(!UA_HOURLY_STATISTICS.is_expired(date(2026, 10, 1), date(2026, 10, 19)));
+    }
+}
@@ -2,11 +2,11 @@ use anyhow::Result;
 use chrono::{DateTime, Local};
 use rust_decimal::Decimal;
 use serde_json::Value;
-use sqlx::{Row, postgres::types::PgInterval};
+use sqlx::{Postgres, QueryBuilder, Row, postgres::types::PgInterval};
 
-use std::{ops::Range, sync::OnceLock};
+use std::{collections::HashMap, ops::Range, sync::OnceLock};
 
-use crate::db::{AppCounts, Database, DbSearch, DownloadIncrement, PageInfo};
+use crate::db::{AppCounts, Database, DbSearch, DownloadIncrement, PageInfo, filter::AppFilter};
 use crate::db::{
     AppIconInfo,
     read_data::{SELECT_APP_INFO_FIELDS, SELECT_APP_METRIC_FIELDS, SELECT_APP_RATING_FIELDS},
@@ -22,10 +22,57 @@ pub fn get_max_limit() -> u32 {
     *SELECT_MAX_LIMIT.get().unwrap_or(&100)
 }
 
+/// 排除华为 / 原子化服务的公共条件
+fn push_exclude_conditions(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    exclude_huawei: bool,
+    exclude_atomic: bool,
+) {
+    if exclude_huawei {
+        qb.push(" AND dev_en_name NOT ILIKE '%huawei%'");
+    }
+    if exclude_atomic {
+        qb.push(" AND pkg_name NOT LIKE 'com.atomicservice%'");
+    }
+}
+
+
+/// 分页查询 app_full_info 的 WHERE 条件 (不含 ORDER BY / LIMIT)
+pub(crate) fn push_list_conditions(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    sort_key: &str,
+    search: Option<&DbSearch>,
+    filter: Option<&AppFilter>,
+    exclude_huawei: bool,
+    exclude_atomic: bool,
+) -> Result<()> {
+    match search {
+        Some(search) => {
+            let key = search.key.as_str();
+            let search_method = search.search_method();
+            qb.push(format!("{key}::text {search_method} "))
+                .push_bind(search.search_value())
+                .push(" AND (NOT ")
+                .push_bind(search.not_null)
+                .push(format!("::boolean OR {sort_key} IS NOT NULL)"));
+        }
+        None => {
+            qb.push(format!(
+                "{sort_key} IS NOT NULL AND app_id != 'C5765880207854862721'"
+            ));
+        }
+    }
+    push_exclude_conditions(qb, exclude_huawei, exclude_atomic);
+    if let Some(filter) = filter {
+        qb.push(" AND ");
+        filter.push_sql(qb)?;
+    }
+    Ok(())
+}
 
 /// 递归清洗 JSON，逻辑与 SQL 中的 normalize_json_by_value 一致
 /// 如果 Value 是字符串且包含 "trace" (不区分大小写)，则替换为 "TRACE_MASKED"
-fn normalize_json_for_comparison(val: &Value) -> Value {
+pub(crate) fn normalize_json_for_comparison(val: &Value) -> Value {
     match val {
         // 1. 如果是对象，递归处理每个字段的值
         Value::Object(map) => {
@@ -85,7 +132,7 @@ impl Database {
     /// 获取指定 substance 的最后一条原始JSON数据
     pub async fn get_last_substance_raw_json(&self, substance_id: &str) -> Option<Value> {
         let query = r#"
-            SELECT raw_json_substance
+            SELECT COALESCE(json_blob_load(raw_json_hash), raw_json_substance) AS raw_json_substance
             FROM substance_history
             WHERE substance_id = $1
             ORDER BY created_at DESC
@@ -152,7 +199,7 @@ impl Database {
     pub async fn get_last_raw_json_data(&self, app: &AppQuery) -> Option<Value> {
         let query = format!(
             r#"
-                SELECT raw_json_data
+                SELECT COALESCE(json_blob_load(raw_json_hash), raw_json_data) AS raw_json_data
                 FROM app_data_history
                 WHERE {} = $1
                 ORDER BY created_at DESC
@@ -248,6 +295,19 @@ impl Database {
         Ok(full_info)
     }
 
+    /// 按 app_id 批量查询完整应用信息, 不存在的 app_id 不会出现在结果里
+    ///
+    /// 不分页也不统计总数, 调用方自己控制每次传入的数量
+    pub async fn get_full_app_infos(&self, app_ids: &[String]) -> Result<Vec<FullAppInfo>> {
+        let apps = sqlx::query_as::<_, FullAppInfo>(
+            "SELECT * FROM app_full_info WHERE app_id = ANY($1)",
+        )
+        .bind(app_ids)
+        .fetch_all(self.read_pool())
+        .await?;
+        Ok(apps)
+    }
+
     pub async fn get_app_info(&self, app: &AppQuery) -> Option<AppInfo> {
         let query = format!(
             "SELECT {} FROM app_info WHERE {} = $1",
@@ -343,12 +403,14 @@ impl Database {
     /// let apps = db.get_app_info_paginated(0..10).await?;
     /// println!("获取到 {} 条应用信息", apps.len());
     /// ```
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_app_info_paginated(
         &self,
         range: Range<u32>,
         sort_key: &str,
         sort_desc: bool,
         search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
         exclude_huawei: bool,
         exclude_atomic: bool,
     ) -> Result<Vec<FullAppInfo>> {
@@ -358,56 +420,24 @@ impl Database {
 
         let order_clause = if sort_desc { "DESC" } else { "ASC" };
 
-        let app_infos = match search {
-            Some(search) => {
-                // 搜索模式下参数绑定： $1(value), $2(limit), $3(offset), $4(bool), $5(bool), $6(bool)
-                let key = search.key.as_str();
-                let search_method = search.search_method();
-                let query = format!(
-                    r#"
-                    SELECT *
-                    FROM app_full_info
-                    WHERE {key}::text {search_method} $1
-                    AND (NOT $6::boolean OR {sort_key} IS NOT NULL)
-                    AND (NOT $4::boolean OR dev_en_name NOT ILIKE '%huawei%')
-                    AND (NOT $5::boolean OR pkg_name NOT LIKE 'com.atomicservice%')
-                    ORDER BY {sort_key} {order_clause}
-                    LIMIT $2 OFFSET $3
-                "#
-                );
-                sqlx::query_as::<_, FullAppInfo>(&query)
-                    .bind(search.search_value()) // $1 (搜索值)
-                    .bind(limit) // $2 (LIMIT)
-                    .bind(offset) // $3 (OFFSET)
-                    .bind(exclude_huawei) // $4 (排除华为)
-                    .bind(exclude_atomic) // $5 (排除 Atomic)
-                    .bind(search.not_null) // $6 (not null 检查)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            None => {
-                // 无搜索模式下参数绑定： $1(limit), $2(offset), $3(bool), $4(bool)
-                let query = format!(
-                    r#"
-                    SELECT *
-                    FROM app_full_info
-                    WHERE {sort_key} IS NOT NULL
-                    AND app_id != 'C5765880207854862721'
-                    AND (NOT $3::boolean OR dev_en_name NOT ILIKE '%huawei%')
-                    AND (NOT $4::boolean OR pkg_name NOT LIKE 'com.atomicservice%')
-                    ORDER BY {sort_key} {order_clause}
-                    LIMIT $1 OFFSET $2
-                "#
-                );
-                sqlx::query_as::<_, FullAppInfo>(&query)
-                    .bind(limit) // $1 (LIMIT)
-                    .bind(offset) // $2 (OFFSET)
-                    .bind(exclude_huawei) // $3 (排除华为)
-                    .bind(exclude_atomic) // $4 (排除 Atomic)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT * FROM app_full_info WHERE ");
+        push_list_conditions(
+            &mut qb,
+            sort_key,
+            search.as_ref(),
+            filter,
+            exclude_huawei,
+            exclude_atomic,
+        )?;
+        qb.push(format!(" ORDER BY {sort_key} {order_clause} LIMIT "))
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        let app_infos = qb
+            .build_query_as::<FullAppInfo>()
+            .fetch_all(self.read_pool())
+            .await?;
 
         Ok(app_infos)
     }
@@ -421,7 +451,7 @@ impl Database {
             WHERE developer_name IS NOT NULL
             "#,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.read_pool())
         .await
     }
 
@@ -440,7 +470,7 @@ impl Database {
             FROM app_full_info
             "#,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.read_pool())
         .await
     }
 
@@ -451,7 +481,7 @@ impl Database {
             SELECT COUNT(*) FROM substance_info
             "#,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.read_pool())
         .await
     }
 
@@ -460,6 +490,7 @@ impl Database {
     /// # 参数
     /// - `page`: 页码（从1开始）
     /// - `page_size`: 每页大小
+    /// - `filter`: 组合过滤条件, 与 `search` 同时生效
     ///
     /// # 示例
     /// ```rust
@@ -476,54 +507,14 @@ impl Database {
         sort_key: &str,
         sort_desc: bool,
         search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
         exclude_huawei: bool,
         exclude_atomic: bool,
     ) -> Result<PageInfo<D>> {
-        // --- 1. 动态统计总数 (使用 SQL 条件判断) ---
-        let total_count: i64 = match &search {
-            Some(search @ DbSearch {
-                key,
-                value: _,
-                is_exact: _,
-                not_null,
-            }) => {
-                // 搜索模式下的 COUNT 查询： $1(value), $2(bool: huawei), $3(bool: atomic), $4(bool: not_null)
-                let search_method = search.search_method();
-                let query = format!(
-                    r#"
-                    SELECT COUNT(*)
-                    FROM app_full_info
-                    WHERE {key}::text {search_method} $1
-                    AND (NOT $4::boolean OR {key} IS NOT NULL)
-                    AND (NOT $2::boolean OR dev_en_name NOT ILIKE '%huawei%')
-                    AND (NOT $3::boolean OR pkg_name NOT LIKE 'com.atomicservice%')
-                    "#
-                );
-                let result: (i64,) = sqlx::query_as(&query)
-                    .bind(search.search_value()) // $1 (Search Value)
-                    .bind(exclude_huawei) // $2
-                    .bind(exclude_atomic) // $3
-                    .bind(not_null) // $4
-                    .fetch_one(&self.pool)
-                    .await?;
-                result.0
-            }
-            None => {
-                // 非搜索模式下的 COUNT 查询： $1(bool: huawei), $2(bool: atomic)
-                let query = r#"
-                    SELECT COUNT(*)
-                    FROM app_full_info
-                    WHERE (NOT $1::boolean OR dev_en_name NOT ILIKE '%huawei%')
-                    AND (NOT $2::boolean OR pkg_name NOT LIKE 'com.atomicservice%')
-                "#;
-                let result: (i64,) = sqlx::query_as(query)
-                    .bind(exclude_huawei) // $1
-                    .bind(exclude_atomic) // $2
-                    .fetch_one(&self.pool)
-                    .await?;
-                result.0
-            }
-        };
+        // --- 1. 动态统计总数 ---
+        let total_count = self
+            .count_app_list(search.as_ref(), filter, exclude_huawei, exclude_atomic)
+            .await?;
         // --- 2. 分页逻辑 ---
         let total_pages = if page_size == 0 {
             0
@@ -538,6 +529,7 @@ impl Database {
                 sort_key,
                 sort_desc,
                 search,
+                filter,
                 exclude_huawei,
                 exclude_atomic,
             )
@@ -555,6 +547,37 @@ impl Database {
         })
     }
 
+    /// 统计分页查询的总数, 条件与 [`push_list_conditions`] 对应
+    pub(crate) async fn count_app_list(
+        &self,
+        search: Option<&DbSearch>,
+        filter: Option<&AppFilter>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+    ) -> Result<i64> {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM app_full_info WHERE ");
+        match search {
+            Some(search) => {
+                let key = search.key.as_str();
+                let search_method = search.search_method();
+                qb.push(format!("{key}::text {search_method} "))
+                    .push_bind(search.search_value())
+                    .push(" AND (NOT ")
+                    .push_bind(search.not_null)
+                    .push(format!("::boolean OR {key} IS NOT NULL)"));
+            }
+            None => {
+                qb.push("TRUE");
+            }
+        }
+        push_exclude_conditions(&mut qb, exclude_huawei, exclude_atomic);
+        if let Some(filter) = filter {
+            qb.push(" AND ");
+            filter.push_sql(&mut qb)?;
+        }
+        Ok(qb.build_query_scalar().fetch_one(self.read_pool()).await?)
+    }
+
     /// 获取数据库内应用数量
     pub async fn count_apps(&self) -> Result<AppCounts> {
         const QUERY: &str = r#"
@@ -566,7 +589,7 @@ impl Database {
             app_info
         "#;
 
-        Ok(sqlx::query_as(QUERY).fetch_one(&self.pool).await?)
+        Ok(sqlx::query_as(QUERY).fetch_one(self.read_pool()).await?)
     }
 
     /// 获取 app_info 表中的总记录数
@@ -580,12 +603,15 @@ impl Database {
     pub async fn get_app_info_count(&self) -> Result<u32> {
         const QUERY: &str = "SELECT COUNT(*) FROM app_info";
 
-        let count: i64 = sqlx::query_scalar(QUERY).fetch_one(&self.pool).await?;
+        let count: i64 = sqlx::query_scalar(QUERY).fetch_one(self.read_pool()).await?;
 
         Ok(count as u32)
     }
 
     /// 获取指定 pkg_id 的所有 app_metric 信息
+    ///
+    /// 近期为原始记录, 较早的为按天 / 按周降采样后的记录.
+    /// 降采样记录在 app_metrics_timeline 视图里 id 为 NULL, 读出来时转成 0
     pub async fn get_app_metrics_by_pkg_id(&self, pkg_id: &str) -> Result<Vec<AppMetric>> {
         const QUERY: &str = r#"
             SELECT
@@ -608,7 +634,7 @@ impl Database {
                 am.min_hmos_api_level,
                 am.api_release_type,
                 am.created_at metrics_created_at
-            FROM app_metrics am
+            FROM app_metrics_timeline am
             JOIN app_info ai ON am.app_id = ai.app_id
             WHERE ai.pkg_name = $1
             ORDER BY am.created_at DESC
@@ -616,7 +642,7 @@ impl Database {
 
         let rows = sqlx::query(QUERY)
             .bind(pkg_id)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let app_metrics = rows.iter().map(Self::read_app_metric_from_row).collect();
@@ -653,7 +679,7 @@ impl Database {
 
         let rows = sqlx::query(QUERY)
             .bind(limit.min(get_max_limit()) as i64)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let mut app_ratings = Vec::new();
@@ -698,7 +724,7 @@ impl Database {
 
         let rows = sqlx::query_as::<_, FullAppInfo>(QUERY)
             .bind(limit.min(get_max_limit()) as i64)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         Ok(rows)
@@ -736,15 +762,15 @@ impl Database {
                 am.compile_sdk_version,
                 am.min_hmos_api_level,
                 am.api_release_type,
-                am.created_at
-            FROM app_metrics am
+                am.created_at metrics_created_at
+            FROM app_metrics_timeline am
             ORDER BY am.price DESC
             LIMIT $1
         "#;
 
         let rows = sqlx::query(QUERY)
             .bind(limit.min(get_max_limit()) as i64)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let app_metrics = rows.iter().map(Self::read_app_metric_from_row).collect();
@@ -777,7 +803,7 @@ impl Database {
 
         let rows = sqlx::query(QUERY)
             .bind(limit.min(get_max_limit()) as i64)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let mut developers = Vec::new();
@@ -803,7 +829,7 @@ impl Database {
         ORDER BY
             count DESC, device_code"#;
 
-        let rows = sqlx::query(QUERY).fetch_all(&self.pool).await?;
+        let rows = sqlx::query(QUERY).fetch_all(self.read_pool()).await?;
 
         let mut device_codes = Vec::new();
         for row in rows {
@@ -829,7 +855,7 @@ impl Database {
         ORDER BY
             minsdk;"#;
 
-        let rows = sqlx::query(QUERY).fetch_all(&self.pool).await?;
+        let rows = sqlx::query(QUERY).fetch_all(self.read_pool()).await?;
 
         let mut min_sdks = Vec::new();
         for row in rows {
@@ -855,7 +881,7 @@ impl Database {
         ORDER BY
             target_sdk;"#;
 
-        let rows = sqlx::query(QUERY).fetch_all(&self.pool).await?;
+        let rows = sqlx::query(QUERY).fetch_all(self.read_pool()).await?;
 
         let mut target_sdks = Vec::new();
         for row in rows {
@@ -896,6 +922,7 @@ impl Database {
                 ((current_period_date + interval '1 day')::timestamp AT TIME ZONE 'Asia/Shanghai') AS current_end_ts
             FROM period_dates
         ),
+        -- 当天的数据一定还在原始表里, 对比期的数据可能已经降采样, 要跨层读
         current_period_metrics AS (
             SELECT DISTINCT ON (app_id)
                 app_id, pkg_name, download_count, created_at
@@ -911,7 +938,7 @@ impl Database {
         CROSS JOIN time_ranges tr
         INNER JOIN LATERAL (
             SELECT am.download_count
-            FROM app_metrics am
+            FROM app_download_timeline am
             WHERE am.app_id = cpm.app_id
                 AND am.created_at < (tr.prior_period_date + interval '1 day')::timestamp AT TIME ZONE 'Asia/Shanghai'
             ORDER BY am.created_at DESC
@@ -966,7 +993,7 @@ impl Database {
             .bind(exclude_huawei) // $4
             .bind(exclude_atomic) // $5
             .bind(listed_interval) // $6
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let total_count: i64 = sqlx::query_scalar(&count_query)
@@ -974,7 +1001,7 @@ impl Database {
             .bind(exclude_huawei) // $2
             .bind(exclude_atomic) // $3
             .bind(listed_interval) // $4
-            .fetch_one(&self.pool)
+            .fetch_one(self.read_pool())
             .await?;
 
         Ok((results, total_count))
@@ -1055,6 +1082,28 @@ impl Database {
         }))
     }
 
+    /// 批量查询专题包含的应用ID, 返回 substance_id -> app_id 列表 (按 app_id 排序)
+    ///
+    /// 没有收录应用的专题不会出现在结果里
+    pub async fn get_substance_app_ids(
+        &self,
+        substance_ids: &[String],
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT substance_id, app_id FROM substance_app_map
+             WHERE substance_id = ANY($1)
+             ORDER BY substance_id, app_id",
+        )
+        .bind(substance_ids)
+        .fetch_all(self.read_pool())
+        .await?;
+        let mut result: HashMap<String, Vec<String>> = HashMap::new();
+        for (substance_id, app_id) in rows {
+            result.entry(substance_id).or_default().push(app_id);
+        }
+        Ok(result)
+    }
+
     /// 分页获取专题列表（简略信息）
     pub async fn get_substance_list_paged(
         &self,
@@ -1092,11 +1141,11 @@ impl Database {
         let results = sqlx::query_as::<_, ShortSubstanceInfo>(&query)
             .bind(safe_limit as i64)
             .bind(offset as i64)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await?;
 
         let total_count: i64 = sqlx::query_scalar(count_query)
-            .fetch_one(&self.pool)
+            .fetch_one(self.read_pool())
             .await?;
 
         let total_count = total_count as u32;
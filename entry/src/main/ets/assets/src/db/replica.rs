@@ -0,0 +1,210 @@
+//! 只读副本路由
+//!
+//! 看板上的重查询 (排行、图表、下载增量、搜索、访问统计) 走 [`Database::read_pool`],
+//! 有可用副本时轮询分配, 否则回落到主库.
+//! 同步流程里写完马上读的查询 (`get_full_app_info`、`is_same_*` 等) 仍然使用 `pool`
+//!
+//! 副本是否可用由 [`start_replica_monitor`] 定时检查: 连不上或复制延迟超过阈值的副本
+//! 暂时不参与分配, 恢复后自动加回
+
+use std::{
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use sqlx::{
+    PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+use tracing::{Level, event};
+
+use crate::db::Database;
+
+/// 检查副本状态的单次超时
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 副本复制延迟 (秒)
+///
+/// 已经回放到接收位置说明没有积压, 直接算 0,
+/// 不然主库空闲时 `pg_last_xact_replay_timestamp` 会一直变旧
+const LAG_QUERY: &str = r#"
+    SELECT (CASE
+        WHEN NOT pg_is_in_recovery() THEN 0
+        WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+        ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0)
+    END)::float8
+"#;
+
+#[derive(Debug)]
+struct Replica {
+    /// host:port/db, 日志用, 不带密码
+    name: String,
+    pool: PgPool,
+    /// 第一次检查通过前不参与分配
+    healthy: AtomicBool,
+    /// 最近一次检查到的复制延迟 (毫秒)
+    lag_ms: AtomicU64,
+}
+
+/// 一组只读副本
+#[derive(Debug, Default)]
+pub struct ReplicaSet {
+    replicas: Vec<Replica>,
+    /// 轮询计数
+    next: AtomicUsize,
+}
+
+/// 副本状态, 用于日志和状态接口
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReplicaStatus {
+    pub name: String,
+    pub healthy: bool,
+    pub lag_ms: u64,
+}
+
+impl Database {
+    /// 挂上只读副本
+    ///
+    /// 副本连接是惰性的, 启动时副本不可用不影响主库
+    pub fn with_replicas(mut self, urls: &[String], max_connect: u32) -> Result<Self> {
+        if urls.is_empty() {
+            return Ok(self);
+        }
+        let mut replicas = Vec::with_capacity(urls.len());
+        for url in urls {
+            let options = PgConnectOptions::from_str(url)
+                .with_context(|| "无法解析只读副本连接串")?;
+            let name = format!(
+                "{}:{}/{}",
+                options.get_host(),
+                options.get_port(),
+                options.get_database().unwrap_or_default()
+            );
+            let pool = PgPoolOptions::new()
+                .max_connections(max_connect)
+                .acquire_timeout(CHECK_TIMEOUT)
+                .connect_lazy_with(options);
+            replicas.push(Replica {
+                name,
+                pool,
+                healthy: AtomicBool::new(false),
+                lag_ms: AtomicU64::new(0),
+            });
+        }
+        event!(Level::INFO, "配置了 {} 个只读副本", replicas.len());
+        self.replicas = Arc::new(ReplicaSet {
+            replicas,
+            next: AtomicUsize::new(0),
+        });
+        Ok(self)
+    }
+
+    /// 只读查询使用的连接池
+    ///
+    /// 轮询选择一个可用副本, 都不可用时返回主库
+    pub fn read_pool(&self) -> &PgPool {
+        let set = &self.replicas;
+        let len = set.replicas.len();
+        if len == 0 {
+            return &self.pool;
+        }
+        let start = set.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|i| &set.replicas[(start + i) % len])
+            .find(|r| r.healthy.load(Ordering::Relaxed))
+            .map(|r| &r.pool)
+            .unwrap_or(&self.pool)
+    }
+
+    /// 检查所有副本的连通性和复制延迟, 更新可用状态
+    pub async fn check_replicas(&self, max_lag: Duration) {
+        for replica in &self.replicas.replicas {
+            let lag = tokio::time::timeout(
+                CHECK_TIMEOUT,
+                sqlx::query_scalar::<_, f64>(LAG_QUERY).fetch_one(&replica.pool),
+            )
+            .await;
+            let was_healthy = replica.healthy.load(Ordering::Relaxed);
+            let healthy = match lag {
+                Ok(Ok(lag)) => {
+                    let lag = Duration::from_secs_f64(lag.max(0.0));
+                    replica
+                        .lag_ms
+                        .store(lag.as_millis() as u64, Ordering::Relaxed);
+                    if lag > max_lag && was_healthy {
+                        event!(
+                            Level::WARN,
+                            "只读副本 {} 延迟 {:?} 超过阈值 {:?}, 暂停使用",
+                            replica.name,
+                            lag,
+                            max_lag
+                        );
+                    }
+                    lag <= max_lag
+                }
+                Ok(Err(e)) => {
+                    if was_healthy {
+                        event!(Level::WARN, "只读副本 {} 不可用, 暂停使用: {e}", replica.name);
+                    }
+                    false
+                }
+                Err(_) => {
+                    if was_healthy {
+                        event!(Level::WARN, "只读副本 {} 检查超时, 暂停使用", replica.name);
+                    }
+                    false
+                }
+            };
+            if healthy && !was_healthy {
+                event!(Level::INFO, "只读副本 {} 可用", replica.name);
+            }
+            replica.healthy.store(healthy, Ordering::Relaxed);
+        }
+    }
+
+    /// 各副本当前状态
+    pub fn replica_status(&self) -> Vec<ReplicaStatus> {
+        self.replicas
+            .replicas
+            .iter()
+            .map(|r| ReplicaStatus {
+                name: r.name.clone(),
+                healthy: r.healthy.load(Ordering::Relaxed),
+                lag_ms: r.lag_ms.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// 是否配置了副本
+    pub fn has_replicas(&self) -> bool {
+        !self.replicas.replicas.is_empty()
+    }
+}
+
+/// 启动副本检查任务
+pub fn start_replica_monitor(
+    db: Database,
+    max_lag_seconds: u64,
+    interval_seconds: u64,
+) -> tokio::task::JoinHandle<()> {
+    event!(
+        Level::INFO,
+        max_lag_seconds,
+        interval_seconds,
+        "启动只读副本检查任务"
+    );
+    let max_lag = Duration::from_secs(max_lag_seconds);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            db.check_replicas(max_lag).await;
+        }
+    })
+}
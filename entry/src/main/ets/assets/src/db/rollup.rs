@@ -0,0 +1,126 @@
+//! app_metrics / app_rating 降采样
+//!
+//! 压缩逻辑在 017 迁移创建的 `rollup_app_metrics` 和 027 迁移创建的 `rollup_app_rating`
+//! 函数里, 这里只负责定时调用
+//!
+//! 读取时用 `app_metrics_timeline` / `app_download_timeline` / `app_rating_timeline`
+//! 视图跨层读取
+
+use anyhow::Result;
+use sqlx::Row;
+use tracing::{Level, event};
+
+use crate::db::Database;
+
+/// 一次降采样的结果
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RollupResult {
+    /// 压缩进按天聚合的原始记录数
+    pub raw_rolled: u64,
+    /// 压缩进按周聚合的按天记录数
+    pub daily_rolled: u64,
+    /// 新增的文本变化记录数
+    pub text_saved: u64,
+}
+
+/// 一次 app_rating 降采样的结果
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RatingRollupResult {
+    /// 压缩进按天聚合的原始记录数
+    pub raw_rolled: u64,
+    /// 压缩进按周聚合的按天记录数
+    pub daily_rolled: u64,
+}
+
+impl Database {
+    /// 执行一次 app_metrics 降采样
+    ///
+    /// # 参数
+    /// - `raw_days`: 原始记录保留天数
+    /// - `daily_days`: 按天聚合保留天数, 更早的合并为按周
+    pub async fn rollup_app_metrics(
+        &self,
+        raw_days: i32,
+        daily_days: i32,
+    ) -> Result<RollupResult> {
+        let row = sqlx::query(
+            "SELECT raw_rolled, daily_rolled, text_saved FROM rollup_app_metrics($1, $2)",
+        )
+        .bind(raw_days)
+        .bind(daily_days)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let get = |name: &str| row.try_get::<i32, _>(name).unwrap_or(0).max(0) as u64;
+        Ok(RollupResult {
+            raw_rolled: get("raw_rolled"),
+            daily_rolled: get("daily_rolled"),
+            text_saved: get("text_saved"),
+        })
+    }
+
+    /// 执行一次 app_rating 降采样, 参数同 [`Self::rollup_app_metrics`]
+    pub async fn rollup_app_rating(
+        &self,
+        raw_days: i32,
+        daily_days: i32,
+    ) -> Result<RatingRollupResult> {
+        let row = sqlx::query("SELECT raw_rolled, daily_rolled FROM rollup_app_rating($1, $2)")
+            .bind(raw_days)
+            .bind(daily_days)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let get = |name: &str| row.try_get::<i32, _>(name).unwrap_or(0).max(0) as u64;
+        Ok(RatingRollupResult {
+            raw_rolled: get("raw_rolled"),
+            daily_rolled: get("daily_rolled"),
+        })
+    }
+}
+
+/// 启动定时降采样任务
+pub fn start_rollup_task(
+    db: Database,
+    raw_days: i32,
+    daily_days: i32,
+    interval_seconds: u64,
+) -> tokio::task::JoinHandle<()> {
+    event!(
+        Level::INFO,
+        raw_days,
+        daily_days,
+        interval_seconds,
+        "启动 app_metrics / app_rating 降采样任务"
+    );
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(interval_seconds));
+        loop {
+            interval.tick().await;
+            let start = std::time::Instant::now();
+            match db.rollup_app_metrics(raw_days, daily_days).await {
+                Ok(result) => event!(
+                    Level::INFO,
+                    "app_metrics 降采样完成: 原始记录 {} 条, 按天记录 {} 条, 文本变化 {} 条, 耗时 {:?}",
+                    result.raw_rolled,
+                    result.daily_rolled,
+                    result.text_saved,
+                    start.elapsed()
+                ),
+                Err(e) => event!(Level::WARN, "app_metrics 降采样失败: {e}"),
+            }
+            let start = std::time::Instant::now();
+            match db.rollup_app_rating(raw_days, daily_days).await {
+                Ok(result) => event!(
+                    Level::INFO,
+                    "app_rating 降采样完成: 原始记录 {} 条, 按天记录 {} 条, 耗时 {:?}",
+                    result.raw_rolled,
+                    result.daily_rolled,
+                    start.elapsed()
+                ),
+                Err(e) => event!(Level::WARN, "app_rating 降采样失败: {e}"),
+            }
+        }
+    })
+}
@@ -0,0 +1,440 @@
+//! 应用搜索
+//!
+//! 依赖 016 迁移创建的 `app_search_vector` 表达式索引和 pg_trgm 索引,
+//! 以及 026 迁移加的应用名拼音列
+//!
+//! 打分 = 全文检索 ts_rank + 名称/开发者 trigram 相似度 + 各字段的子串命中加权,
+//! 中文没有分词, 主要靠 trigram 和子串命中, 拼写错误靠 trigram 相似度兜底.
+//! 应用名的全拼 / 首字母在写入 app_info 时由 [`name_pinyin`] 算好,
+//! 输入 `weixin` 或 `wx` 都能搜到 "微信"
+
+use anyhow::Result;
+use pinyin::ToPinyin;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::db::{Database, filter::escape_like, query::get_max_limit};
+
+/// 摘要前后保留的字符数
+const SNIPPET_RADIUS: usize = 40;
+
+/// 应用名的全拼和首字母, 存进 app_info 的 name_pinyin / name_initials
+///
+/// 汉字按默认读音转换, 字母和数字原样保留 (转小写), 空格和标点丢掉.
+/// 例如 "华为视频" 为 ("huaweishipin", "hwsp")
+pub fn name_pinyin(name: &str) -> (String, String) {
+    let mut full = String::with_capacity(name.len() * 2);
+    let mut initials = String::new();
+    for c in name.chars() {
+        if let Some(pinyin) = c.to_pinyin() {
+            full.push_str(pinyin.plain());
+            initials.push_str(pinyin.first_letter());
+        } else if c.is_ascii_alphanumeric() {
+            let c = c.to_ascii_lowercase();
+            full.push(c);
+            initials.push(c);
+        }
+    }
+    (full, initials)
+}
+
+/// 把搜索词整理成和拼音列比较的形式: 转小写, 去掉空格和隔音符号
+///
+/// 含有字母数字以外的字符 (比如汉字) 时返回 None, 不参与拼音匹配
+fn pinyin_term(term: &str) -> Option<String> {
+    let compact: String = term
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    (!compact.is_empty() && compact.chars().all(|c| c.is_ascii_alphanumeric())).then_some(compact)
+}
+
+/// 搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    pub app_id: String,
+    pub name: String,
+    pub pkg_name: String,
+    pub icon_url: String,
+    pub developer_name: String,
+    pub kind_name: String,
+    pub download_count: Option<i64>,
+    #[schema(value_type = Option<f64>)]
+    pub average_rating: Option<Decimal>,
+    /// 相关度得分, 越大越相关
+    pub score: f32,
+    /// 命中的字段
+    pub matched_field: Option<String>,
+    /// 高亮摘要, 命中部分用 `<mark></mark>` 包裹, 其余内容已做 HTML 转义
+    pub snippet: String,
+}
+
+#[derive(Debug, FromRow)]
+struct SearchRow {
+    app_id: String,
+    name: String,
+    pkg_name: String,
+    icon_url: String,
+    developer_name: String,
+    kind_name: String,
+    brief_desc: String,
+    description: String,
+    download_count: Option<i64>,
+    average_rating: Option<Decimal>,
+    score: f32,
+}
+
+/// 自动补全的类型
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    App,
+    Developer,
+}
+
+/// 自动补全候选
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    /// 补全文本 (应用名 / 开发者名)
+    pub value: String,
+    /// 应用 ID, 开发者为 None
+    pub app_id: Option<String>,
+    /// 开发者 ID, 应用为 None
+    pub dev_id: Option<String>,
+    /// 排序用的权重 (下载量)
+    pub weight: i64,
+}
+
+impl SearchRow {
+    fn into_hit(self, term: &str) -> SearchHit {
+        let candidates = [
+            ("name", &self.name),
+            ("developer_name", &self.developer_name),
+            ("kind_name", &self.kind_name),
+            ("brief_desc", &self.brief_desc),
+            ("description", &self.description),
+        ];
+        let matched = candidates
+            .iter()
+            .find_map(|(field, text)| highlight_snippet(text, term, SNIPPET_RADIUS).map(|s| (*field, s)));
+        let (matched_field, snippet) = match matched {
+            Some((field, snippet)) => (Some(field.to_string()), snippet),
+            // trigram 模糊命中时没有完整子串, 用简介兜底
+            None => (None, truncate_escaped(&self.brief_desc, SNIPPET_RADIUS * 2)),
+        };
+        SearchHit {
+            app_id: self.app_id,
+            name: self.name,
+            pkg_name: self.pkg_name,
+            icon_url: self.icon_url,
+            developer_name: self.developer_name,
+            kind_name: self.kind_name,
+            download_count: self.download_count,
+            average_rating: self.average_rating,
+            score: self.score,
+            matched_field,
+            snippet,
+        }
+    }
+}
+
+impl Database {
+    /// 搜索应用, 按相关度排序
+    ///
+    /// 返回 (结果, 总数)
+    pub async fn search_apps(
+        &self,
+        term: &str,
+        page: u32,
+        page_size: u32,
+        exclude_atomic: bool,
+    ) -> Result<(Vec<SearchHit>, i64)> {
+        const WHERE: &str = r#"
+            (
+                app_search_vector(
+                    name, brief_desc, description, developer_name, kind_name,
+                    name_pinyin, name_initials
+                ) @@ plainto_tsquery('simple', $1)
+                OR name % $1
+                OR name_pinyin LIKE $4
+                OR name_initials LIKE $5
+                OR developer_name % $1
+                OR name ILIKE $2
+                OR developer_name ILIKE $2
+                OR kind_name ILIKE $2
+                OR brief_desc ILIKE $2
+                OR description ILIKE $2
+            )
+            AND (NOT $3::boolean OR pkg_name NOT LIKE 'com.atomicservice%')
+        "#;
+
+        let query = format!(
+            r#"
+            SELECT
+                app_id, name, pkg_name, icon_url, developer_name, kind_name,
+                brief_desc, description, download_count, average_rating,
+                (
+                    ts_rank(
+                        app_search_vector(
+                            name, brief_desc, description, developer_name, kind_name,
+                            name_pinyin, name_initials
+                        ),
+                        plainto_tsquery('simple', $1)
+                    ) * 2
+                    + similarity(name, $1) * 3
+                    + similarity(developer_name, $1)
+                    + CASE WHEN name ILIKE $2 THEN 2 ELSE 0 END
+                    + CASE WHEN lower(name) = lower($1) THEN 3 ELSE 0 END
+                    + CASE WHEN developer_name ILIKE $2 THEN 1 ELSE 0 END
+                    + CASE WHEN kind_name ILIKE $2 THEN 0.5 ELSE 0 END
+                    + CASE WHEN brief_desc ILIKE $2 THEN 0.5 ELSE 0 END
+                    + CASE WHEN description ILIKE $2 THEN 0.2 ELSE 0 END
+                    + CASE WHEN name_pinyin LIKE $4 THEN 1.5 ELSE 0 END
+                    + CASE WHEN name_initials LIKE $5 THEN 1.5 ELSE 0 END
+                )::real AS score
+            FROM app_full_info
+            WHERE {WHERE}
+            ORDER BY score DESC, download_count DESC NULLS LAST
+            LIMIT $6 OFFSET $7
+            "#
+        );
+        let count_query = format!("SELECT COUNT(*) FROM app_full_info WHERE {WHERE}");
+
+        let pattern = format!("%{}%", escape_like(term));
+        // 不能按拼音匹配时绑定 NULL, LIKE NULL 永远不成立
+        let pinyin = pinyin_term(term);
+        let pinyin_pattern = pinyin.as_ref().map(|p| format!("%{p}%"));
+        let initials_pattern = pinyin.as_ref().map(|p| format!("{p}%"));
+        let limit = page_size.min(get_max_limit()) as i64;
+        let offset = page.saturating_sub(1) as i64 * limit;
+
+        let total: i64 = sqlx::query_scalar(&count_query)
+            .bind(term)
+            .bind(&pattern)
+            .bind(exclude_atomic)
+            .bind(&pinyin_pattern)
+            .bind(&initials_pattern)
+            .fetch_one(self.read_pool())
+            .await?;
+
+        let rows: Vec<SearchRow> = sqlx::query_as(&query)
+            .bind(term) // $1 原始关键词
+            .bind(&pattern) // $2 ILIKE
+            .bind(exclude_atomic) // $3
+            .bind(&pinyin_pattern) // $4 全拼子串
+            .bind(&initials_pattern) // $5 首字母前缀
+            .bind(limit) // $6
+            .bind(offset) // $7
+            .fetch_all(self.read_pool())
+            .await?;
+
+        Ok((rows.into_iter().map(|row| row.into_hit(term)).collect(), total))
+    }
+
+    /// 应用名 / 开发者名自动补全
+    ///
+    /// 前缀命中优先 (应用名的全拼 / 首字母前缀也算), 其次按 trigram 相似度, 最后按下载量
+    pub async fn suggest(&self, prefix: &str, limit: u32) -> Result<Vec<Suggestion>> {
+        const QUERY: &str = r#"
+            SELECT kind, value, app_id, dev_id, weight FROM (
+                (
+                    SELECT
+                        'app'::text AS kind, name AS value, app_id, NULL::text AS dev_id,
+                        COALESCE(download_count, 0) AS weight,
+                        (name ILIKE $1 OR name_pinyin LIKE $4 OR name_initials LIKE $4) AS is_prefix,
+                        similarity(name, $2) AS sim
+                    FROM app_full_info
+                    WHERE name ILIKE $1 OR name % $2 OR name_pinyin LIKE $4 OR name_initials LIKE $4
+                    ORDER BY is_prefix DESC, sim DESC, weight DESC
+                    LIMIT $3
+                )
+                UNION ALL
+                (
+                    SELECT
+                        'developer'::text AS kind, developer_name AS value, NULL::text AS app_id, dev_id,
+                        COALESCE(SUM(download_count), 0)::bigint AS weight,
+                        (developer_name ILIKE $1) AS is_prefix,
+                        similarity(developer_name, $2) AS sim
+                    FROM app_full_info
+                    WHERE developer_name ILIKE $1 OR developer_name % $2
+                    GROUP BY dev_id, developer_name
+                    ORDER BY is_prefix DESC, sim DESC, weight DESC
+                    LIMIT $3
+                )
+            ) AS candidates
+            ORDER BY is_prefix DESC, sim DESC, weight DESC
+            LIMIT $3
+        "#;
+
+        let pattern = format!("{}%", escape_like(prefix));
+        let pinyin_pattern = pinyin_term(prefix).map(|p| format!("{p}%"));
+        let rows: Vec<(String, String, Option<String>, Option<String>, i64)> =
+            sqlx::query_as(QUERY)
+                .bind(pattern)
+                .bind(prefix)
+                .bind(limit.min(get_max_limit()) as i64)
+                .bind(pinyin_pattern)
+                .fetch_all(self.read_pool())
+                .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(kind, value, app_id, dev_id, weight)| Suggestion {
+                kind: if kind == "app" {
+                    SuggestionKind::App
+                } else {
+                    SuggestionKind::Developer
+                },
+                value,
+                app_id,
+                dev_id,
+                weight,
+            })
+            .collect())
+    }
+}
+
+impl Database {
+    /// 给还没有拼音的应用补上 name_pinyin / name_initials, 按 app_id 分批, 返回更新的数量
+    ///
+    /// 026 迁移之前入库的应用只有这样才能按拼音搜到; 之后写入的由 upsert 顺带算好
+    pub async fn backfill_name_pinyin(&self, batch_size: usize) -> Result<u64> {
+        let mut after = String::new();
+        let mut updated = 0;
+        loop {
+            let rows: Vec<(String, String)> = sqlx::query_as(
+                "SELECT app_id, name FROM app_info
+                 WHERE name_pinyin IS NULL AND app_id > $1
+                 ORDER BY app_id LIMIT $2",
+            )
+            .bind(&after)
+            .bind(batch_size.max(1) as i64)
+            .fetch_all(&self.pool)
+            .await?;
+            let Some((last, _)) = rows.last() else {
+                break;
+            };
+            after = last.clone();
+
+            let (app_ids, (pinyins, initials)): (Vec<String>, (Vec<String>, Vec<String>)) = rows
+                .into_iter()
+                .map(|(app_id, name)| (app_id, name_pinyin(&name)))
+                .unzip();
+            updated += sqlx::query(
+                "UPDATE app_info a SET name_pinyin = v.pinyin, name_initials = v.initials
+                 FROM UNNEST($1::text[], $2::text[], $3::text[]) AS v(app_id, pinyin, initials)
+                 WHERE a.app_id = v.app_id",
+            )
+            .bind(&app_ids)
+            .bind(&pinyins)
+            .bind(&initials)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        }
+        Ok(updated)
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 截断并转义
+fn truncate_escaped(text: &str, max_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        escape_html(text)
+    } else {
+        format!("{}…", escape_html(&chars[..max_chars].iter().collect::<String>()))
+    }
+}
+
+/// 在 `text` 中找到 `term` (不区分大小写), 截取前后 `radius` 个字符并高亮
+///
+/// 没找到返回 None
+pub fn highlight_snippet(text: &str, term: &str, radius: usize) -> Option<String> {
+    let term = term.trim();
+    if term.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    let needle: Vec<char> = term.chars().flat_map(|c| c.to_lowercase()).collect();
+    // to_lowercase 可能改变长度, 这种情况下下标对不上, 直接放弃
+    if lower.len() != chars.len() || needle.len() > lower.len() {
+        return None;
+    }
+    let start = lower.windows(needle.len()).position(|w| w == needle.as_slice())?;
+    let end = start + needle.len();
+
+    let from = start.saturating_sub(radius);
+    let to = (end + radius).min(chars.len());
+    let collect = |range: std::ops::Range<usize>| chars[range].iter().collect::<String>();
+
+    Some(format!(
+        "{}{}<mark>{}</mark>{}{}",
+        if from > 0 { "…" } else { "" },
+        escape_html(&collect(from..start)),
+        escape_html(&collect(start..end)),
+        escape_html(&collect(end..to)),
+        if to < chars.len() { "…" } else { "" },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_snippet() {
+        assert_eq!(
+            highlight_snippet("华为应用市场", "应用", 1).as_deref(),
+            Some("…为<mark>应用</mark>市…")
+        );
+        // 不区分大小写, 保留原文大小写
+        assert_eq!(
+            highlight_snippet("Hello World", "world", 10).as_deref(),
+            Some("Hello <mark>World</mark>")
+        );
+        // 原文中的 HTML 需要转义
+        assert_eq!(
+            highlight_snippet("<b>游戏</b>", "游戏", 10).as_deref(),
+            Some("&lt;b&gt;<mark>游戏</mark>&lt;/b&gt;")
+        );
+        assert_eq!(highlight_snippet("华为应用市场", "微信", 5), None);
+        assert_eq!(highlight_snippet("华为应用市场", "  ", 5), None);
+    }
+
+    #[test]
+    fn test_name_pinyin() {
+        assert_eq!(
+            name_pinyin("华为视频"),
+            ("huaweishipin".to_string(), "hwsp".to_string())
+        );
+        // 字母数字保留并转小写, 标点丢掉
+        assert_eq!(
+            name_pinyin("QQ 浏览器!"),
+            ("qqliulanqi".to_string(), "qqllq".to_string())
+        );
+        assert_eq!(pinyin_term(" Hua Wei "), Some("huawei".to_string()));
+        assert_eq!(pinyin_term("xi'an"), Some("xian".to_string()));
+        assert_eq!(pinyin_term("华为"), None);
+        assert_eq!(pinyin_term("  "), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! SQLite 后端: API key
+//!
+//! scopes 存成逗号分隔的文本
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::{Row, sqlite::SqliteRow};
+
+use super::{SqliteStorage, decode_time, encode_time};
+use crate::db::api_key::{
+    ApiKey, ApiScope, NewApiKey, display_prefix, generate_secret, hash_secret,
+};
+
+const SELECT_API_KEY_FIELDS: &str =
+    "id, name, key_prefix, scopes, created_at, last_used_at, revoked_at";
+
+fn read_api_key(row: &SqliteRow) -> Result<ApiKey> {
+    let scopes: String = row.get("scopes");
+    let scopes: Vec<&str> = scopes.split(',').collect();
+    let optional_time = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(|text| decode_time(&text))
+            .transpose()
+    };
+    Ok(ApiKey {
+        id: row.get("id"),
+        name: row.get("name"),
+        key_prefix: row.get("key_prefix"),
+        scopes: ApiScope::from_names(&scopes),
+        created_at: decode_time(row.get("created_at"))?,
+        last_used_at: optional_time("last_used_at")?,
+        revoked_at: optional_time("revoked_at")?,
+    })
+}
+
+impl SqliteStorage {
+    pub async fn create_api_key(&self, name: &str, scopes: &[ApiScope]) -> Result<NewApiKey> {
+        let secret = generate_secret();
+        let names: Vec<&str> = scopes.iter().map(ApiScope::name).collect();
+        let _guard = self.write_lock.lock().await;
+        let row = sqlx::query(&format!(
+            "INSERT INTO api_keys (name, key_prefix, key_hash, scopes, created_at)
+             VALUES (?, ?, ?, ?, ?) RETURNING {SELECT_API_KEY_FIELDS}"
+        ))
+        .bind(name)
+        .bind(display_prefix(&secret))
+        .bind(hash_secret(&secret))
+        .bind(names.join(","))
+        .bind(encode_time(&Utc::now()))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(NewApiKey {
+            key: read_api_key(&row)?,
+            secret,
+        })
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        sqlx::query(&format!(
+            "SELECT {SELECT_API_KEY_FIELDS} FROM api_keys ORDER BY id"
+        ))
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_api_key)
+        .collect()
+    }
+
+    pub async fn revoke_api_key(&self, id: i64) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+                .bind(encode_time(&Utc::now()))
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        sqlx::query(&format!(
+            "SELECT {SELECT_API_KEY_FIELDS} FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL"
+        ))
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(read_api_key)
+        .transpose()
+    }
+
+    pub async fn touch_api_keys(&self, usages: &[(i64, DateTime<Utc>)]) -> Result<u64> {
+        if usages.is_empty() {
+            return Ok(0);
+        }
+        let _guard = self.write_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let mut affected = 0;
+        for (id, used_at) in usages {
+            affected += sqlx::query(
+                "UPDATE api_keys SET last_used_at = MAX(COALESCE(last_used_at, ''), ?) WHERE id = ?",
+            )
+            .bind(encode_time(used_at))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(affected)
+    }
+
+    pub async fn count_api_key_requests(&self) -> Result<Vec<(i64, i64)>> {
+        let rows = sqlx::query(
+            "SELECT api_key_id, COUNT(*) AS requests FROM access_logs
+             WHERE api_key_id IS NOT NULL GROUP BY api_key_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("api_key_id"), row.get("requests")))
+            .collect())
+    }
+}
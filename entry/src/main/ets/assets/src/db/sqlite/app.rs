@@ -0,0 +1,730 @@
+//! SQLite 后端: 应用 / 指标 / 评分
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection};
+
+use super::{SqliteStorage, encode_time};
+use crate::{
+    db::{AppCounts, AppIconInfo, DbSearch, PageInfo, query::get_max_limit},
+    model::{
+        AppInfo, AppMetric, AppQuery, AppRating, AppRecord, FullAppInfo, ShortAppRating,
+        raw::RawAppData,
+    },
+};
+
+/// 数值型字段, 排序和比较时要转成数字
+///
+/// Decimal 序列化出来是字符串, 不转就会按字典序排
+const NUMERIC_FIELDS: &[&str] = &[
+    "download_count",
+    "average_rating",
+    "full_average_rating",
+    "total_star_rating_count",
+    "only_star_count",
+    "price",
+    "size_bytes",
+    "info_score",
+    "info_rate_count",
+    "version_code",
+    "release_date",
+    "minsdk",
+    "target_sdk",
+    "compile_sdk_version",
+    "min_hmos_api_level",
+    "kind_id",
+    "kind_type_id",
+];
+
+/// 读取 app_full_info.data 里某个字段的表达式
+///
+/// `as_number` 为 true 时数值字段转成 REAL
+fn json_field(key: &str, as_number: bool) -> Result<String> {
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        bail!("非法的字段名: {key}");
+    }
+    Ok(if as_number && NUMERIC_FIELDS.contains(&key) {
+        format!("CAST(json_extract(data, '$.{key}') AS REAL)")
+    } else {
+        format!("json_extract(data, '$.{key}')")
+    })
+}
+
+/// 搜索 / 排除条件, 语义和 Postgres 版本的 `get_app_info_paginated_enhanced` 一致
+///
+/// `not_null_key` 为 None 时表示统计总数, 不加默认的非空条件
+fn push_conditions(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    search: Option<&DbSearch>,
+    not_null_key: Option<&str>,
+    exclude_huawei: bool,
+    exclude_atomic: bool,
+) -> Result<()> {
+    match search {
+        Some(search) => {
+            if search.is_int_search() {
+                qb.push(json_field(&search.key, true)?)
+                    .push(" = CAST(")
+                    .push_bind(search.search_value())
+                    .push(" AS REAL)");
+            } else {
+                // SQLite 的 LIKE 对 ASCII 本来就不区分大小写
+                qb.push(json_field(&search.key, false)?)
+                    .push(" LIKE ")
+                    .push_bind(search.search_value());
+            }
+            let key = not_null_key.unwrap_or(&search.key);
+            qb.push(" AND (NOT ")
+                .push_bind(search.not_null)
+                .push(format!(" OR {} IS NOT NULL)", json_field(key, false)?));
+        }
+        None => match not_null_key {
+            Some(key) => {
+                qb.push(format!(
+                    "{} IS NOT NULL AND app_id != 'C5765880207854862721'",
+                    json_field(key, false)?
+                ));
+            }
+            None => {
+                qb.push("1");
+            }
+        },
+    }
+    if exclude_huawei {
+        qb.push(" AND json_extract(data, '$.dev_en_name') NOT LIKE '%huawei%'");
+    }
+    if exclude_atomic {
+        qb.push(" AND pkg_name NOT LIKE 'com.atomicservice%'");
+    }
+    Ok(())
+}
+
+fn from_json<T: DeserializeOwned>(text: &str) -> Result<T> {
+    serde_json::from_str(text).with_context(|| "SQLite 中的 JSON 数据无法解析")
+}
+
+/// 用各表的最新记录拼出 app_full_info
+fn compose_full_info(
+    info: &AppInfo,
+    metric: &AppMetric,
+    rating: Option<&AppRating>,
+    record: Option<&AppRecord>,
+) -> FullAppInfo {
+    FullAppInfo {
+        app_id: info.app_id.clone(),
+        alliance_app_id: info.alliance_app_id.clone(),
+        name: info.name.clone(),
+        pkg_name: info.pkg_name.clone(),
+        dev_id: info.dev_id.clone(),
+        developer_name: info.developer_name.clone(),
+        dev_en_name: info.dev_en_name.clone(),
+        supplier: info.supplier.clone(),
+        kind_id: info.kind_id,
+        kind_name: info.kind_name.clone(),
+        tag_name: info.tag_name.clone(),
+        kind_type_id: info.kind_type_id,
+        kind_type_name: info.kind_type_name.clone(),
+        icon_url: info.icon_url.clone(),
+        brief_desc: info.brief_desc.clone(),
+        description: info.description.clone(),
+        privacy_url: info.privacy_url.clone(),
+        ctype: info.ctype,
+        detail_id: info.detail_id.clone(),
+        app_level: info.app_level,
+        jocat_id: info.jocat_id,
+        iap: info.iap,
+        hms: info.hms,
+        tariff_type: info.tariff_type.clone(),
+        packing_type: info.packing_type,
+        order_app: info.order_app,
+        denpend_gms: info.denpend_gms,
+        denpend_hms: info.denpend_hms,
+        force_update: info.force_update,
+        img_tag: info.img_tag.clone(),
+        is_pay: info.is_pay,
+        is_disciplined: info.is_disciplined,
+        is_shelves: info.is_shelves,
+        submit_type: info.submit_type,
+        delete_archive: info.delete_archive,
+        charging: info.charging,
+        button_grey: info.button_grey,
+        app_gift: info.app_gift,
+        free_days: info.free_days,
+        pay_install_type: info.pay_install_type,
+        created_at: info.created_at,
+        listed_at: info.listed_at,
+        comment: info.comment.clone(),
+        release_countries: info.release_countries.clone(),
+        main_device_codes: info.main_device_codes.clone(),
+        version: metric.version.clone(),
+        version_code: metric.version_code,
+        size_bytes: metric.size_bytes,
+        sha256: metric.sha256.clone(),
+        info_score: metric.info_score,
+        info_rate_count: metric.info_rate_count,
+        download_count: metric.download_count,
+        price: metric.price,
+        release_date: metric.release_date,
+        new_features: metric.new_features.clone(),
+        upgrade_msg: metric.upgrade_msg.clone(),
+        target_sdk: metric.target_sdk,
+        minsdk: metric.minsdk,
+        compile_sdk_version: metric.compile_sdk_version,
+        min_hmos_api_level: metric.min_hmos_api_level,
+        api_release_type: metric.api_release_type.clone(),
+        metrics_created_at: metric.created_at,
+        average_rating: rating.map(|r| r.average_rating),
+        star_1_rating_count: rating.map(|r| r.star_1_rating_count),
+        star_2_rating_count: rating.map(|r| r.star_2_rating_count),
+        star_3_rating_count: rating.map(|r| r.star_3_rating_count),
+        star_4_rating_count: rating.map(|r| r.star_4_rating_count),
+        star_5_rating_count: rating.map(|r| r.star_5_rating_count),
+        my_star_rating: rating.map(|r| r.my_star_rating),
+        total_star_rating_count: rating.map(|r| r.total_star_rating_count),
+        only_star_count: rating.map(|r| r.only_star_count),
+        full_average_rating: rating.map(|r| r.full_average_rating),
+        source_type: rating.map(|r| r.source_type.clone()),
+        rating_created_at: rating.map(|r| r.created_at),
+        title: record.map(|r| r.title.clone()),
+        app_recordal_info: record.map(|r| r.app_recordal_info.clone()),
+        recordal_entity_title: record.map(|r| r.recordal_entity_title.clone()),
+        recordal_entity_name: record.map(|r| r.recordal_entity_name.clone()),
+        updated_at: Local::now(),
+    }
+}
+
+async fn last_app_info(conn: &mut SqliteConnection, app_id: &str) -> Result<Option<AppInfo>> {
+    let data: Option<String> = sqlx::query_scalar("SELECT data FROM app_info WHERE app_id = ?")
+        .bind(app_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    data.as_deref().map(from_json).transpose()
+}
+
+async fn last_app_metric(conn: &mut SqliteConnection, app_id: &str) -> Result<Option<AppMetric>> {
+    let row = sqlx::query("SELECT id, data FROM app_metrics WHERE app_id = ? ORDER BY id DESC LIMIT 1")
+        .bind(app_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    row.map(|row| {
+        let mut metric: AppMetric = from_json(row.get::<&str, _>("data"))?;
+        metric.id = row.get("id");
+        Ok(metric)
+    })
+    .transpose()
+}
+
+async fn last_app_rating(conn: &mut SqliteConnection, app_id: &str) -> Result<Option<AppRating>> {
+    let row = sqlx::query("SELECT id, data FROM app_rating WHERE app_id = ? ORDER BY id DESC LIMIT 1")
+        .bind(app_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    row.map(|row| {
+        let mut rating: AppRating = from_json(row.get::<&str, _>("data"))?;
+        rating.id = row.get("id");
+        Ok(rating)
+    })
+    .transpose()
+}
+
+async fn app_record(conn: &mut SqliteConnection, app_id: &str) -> Result<Option<AppRecord>> {
+    let data: Option<String> = sqlx::query_scalar("SELECT data FROM app_record WHERE app_id = ?")
+        .bind(app_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    data.as_deref().map(from_json).transpose()
+}
+
+async fn last_raw_json(conn: &mut SqliteConnection, app_id: &str) -> Result<Option<JsonValue>> {
+    let data: Option<String> = sqlx::query_scalar(
+        "SELECT raw_json_data FROM app_data_history WHERE app_id = ? ORDER BY id DESC LIMIT 1",
+    )
+    .bind(app_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    data.as_deref().map(from_json).transpose()
+}
+
+async fn upsert_app_info(conn: &mut SqliteConnection, info: &AppInfo) -> Result<()> {
+    const QUERY: &str = r#"
+        INSERT INTO app_info (
+            app_id, pkg_name, name, dev_id, developer_name, dev_en_name, icon_url, created_at, data
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (app_id) DO UPDATE SET
+            pkg_name = excluded.pkg_name,
+            name = excluded.name,
+            dev_id = excluded.dev_id,
+            developer_name = excluded.developer_name,
+            dev_en_name = excluded.dev_en_name,
+            icon_url = excluded.icon_url,
+            data = excluded.data
+    "#;
+
+    sqlx::query(QUERY)
+        .bind(&info.app_id)
+        .bind(&info.pkg_name)
+        .bind(&info.name)
+        .bind(&info.dev_id)
+        .bind(&info.developer_name)
+        .bind(&info.dev_en_name)
+        .bind(&info.icon_url)
+        .bind(encode_time(&info.created_at))
+        .bind(serde_json::to_string(info)?)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// 写入审核通过的备注 / 上架时间, 只写不为空的字段, 应用不存在时返回 false
+pub(super) async fn apply_app_metadata(
+    conn: &mut SqliteConnection,
+    app: &AppQuery,
+    comment: Option<&JsonValue>,
+    listed_at: Option<DateTime<Local>>,
+) -> Result<bool> {
+    let app_id: Option<String> = sqlx::query_scalar(&format!(
+        "SELECT app_id FROM app_info WHERE {} = ?",
+        app.app_db_name()
+    ))
+    .bind(app.name())
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(app_id) = app_id else {
+        return Ok(false);
+    };
+    let Some(mut info) = last_app_info(conn, &app_id).await? else {
+        return Ok(false);
+    };
+    if let Some(comment) = comment {
+        info.comment = Some(comment.clone());
+    }
+    if let Some(listed_at) = listed_at {
+        info.listed_at = listed_at;
+    }
+    upsert_app_info(conn, &info).await?;
+    SqliteStorage::rebuild_full_info(conn, &app_id).await?;
+    Ok(true)
+}
+
+impl SqliteStorage {
+    /// 检查应用是否已存在
+    pub async fn app_exists(&self, app: &AppQuery) -> bool {
+        let query = format!(
+            "SELECT COUNT(*) FROM app_info WHERE {} = ?",
+            app.app_db_name()
+        );
+        sqlx::query_scalar::<_, i64>(&query)
+            .bind(app.name())
+            .fetch_one(&self.pool)
+            .await
+            .map(|c| c > 0)
+            .unwrap_or(false)
+    }
+
+    /// 保存应用数据, 流程和 Postgres 版本一致
+    ///
+    /// 所有写入在一个事务里完成, 最后由程序重建 app_full_info
+    pub async fn save_app_data(
+        &self,
+        data: RawAppData,
+        listed_at: Option<DateTime<Local>>,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool, bool, FullAppInfo)> {
+        let app_id = data.app_id();
+        let raw_data = data.app_info;
+        let raw_value = data.app_info_json;
+
+        let _guard = self.write_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+
+        let old_info = last_app_info(&mut tx, &app_id).await?;
+        let same_data = old_info.is_some()
+            && last_raw_json(&mut tx, &app_id).await?.as_ref() == Some(&raw_value);
+
+        let (info_new, metric_new) = if same_data {
+            (false, false)
+        } else {
+            let mut app_info: AppInfo = (&raw_data).into();
+            app_info.comment = comment;
+            if let Some(listed_at) = listed_at {
+                app_info.listed_at = listed_at;
+            }
+            // 已有的应用保留首次入库时间, 没有明确传入时保留备注和上架时间
+            if let Some(old) = old_info.as_ref() {
+                app_info.created_at = old.created_at;
+                if app_info.comment.is_none() {
+                    app_info.comment = old.comment.clone();
+                }
+                if listed_at.is_none() {
+                    app_info.listed_at = old.listed_at;
+                }
+            }
+            let info_new = if old_info.as_ref() == Some(&app_info) {
+                false
+            } else {
+                upsert_app_info(&mut tx, &app_info).await?;
+                true
+            };
+
+            let mut app_metric = AppMetric::from_raw_data(&raw_data);
+            let last_metric = last_app_metric(&mut tx, &app_id).await?;
+            let metric_new = match last_metric {
+                Some(last) => {
+                    app_metric.update_from_db(&last);
+                    app_metric != last
+                }
+                None => true,
+            };
+            if metric_new {
+                sqlx::query("INSERT INTO app_metrics (app_id, created_at, data) VALUES (?, ?, ?)")
+                    .bind(&app_id)
+                    .bind(encode_time(&app_metric.created_at))
+                    .bind(serde_json::to_string(&app_metric)?)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            (info_new, metric_new)
+        };
+
+        let rating_new = if let Some(raw_star) = data.app_rating.as_ref() {
+            let app_rating = AppRating::from_raw_star(&raw_data, raw_star);
+            let is_new = last_app_rating(&mut tx, &app_id)
+                .await?
+                .map(|mut last| {
+                    last.update_from_db(&app_rating);
+                    last != app_rating
+                })
+                .unwrap_or(true);
+            if is_new {
+                sqlx::query("INSERT INTO app_rating (app_id, created_at, data) VALUES (?, ?, ?)")
+                    .bind(&app_id)
+                    .bind(encode_time(&app_rating.created_at))
+                    .bind(serde_json::to_string(&app_rating)?)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            is_new
+        } else {
+            false
+        };
+
+        if let Some(record) = data.app_record.as_ref() {
+            let app_record = AppRecord::from_raw_record(&app_id, record);
+            sqlx::query(
+                "INSERT INTO app_record (app_id, data) VALUES (?, ?)
+                ON CONFLICT (app_id) DO UPDATE SET data = excluded.data",
+            )
+            .bind(&app_id)
+            .bind(serde_json::to_string(&app_record)?)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if info_new || metric_new {
+            sqlx::query(
+                "INSERT INTO app_data_history (app_id, raw_json_data, created_at) VALUES (?, ?, ?)",
+            )
+            .bind(&app_id)
+            .bind(serde_json::to_string(&raw_value)?)
+            .bind(encode_time(&Local::now()))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let full_info = Self::rebuild_full_info(&mut tx, &app_id).await?;
+        tx.commit().await?;
+
+        Ok((info_new, metric_new, rating_new, full_info))
+    }
+
+    /// 重新拼出某个应用的 app_full_info, 相当于 Postgres 里的触发器
+    async fn rebuild_full_info(conn: &mut SqliteConnection, app_id: &str) -> Result<FullAppInfo> {
+        let Some(info) = last_app_info(conn, app_id).await? else {
+            bail!("应用 {app_id} 不存在");
+        };
+        let Some(metric) = last_app_metric(conn, app_id).await? else {
+            bail!("应用 {app_id} 没有指标数据");
+        };
+        let rating = last_app_rating(conn, app_id).await?;
+        let record = app_record(conn, app_id).await?;
+        let full_info = compose_full_info(&info, &metric, rating.as_ref(), record.as_ref());
+
+        sqlx::query(
+            "INSERT INTO app_full_info (app_id, pkg_name, data) VALUES (?, ?, ?)
+            ON CONFLICT (app_id) DO UPDATE SET pkg_name = excluded.pkg_name, data = excluded.data",
+        )
+        .bind(&full_info.app_id)
+        .bind(&full_info.pkg_name)
+        .bind(serde_json::to_string(&full_info)?)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(full_info)
+    }
+
+    /// 从 app_full_info 表查询完整应用信息
+    pub async fn get_full_app_info(&self, app: &AppQuery) -> Result<FullAppInfo> {
+        let query = format!(
+            "SELECT data FROM app_full_info WHERE {} = ?",
+            app.app_db_name()
+        );
+        let data: String = sqlx::query_scalar(&query)
+            .bind(app.name())
+            .fetch_one(&self.pool)
+            .await?;
+        from_json(&data)
+    }
+
+    pub async fn get_app_icon(&self, app: &AppQuery) -> Option<AppIconInfo> {
+        let query = format!(
+            "SELECT app_id, pkg_name, name, icon_url FROM app_info WHERE {} = ?",
+            app.app_db_name()
+        );
+        sqlx::query_as::<_, AppIconInfo>(&query)
+            .bind(app.name())
+            .fetch_optional(&self.pool)
+            .await
+            .ok()?
+    }
+
+    pub async fn get_all_pkg_names(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar("SELECT pkg_name FROM app_info")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// 分页查询应用, 不支持组合过滤
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_app_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        sort_desc: bool,
+        search: Option<DbSearch>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+    ) -> Result<PageInfo<FullAppInfo>> {
+        let mut qb = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM app_full_info WHERE ");
+        push_conditions(&mut qb, search.as_ref(), None, exclude_huawei, exclude_atomic)?;
+        let total_count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let total_pages = if page_size == 0 {
+            0
+        } else {
+            (total_count as u32).div_ceil(page_size)
+        };
+        let limit = page_size.min(get_max_limit()) as i64;
+        let offset = (page.saturating_sub(1) * page_size) as i64;
+        let order_clause = if sort_desc { "DESC" } else { "ASC" };
+
+        let mut qb = QueryBuilder::<Sqlite>::new("SELECT data FROM app_full_info WHERE ");
+        push_conditions(
+            &mut qb,
+            search.as_ref(),
+            Some(sort_key),
+            exclude_huawei,
+            exclude_atomic,
+        )?;
+        qb.push(format!(
+            " ORDER BY {} {order_clause} LIMIT ",
+            json_field(sort_key, true)?
+        ))
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+        let rows: Vec<String> = qb.build_query_scalar().fetch_all(&self.pool).await?;
+        let data = rows
+            .iter()
+            .map(|data| from_json(data))
+            .collect::<Result<Vec<FullAppInfo>>>()?;
+
+        Ok(PageInfo {
+            data,
+            total_count: total_count as u32,
+            page,
+            page_size,
+            total_pages,
+        })
+    }
+
+    /// 获取数据库内应用数量
+    pub async fn count_apps(&self) -> Result<AppCounts> {
+        const QUERY: &str = r#"
+        SELECT
+            COUNT(*) AS total,
+            COALESCE(SUM(CASE WHEN pkg_name NOT LIKE 'com.atomicservice.%' THEN 1 ELSE 0 END), 0) AS apps,
+            COALESCE(SUM(CASE WHEN pkg_name LIKE 'com.atomicservice.%' THEN 1 ELSE 0 END), 0) AS atomic_services
+        FROM app_info
+        "#;
+
+        Ok(sqlx::query_as(QUERY).fetch_one(&self.pool).await?)
+    }
+
+    pub async fn count_developers(&self) -> Result<i64> {
+        Ok(
+            sqlx::query_scalar("SELECT COUNT(DISTINCT developer_name) FROM app_info")
+                .fetch_one(&self.pool)
+                .await?,
+        )
+    }
+
+    /// 获取指定 pkg_name 的所有 app_metric 信息, 新的在前
+    pub async fn get_app_metrics_by_pkg_id(&self, pkg_id: &str) -> Result<Vec<AppMetric>> {
+        const QUERY: &str = r#"
+            SELECT am.id, am.data
+            FROM app_metrics am
+            JOIN app_info ai ON am.app_id = ai.app_id
+            WHERE ai.pkg_name = ?
+            ORDER BY am.id DESC
+        "#;
+
+        let rows = sqlx::query(QUERY)
+            .bind(pkg_id)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let mut metric: AppMetric = from_json(row.get::<&str, _>("data"))?;
+                metric.id = row.get("id");
+                Ok(metric)
+            })
+            .collect()
+    }
+
+    /// 按 app_full_info 的某个表达式排序取前 limit 个
+    async fn top_full_infos(&self, condition: &str, order: &str, limit: u32) -> Result<Vec<FullAppInfo>> {
+        let query = format!(
+            "SELECT data FROM app_full_info WHERE {condition} ORDER BY {order} LIMIT ?"
+        );
+        let rows: Vec<String> = sqlx::query_scalar(&query)
+            .bind(limit.min(get_max_limit()) as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+
+    pub async fn get_top_rated_apps(&self, limit: u32) -> Result<Vec<ShortAppRating>> {
+        let apps = self
+            .top_full_infos(
+                "json_extract(data, '$.average_rating') IS NOT NULL",
+                "CAST(json_extract(data, '$.average_rating') AS REAL) DESC, \
+                 CAST(json_extract(data, '$.total_star_rating_count') AS INTEGER) DESC",
+                limit,
+            )
+            .await?;
+
+        Ok(apps
+            .into_iter()
+            .map(|app| ShortAppRating {
+                average_rating: app.average_rating.unwrap_or_default(),
+                total_star_rating_count: app.total_star_rating_count.unwrap_or_default(),
+                app_id: app.app_id,
+                name: app.name,
+                pkg_name: app.pkg_name,
+                developer_name: app.developer_name,
+                icon_url: app.icon_url,
+            })
+            .collect())
+    }
+
+    pub async fn get_recently_updated_apps(&self, limit: u32) -> Result<Vec<FullAppInfo>> {
+        self.top_full_infos(
+            "1",
+            "CAST(json_extract(data, '$.release_date') AS INTEGER) DESC",
+            limit,
+        )
+        .await
+    }
+
+    pub async fn get_top_developers(&self, limit: u32) -> Result<Vec<(String, String, i64)>> {
+        const QUERY: &str = r#"
+            SELECT dev_id, developer_name, COUNT(*) AS app_count
+            FROM app_info
+            GROUP BY dev_id, developer_name
+            ORDER BY app_count DESC
+            LIMIT ?
+        "#;
+
+        Ok(sqlx::query_as(QUERY)
+            .bind(limit.min(get_max_limit()) as i64)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// 星级分布: 无评分、1-2星、2-3星、3-4星、4-5星
+    pub async fn get_star_distribution(&self) -> Result<(i64, i64, i64, i64, i64)> {
+        const QUERY: &str = r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN r IS NULL OR r = 0.0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN r >= 1.0 AND r < 2.0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN r >= 2.0 AND r < 3.0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN r >= 3.0 AND r < 4.0 THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN r >= 4.0 AND r <= 5.0 THEN 1 ELSE 0 END), 0)
+            FROM (
+                SELECT CAST(json_extract(data, '$.average_rating') AS REAL) AS r
+                FROM app_full_info
+            )
+        "#;
+
+        Ok(sqlx::query_as(QUERY).fetch_one(&self.pool).await?)
+    }
+
+    /// 按 sdk 字段统计非元服务的应用数量
+    async fn count_sdk(&self, key: &str) -> Result<Vec<(i32, u32)>> {
+        let query = format!(
+            r#"
+            SELECT CAST(json_extract(data, '$.{key}') AS INTEGER) AS sdk, COUNT(*) AS app_count
+            FROM app_full_info
+            WHERE pkg_name NOT LIKE 'com.atomicservice%'
+            GROUP BY sdk
+            ORDER BY sdk
+            "#
+        );
+        let rows: Vec<(i64, i64)> = sqlx::query_as(&query).fetch_all(&self.pool).await?;
+        Ok(rows
+            .into_iter()
+            .map(|(sdk, count)| (sdk as i32, count as u32))
+            .collect())
+    }
+
+    pub async fn count_min_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        self.count_sdk("minsdk").await
+    }
+
+    pub async fn count_target_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        self.count_sdk("target_sdk").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_field() {
+        // 数值字段排序时转成数字, 搜索时按原值
+        assert_eq!(
+            json_field("price", true).unwrap(),
+            "CAST(json_extract(data, '$.price') AS REAL)"
+        );
+        assert_eq!(
+            json_field("price", false).unwrap(),
+            "json_extract(data, '$.price')"
+        );
+        assert_eq!(
+            json_field("name", true).unwrap(),
+            "json_extract(data, '$.name')"
+        );
+        // 不能拼进 SQL 的字段名直接拒绝
+        assert!(json_field("name') OR 1=1 --", false).is_err());
+        assert!(json_field("", false).is_err());
+    }
+}
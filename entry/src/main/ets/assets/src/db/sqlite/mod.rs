@@ -0,0 +1,519 @@
+//! SQLite 存储后端
+//!
+//! 用一个本地文件跑起完整的同步和 Web 服务, 不需要 Postgres.
+//! `database.url` 写成 `sqlite://data.db` 即可 (文件不存在时自动创建)
+//!
+//! 不支持的功能: 搜索 / 补全、变更记录、下载增量、组合过滤、指标降采样
+
+use std::{net::IpAddr, str::FromStr, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use tokio::sync::Mutex;
+use tracing::{Level, event};
+
+use crate::{
+    db::{
+        AppCounts, AppIconInfo, DbSearch, PageInfo,
+        api_key::{ApiKey, ApiScope, NewApiKey},
+        filter::AppFilter,
+        moderation::{
+            ModerationLogEntry, ModerationOutcome, ModerationRequest, NewReview, ReviewStatus,
+            SubmissionReview,
+        },
+        statistics::{
+            AccessLog, AccessLogRecord, IpHourlyStatistic, IpStatistic, UaHourlyStatistic,
+            UaStatistic,
+        },
+        storage::Storage,
+        submission::{NewSubmission, SubmissionJob},
+    },
+    model::{
+        AppMetric, AppQuery, FullAppInfo, FullSubstanceInfo, ShortAppRating, ShortSubstanceInfo,
+        raw::RawAppData,
+    },
+    sync::substance::SubstanceData,
+};
+
+mod api_key;
+mod app;
+mod moderation;
+mod statistics;
+mod submission;
+mod substance;
+
+/// 建表语句
+const SCHEMA_SQL: &str = include_str!("schema.sql");
+
+/// schema 版本, 记在 `PRAGMA user_version` 里
+const SCHEMA_VERSION: i64 = 6;
+
+/// 老版本升级时先于 [`SCHEMA_SQL`] 执行的语句, `(升级到的版本, 修改的表, SQL)`
+///
+/// 新表直接写进 schema.sql 即可, 这里只放给已有表加列这类 `IF NOT EXISTS` 做不到的变更.
+/// 修改的表还不存在时跳过, 之后由 schema.sql 直接建成最新结构
+const UPGRADES: &[(i64, &str, &str)] = &[
+    (2, "access_logs", "ALTER TABLE access_logs ADD COLUMN api_key_id INTEGER;"),
+    (
+        3,
+        "ip_hourly_statistics",
+        "ALTER TABLE ip_hourly_statistics ADD COLUMN throttled_count INTEGER NOT NULL DEFAULT 0;",
+    ),
+    (
+        6,
+        "submission_jobs",
+        "ALTER TABLE submission_jobs ADD COLUMN worker_id TEXT;
+         ALTER TABLE submission_jobs ADD COLUMN heartbeat_at TEXT;",
+    ),
+];
+
+/// SQLite 存储
+#[derive(Debug)]
+pub struct SqliteStorage {
+    pub pool: SqlitePool,
+    /// SQLite 同一时间只能有一个写事务, 写操作在这里排队,
+    /// 避免并发同步时事务升级写锁直接报 SQLITE_BUSY
+    write_lock: Mutex<()>,
+}
+
+impl SqliteStorage {
+    /// 是否是 SQLite 的连接串
+    pub fn is_sqlite_url(url: &str) -> bool {
+        url.starts_with("sqlite:")
+    }
+
+    /// 打开数据库文件, 不存在时创建
+    pub async fn new(database_url: &str, max_connect: u32) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true)
+            .busy_timeout(Duration::from_secs(30));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connect)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self {
+            pool,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    /// 建表 / 检查 schema 版本
+    pub async fn migrate(&self, auto_migrate: bool) -> Result<()> {
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await?;
+
+        if version > SCHEMA_VERSION {
+            bail!("SQLite 数据库 schema 版本 {version} 比程序支持的 {SCHEMA_VERSION} 新, 请升级程序");
+        }
+        if version < SCHEMA_VERSION {
+            if !auto_migrate {
+                bail!(
+                    "SQLite 数据库 schema 版本 {version}, 程序需要 {SCHEMA_VERSION}, 请开启 database.auto_migrate 或执行 migrate"
+                );
+            }
+            let _guard = self.write_lock.lock().await;
+            // 空库 (版本 0) 直接建最新的表
+            if version > 0 {
+                let pending = UPGRADES.iter().filter(|(target, ..)| *target > version);
+                for (target, table, sql) in pending {
+                    let exists: bool = sqlx::query_scalar(
+                        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+                    )
+                    .bind(table)
+                    .fetch_one(&self.pool)
+                    .await?;
+                    if !exists {
+                        continue;
+                    }
+                    sqlx::raw_sql(sql)
+                        .execute(&self.pool)
+                        .await
+                        .with_context(|| format!("SQLite schema 升级到版本 {target} 失败"))?;
+                }
+            }
+            sqlx::raw_sql(SCHEMA_SQL)
+                .execute(&self.pool)
+                .await
+                .with_context(|| "SQLite 建表失败")?;
+            sqlx::raw_sql(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))
+                .execute(&self.pool)
+                .await?;
+        }
+
+        event!(
+            Level::INFO,
+            "SQLite schema 版本 {} (程序需要 {SCHEMA_VERSION})",
+            version.max(SCHEMA_VERSION)
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn prepare(&self, auto_migrate: bool) -> Result<()> {
+        self.migrate(auto_migrate).await
+    }
+
+    async fn app_exists(&self, app: &AppQuery) -> bool {
+        SqliteStorage::app_exists(self, app).await
+    }
+
+    async fn save_app_data(
+        &self,
+        data: RawAppData,
+        listed_at: Option<DateTime<Local>>,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool, bool, FullAppInfo)> {
+        SqliteStorage::save_app_data(self, data, listed_at, comment).await
+    }
+
+    async fn get_full_app_info(&self, app: &AppQuery) -> Result<FullAppInfo> {
+        SqliteStorage::get_full_app_info(self, app).await
+    }
+
+    async fn get_app_icon(&self, app: &AppQuery) -> Option<AppIconInfo> {
+        SqliteStorage::get_app_icon(self, app).await
+    }
+
+    async fn get_all_pkg_names(&self) -> Result<Vec<String>> {
+        SqliteStorage::get_all_pkg_names(self).await
+    }
+
+    async fn get_app_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        sort_desc: bool,
+        search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+    ) -> Result<PageInfo<FullAppInfo>> {
+        if filter.is_some() {
+            bail!("SQLite 后端不支持组合过滤");
+        }
+        SqliteStorage::get_app_list_paged(
+            self,
+            page,
+            page_size,
+            sort_key,
+            sort_desc,
+            search,
+            exclude_huawei,
+            exclude_atomic,
+        )
+        .await
+    }
+
+    async fn count_apps(&self) -> Result<AppCounts> {
+        SqliteStorage::count_apps(self).await
+    }
+
+    async fn count_developers(&self) -> Result<i64> {
+        SqliteStorage::count_developers(self).await
+    }
+
+    async fn get_app_metrics_by_pkg_id(&self, pkg_id: &str) -> Result<Vec<AppMetric>> {
+        SqliteStorage::get_app_metrics_by_pkg_id(self, pkg_id).await
+    }
+
+    async fn get_top_rated_apps(&self, limit: u32) -> Result<Vec<ShortAppRating>> {
+        SqliteStorage::get_top_rated_apps(self, limit).await
+    }
+
+    async fn get_recently_updated_apps(&self, limit: u32) -> Result<Vec<FullAppInfo>> {
+        SqliteStorage::get_recently_updated_apps(self, limit).await
+    }
+
+    async fn get_top_developers(&self, limit: u32) -> Result<Vec<(String, String, i64)>> {
+        SqliteStorage::get_top_developers(self, limit).await
+    }
+
+    async fn get_star_distribution(&self) -> Result<(i64, i64, i64, i64, i64)> {
+        SqliteStorage::get_star_distribution(self).await
+    }
+
+    async fn count_min_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        SqliteStorage::count_min_sdk(self).await
+    }
+
+    async fn count_target_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        SqliteStorage::count_target_sdk(self).await
+    }
+
+    async fn save_substance(
+        &self,
+        substance: &SubstanceData,
+        raw_substance: &JsonValue,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool)> {
+        SqliteStorage::save_substance(self, substance, raw_substance, comment).await
+    }
+
+    async fn get_substance_by_id(&self, substance_id: &str) -> Result<Option<FullSubstanceInfo>> {
+        SqliteStorage::get_substance_by_id(self, substance_id).await
+    }
+
+    async fn get_all_substance_id(&self) -> Result<Vec<String>> {
+        SqliteStorage::get_all_substance_id(self).await
+    }
+
+    async fn count_substances(&self) -> Result<i64> {
+        SqliteStorage::count_substances(self).await
+    }
+
+    async fn get_substance_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        desc: bool,
+    ) -> Result<PageInfo<ShortSubstanceInfo>> {
+        SqliteStorage::get_substance_list_paged(self, page, page_size, sort_key, desc).await
+    }
+
+    async fn batch_insert_access_logs(&self, logs: &[AccessLog]) -> Result<u64> {
+        SqliteStorage::batch_insert_access_logs(self, logs).await
+    }
+
+    async fn batch_upsert_ua_statistics(
+        &self,
+        stats: &[(String, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        SqliteStorage::batch_upsert_ua_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ip_statistics(
+        &self,
+        stats: &[(IpAddr, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        SqliteStorage::batch_upsert_ip_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ua_hourly_statistics(
+        &self,
+        stats: &[(String, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        SqliteStorage::batch_upsert_ua_hourly_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ip_hourly_statistics(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        SqliteStorage::batch_upsert_ip_hourly_statistics(self, stats).await
+    }
+
+    async fn batch_add_ip_hourly_throttled(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        SqliteStorage::batch_add_ip_hourly_throttled(self, stats).await
+    }
+
+    async fn load_ua_statistics(&self) -> Result<Vec<UaStatistic>> {
+        SqliteStorage::load_ua_statistics(self).await
+    }
+
+    async fn load_ip_statistics(&self) -> Result<Vec<IpStatistic>> {
+        SqliteStorage::load_ip_statistics(self).await
+    }
+
+    async fn query_ua_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<UaStatistic>, i64)> {
+        SqliteStorage::query_ua_statistics(self, page, page_size).await
+    }
+
+    async fn query_ip_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<IpStatistic>, i64)> {
+        SqliteStorage::query_ip_statistics(self, page, page_size).await
+    }
+
+    async fn query_ua_hourly_statistics(
+        &self,
+        user_agent: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<UaHourlyStatistic>> {
+        SqliteStorage::query_ua_hourly_statistics(self, user_agent, start_time, end_time).await
+    }
+
+    async fn query_ip_hourly_statistics(
+        &self,
+        ip_address: IpAddr,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<IpHourlyStatistic>> {
+        SqliteStorage::query_ip_hourly_statistics(self, ip_address, start_time, end_time).await
+    }
+
+    async fn query_access_logs(
+        &self,
+        page: u32,
+        page_size: u32,
+        ip_filter: Option<IpAddr>,
+        ua_filter: Option<String>,
+        path_filter: Option<String>,
+        api_key_filter: Option<i64>,
+    ) -> Result<(Vec<AccessLogRecord>, i64)> {
+        SqliteStorage::query_access_logs(
+            self,
+            page,
+            page_size,
+            ip_filter,
+            ua_filter,
+            path_filter,
+            api_key_filter,
+        )
+        .await
+    }
+
+    async fn create_api_key(&self, name: &str, scopes: &[ApiScope]) -> Result<NewApiKey> {
+        SqliteStorage::create_api_key(self, name, scopes).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        SqliteStorage::list_api_keys(self).await
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> Result<bool> {
+        SqliteStorage::revoke_api_key(self, id).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        SqliteStorage::find_api_key(self, key_hash).await
+    }
+
+    async fn touch_api_keys(&self, usages: &[(i64, DateTime<Utc>)]) -> Result<u64> {
+        SqliteStorage::touch_api_keys(self, usages).await
+    }
+
+    async fn count_api_key_requests(&self) -> Result<Vec<(i64, i64)>> {
+        SqliteStorage::count_api_key_requests(self).await
+    }
+
+    async fn enqueue_submission(
+        &self,
+        submission: &NewSubmission,
+    ) -> Result<(SubmissionJob, bool)> {
+        SqliteStorage::enqueue_submission(self, submission).await
+    }
+
+    async fn get_submission_job(&self, id: i64) -> Result<Option<SubmissionJob>> {
+        SqliteStorage::get_submission_job(self, id).await
+    }
+
+    async fn claim_submission_job(&self, worker_id: &str) -> Result<Option<SubmissionJob>> {
+        SqliteStorage::claim_submission_job(self, worker_id).await
+    }
+
+    async fn heartbeat_submission_job(&self, id: i64, worker_id: &str) -> Result<bool> {
+        SqliteStorage::heartbeat_submission_job(self, id, worker_id).await
+    }
+
+    async fn finish_submission_job(
+        &self,
+        id: i64,
+        worker_id: &str,
+        result: Result<JsonValue, String>,
+    ) -> Result<bool> {
+        SqliteStorage::finish_submission_job(self, id, worker_id, result).await
+    }
+
+    async fn requeue_stale_submissions(&self, lease_seconds: u64) -> Result<u64> {
+        SqliteStorage::requeue_stale_submissions(self, lease_seconds).await
+    }
+
+    async fn create_submission_review(&self, review: &NewReview) -> Result<SubmissionReview> {
+        SqliteStorage::create_submission_review(self, review).await
+    }
+
+    async fn get_submission_review(&self, id: i64) -> Result<Option<SubmissionReview>> {
+        SqliteStorage::get_submission_review(self, id).await
+    }
+
+    async fn list_submission_reviews(
+        &self,
+        status: Option<ReviewStatus>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<SubmissionReview>, i64)> {
+        SqliteStorage::list_submission_reviews(self, status, page, page_size).await
+    }
+
+    async fn moderate_submission_review(
+        &self,
+        id: i64,
+        request: &ModerationRequest,
+    ) -> Result<ModerationOutcome> {
+        SqliteStorage::moderate_submission_review(self, id, request).await
+    }
+
+    async fn list_moderation_log(
+        &self,
+        review_id: Option<i64>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ModerationLogEntry>, i64)> {
+        SqliteStorage::list_moderation_log(self, review_id, page, page_size).await
+    }
+}
+
+/// 时间统一转成 UTC、固定精度的 RFC3339 文本, 保证按字符串排序就是按时间排序
+pub(crate) fn encode_time<Tz: TimeZone>(time: &DateTime<Tz>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// 解析 [`encode_time`] 写入的时间
+pub(crate) fn decode_time(text: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(text)
+        .with_context(|| format!("无法解析时间 {text}"))?
+        .with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Local};
+
+    #[test]
+    fn test_time_roundtrip_and_order() {
+        // 不同时区、不同小数位的时间编码后按字符串比较要和时间比较一致
+        let a = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2025, 1, 1, 8, 0, 0)
+            .unwrap();
+        let b = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+            + chrono::Duration::milliseconds(500);
+        let (ea, eb) = (encode_time(&a), encode_time(&b));
+        assert!(ea < eb);
+        assert_eq!(decode_time(&ea).unwrap(), a.with_timezone(&Utc));
+        assert_eq!(decode_time(&eb).unwrap(), b);
+
+        let now = Local::now();
+        assert_eq!(
+            decode_time(&encode_time(&now)).unwrap().timestamp_micros(),
+            now.timestamp_micros()
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! SQLite 后端: 投稿审核
+//!
+//! comment / old_value / new_value 存成 JSON 文本, IP 存成文本
+
+use anyhow::Result;
+use chrono::{Local, Utc};
+use serde_json::Value as JsonValue;
+use sqlx::{Row, sqlite::SqliteRow};
+
+use super::{SqliteStorage, app::apply_app_metadata, decode_time, encode_time, statistics::parse_ip};
+use crate::db::{
+    moderation::{
+        ModerationAction, ModerationLogEntry, ModerationOutcome, ModerationRequest, NewReview,
+        ReviewStatus, SubmissionReview,
+    },
+    submission::SubmissionKind,
+};
+
+const SELECT_REVIEW_FIELDS: &str = "id, job_id, kind, target, status, comment, listed_at, \
+     submitter_ip, submitter_ua, api_key_id, reviewed_by, review_note, created_at, reviewed_at";
+
+const SELECT_LOG_FIELDS: &str =
+    "id, review_id, action, actor_key_id, actor_ip, old_value, new_value, note, created_at";
+
+fn read_review(row: &SqliteRow) -> Result<SubmissionReview> {
+    let kind: String = row.get("kind");
+    let status: String = row.get("status");
+    let optional_time = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(|text| decode_time(&text))
+            .transpose()
+    };
+    Ok(SubmissionReview {
+        id: row.get("id"),
+        job_id: row.get("job_id"),
+        kind: SubmissionKind::from_name(&kind)
+            .ok_or_else(|| anyhow::anyhow!("未知的投稿类型: {kind}"))?,
+        target: row.get("target"),
+        status: ReviewStatus::from_name(&status)
+            .ok_or_else(|| anyhow::anyhow!("未知的审核状态: {status}"))?,
+        comment: row
+            .get::<Option<String>, _>("comment")
+            .and_then(|text| serde_json::from_str(&text).ok()),
+        listed_at: optional_time("listed_at")?,
+        submitter_ip: parse_ip(row.get("submitter_ip"))?,
+        submitter_ua: row.get("submitter_ua"),
+        api_key_id: row.get("api_key_id"),
+        reviewed_by: row.get("reviewed_by"),
+        review_note: row.get("review_note"),
+        created_at: decode_time(row.get("created_at"))?,
+        reviewed_at: optional_time("reviewed_at")?,
+    })
+}
+
+fn read_log(row: &SqliteRow) -> Result<ModerationLogEntry> {
+    let action: String = row.get("action");
+    Ok(ModerationLogEntry {
+        id: row.get("id"),
+        review_id: row.get("review_id"),
+        action: ModerationAction::from_name(&action)
+            .ok_or_else(|| anyhow::anyhow!("未知的审核操作: {action}"))?,
+        actor_key_id: row.get("actor_key_id"),
+        actor_ip: row
+            .get::<Option<&str>, _>("actor_ip")
+            .map(parse_ip)
+            .transpose()?,
+        old_value: serde_json::from_str(row.get("old_value"))?,
+        new_value: serde_json::from_str(row.get("new_value"))?,
+        note: row.get("note"),
+        created_at: decode_time(row.get("created_at"))?,
+    })
+}
+
+impl SqliteStorage {
+    pub async fn create_submission_review(&self, review: &NewReview) -> Result<SubmissionReview> {
+        let _guard = self.write_lock.lock().await;
+        let row = sqlx::query(&format!(
+            "INSERT INTO submission_reviews
+                 (job_id, kind, target, comment, listed_at, submitter_ip, submitter_ua,
+                  api_key_id, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             RETURNING {SELECT_REVIEW_FIELDS}"
+        ))
+        .bind(review.job_id)
+        .bind(review.kind.name())
+        .bind(&review.target)
+        .bind(review.metadata.comment.as_ref().map(JsonValue::to_string))
+        .bind(review.metadata.listed_at.map(|time| encode_time(&time)))
+        .bind(review.submitter_ip.to_string())
+        .bind(&review.submitter_ua)
+        .bind(review.api_key_id)
+        .bind(encode_time(&Utc::now()))
+        .fetch_one(&self.pool)
+        .await?;
+        read_review(&row)
+    }
+
+    pub async fn get_submission_review(&self, id: i64) -> Result<Option<SubmissionReview>> {
+        sqlx::query(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(read_review)
+        .transpose()
+    }
+
+    pub async fn list_submission_reviews(
+        &self,
+        status: Option<ReviewStatus>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<SubmissionReview>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let status = status.map(|status| status.name());
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM submission_reviews WHERE ? IS NULL OR status = ?",
+        )
+        .bind(status)
+        .bind(status)
+        .fetch_one(&self.pool)
+        .await?;
+        let reviews = sqlx::query(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews
+             WHERE ? IS NULL OR status = ?
+             ORDER BY id DESC LIMIT ? OFFSET ?"
+        ))
+        .bind(status)
+        .bind(status)
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_review)
+        .collect::<Result<_>>()?;
+        Ok((reviews, total))
+    }
+
+    /// 流程和 Postgres 版本一致, 写入应用元数据后由程序重建 app_full_info
+    pub async fn moderate_submission_review(
+        &self,
+        id: i64,
+        request: &ModerationRequest,
+    ) -> Result<ModerationOutcome> {
+        let _guard = self.write_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_REVIEW_FIELDS} FROM submission_reviews WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(review) = row.as_ref().map(read_review).transpose()? else {
+            return Ok(ModerationOutcome::NotFound);
+        };
+        if review.status != ReviewStatus::Pending {
+            return Ok(ModerationOutcome::NotPending(review));
+        }
+
+        let next = review.moderated(request, Utc::now());
+        if request.action == ModerationAction::Approve {
+            let applied = match next.kind {
+                SubmissionKind::App => {
+                    let query = next.app_query().ok_or_else(|| {
+                        anyhow::anyhow!("无法识别的应用投稿目标: {}", next.target)
+                    })?;
+                    let listed_at = next.listed_at.map(|time| time.with_timezone(&Local));
+                    apply_app_metadata(&mut tx, &query, next.comment.as_ref(), listed_at).await?
+                }
+                SubmissionKind::Substance => {
+                    let result = sqlx::query(
+                        "UPDATE substance_info SET comment = COALESCE(?, comment)
+                         WHERE substance_id = ?",
+                    )
+                    .bind(next.comment.as_ref().map(JsonValue::to_string))
+                    .bind(&next.target)
+                    .execute(&mut *tx)
+                    .await?;
+                    result.rows_affected() > 0
+                }
+            };
+            if !applied {
+                return Ok(ModerationOutcome::TargetMissing);
+            }
+        }
+
+        let row = sqlx::query(&format!(
+            "UPDATE submission_reviews
+             SET status = ?, comment = ?, listed_at = ?, reviewed_by = ?, review_note = ?,
+                 reviewed_at = ?
+             WHERE id = ?
+             RETURNING {SELECT_REVIEW_FIELDS}"
+        ))
+        .bind(next.status.name())
+        .bind(next.comment.as_ref().map(JsonValue::to_string))
+        .bind(next.listed_at.map(|time| encode_time(&time)))
+        .bind(next.reviewed_by)
+        .bind(&next.review_note)
+        .bind(next.reviewed_at.map(|time| encode_time(&time)))
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO moderation_log
+                 (review_id, action, actor_key_id, actor_ip, old_value, new_value, note, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(request.action.name())
+        .bind(request.actor_key_id)
+        .bind(request.actor_ip.map(|ip| ip.to_string()))
+        .bind(review.snapshot().to_string())
+        .bind(next.snapshot().to_string())
+        .bind(&request.note)
+        .bind(encode_time(&Utc::now()))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(ModerationOutcome::Done(read_review(&row)?))
+    }
+
+    pub async fn list_moderation_log(
+        &self,
+        review_id: Option<i64>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ModerationLogEntry>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM moderation_log WHERE ? IS NULL OR review_id = ?",
+        )
+        .bind(review_id)
+        .bind(review_id)
+        .fetch_one(&self.pool)
+        .await?;
+        let entries = sqlx::query(&format!(
+            "SELECT {SELECT_LOG_FIELDS} FROM moderation_log
+             WHERE ? IS NULL OR review_id = ?
+             ORDER BY id DESC LIMIT ? OFFSET ?"
+        ))
+        .bind(review_id)
+        .bind(review_id)
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_log)
+        .collect::<Result<_>>()?;
+        Ok((entries, total))
+    }
+}
@@ -0,0 +1,187 @@
+-- SQLite 单文件数据库 schema
+--
+-- 和 Postgres 版本不同, 各表只把查询要用的列拆出来, 完整记录按 JSON 存在 data 里,
+-- app_full_info 由程序在每次保存后重建, 没有触发器
+-- 时间一律存 UTC 的 RFC3339 文本, 可以直接按字符串比较
+
+CREATE TABLE IF NOT EXISTS app_info (
+    app_id TEXT PRIMARY KEY,
+    pkg_name TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    dev_id TEXT NOT NULL,
+    developer_name TEXT NOT NULL,
+    dev_en_name TEXT NOT NULL,
+    icon_url TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS app_metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app_id TEXT NOT NULL REFERENCES app_info(app_id),
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_app_metrics_app_id ON app_metrics(app_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS app_rating (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app_id TEXT NOT NULL REFERENCES app_info(app_id),
+    created_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_app_rating_app_id ON app_rating(app_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS app_record (
+    app_id TEXT PRIMARY KEY REFERENCES app_info(app_id),
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS app_data_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    app_id TEXT NOT NULL REFERENCES app_info(app_id),
+    raw_json_data TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_app_data_history_app_id ON app_data_history(app_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS app_full_info (
+    app_id TEXT PRIMARY KEY REFERENCES app_info(app_id),
+    pkg_name TEXT NOT NULL UNIQUE,
+    data TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS substance_info (
+    substance_id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    subtitle TEXT,
+    name TEXT,
+    comment TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS substance_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    substance_id TEXT NOT NULL REFERENCES substance_info(substance_id),
+    raw_json_substance TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_substance_history_substance_id ON substance_history(substance_id, id DESC);
+
+CREATE TABLE IF NOT EXISTS substance_app_map (
+    substance_id TEXT NOT NULL REFERENCES substance_info(substance_id),
+    app_id TEXT NOT NULL REFERENCES app_info(app_id),
+    PRIMARY KEY (substance_id, app_id)
+);
+
+CREATE TABLE IF NOT EXISTS ua_statistics (
+    user_agent TEXT PRIMARY KEY,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_ua_statistics_access_count ON ua_statistics(access_count DESC);
+
+CREATE TABLE IF NOT EXISTS ip_statistics (
+    ip_address TEXT PRIMARY KEY,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_ip_statistics_access_count ON ip_statistics(access_count DESC);
+
+CREATE TABLE IF NOT EXISTS ua_hourly_statistics (
+    user_agent TEXT NOT NULL,
+    hour_timestamp TEXT NOT NULL,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_agent, hour_timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS ip_hourly_statistics (
+    ip_address TEXT NOT NULL,
+    hour_timestamp TEXT NOT NULL,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    throttled_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (ip_address, hour_timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS access_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    user_agent TEXT NOT NULL,
+    request_method TEXT NOT NULL,
+    request_path TEXT NOT NULL,
+    api_key_id INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_access_logs_timestamp ON access_logs(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_access_logs_api_key_id ON access_logs(api_key_id, timestamp DESC)
+    WHERE api_key_id IS NOT NULL;
+
+-- scopes 用逗号分隔
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL,
+    last_used_at TEXT,
+    revoked_at TEXT
+);
+
+-- comment / result 为 JSON 文本
+CREATE TABLE IF NOT EXISTS submission_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    comment TEXT,
+    listed_at TEXT,
+    api_key_id INTEGER,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    result TEXT,
+    error TEXT,
+    created_at TEXT NOT NULL,
+    started_at TEXT,
+    finished_at TEXT,
+    worker_id TEXT,
+    heartbeat_at TEXT
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_submission_jobs_active
+    ON submission_jobs(kind, target) WHERE status IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS idx_submission_jobs_queued
+    ON submission_jobs(id) WHERE status = 'queued';
+
+-- comment / old_value / new_value 为 JSON 文本
+CREATE TABLE IF NOT EXISTS submission_reviews (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id INTEGER REFERENCES submission_jobs(id) ON DELETE SET NULL,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    comment TEXT,
+    listed_at TEXT,
+    submitter_ip TEXT NOT NULL,
+    submitter_ua TEXT,
+    api_key_id INTEGER,
+    reviewed_by INTEGER,
+    review_note TEXT,
+    created_at TEXT NOT NULL,
+    reviewed_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_status ON submission_reviews(status, id DESC);
+CREATE INDEX IF NOT EXISTS idx_submission_reviews_target ON submission_reviews(kind, target);
+
+CREATE TABLE IF NOT EXISTS moderation_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    review_id INTEGER NOT NULL REFERENCES submission_reviews(id),
+    action TEXT NOT NULL,
+    actor_key_id INTEGER,
+    actor_ip TEXT,
+    old_value TEXT NOT NULL,
+    new_value TEXT NOT NULL,
+    note TEXT,
+    created_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_moderation_log_review_id ON moderation_log(review_id, id);
@@ -0,0 +1,375 @@
+//! SQLite 后端: 访问统计
+//!
+//! IP 存成文本, 时间用 [`encode_time`] 编码
+
+use std::net::IpAddr;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use sqlx::{QueryBuilder, Row, Sqlite, sqlite::SqliteRow};
+
+use super::{SqliteStorage, decode_time, encode_time};
+use crate::db::statistics::{
+    AccessLog, AccessLogRecord, IpHourlyStatistic, IpStatistic, UaHourlyStatistic, UaStatistic,
+};
+
+/// 单条 INSERT 的最大行数, SQLite 对绑定参数数量有上限
+const BATCH_ROWS: usize = 1000;
+
+pub(super) fn parse_ip(text: &str) -> Result<IpAddr> {
+    text.parse()
+        .with_context(|| format!("无法解析 IP 地址 {text}"))
+}
+
+fn read_ua_statistic(row: &SqliteRow) -> Result<UaStatistic> {
+    Ok(UaStatistic {
+        user_agent: row.get("user_agent"),
+        access_count: row.get("access_count"),
+        first_seen_at: decode_time(row.get("first_seen_at"))?,
+        last_seen_at: decode_time(row.get("last_seen_at"))?,
+    })
+}
+
+fn read_ip_statistic(row: &SqliteRow) -> Result<IpStatistic> {
+    Ok(IpStatistic {
+        ip_address: parse_ip(row.get("ip_address"))?,
+        access_count: row.get("access_count"),
+        first_seen_at: decode_time(row.get("first_seen_at"))?,
+        last_seen_at: decode_time(row.get("last_seen_at"))?,
+    })
+}
+
+impl SqliteStorage {
+    /// 批量插入访问日志
+    pub async fn batch_insert_access_logs(&self, logs: &[AccessLog]) -> Result<u64> {
+        let _guard = self.write_lock.lock().await;
+        let mut affected = 0;
+        for chunk in logs.chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO access_logs (timestamp, ip_address, user_agent, request_method, request_path, api_key_id) ",
+            );
+            qb.push_values(chunk, |mut b, log| {
+                b.push_bind(encode_time(&log.timestamp))
+                    .push_bind(log.ip_address.to_string())
+                    .push_bind(&log.user_agent)
+                    .push_bind(&log.request_method)
+                    .push_bind(&log.request_path)
+                    .push_bind(log.api_key_id);
+            });
+            affected += qb.build().execute(&self.pool).await?.rows_affected();
+        }
+        Ok(affected)
+    }
+
+    /// 批量 UPSERT UA / IP 总体统计, 传入的是增量
+    async fn batch_upsert_statistics(
+        &self,
+        table: &str,
+        key_column: &str,
+        stats: &[(String, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        let _guard = self.write_lock.lock().await;
+        let mut affected = 0;
+        for chunk in stats.chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(format!(
+                "INSERT INTO {table} ({key_column}, access_count, first_seen_at, last_seen_at) "
+            ));
+            qb.push_values(chunk, |mut b, stat| {
+                b.push_bind(&stat.0)
+                    .push_bind(stat.1 as i64)
+                    .push_bind(encode_time(&stat.2))
+                    .push_bind(encode_time(&stat.3));
+            });
+            qb.push(format!(
+                " ON CONFLICT ({key_column}) DO UPDATE SET
+                    access_count = {table}.access_count + excluded.access_count,
+                    last_seen_at = MAX({table}.last_seen_at, excluded.last_seen_at)"
+            ));
+            affected += qb.build().execute(&self.pool).await?.rows_affected();
+        }
+        Ok(affected)
+    }
+
+    /// 批量 UPSERT UA / IP 每小时统计, 传入的是增量
+    async fn batch_upsert_hourly_statistics(
+        &self,
+        table: &str,
+        key_column: &str,
+        stats: &[(String, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        let _guard = self.write_lock.lock().await;
+        let mut affected = 0;
+        for chunk in stats.chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(format!(
+                "INSERT INTO {table} ({key_column}, hour_timestamp, access_count) "
+            ));
+            qb.push_values(chunk, |mut b, stat| {
+                b.push_bind(&stat.0)
+                    .push_bind(encode_time(&stat.1))
+                    .push_bind(stat.2 as i64);
+            });
+            qb.push(format!(
+                " ON CONFLICT ({key_column}, hour_timestamp) DO UPDATE SET
+                    access_count = {table}.access_count + excluded.access_count"
+            ));
+            affected += qb.build().execute(&self.pool).await?.rows_affected();
+        }
+        Ok(affected)
+    }
+
+    pub async fn batch_upsert_ua_statistics(
+        &self,
+        stats: &[(String, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        self.batch_upsert_statistics("ua_statistics", "user_agent", stats)
+            .await
+    }
+
+    pub async fn batch_upsert_ip_statistics(
+        &self,
+        stats: &[(IpAddr, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        let stats: Vec<_> = stats
+            .iter()
+            .map(|(ip, count, first, last)| (ip.to_string(), *count, *first, *last))
+            .collect();
+        self.batch_upsert_statistics("ip_statistics", "ip_address", &stats)
+            .await
+    }
+
+    pub async fn batch_upsert_ua_hourly_statistics(
+        &self,
+        stats: &[(String, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        self.batch_upsert_hourly_statistics("ua_hourly_statistics", "user_agent", stats)
+            .await
+    }
+
+    pub async fn batch_upsert_ip_hourly_statistics(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        let stats: Vec<_> = stats
+            .iter()
+            .map(|(ip, hour, count)| (ip.to_string(), *hour, *count))
+            .collect();
+        self.batch_upsert_hourly_statistics("ip_hourly_statistics", "ip_address", &stats)
+            .await
+    }
+
+    pub async fn batch_add_ip_hourly_throttled(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        if stats.is_empty() {
+            return Ok(0);
+        }
+        let _guard = self.write_lock.lock().await;
+        let mut affected = 0;
+        for chunk in stats.chunks(BATCH_ROWS) {
+            let mut qb = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO ip_hourly_statistics (ip_address, hour_timestamp, throttled_count) ",
+            );
+            qb.push_values(chunk, |mut b, stat| {
+                b.push_bind(stat.0.to_string())
+                    .push_bind(encode_time(&stat.1))
+                    .push_bind(stat.2 as i64);
+            });
+            qb.push(
+                " ON CONFLICT (ip_address, hour_timestamp) DO UPDATE SET
+                    throttled_count = ip_hourly_statistics.throttled_count + excluded.throttled_count",
+            );
+            affected += qb.build().execute(&self.pool).await?.rows_affected();
+        }
+        Ok(affected)
+    }
+
+    pub async fn load_ua_statistics(&self) -> Result<Vec<UaStatistic>> {
+        sqlx::query(
+            "SELECT user_agent, access_count, first_seen_at, last_seen_at
+            FROM ua_statistics ORDER BY access_count DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_ua_statistic)
+        .collect()
+    }
+
+    pub async fn load_ip_statistics(&self) -> Result<Vec<IpStatistic>> {
+        sqlx::query(
+            "SELECT ip_address, access_count, first_seen_at, last_seen_at
+            FROM ip_statistics ORDER BY access_count DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_ip_statistic)
+        .collect()
+    }
+
+    pub async fn query_ua_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<UaStatistic>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ua_statistics")
+            .fetch_one(&self.pool)
+            .await?;
+        let records = sqlx::query(
+            "SELECT user_agent, access_count, first_seen_at, last_seen_at
+            FROM ua_statistics ORDER BY access_count DESC LIMIT ? OFFSET ?",
+        )
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_ua_statistic)
+        .collect::<Result<_>>()?;
+        Ok((records, total))
+    }
+
+    pub async fn query_ip_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<IpStatistic>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ip_statistics")
+            .fetch_one(&self.pool)
+            .await?;
+        let records = sqlx::query(
+            "SELECT ip_address, access_count, first_seen_at, last_seen_at
+            FROM ip_statistics ORDER BY access_count DESC LIMIT ? OFFSET ?",
+        )
+        .bind(page_size as i64)
+        .bind(offset as i64)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(read_ip_statistic)
+        .collect::<Result<_>>()?;
+        Ok((records, total))
+    }
+
+    pub async fn query_ua_hourly_statistics(
+        &self,
+        user_agent: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<UaHourlyStatistic>> {
+        sqlx::query(
+            "SELECT user_agent, hour_timestamp, access_count FROM ua_hourly_statistics
+            WHERE user_agent = ? AND hour_timestamp BETWEEN ? AND ?
+            ORDER BY hour_timestamp ASC",
+        )
+        .bind(user_agent)
+        .bind(encode_time(&start_time))
+        .bind(encode_time(&end_time))
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(UaHourlyStatistic {
+                user_agent: row.get("user_agent"),
+                hour_timestamp: decode_time(row.get("hour_timestamp"))?,
+                access_count: row.get("access_count"),
+            })
+        })
+        .collect()
+    }
+
+    pub async fn query_ip_hourly_statistics(
+        &self,
+        ip_address: IpAddr,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<IpHourlyStatistic>> {
+        sqlx::query(
+            "SELECT ip_address, hour_timestamp, access_count, throttled_count
+            FROM ip_hourly_statistics
+            WHERE ip_address = ? AND hour_timestamp BETWEEN ? AND ?
+            ORDER BY hour_timestamp ASC",
+        )
+        .bind(ip_address.to_string())
+        .bind(encode_time(&start_time))
+        .bind(encode_time(&end_time))
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(IpHourlyStatistic {
+                ip_address: parse_ip(row.get("ip_address"))?,
+                hour_timestamp: decode_time(row.get("hour_timestamp"))?,
+                access_count: row.get("access_count"),
+                throttled_count: row.get("throttled_count"),
+            })
+        })
+        .collect()
+    }
+
+    /// 查询访问日志 (分页), 过滤条件和 Postgres 版本一致
+    pub async fn query_access_logs(
+        &self,
+        page: u32,
+        page_size: u32,
+        ip_filter: Option<IpAddr>,
+        ua_filter: Option<String>,
+        path_filter: Option<String>,
+        api_key_filter: Option<i64>,
+    ) -> Result<(Vec<AccessLogRecord>, i64)> {
+        let offset = page.saturating_sub(1) * page_size;
+
+        let push_where = |qb: &mut QueryBuilder<'_, Sqlite>| {
+            qb.push(" WHERE 1");
+            if let Some(ip) = ip_filter {
+                qb.push(" AND ip_address = ").push_bind(ip.to_string());
+            }
+            if let Some(ua) = ua_filter.as_ref() {
+                qb.push(" AND user_agent LIKE ").push_bind(format!("%{ua}%"));
+            }
+            if let Some(path) = path_filter.as_ref() {
+                qb.push(" AND request_path LIKE ")
+                    .push_bind(format!("%{path}%"));
+            }
+            if let Some(api_key_id) = api_key_filter {
+                qb.push(" AND api_key_id = ").push_bind(api_key_id);
+            }
+        };
+
+        let mut qb = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM access_logs");
+        push_where(&mut qb);
+        let total: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            "SELECT id, timestamp, ip_address, user_agent, request_method, request_path, api_key_id FROM access_logs",
+        );
+        push_where(&mut qb);
+        qb.push(" ORDER BY timestamp DESC LIMIT ")
+            .push_bind(page_size as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+
+        let records = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(AccessLogRecord {
+                    id: row.get("id"),
+                    timestamp: decode_time(row.get("timestamp"))?,
+                    ip_address: parse_ip(row.get("ip_address"))?,
+                    user_agent: row.get("user_agent"),
+                    request_method: row.get("request_method"),
+                    request_path: row.get("request_path"),
+                    api_key_id: row.get("api_key_id"),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok((records, total))
+    }
+}
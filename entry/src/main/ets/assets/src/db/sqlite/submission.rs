@@ -0,0 +1,173 @@
+//! SQLite 后端: 投稿队列
+//!
+//! comment / result 存成 JSON 文本
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+use sqlx::{Row, sqlite::SqliteRow};
+
+use super::{SqliteStorage, decode_time, encode_time};
+use crate::db::submission::{JobStatus, NewSubmission, SubmissionJob, SubmissionKind};
+
+const SELECT_JOB_FIELDS: &str = "id, kind, target, status, comment, listed_at, api_key_id, \
+     attempts, result, error, created_at, started_at, finished_at";
+
+fn read_job(row: &SqliteRow) -> Result<SubmissionJob> {
+    let kind: String = row.get("kind");
+    let status: String = row.get("status");
+    let optional_time = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .map(|text| decode_time(&text))
+            .transpose()
+    };
+    let optional_json = |column: &str| {
+        row.get::<Option<String>, _>(column)
+            .and_then(|text| serde_json::from_str::<JsonValue>(&text).ok())
+    };
+    Ok(SubmissionJob {
+        id: row.get("id"),
+        kind: SubmissionKind::from_name(&kind)
+            .ok_or_else(|| anyhow::anyhow!("未知的投稿类型: {kind}"))?,
+        target: row.get("target"),
+        status: JobStatus::from_name(&status)
+            .ok_or_else(|| anyhow::anyhow!("未知的任务状态: {status}"))?,
+        comment: optional_json("comment"),
+        listed_at: optional_time("listed_at")?,
+        api_key_id: row.get("api_key_id"),
+        attempts: row.get("attempts"),
+        result: optional_json("result"),
+        error: row.get("error"),
+        created_at: decode_time(row.get("created_at"))?,
+        started_at: optional_time("started_at")?,
+        finished_at: optional_time("finished_at")?,
+    })
+}
+
+impl SqliteStorage {
+    pub async fn enqueue_submission(
+        &self,
+        submission: &NewSubmission,
+    ) -> Result<(SubmissionJob, bool)> {
+        // 写锁内插入和查询, 不会有别的写入插进来
+        let _guard = self.write_lock.lock().await;
+        let inserted = sqlx::query(&format!(
+            "INSERT INTO submission_jobs (kind, target, comment, listed_at, api_key_id, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (kind, target) WHERE status IN ('queued', 'running') DO NOTHING
+             RETURNING {SELECT_JOB_FIELDS}"
+        ))
+        .bind(submission.kind.name())
+        .bind(&submission.target)
+        .bind(submission.comment.as_ref().map(JsonValue::to_string))
+        .bind(submission.listed_at.map(|time| encode_time(&time)))
+        .bind(submission.api_key_id)
+        .bind(encode_time(&Utc::now()))
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(row) = inserted {
+            return Ok((read_job(&row)?, true));
+        }
+
+        let existing = sqlx::query(&format!(
+            "SELECT {SELECT_JOB_FIELDS} FROM submission_jobs
+             WHERE kind = ? AND target = ? AND status IN ('queued', 'running')"
+        ))
+        .bind(submission.kind.name())
+        .bind(&submission.target)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((read_job(&existing)?, false))
+    }
+
+    pub async fn get_submission_job(&self, id: i64) -> Result<Option<SubmissionJob>> {
+        sqlx::query(&format!(
+            "SELECT {SELECT_JOB_FIELDS} FROM submission_jobs WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(read_job)
+        .transpose()
+    }
+
+    pub async fn claim_submission_job(&self, worker_id: &str) -> Result<Option<SubmissionJob>> {
+        let _guard = self.write_lock.lock().await;
+        let now = encode_time(&Utc::now());
+        sqlx::query(&format!(
+            "UPDATE submission_jobs
+             SET status = 'running', started_at = ?, heartbeat_at = ?, worker_id = ?,
+                 attempts = attempts + 1
+             WHERE id = (
+                 SELECT id FROM submission_jobs WHERE status = 'queued' ORDER BY id LIMIT 1
+             )
+             RETURNING {SELECT_JOB_FIELDS}"
+        ))
+        .bind(&now)
+        .bind(&now)
+        .bind(worker_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .as_ref()
+        .map(read_job)
+        .transpose()
+    }
+
+    pub async fn heartbeat_submission_job(&self, id: i64, worker_id: &str) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        let result = sqlx::query(
+            "UPDATE submission_jobs SET heartbeat_at = ?
+             WHERE id = ? AND status = 'running' AND worker_id = ?",
+        )
+        .bind(encode_time(&Utc::now()))
+        .bind(id)
+        .bind(worker_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn finish_submission_job(
+        &self,
+        id: i64,
+        worker_id: &str,
+        result: Result<JsonValue, String>,
+    ) -> Result<bool> {
+        let (status, result, error) = match result {
+            Ok(value) => (JobStatus::Succeeded, Some(value.to_string()), None),
+            Err(error) => (JobStatus::Failed, None, Some(error)),
+        };
+        let _guard = self.write_lock.lock().await;
+        let result = sqlx::query(
+            "UPDATE submission_jobs
+             SET status = ?, result = ?, error = ?, finished_at = ?
+             WHERE id = ? AND status = 'running' AND worker_id = ?",
+        )
+        .bind(status.name())
+        .bind(result)
+        .bind(error)
+        .bind(encode_time(&Utc::now()))
+        .bind(id)
+        .bind(worker_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 时间都是 [`encode_time`] 写入的同一格式, 可以直接按字符串比较
+    pub async fn requeue_stale_submissions(&self, lease_seconds: u64) -> Result<u64> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(lease_seconds as i64);
+        let _guard = self.write_lock.lock().await;
+        let result = sqlx::query(
+            "UPDATE submission_jobs
+             SET status = 'queued', started_at = NULL, heartbeat_at = NULL, worker_id = NULL
+             WHERE status = 'running'
+               AND COALESCE(heartbeat_at, started_at, created_at) < ?",
+        )
+        .bind(encode_time(&cutoff))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,224 @@
+//! SQLite 后端: 专题
+
+use anyhow::{Result, bail};
+use chrono::Local;
+use serde_json::Value as JsonValue;
+use sqlx::Row;
+
+use super::{SqliteStorage, decode_time, encode_time};
+use crate::{
+    db::{PageInfo, query::{get_max_limit, normalize_json_for_comparison}},
+    model::{AppQuery, FullSubstanceInfo, ShortAppInfo, ShortSubstanceInfo},
+    sync::substance::SubstanceData,
+};
+
+impl SqliteStorage {
+    /// 保存专题数据, 返回 (是否是新专题, 是否有变化)
+    pub async fn save_substance(
+        &self,
+        substance: &SubstanceData,
+        raw_substance: &JsonValue,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool)> {
+        let _guard = self.write_lock.lock().await;
+        let mut tx = self.pool.begin().await?;
+
+        let exists: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM substance_info WHERE substance_id = ?")
+                .bind(&substance.id)
+                .fetch_one(&mut *tx)
+                .await?;
+        let is_new = exists == 0;
+
+        let mut changed = sqlx::query(
+            r#"
+            INSERT INTO substance_info (substance_id, title, subtitle, name, comment, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (substance_id) DO UPDATE SET
+                title = excluded.title,
+                subtitle = excluded.subtitle,
+                name = excluded.name
+            WHERE substance_info.title IS NOT excluded.title
+                OR substance_info.subtitle IS NOT excluded.subtitle
+                OR substance_info.name IS NOT excluded.name
+            "#,
+        )
+        .bind(&substance.id)
+        .bind(&substance.title)
+        .bind(&substance.sub_title)
+        .bind(&substance.name)
+        .bind(
+            comment
+                .filter(|_| is_new)
+                .map(|c| c.to_string()),
+        )
+        .bind(encode_time(&Local::now()))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        // 只在数据发生变化时插入历史记录 (忽略 trace 信息)
+        let last: Option<String> = sqlx::query_scalar(
+            "SELECT raw_json_substance FROM substance_history WHERE substance_id = ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(&substance.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let same = last
+            .and_then(|last| serde_json::from_str::<JsonValue>(&last).ok())
+            .is_some_and(|last| {
+                normalize_json_for_comparison(&last) == normalize_json_for_comparison(raw_substance)
+            });
+        if !same {
+            sqlx::query(
+                "INSERT INTO substance_history (substance_id, raw_json_substance, created_at) VALUES (?, ?, ?)",
+            )
+            .bind(&substance.id)
+            .bind(raw_substance.to_string())
+            .bind(encode_time(&Local::now()))
+            .execute(&mut *tx)
+            .await?;
+            changed = true;
+        }
+
+        for app_query in &substance.data {
+            let app_id = match app_query {
+                AppQuery::AppId(app_id) => app_id.clone(),
+                AppQuery::PkgName(pkg_name) => {
+                    let app_id: Option<String> =
+                        sqlx::query_scalar("SELECT app_id FROM app_info WHERE pkg_name = ?")
+                            .bind(pkg_name)
+                            .fetch_optional(&mut *tx)
+                            .await?;
+                    let Some(app_id) = app_id else {
+                        bail!("专题 {} 中的应用 {pkg_name} 不存在", substance.id);
+                    };
+                    app_id
+                }
+            };
+            changed |= sqlx::query(
+                "INSERT INTO substance_app_map (substance_id, app_id) VALUES (?, ?)
+                ON CONFLICT (substance_id, app_id) DO NOTHING",
+            )
+            .bind(&substance.id)
+            .bind(app_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+                > 0;
+        }
+
+        tx.commit().await?;
+        Ok((is_new, changed))
+    }
+
+    /// 根据 substance_id 查询专题信息
+    pub async fn get_substance_by_id(&self, substance_id: &str) -> Result<Option<FullSubstanceInfo>> {
+        let row = sqlx::query(
+            "SELECT substance_id, title, subtitle, name, comment, created_at
+            FROM substance_info WHERE substance_id = ?",
+        )
+        .bind(substance_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let apps = sqlx::query(
+            r#"
+            SELECT ai.app_id, ai.name, ai.pkg_name, ai.icon_url, ai.created_at
+            FROM substance_app_map sam
+            INNER JOIN app_info ai ON sam.app_id = ai.app_id
+            WHERE sam.substance_id = ?
+            ORDER BY ai.app_id
+            "#,
+        )
+        .bind(substance_id)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|app| {
+            Ok(ShortAppInfo {
+                app_id: app.get("app_id"),
+                name: app.get("name"),
+                pkg_name: app.get("pkg_name"),
+                icon_url: app.get("icon_url"),
+                create_at: decode_time(app.get("created_at"))?.with_timezone(&Local),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+        let comment: Option<String> = row.get("comment");
+        Ok(Some(FullSubstanceInfo {
+            substance_id: row.get("substance_id"),
+            title: row.get("title"),
+            subtitle: row.get("subtitle"),
+            name: row.get("name"),
+            comment: comment.and_then(|c| serde_json::from_str(&c).ok()),
+            created_at: decode_time(row.get("created_at"))?.with_timezone(&Local),
+            apps,
+        }))
+    }
+
+    pub async fn get_all_substance_id(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar("SELECT substance_id FROM substance_info")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    pub async fn count_substances(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar("SELECT COUNT(*) FROM substance_info")
+            .fetch_one(&self.pool)
+            .await?)
+    }
+
+    /// 分页获取专题列表, 页码语义和 Postgres 版本一致 (从 0 开始)
+    pub async fn get_substance_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        desc: bool,
+    ) -> Result<PageInfo<ShortSubstanceInfo>> {
+        let safe_limit = page_size.min(get_max_limit());
+        let offset = page * safe_limit;
+        let sort_field = if ["created_at", "substance_id"].contains(&sort_key) {
+            sort_key
+        } else {
+            "created_at"
+        };
+        let order = if desc { "DESC" } else { "ASC" };
+
+        let query = format!(
+            "SELECT substance_id, title, subtitle, created_at FROM substance_info
+            ORDER BY {sort_field} {order} LIMIT ? OFFSET ?"
+        );
+        let data = sqlx::query(&query)
+            .bind(safe_limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(ShortSubstanceInfo {
+                    substance_id: row.get("substance_id"),
+                    title: row.get("title"),
+                    subtitle: row.get("subtitle"),
+                    created_at: decode_time(row.get("created_at"))?.with_timezone(&Local),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let total_count = self.count_substances().await? as u32;
+        Ok(PageInfo {
+            data,
+            total_count,
+            page,
+            page_size: safe_limit,
+            total_pages: total_count.div_ceil(safe_limit),
+        })
+    }
+}
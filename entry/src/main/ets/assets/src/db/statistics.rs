@@ -5,7 +5,7 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Row};
+use sqlx::FromRow;
 use std::net::IpAddr;
 
 use super::Database;
@@ -18,6 +18,8 @@ pub struct AccessLog {
     pub user_agent: String,
     pub request_method: String,
     pub request_path: String,
+    /// 请求使用的 API key, 匿名请求为 None
+    pub api_key_id: Option<i64>,
 }
 
 /// UA 统计记录（总体）
@@ -52,6 +54,8 @@ pub struct IpHourlyStatistic {
     pub ip_address: IpAddr,
     pub hour_timestamp: DateTime<Utc>,
     pub access_count: i64,
+    /// 其中被限流的请求数
+    pub throttled_count: i64,
 }
 
 /// 访问日志记录（带ID，从数据库查询）
@@ -63,6 +67,7 @@ pub struct AccessLogRecord {
     pub user_agent: String,
     pub request_method: String,
     pub request_path: String,
+    pub api_key_id: Option<i64>,
 }
 
 impl Database {
@@ -75,7 +80,7 @@ impl Database {
         }
 
         let mut query_builder = sqlx::QueryBuilder::new(
-            "INSERT INTO access_logs (timestamp, ip_address, user_agent, request_method, request_path) ",
+            "INSERT INTO access_logs (timestamp, ip_address, user_agent, request_method, request_path, api_key_id) ",
         );
 
         query_builder.push_values(logs, |mut b, log| {
@@ -83,7 +88,8 @@ impl Database {
                 .push_bind(log.ip_address)
                 .push_bind(&log.user_agent)
                 .push_bind(&log.request_method)
-                .push_bind(&log.request_path);
+                .push_bind(&log.request_path)
+                .push_bind(log.api_key_id);
         });
 
         let result = query_builder.build().execute(&self.pool).await?;
@@ -324,6 +330,37 @@ impl Database {
         Ok(result.rows_affected())
     }
 
+    /// 批量累加 IP 每小时被限流的请求数
+    ///
+    /// 行不存在时先插入 access_count 为 0 的行, access_count 由
+    /// [`Self::batch_upsert_ip_hourly_statistics`] 负责
+    pub async fn batch_add_ip_hourly_throttled(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        if stats.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder = sqlx::QueryBuilder::new(
+            r#"INSERT INTO ip_hourly_statistics (ip_address, hour_timestamp, throttled_count) "#,
+        );
+
+        query_builder.push_values(stats, |mut b, stat| {
+            b.push_bind(stat.0)
+                .push_bind(stat.1)
+                .push_bind(stat.2 as i64);
+        });
+
+        query_builder.push(
+            r#" ON CONFLICT (ip_address, hour_timestamp) DO UPDATE SET
+                throttled_count = ip_hourly_statistics.throttled_count + EXCLUDED.throttled_count"#,
+        );
+
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
     /// 从数据库加载 UA 统计数据
     pub async fn load_ua_statistics(&self) -> Result<Vec<UaStatistic>> {
         let records = sqlx::query_as::<_, UaStatistic>(
@@ -363,7 +400,7 @@ impl Database {
         let offset = (page.saturating_sub(1)) * page_size;
 
         let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM ua_statistics")
-            .fetch_one(&self.pool)
+            .fetch_one(self.read_pool())
             .await?;
 
         let records = sqlx::query_as::<_, UaStatistic>(
@@ -376,7 +413,7 @@ impl Database {
         )
         .bind(page_size as i64)
         .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool())
         .await?;
 
         Ok((records, total.0))
@@ -391,7 +428,7 @@ impl Database {
         let offset = (page.saturating_sub(1)) * page_size;
 
         let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM ip_statistics")
-            .fetch_one(&self.pool)
+            .fetch_one(self.read_pool())
             .await?;
 
         let records = sqlx::query_as::<_, IpStatistic>(
@@ -404,7 +441,7 @@ impl Database {
         )
         .bind(page_size as i64)
         .bind(offset as i64)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool())
         .await?;
 
         Ok((records, total.0))
@@ -428,7 +465,7 @@ impl Database {
         .bind(user_agent)
         .bind(start_time)
         .bind(end_time)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool())
         .await?;
 
         Ok(records)
@@ -443,7 +480,7 @@ impl Database {
     ) -> Result<Vec<IpHourlyStatistic>> {
         let records = sqlx::query_as::<_, IpHourlyStatistic>(
             r#"
-            SELECT ip_address, hour_timestamp, access_count
+            SELECT ip_address, hour_timestamp, access_count, throttled_count
             FROM ip_hourly_statistics
             WHERE ip_address = $1 AND hour_timestamp BETWEEN $2 AND $3
             ORDER BY hour_timestamp ASC
@@ -452,7 +489,7 @@ impl Database {
         .bind(ip_address)
         .bind(start_time)
         .bind(end_time)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool())
         .await?;
 
         Ok(records)
@@ -466,6 +503,7 @@ impl Database {
         ip_filter: Option<IpAddr>,
         ua_filter: Option<String>,
         path_filter: Option<String>,
+        api_key_filter: Option<i64>,
     ) -> Result<(Vec<AccessLogRecord>, i64)> {
         let offset = (page.saturating_sub(1)) * page_size;
 
@@ -483,6 +521,10 @@ impl Database {
         }
         if path_filter.is_some() {
             where_clauses.push(format!("request_path LIKE ${}", bind_index));
+            bind_index += 1;
+        }
+        if api_key_filter.is_some() {
+            where_clauses.push(format!("api_key_id = ${}", bind_index));
         }
 
         let where_clause = if where_clauses.is_empty() {
@@ -504,12 +546,15 @@ impl Database {
         if let Some(ref path) = path_filter {
             count_query_builder = count_query_builder.bind(format!("%{}%", path));
         }
+        if let Some(api_key_id) = api_key_filter {
+            count_query_builder = count_query_builder.bind(api_key_id);
+        }
 
-        let total: (i64,) = count_query_builder.fetch_one(&self.pool).await?;
+        let total: (i64,) = count_query_builder.fetch_one(self.read_pool()).await?;
 
         // 查询数据
         let select_query = format!(
-            "SELECT id, timestamp, ip_address, user_agent, request_method, request_path FROM access_logs {} ORDER BY timestamp DESC LIMIT $1 OFFSET $2",
+            "SELECT id, timestamp, ip_address, user_agent, request_method, request_path, api_key_id FROM access_logs {} ORDER BY timestamp DESC LIMIT $1 OFFSET $2",
             where_clause
         );
 
@@ -526,31 +571,12 @@ impl Database {
         if let Some(ref path) = path_filter {
             query_builder = query_builder.bind(format!("%{}%", path));
         }
+        if let Some(api_key_id) = api_key_filter {
+            query_builder = query_builder.bind(api_key_id);
+        }
 
-        let records = query_builder.fetch_all(&self.pool).await?;
+        let records = query_builder.fetch_all(self.read_pool()).await?;
 
         Ok((records, total.0))
     }
-
-    /// 清理旧的访问日志
-    pub async fn cleanup_old_access_logs(&self, days_to_keep: i32) -> Result<u64> {
-        let result = sqlx::query("SELECT cleanup_old_access_logs($1)")
-            .bind(days_to_keep)
-            .fetch_one(&self.pool)
-            .await?;
-
-        let deleted_count: i32 = result.try_get(0).unwrap_or(0);
-        Ok(deleted_count as u64)
-    }
-
-    /// 清理旧的每小时统计数据
-    pub async fn cleanup_old_hourly_statistics(&self, days_to_keep: i32) -> Result<u64> {
-        let result = sqlx::query("SELECT cleanup_old_hourly_statistics($1)")
-            .bind(days_to_keep)
-            .fetch_one(&self.pool)
-            .await?;
-
-        let deleted_count: i32 = result.try_get(0).unwrap_or(0);
-        Ok(deleted_count as u64)
-    }
 }
@@ -0,0 +1,656 @@
+//! 存储后端抽象
+//!
+//! 服务和同步只依赖 [`Storage`] trait, 目前有两个实现:
+//!
+//! - [`Database`]: Postgres, 完整功能
+//! - [`SqliteStorage`]: 单文件 SQLite, 覆盖应用 / 指标 / 评分 / 专题 / 访问统计,
+//!   方便本地开发和测试时不用起 Postgres
+//!
+//! 搜索、变更记录、下载增量、组合过滤、降采样这些依赖 Postgres 特性的功能
+//! 通过 [`Storage::as_postgres`] 拿到 [`Database`] 后单独调用
+
+use std::{net::IpAddr, sync::Arc};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, Utc};
+use serde_json::Value as JsonValue;
+use tracing::{Level, event};
+
+use crate::{
+    config::Config,
+    db::{
+        AppCounts, AppIconInfo, Database, DbSearch, PageInfo,
+        api_key::{ApiKey, ApiScope, NewApiKey},
+        batch::PendingApp,
+        filter::AppFilter,
+        moderation::{
+            ModerationLogEntry, ModerationOutcome, ModerationRequest, NewReview, ReviewStatus,
+            SubmissionReview,
+        },
+        sqlite::SqliteStorage,
+        statistics::{
+            AccessLog, AccessLogRecord, IpHourlyStatistic, IpStatistic, UaHourlyStatistic,
+            UaStatistic,
+        },
+        submission::{NewSubmission, SubmissionJob},
+    },
+    model::{
+        AppMetric, AppQuery, FullAppInfo, FullSubstanceInfo, ShortAppRating, ShortSubstanceInfo,
+        raw::RawAppData,
+    },
+    sync::substance::SubstanceData,
+};
+
+/// 共享的存储后端
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// 不支持的功能返回的提示
+pub const UNSUPPORTED_BACKEND: &str = "当前存储后端不支持该功能, 请使用 Postgres";
+
+/// 存储后端
+///
+/// 方法语义和 [`Database`] 上的同名方法一致
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// 后端名称, 用于日志
+    fn backend(&self) -> &'static str;
+
+    /// Postgres 后端返回自身, 用于调用 Postgres 专有的功能
+    fn as_postgres(&self) -> Option<&Database> {
+        None
+    }
+
+    /// 启动前准备 schema
+    ///
+    /// `auto_migrate` 为 false 时只检查, schema 不是最新就报错
+    async fn prepare(&self, auto_migrate: bool) -> Result<()>;
+
+    // ---- 应用 ----
+
+    async fn app_exists(&self, app: &AppQuery) -> bool;
+
+    /// 返回: (info_updated, metric_updated, rating_updated, full_app_info)
+    async fn save_app_data(
+        &self,
+        data: RawAppData,
+        listed_at: Option<DateTime<Local>>,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool, bool, FullAppInfo)>;
+
+    /// 批量保存, 返回值和输入一一对应
+    ///
+    /// 默认逐个调用 [`Storage::save_app_data`], Postgres 整批放在一个事务里
+    async fn save_app_data_batch(
+        &self,
+        apps: Vec<PendingApp>,
+    ) -> Result<Vec<(bool, bool, bool, FullAppInfo)>> {
+        let mut results = Vec::with_capacity(apps.len());
+        for app in apps {
+            results.push(self.save_app_data(app.data, app.listed_at, app.comment).await?);
+        }
+        Ok(results)
+    }
+
+    async fn get_full_app_info(&self, app: &AppQuery) -> Result<FullAppInfo>;
+
+    async fn get_app_icon(&self, app: &AppQuery) -> Option<AppIconInfo>;
+
+    async fn get_all_pkg_names(&self) -> Result<Vec<String>>;
+
+    /// 分页查询应用, `filter` 只有 Postgres 支持
+    #[allow(clippy::too_many_arguments)]
+    async fn get_app_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        sort_desc: bool,
+        search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+    ) -> Result<PageInfo<FullAppInfo>>;
+
+    async fn count_apps(&self) -> Result<AppCounts>;
+
+    async fn count_developers(&self) -> Result<i64>;
+
+    // ---- 指标 / 评分 / 排行 ----
+
+    async fn get_app_metrics_by_pkg_id(&self, pkg_id: &str) -> Result<Vec<AppMetric>>;
+
+    async fn get_top_rated_apps(&self, limit: u32) -> Result<Vec<ShortAppRating>>;
+
+    async fn get_recently_updated_apps(&self, limit: u32) -> Result<Vec<FullAppInfo>>;
+
+    async fn get_top_developers(&self, limit: u32) -> Result<Vec<(String, String, i64)>>;
+
+    async fn get_star_distribution(&self) -> Result<(i64, i64, i64, i64, i64)>;
+
+    async fn count_min_sdk(&self) -> Result<Vec<(i32, u32)>>;
+
+    async fn count_target_sdk(&self) -> Result<Vec<(i32, u32)>>;
+
+    // ---- 专题 ----
+
+    /// 返回 (是否是新专题, 专题信息/历史/应用映射是否有变化)
+    async fn save_substance(
+        &self,
+        substance: &SubstanceData,
+        raw_substance: &JsonValue,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool)>;
+
+    async fn get_substance_by_id(&self, substance_id: &str) -> Result<Option<FullSubstanceInfo>>;
+
+    async fn get_all_substance_id(&self) -> Result<Vec<String>>;
+
+    async fn count_substances(&self) -> Result<i64>;
+
+    async fn get_substance_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        desc: bool,
+    ) -> Result<PageInfo<ShortSubstanceInfo>>;
+
+    // ---- 访问统计 ----
+
+    async fn batch_insert_access_logs(&self, logs: &[AccessLog]) -> Result<u64>;
+
+    async fn batch_upsert_ua_statistics(
+        &self,
+        stats: &[(String, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64>;
+
+    async fn batch_upsert_ip_statistics(
+        &self,
+        stats: &[(IpAddr, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64>;
+
+    async fn batch_upsert_ua_hourly_statistics(
+        &self,
+        stats: &[(String, DateTime<Utc>, u64)],
+    ) -> Result<u64>;
+
+    async fn batch_upsert_ip_hourly_statistics(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64>;
+
+    /// 累加每小时被限流的请求数: `(ip, 整点, 增量)`
+    async fn batch_add_ip_hourly_throttled(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64>;
+
+    async fn load_ua_statistics(&self) -> Result<Vec<UaStatistic>>;
+
+    async fn load_ip_statistics(&self) -> Result<Vec<IpStatistic>>;
+
+    async fn query_ua_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<UaStatistic>, i64)>;
+
+    async fn query_ip_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<IpStatistic>, i64)>;
+
+    async fn query_ua_hourly_statistics(
+        &self,
+        user_agent: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<UaHourlyStatistic>>;
+
+    async fn query_ip_hourly_statistics(
+        &self,
+        ip_address: IpAddr,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<IpHourlyStatistic>>;
+
+    async fn query_access_logs(
+        &self,
+        page: u32,
+        page_size: u32,
+        ip_filter: Option<IpAddr>,
+        ua_filter: Option<String>,
+        path_filter: Option<String>,
+        api_key_filter: Option<i64>,
+    ) -> Result<(Vec<AccessLogRecord>, i64)>;
+
+    // ---- API key ----
+
+    async fn create_api_key(&self, name: &str, scopes: &[ApiScope]) -> Result<NewApiKey>;
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>>;
+
+    async fn revoke_api_key(&self, id: i64) -> Result<bool>;
+
+    /// 按明文的 sha256 查找未吊销的 key
+    async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>>;
+
+    async fn touch_api_keys(&self, usages: &[(i64, DateTime<Utc>)]) -> Result<u64>;
+
+    /// 每个 key 的请求数: `(key id, 访问日志记录数)`
+    async fn count_api_key_requests(&self) -> Result<Vec<(i64, i64)>>;
+
+    // ---- 投稿队列 ----
+
+    /// 入队, 返回 `(任务, 是否新建)`, 已有同一对象的未完成任务时返回那个任务
+    async fn enqueue_submission(&self, submission: &NewSubmission) -> Result<(SubmissionJob, bool)>;
+
+    async fn get_submission_job(&self, id: i64) -> Result<Option<SubmissionJob>>;
+
+    /// 取出最早的排队中任务并标记为 `worker_id` 执行中
+    async fn claim_submission_job(&self, worker_id: &str) -> Result<Option<SubmissionJob>>;
+
+    /// 续约执行中的任务, 任务已经不归 `worker_id` 时返回 false
+    async fn heartbeat_submission_job(&self, id: i64, worker_id: &str) -> Result<bool>;
+
+    /// 记录任务结果, 任务已经不归 `worker_id` 时不写入并返回 false
+    async fn finish_submission_job(
+        &self,
+        id: i64,
+        worker_id: &str,
+        result: Result<JsonValue, String>,
+    ) -> Result<bool>;
+
+    /// 把租约过期的执行中任务放回队列
+    async fn requeue_stale_submissions(&self, lease_seconds: u64) -> Result<u64>;
+    // ---- 投稿审核 ----
+
+    async fn create_submission_review(&self, review: &NewReview) -> Result<SubmissionReview>;
+
+    async fn get_submission_review(&self, id: i64) -> Result<Option<SubmissionReview>>;
+
+    /// 分页列出审核, 新的在前, 返回 `(当前页, 总数)`
+    async fn list_submission_reviews(
+        &self,
+        status: Option<ReviewStatus>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<SubmissionReview>, i64)>;
+
+    /// 修改 / 通过 / 驳回, 同时写入元数据 (通过时) 和审核记录
+    async fn moderate_submission_review(
+        &self,
+        id: i64,
+        request: &ModerationRequest,
+    ) -> Result<ModerationOutcome>;
+
+    async fn list_moderation_log(
+        &self,
+        review_id: Option<i64>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ModerationLogEntry>, i64)>;
+}
+
+/// 按 `database.url` 选择后端
+///
+/// `sqlite:` 开头的使用 SQLite, 其余按 Postgres 处理
+pub async fn open_storage(config: &Config) -> Result<SharedStorage> {
+    let url = config.database_url();
+    event!(Level::INFO, "connecting to db");
+    let storage: SharedStorage = if SqliteStorage::is_sqlite_url(url) {
+        Arc::new(
+            SqliteStorage::new(url, config.db_max_connect())
+                .await
+                .with_context(|| "无法打开 SQLite 数据库")?,
+        )
+    } else {
+        Arc::new(
+            Database::new(url, config.db_max_connect())
+                .await
+                .with_context(|| "无法连接数据库")?
+                .with_replicas(config.db_replica_urls(), config.db_max_connect())?,
+        )
+    };
+    event!(Level::INFO, "connected to db ({})", storage.backend());
+    Ok(storage)
+}
+
+#[async_trait]
+impl Storage for Database {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn as_postgres(&self) -> Option<&Database> {
+        Some(self)
+    }
+
+    async fn prepare(&self, auto_migrate: bool) -> Result<()> {
+        let schema = self.migrate(auto_migrate).await?;
+        event!(
+            Level::INFO,
+            "数据库 schema 版本 {:?} (程序需要 {})",
+            schema.current,
+            schema.latest
+        );
+        Ok(())
+    }
+
+    async fn app_exists(&self, app: &AppQuery) -> bool {
+        Database::app_exists(self, app).await
+    }
+
+    async fn save_app_data(
+        &self,
+        data: RawAppData,
+        listed_at: Option<DateTime<Local>>,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool, bool, FullAppInfo)> {
+        Database::save_app_data(self, data, listed_at, comment).await
+    }
+
+    async fn save_app_data_batch(
+        &self,
+        apps: Vec<PendingApp>,
+    ) -> Result<Vec<(bool, bool, bool, FullAppInfo)>> {
+        Database::save_app_data_batch(self, apps).await
+    }
+
+    async fn get_full_app_info(&self, app: &AppQuery) -> Result<FullAppInfo> {
+        Database::get_full_app_info(self, app).await
+    }
+
+    async fn get_app_icon(&self, app: &AppQuery) -> Option<AppIconInfo> {
+        Database::get_app_icon(self, app).await
+    }
+
+    async fn get_all_pkg_names(&self) -> Result<Vec<String>> {
+        Ok(Database::get_all_pkg_names(self).await?)
+    }
+
+    async fn get_app_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        sort_desc: bool,
+        search: Option<DbSearch>,
+        filter: Option<&AppFilter>,
+        exclude_huawei: bool,
+        exclude_atomic: bool,
+    ) -> Result<PageInfo<FullAppInfo>> {
+        self.get_app_info_paginated_enhanced::<FullAppInfo>(
+            page,
+            page_size,
+            sort_key,
+            sort_desc,
+            search,
+            filter,
+            exclude_huawei,
+            exclude_atomic,
+        )
+        .await
+    }
+
+    async fn count_apps(&self) -> Result<AppCounts> {
+        Database::count_apps(self).await
+    }
+
+    async fn count_developers(&self) -> Result<i64> {
+        Ok(Database::count_developers(self).await?)
+    }
+
+    async fn get_app_metrics_by_pkg_id(&self, pkg_id: &str) -> Result<Vec<AppMetric>> {
+        Database::get_app_metrics_by_pkg_id(self, pkg_id).await
+    }
+
+    async fn get_top_rated_apps(&self, limit: u32) -> Result<Vec<ShortAppRating>> {
+        Database::get_top_rated_apps(self, limit).await
+    }
+
+    async fn get_recently_updated_apps(&self, limit: u32) -> Result<Vec<FullAppInfo>> {
+        Database::get_recently_updated_apps(self, limit).await
+    }
+
+    async fn get_top_developers(&self, limit: u32) -> Result<Vec<(String, String, i64)>> {
+        Database::get_top_developers(self, limit).await
+    }
+
+    async fn get_star_distribution(&self) -> Result<(i64, i64, i64, i64, i64)> {
+        Ok(Database::get_star_distribution(self).await?)
+    }
+
+    async fn count_min_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        Database::count_min_sdk(self).await
+    }
+
+    async fn count_target_sdk(&self) -> Result<Vec<(i32, u32)>> {
+        Database::count_target_sdk(self).await
+    }
+
+    async fn save_substance(
+        &self,
+        substance: &SubstanceData,
+        raw_substance: &JsonValue,
+        comment: Option<JsonValue>,
+    ) -> Result<(bool, bool)> {
+        Database::save_substance(self, substance, raw_substance, comment).await
+    }
+
+    async fn get_substance_by_id(&self, substance_id: &str) -> Result<Option<FullSubstanceInfo>> {
+        Database::get_substance_by_id(self, substance_id).await
+    }
+
+    async fn get_all_substance_id(&self) -> Result<Vec<String>> {
+        Ok(Database::get_all_substance_id(self).await?)
+    }
+
+    async fn count_substances(&self) -> Result<i64> {
+        Ok(Database::count_substances(self).await?)
+    }
+
+    async fn get_substance_list_paged(
+        &self,
+        page: u32,
+        page_size: u32,
+        sort_key: &str,
+        desc: bool,
+    ) -> Result<PageInfo<ShortSubstanceInfo>> {
+        Database::get_substance_list_paged(self, page, page_size, sort_key, desc).await
+    }
+
+    async fn batch_insert_access_logs(&self, logs: &[AccessLog]) -> Result<u64> {
+        Database::batch_insert_access_logs(self, logs).await
+    }
+
+    async fn batch_upsert_ua_statistics(
+        &self,
+        stats: &[(String, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        Database::batch_upsert_ua_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ip_statistics(
+        &self,
+        stats: &[(IpAddr, u64, DateTime<Utc>, DateTime<Utc>)],
+    ) -> Result<u64> {
+        Database::batch_upsert_ip_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ua_hourly_statistics(
+        &self,
+        stats: &[(String, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        Database::batch_upsert_ua_hourly_statistics(self, stats).await
+    }
+
+    async fn batch_upsert_ip_hourly_statistics(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        Database::batch_upsert_ip_hourly_statistics(self, stats).await
+    }
+
+    async fn batch_add_ip_hourly_throttled(
+        &self,
+        stats: &[(IpAddr, DateTime<Utc>, u64)],
+    ) -> Result<u64> {
+        Database::batch_add_ip_hourly_throttled(self, stats).await
+    }
+
+    async fn load_ua_statistics(&self) -> Result<Vec<UaStatistic>> {
+        Database::load_ua_statistics(self).await
+    }
+
+    async fn load_ip_statistics(&self) -> Result<Vec<IpStatistic>> {
+        Database::load_ip_statistics(self).await
+    }
+
+    async fn query_ua_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<UaStatistic>, i64)> {
+        Database::query_ua_statistics(self, page, page_size).await
+    }
+
+    async fn query_ip_statistics(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<IpStatistic>, i64)> {
+        Database::query_ip_statistics(self, page, page_size).await
+    }
+
+    async fn query_ua_hourly_statistics(
+        &self,
+        user_agent: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<UaHourlyStatistic>> {
+        Database::query_ua_hourly_statistics(self, user_agent, start_time, end_time).await
+    }
+
+    async fn query_ip_hourly_statistics(
+        &self,
+        ip_address: IpAddr,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<IpHourlyStatistic>> {
+        Database::query_ip_hourly_statistics(self, ip_address, start_time, end_time).await
+    }
+
+    async fn query_access_logs(
+        &self,
+        page: u32,
+        page_size: u32,
+        ip_filter: Option<IpAddr>,
+        ua_filter: Option<String>,
+        path_filter: Option<String>,
+        api_key_filter: Option<i64>,
+    ) -> Result<(Vec<AccessLogRecord>, i64)> {
+        Database::query_access_logs(
+            self,
+            page,
+            page_size,
+            ip_filter,
+            ua_filter,
+            path_filter,
+            api_key_filter,
+        )
+        .await
+    }
+
+    async fn create_api_key(&self, name: &str, scopes: &[ApiScope]) -> Result<NewApiKey> {
+        Database::create_api_key(self, name, scopes).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        Database::list_api_keys(self).await
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> Result<bool> {
+        Database::revoke_api_key(self, id).await
+    }
+
+    async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        Database::find_api_key(self, key_hash).await
+    }
+
+    async fn touch_api_keys(&self, usages: &[(i64, DateTime<Utc>)]) -> Result<u64> {
+        Database::touch_api_keys(self, usages).await
+    }
+
+    async fn count_api_key_requests(&self) -> Result<Vec<(i64, i64)>> {
+        Database::count_api_key_requests(self).await
+    }
+
+    async fn enqueue_submission(
+        &self,
+        submission: &NewSubmission,
+    ) -> Result<(SubmissionJob, bool)> {
+        Database::enqueue_submission(self, submission).await
+    }
+
+    async fn get_submission_job(&self, id: i64) -> Result<Option<SubmissionJob>> {
+        Database::get_submission_job(self, id).await
+    }
+
+    async fn claim_submission_job(&self, worker_id: &str) -> Result<Option<SubmissionJob>> {
+        Database::claim_submission_job(self, worker_id).await
+    }
+
+    async fn heartbeat_submission_job(&self, id: i64, worker_id: &str) -> Result<bool> {
+        Database::heartbeat_submission_job(self, id, worker_id).await
+    }
+
+    async fn finish_submission_job(
+        &self,
+        id: i64,
+        worker_id: &str,
+        result: Result<JsonValue, String>,
+    ) -> Result<bool> {
+        Database::finish_submission_job(self, id, worker_id, result).await
+    }
+
+    async fn requeue_stale_submissions(&self, lease_seconds: u64) -> Result<u64> {
+        Database::requeue_stale_submissions(self, lease_seconds).await
+    }
+
+    async fn create_submission_review(&self, review: &NewReview) -> Result<SubmissionReview> {
+        Database::create_submission_review(self, review).await
+    }
+
+    async fn get_submission_review(&self, id: i64) -> Result<Option<SubmissionReview>> {
+        Database::get_submission_review(self, id).await
+    }
+
+    async fn list_submission_reviews(
+        &self,
+        status: Option<ReviewStatus>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<SubmissionReview>, i64)> {
+        Database::list_submission_reviews(self, status, page, page_size).await
+    }
+
+    async fn moderate_submission_review(
+        &self,
+        id: i64,
+        request: &ModerationRequest,
+    ) -> Result<ModerationOutcome> {
+        Database::moderate_submission_review(self, id, request).await
+    }
+
+    async fn list_moderation_log(
+        &self,
+        review_id: Option<i64>,
+        page: u32,
+        page_size: u32,
+    ) -> Result<(Vec<ModerationLogEntry>, i64)> {
+        Database::list_moderation_log(self, review_id, page, page_size).await
+    }
+}
@@ -0,0 +1,352 @@
+//! 投稿队列
+//!
+//! 投稿接口只负责入队, 由 [`crate::server::queue`] 里的 worker 取出来请求华为接口.
+//! 同一个应用 / 专题同时只会有一个排队中或执行中的任务 (部分唯一索引保证),
+//! 重复投稿直接返回已有的任务
+//!
+//! 执行中的任务记着取走它的 worker, worker 定时续约 (`heartbeat_at`);
+//! 只有租约过期的任务 (实例崩溃或被杀掉) 才会重新排队, 别的实例正在执行的任务不受影响
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::{FromRow, types::Json};
+use utoipa::ToSchema;
+
+use crate::{db::Database, model::AppQuery};
+
+/// 投稿类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionKind {
+    App,
+    Substance,
+}
+
+impl SubmissionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::App => "app",
+            Self::Substance => "substance",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::App, Self::Substance]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// 任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// 排队中
+    Queued,
+    /// 执行中
+    Running,
+    /// 已完成, 结果在 `result` 里
+    Succeeded,
+    /// 失败, 原因在 `error` 里
+    Failed,
+}
+
+impl JobStatus {
+    pub const ALL: [JobStatus; 4] = [Self::Queued, Self::Running, Self::Succeeded, Self::Failed];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.name() == name)
+    }
+}
+
+/// 投稿任务
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubmissionJob {
+    pub id: i64,
+    pub kind: SubmissionKind,
+    /// 应用为 `pkg_name:<包名>` 或 `app_id:<应用ID>`, 专题为专题 ID
+    pub target: String,
+    pub status: JobStatus,
+    /// 投稿时附带的备注, 审核通过后才会写入 (见 [`crate::db::moderation`])
+    pub comment: Option<JsonValue>,
+    /// 投稿时指定的上架时间, 同样需要审核
+    pub listed_at: Option<DateTime<Utc>>,
+    /// 投稿使用的 API key, 匿名投稿为 None
+    pub api_key_id: Option<i64>,
+    /// 执行次数 (租约过期的执行中任务会重新排队)
+    pub attempts: i32,
+    /// 执行结果, 应用为查询结果, 专题为专题信息和同步情况
+    pub result: Option<JsonValue>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl SubmissionJob {
+    /// 应用投稿对应的查询
+    pub fn app_query(&self) -> Option<AppQuery> {
+        app_target_query(self.kind, &self.target)
+    }
+}
+
+/// 把应用投稿的 `target` 解析回查询, 专题投稿返回 None
+pub(crate) fn app_target_query(kind: SubmissionKind, target: &str) -> Option<AppQuery> {
+    if kind != SubmissionKind::App {
+        return None;
+    }
+    match target.split_once(':')? {
+        ("pkg_name", pkg_name) => Some(AppQuery::pkg_name(pkg_name)),
+        ("app_id", app_id) => Some(AppQuery::app_id(app_id)),
+        _ => None,
+    }
+}
+
+/// 新投稿
+#[derive(Debug, Clone)]
+pub struct NewSubmission {
+    pub kind: SubmissionKind,
+    pub target: String,
+    pub comment: Option<JsonValue>,
+    pub listed_at: Option<DateTime<Utc>>,
+    pub api_key_id: Option<i64>,
+}
+
+impl NewSubmission {
+    pub fn app(query: &AppQuery) -> Self {
+        let value = match query {
+            AppQuery::PkgName(value) | AppQuery::AppId(value) => value,
+        };
+        Self::new(
+            SubmissionKind::App,
+            format!("{}:{value}", query.app_db_name()),
+        )
+    }
+
+    pub fn substance(substance_id: &str) -> Self {
+        Self::new(SubmissionKind::Substance, substance_id.to_string())
+    }
+
+    fn new(kind: SubmissionKind, target: String) -> Self {
+        Self {
+            kind,
+            target,
+            comment: None,
+            listed_at: None,
+            api_key_id: None,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct SubmissionJobRow {
+    id: i64,
+    kind: String,
+    target: String,
+    status: String,
+    comment: Option<Json<JsonValue>>,
+    listed_at: Option<DateTime<Utc>>,
+    api_key_id: Option<i64>,
+    attempts: i32,
+    result: Option<Json<JsonValue>>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<SubmissionJobRow> for SubmissionJob {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SubmissionJobRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            kind: SubmissionKind::from_name(&row.kind)
+                .ok_or_else(|| anyhow::anyhow!("未知的投稿类型: {}", row.kind))?,
+            target: row.target,
+            status: JobStatus::from_name(&row.status)
+                .ok_or_else(|| anyhow::anyhow!("未知的任务状态: {}", row.status))?,
+            comment: row.comment.map(|json| json.0),
+            listed_at: row.listed_at,
+            api_key_id: row.api_key_id,
+            attempts: row.attempts,
+            result: row.result.map(|json| json.0),
+            error: row.error,
+            created_at: row.created_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        })
+    }
+}
+
+const SELECT_JOB_FIELDS: &str = "id, kind, target, status, comment, listed_at, api_key_id, \
+     attempts, result, error, created_at, started_at, finished_at";
+
+impl Database {
+    /// 入队, 已有同一对象的未完成任务时返回那个任务
+    ///
+    /// 返回 `(任务, 是否新建)`
+    pub async fn enqueue_submission(
+        &self,
+        submission: &NewSubmission,
+    ) -> Result<(SubmissionJob, bool)> {
+        // 插入和查询之间已有任务刚好结束的话再试一次
+        for _ in 0..3 {
+            let inserted: Option<SubmissionJobRow> = sqlx::query_as(&format!(
+                "INSERT INTO submission_jobs (kind, target, comment, listed_at, api_key_id)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (kind, target) WHERE status IN ('queued', 'running') DO NOTHING
+                 RETURNING {SELECT_JOB_FIELDS}"
+            ))
+            .bind(submission.kind.name())
+            .bind(&submission.target)
+            .bind(submission.comment.as_ref().map(Json))
+            .bind(submission.listed_at)
+            .bind(submission.api_key_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(row) = inserted {
+                return Ok((row.try_into()?, true));
+            }
+
+            let existing: Option<SubmissionJobRow> = sqlx::query_as(&format!(
+                "SELECT {SELECT_JOB_FIELDS} FROM submission_jobs
+                 WHERE kind = $1 AND target = $2 AND status IN ('queued', 'running')"
+            ))
+            .bind(submission.kind.name())
+            .bind(&submission.target)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(row) = existing {
+                return Ok((row.try_into()?, false));
+            }
+        }
+        anyhow::bail!("投稿 {} 入队失败: 任务状态变化过快", submission.target)
+    }
+
+    /// 按 ID 查询任务, 走主库 (刚入队的任务马上就会被查询)
+    pub async fn get_submission_job(&self, id: i64) -> Result<Option<SubmissionJob>> {
+        let row: Option<SubmissionJobRow> = sqlx::query_as(&format!(
+            "SELECT {SELECT_JOB_FIELDS} FROM submission_jobs WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(SubmissionJob::try_from).transpose()
+    }
+
+    /// 取出最早的一个排队中任务, 标记为 `worker_id` 执行中, 多个实例同时取也不会重复
+    pub async fn claim_submission_job(&self, worker_id: &str) -> Result<Option<SubmissionJob>> {
+        let row: Option<SubmissionJobRow> = sqlx::query_as(&format!(
+            "UPDATE submission_jobs
+             SET status = 'running', started_at = NOW(), heartbeat_at = NOW(),
+                 worker_id = $1, attempts = attempts + 1
+             WHERE id = (
+                 SELECT id FROM submission_jobs WHERE status = 'queued'
+                 ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING {SELECT_JOB_FIELDS}"
+        ))
+        .bind(worker_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(SubmissionJob::try_from).transpose()
+    }
+
+    /// 续约, 任务已经不归 `worker_id` 时 (租约过期被重新排队) 返回 false
+    pub async fn heartbeat_submission_job(&self, id: i64, worker_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE submission_jobs SET heartbeat_at = NOW()
+             WHERE id = $1 AND status = 'running' AND worker_id = $2",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 记录任务结果, 任务已经不归 `worker_id` 时不写入并返回 false
+    pub async fn finish_submission_job(
+        &self,
+        id: i64,
+        worker_id: &str,
+        result: Result<JsonValue, String>,
+    ) -> Result<bool> {
+        let (status, result, error) = match result {
+            Ok(value) => (JobStatus::Succeeded, Some(value), None),
+            Err(error) => (JobStatus::Failed, None, Some(error)),
+        };
+        let result = sqlx::query(
+            "UPDATE submission_jobs
+             SET status = $3, result = $4, error = $5, finished_at = NOW()
+             WHERE id = $1 AND status = 'running' AND worker_id = $2",
+        )
+        .bind(id)
+        .bind(worker_id)
+        .bind(status.name())
+        .bind(result.map(Json))
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 把超过 `lease_seconds` 没有续约的执行中任务放回队列
+    ///
+    /// 执行它的实例已经退出或者卡住了, 正常执行中的任务会定时续约, 不会被放回
+    pub async fn requeue_stale_submissions(&self, lease_seconds: u64) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE submission_jobs
+             SET status = 'queued', started_at = NULL, heartbeat_at = NULL, worker_id = NULL
+             WHERE status = 'running'
+               AND COALESCE(heartbeat_at, started_at, created_at)
+                   < NOW() - make_interval(secs => $1)",
+        )
+        .bind(lease_seconds as f64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_target_round_trip() {
+        for query in [AppQuery::pkg_name("com.huawei.music"), AppQuery::app_id("C10084839")] {
+            let submission = NewSubmission::app(&query);
+            let job = SubmissionJob {
+                id: 1,
+                kind: submission.kind,
+                target: submission.target,
+                status: JobStatus::Queued,
+                comment: None,
+                listed_at: None,
+                api_key_id: None,
+                attempts: 0,
+                result: None,
+                error: None,
+                created_at: Utc::now(),
+                started_at: None,
+                finished_at: None,
+            };
+            assert_eq!(job.app_query(), Some(query));
+        }
+        assert_eq!(NewSubmission::app(&AppQuery::app_id("C1")).target, "app_id:C1");
+        assert!(NewSubmission::substance("abc").kind == SubmissionKind::Substance);
+    }
+}
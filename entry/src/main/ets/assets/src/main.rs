@@ -1,6 +1,7 @@
 use anyhow::Context;
 use tracing::{Level, event, info};
 
+pub mod cli;
 pub mod config;
 pub mod db;
 pub mod model;
@@ -21,7 +22,36 @@ fn main() -> anyhow::Result<()> {
 
 async fn async_main() -> anyhow::Result<()> {
     // 加载配置
-    let _config = config::Config::load().with_context(|| "无法加载配置文件")?;
+    let config = config::Config::load().with_context(|| "无法加载配置文件")?;
+
+    match cli::Command::from_args()? {
+        cli::Command::Serve => {}
+        cli::Command::Migrate { check } => return cli::run_migrate(config, check).await,
+        cli::Command::Consistency { repair } => return cli::run_consistency(config, repair).await,
+        cli::Command::Export { params, output } => {
+            return cli::run_export(config, params, output).await;
+        }
+        cli::Command::Import {
+            path,
+            dataset,
+            overwrite,
+            batch_size,
+        } => {
+            let options = db::import::ImportOptions {
+                batch_size: batch_size.unwrap_or_else(|| config.sync_batch_size()),
+                overwrite,
+            };
+            return cli::run_import(config, &path, dataset, options).await;
+        }
+        cli::Command::ApiKey(command) => return cli::run_apikey(config, command).await,
+        cli::Command::ChangelogBackfill { kind } => {
+            return cli::run_changelog_backfill(config, kind).await;
+        }
+        cli::Command::BlobCleanup => return cli::run_blob_cleanup(config).await,
+        cli::Command::BlobBackfill => return cli::run_blob_backfill(config).await,
+        cli::Command::SearchReindex => return cli::run_search_reindex(config).await,
+    }
+
     let (worker_send, worker_recv) = tokio::sync::oneshot::channel::<()>();
 
     let worker = tokio::spawn(server::worker(worker_recv));
@@ -0,0 +1,300 @@
+//! 华为接口里的代码字段
+//!
+//! `ctype`、`packingType` 之类的字段上游只给一个数字, 这里把认识的取值做成枚举并附上中文名.
+//! 序列化时仍然是原来的数字 (或字符串), 存库和 v0 接口的原有字段都不变,
+//! v0 单个应用查询另外带一个 `code_labels` 给出中文名;
+//! 不认识的取值落到 `Unknown`, 同步时会记下来 (见 `sync::unknown`)
+//!
+//! 取值含义来自抓到的数据, 没把握的宁可留给 `Unknown`
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::ToSchema;
+
+/// 上游有时给数字, 有时给数字字符串
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IntOrString {
+    Int(i64),
+    Str(String),
+}
+
+impl IntOrString {
+    fn into_i32(self) -> Option<i32> {
+        match self {
+            Self::Int(v) => i32::try_from(v).ok(),
+            Self::Str(s) => s.trim().parse().ok(),
+        }
+    }
+}
+
+/// 以字符串形式传输的整数 id (`kindId` / `kindTypeId`)
+///
+/// 解析不了按 0 处理 (和以前 `parse().unwrap_or(0)` 一致), 序列化时还原成字符串
+pub mod string_i32 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+        Ok(IntOrString::deserialize(deserializer)?
+            .into_i32()
+            .unwrap_or(0))
+    }
+}
+
+/// 定义一个整数代码枚举
+///
+/// 生成 `from_code` / `code` / `label` / `is_known`, 以及按原始数字收发的 serde 实现
+macro_rules! code_enum {
+    (
+        $(#[$meta:meta])*
+        $name:ident {
+            $($(#[$vmeta:meta])* $variant:ident = $code:literal => $label:literal,)+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)+
+            /// 还不认识的取值, 原样保留
+            Unknown(i32),
+        }
+
+        impl $name {
+            pub fn from_code(code: i32) -> Self {
+                match code {
+                    $($code => Self::$variant,)+
+                    other => Self::Unknown(other),
+                }
+            }
+
+            /// 上游的原始取值
+            pub fn code(self) -> i32 {
+                match self {
+                    $(Self::$variant => $code,)+
+                    Self::Unknown(code) => code,
+                }
+            }
+
+            /// 中文名, 不认识的取值为 "未知"
+            pub fn label(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $label,)+
+                    Self::Unknown(_) => "未知",
+                }
+            }
+
+            pub fn is_known(self) -> bool {
+                !matches!(self, Self::Unknown(_))
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(code: i32) -> Self {
+                Self::from_code(code)
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> Self {
+                value.code()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({})", self.label(), self.code())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_i32(self.code())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                IntOrString::deserialize(deserializer)?
+                    .into_i32()
+                    .map(Self::from_code)
+                    .ok_or_else(|| {
+                        serde::de::Error::custom(concat!(stringify!($name), " 不是整数"))
+                    })
+            }
+        }
+    };
+}
+
+code_enum! {
+    /// 客户端类型 (`ctype`)
+    CType {
+        /// 原子化服务
+        AtomicService = 2 => "原子化服务",
+        /// 普通应用
+        App = 17 => "应用",
+    }
+}
+
+code_enum! {
+    /// 应用等级 (`appLevel`)
+    AppLevel {
+        Level1 = 1 => "一级",
+        Level2 = 2 => "二级",
+        Level3 = 3 => "三级",
+    }
+}
+
+code_enum! {
+    /// 打包类型 (`packingType`)
+    PackingType {
+        /// 鸿蒙应用包
+        Hap = 4 => "HAP",
+    }
+}
+
+code_enum! {
+    /// 提交类型 (`submitType`)
+    SubmitType {
+        Normal = 0 => "普通提交",
+    }
+}
+
+code_enum! {
+    /// 付费安装类型 (`payInstallType`)
+    PayInstallType {
+        Free = 0 => "免费安装",
+        Paid = 1 => "付费安装",
+    }
+}
+
+/// 资费类型 (`tariffType`)
+///
+/// 上游直接给中文, 这里只是把认识的值固定下来
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TariffType {
+    Free,
+    Paid,
+    /// 还不认识的取值, 原样保留
+    Unknown(String),
+}
+
+impl TariffType {
+    pub fn from_raw(raw: &str) -> Self {
+        match raw {
+            "免费" => Self::Free,
+            "付费" => Self::Paid,
+            other => Self::Unknown(other.to_string()),
+        }
+    }
+
+    /// 上游的原始取值
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Free => "免费",
+            Self::Paid => "付费",
+            Self::Unknown(raw) => raw,
+        }
+    }
+
+    /// 中文名, 不认识的取值直接用原始字符串
+    pub fn label(&self) -> &str {
+        self.as_str()
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Unknown(_))
+    }
+}
+
+impl fmt::Display for TariffType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for TariffType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TariffType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_raw(&String::deserialize(deserializer)?))
+    }
+}
+
+/// 代码字段的中文名, 给接口展示用
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct CodeLabels {
+    pub ctype: String,
+    pub app_level: String,
+    pub tariff_type: String,
+    pub packing_type: String,
+    pub submit_type: String,
+    pub pay_install_type: String,
+}
+
+impl CodeLabels {
+    /// 从存库的原始取值生成
+    pub fn new(
+        ctype: i32,
+        app_level: i32,
+        tariff_type: &str,
+        packing_type: i32,
+        submit_type: i32,
+        pay_install_type: i32,
+    ) -> Self {
+        Self {
+            ctype: CType::from_code(ctype).label().to_string(),
+            app_level: AppLevel::from_code(app_level).label().to_string(),
+            tariff_type: TariffType::from_raw(tariff_type).label().to_string(),
+            packing_type: PackingType::from_code(packing_type).label().to_string(),
+            submit_type: SubmitType::from_code(submit_type).label().to_string(),
+            pay_install_type: PayInstallType::from_code(pay_install_type).label().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_enum_keeps_raw_value() {
+        let ctype: CType = serde_json::from_str("2").unwrap();
+        assert_eq!(ctype, CType::AtomicService);
+        assert_eq!(ctype.label(), "原子化服务");
+        assert_eq!(serde_json::to_string(&ctype).unwrap(), "2");
+
+        // 不认识的取值原样写回去
+        let unknown: PackingType = serde_json::from_str("\"9\"").unwrap();
+        assert_eq!(unknown, PackingType::Unknown(9));
+        assert_eq!(unknown.label(), "未知");
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), "9");
+
+        let tariff: TariffType = serde_json::from_str("\"限免\"").unwrap();
+        assert!(!tariff.is_known());
+        assert_eq!(serde_json::to_string(&tariff).unwrap(), "\"限免\"");
+    }
+
+    #[test]
+    fn string_i32_round_trip() {
+        #[derive(Deserialize, Serialize)]
+        struct Kind {
+            #[serde(with = "string_i32")]
+            id: i32,
+        }
+        let kind: Kind = serde_json::from_str(r#"{"id": "13"}"#).unwrap();
+        assert_eq!(kind.id, 13);
+        assert_eq!(serde_json::to_string(&kind).unwrap(), r#"{"id":"13"}"#);
+        // 以前 parse 失败按 0 处理, 这里保持一致
+        let kind: Kind = serde_json::from_str(r#"{"id": ""}"#).unwrap();
+        assert_eq!(kind.id, 0);
+    }
+}
@@ -1,5 +1,7 @@
+pub mod codes;
 pub mod query;
 pub mod raw;
+pub mod v1;
 
 use chrono::{DateTime, Local};
 use rust_decimal::Decimal;
@@ -134,6 +136,18 @@ pub struct FullAppInfo {
 }
 
 impl FullAppInfo {
+    /// 代码字段的中文名
+    pub fn code_labels(&self) -> codes::CodeLabels {
+        codes::CodeLabels::new(
+            self.ctype,
+            self.app_level,
+            &self.tariff_type,
+            self.packing_type,
+            self.submit_type,
+            self.pay_install_type,
+        )
+    }
+
     pub fn from_raw(raw: &RawAppData) -> Self {
         let value = &raw.app_info;
         let rating = raw.app_rating.as_ref();
@@ -147,26 +161,26 @@ impl FullAppInfo {
             developer_name: sanitize_utf8_string(&value.developer_name).into_owned(),
             dev_en_name: sanitize_utf8_string(&value.dev_en_name).into_owned(),
             supplier: sanitize_utf8_string(&value.supplier).into_owned(),
-            kind_id: value.kind_id.parse().unwrap_or(0),
+            kind_id: value.kind_id,
             kind_name: sanitize_utf8_string(&value.kind_name).into_owned(),
             tag_name: value
                 .tag_name
                 .as_ref()
                 .map(|s| sanitize_utf8_string(s).into_owned()),
-            kind_type_id: value.kind_type_id.parse().unwrap_or(0),
+            kind_type_id: value.kind_type_id,
             kind_type_name: sanitize_utf8_string(&value.kind_type_name).into_owned(),
             icon_url: sanitize_utf8_string(&value.icon_url).into_owned(),
             brief_desc: sanitize_utf8_string(&value.brief_desc).into_owned(),
             description: sanitize_utf8_string(&value.description).into_owned(),
             privacy_url: sanitize_utf8_string(&value.privacy_url).into_owned(),
-            ctype: value.ctype,
+            ctype: value.ctype.code(),
             detail_id: sanitize_utf8_string(&value.detail_id).into_owned(),
-            app_level: value.app_level,
+            app_level: value.app_level.code(),
             jocat_id: value.jocat_id,
             iap: value.iap != 0,
             hms: value.hms != 0,
-            tariff_type: sanitize_utf8_string(&value.tariff_type).into_owned(),
-            packing_type: value.packing_type,
+            tariff_type: sanitize_utf8_string(value.tariff_type.as_str()).into_owned(),
+            packing_type: value.packing_type.code(),
             order_app: value.order_app,
             denpend_gms: value.denpend_gms != 0,
             denpend_hms: value.denpend_hms != 0,
@@ -175,13 +189,13 @@ impl FullAppInfo {
             is_pay: value.is_pay == "1",
             is_disciplined: value.is_disciplined != 0,
             is_shelves: value.is_shelves != 0,
-            submit_type: value.submit_type,
+            submit_type: value.submit_type.code(),
             delete_archive: value.delete_archive != 0,
             charging: value.charging != 0,
             button_grey: value.button_grey != 0,
             app_gift: value.app_gift != 0,
             free_days: value.free_days,
-            pay_install_type: value.pay_install_type,
+            pay_install_type: value.pay_install_type.code(),
             created_at: Local::now(),
             listed_at: Local::now(),
             comment: None,
@@ -294,26 +308,26 @@ impl AppInfo {
             developer_name: sanitize_utf8_string(&value.developer_name).into_owned(),
             dev_en_name: sanitize_utf8_string(&value.dev_en_name).into_owned(),
             supplier: sanitize_utf8_string(&value.supplier).into_owned(),
-            kind_id: value.kind_id.parse().unwrap_or(0),
+            kind_id: value.kind_id,
             kind_name: sanitize_utf8_string(&value.kind_name).into_owned(),
             tag_name: value
                 .tag_name
                 .as_ref()
                 .map(|s| sanitize_utf8_string(s).into_owned()),
-            kind_type_id: value.kind_type_id.parse().unwrap_or(0),
+            kind_type_id: value.kind_type_id,
             kind_type_name: sanitize_utf8_string(&value.kind_type_name).into_owned(),
             icon_url: sanitize_utf8_string(&value.icon_url).into_owned(),
             brief_desc: sanitize_utf8_string(&value.brief_desc).into_owned(),
             description: sanitize_utf8_string(&value.description).into_owned(),
             privacy_url: sanitize_utf8_string(&value.privacy_url).into_owned(),
-            ctype: value.ctype,
+            ctype: value.ctype.code(),
             detail_id: sanitize_utf8_string(&value.detail_id).into_owned(),
-            app_level: value.app_level,
+            app_level: value.app_level.code(),
             jocat_id: value.jocat_id,
             iap: value.iap != 0,
             hms: value.hms != 0,
-            tariff_type: sanitize_utf8_string(&value.tariff_type).into_owned(),
-            packing_type: value.packing_type,
+            tariff_type: sanitize_utf8_string(value.tariff_type.as_str()).into_owned(),
+            packing_type: value.packing_type.code(),
             order_app: value.order_app,
             denpend_gms: value.denpend_gms != 0,
             denpend_hms: value.denpend_hms != 0,
@@ -322,13 +336,13 @@ impl AppInfo {
             is_pay: value.is_pay == "1",
             is_disciplined: value.is_disciplined != 0,
             is_shelves: value.is_shelves != 0,
-            submit_type: value.submit_type,
+            submit_type: value.submit_type.code(),
             delete_archive: value.delete_archive != 0,
             charging: value.charging != 0,
             button_grey: value.button_grey != 0,
             app_gift: value.app_gift != 0,
             free_days: value.free_days,
-            pay_install_type: value.pay_install_type,
+            pay_install_type: value.pay_install_type.code(),
             created_at: Local::now(),
             listed_at: Local::now(),
             comment: None,
@@ -392,26 +406,26 @@ impl From<&RawJsonData> for AppInfo {
             developer_name: sanitize_utf8_string(&raw_data.developer_name).into_owned(),
             dev_en_name: sanitize_utf8_string(&raw_data.dev_en_name).into_owned(),
             supplier: sanitize_utf8_string(&raw_data.supplier).into_owned(),
-            kind_id: raw_data.kind_id.parse().unwrap_or(0),
+            kind_id: raw_data.kind_id,
             kind_name: sanitize_utf8_string(&raw_data.kind_name).into_owned(),
             tag_name: raw_data
                 .tag_name
                 .as_ref()
                 .map(|s| sanitize_utf8_string(s).into_owned()),
-            kind_type_id: raw_data.kind_type_id.parse().unwrap_or(0),
+            kind_type_id: raw_data.kind_type_id,
             kind_type_name: sanitize_utf8_string(&raw_data.kind_type_name).into_owned(),
             icon_url: sanitize_utf8_string(&raw_data.icon_url).into_owned(),
             brief_desc: sanitize_utf8_string(&raw_data.brief_desc).into_owned(),
             description: sanitize_utf8_string(&raw_data.description).into_owned(),
             privacy_url: sanitize_utf8_string(&raw_data.privacy_url).into_owned(),
-            ctype: raw_data.ctype,
+            ctype: raw_data.ctype.code(),
             detail_id: sanitize_utf8_string(&raw_data.detail_id).into_owned(),
-            app_level: raw_data.app_level,
+            app_level: raw_data.app_level.code(),
             jocat_id: raw_data.jocat_id,
             iap: raw_data.iap != 0,
             hms: raw_data.hms != 0,
-            tariff_type: sanitize_utf8_string(&raw_data.tariff_type).into_owned(),
-            packing_type: raw_data.packing_type,
+            tariff_type: sanitize_utf8_string(raw_data.tariff_type.as_str()).into_owned(),
+            packing_type: raw_data.packing_type.code(),
             order_app: raw_data.order_app,
             denpend_gms: raw_data.denpend_gms != 0,
             denpend_hms: raw_data.denpend_hms != 0,
@@ -420,13 +434,13 @@ impl From<&RawJsonData> for AppInfo {
             is_pay: raw_data.is_pay.parse::<i32>().unwrap_or(0) != 0,
             is_disciplined: raw_data.is_disciplined != 0,
             is_shelves: raw_data.is_shelves != 0,
-            submit_type: raw_data.submit_type,
+            submit_type: raw_data.submit_type.code(),
             delete_archive: raw_data.delete_archive != 0,
             charging: raw_data.charging != 0,
             button_grey: raw_data.button_grey != 0,
             app_gift: raw_data.app_gift != 0,
             free_days: raw_data.free_days,
-            pay_install_type: raw_data.pay_install_type,
+            pay_install_type: raw_data.pay_install_type.code(),
             created_at: Local::now(),
             listed_at: Local::now(),
             comment: None,
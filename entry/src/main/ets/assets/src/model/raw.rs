@@ -1,11 +1,19 @@
+use std::collections::BTreeMap;
+
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
-use crate::model::AppQuery;
+use crate::model::{
+    AppQuery,
+    codes::{AppLevel, CType, PackingType, PayInstallType, SubmitType, TariffType, string_i32},
+};
+
+/// 模型里没有声明的上游字段, 原样收集起来, 同步时会记日志
+pub type ExtraFields = BTreeMap<String, JsonValue>;
 
 /// 从 api 上获取到的数据合集
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RawAppData {
     pub app_info: RawJsonData,
     pub app_info_json: JsonValue,
@@ -70,10 +78,28 @@ impl RawAppData {
     pub fn pkg_name(&self) -> String {
         self.app_info.pkg_name.clone()
     }
+
+    /// 所有没声明的字段, 带上来源前缀 (`info.` / `rating.` / `record.`)
+    pub fn unknown_fields(&self) -> Vec<(String, &JsonValue)> {
+        let prefixed = |prefix: &str, extra: &'_ ExtraFields| {
+            extra
+                .iter()
+                .map(|(key, value)| (format!("{prefix}.{key}"), value))
+                .collect::<Vec<_>>()
+        };
+        let mut fields = prefixed("info", &self.app_info.extra);
+        if let Some(rating) = &self.app_rating {
+            fields.extend(prefixed("rating", &rating.extra));
+        }
+        if let Some(record) = &self.app_record {
+            fields.extend(prefixed("record", &record.extra));
+        }
+        fields
+    }
 }
 
 /// 评分数据
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RawRatingData {
     #[serde(rename = "averageRating")]
     pub average_rating: String,
@@ -97,6 +123,9 @@ pub struct RawRatingData {
     pub full_average_rating: String,
     #[serde(rename = "sourceType")]
     pub source_type: String,
+    /// 没声明的字段
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// 备案数据
@@ -110,7 +139,7 @@ pub struct RawRatingData {
 ///     "recordalEntityName": "苏交科集xxxx"
 /// }
 /// ```
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RawRecordalInfo {
     /// 标题，例如 "服务备案号"
     #[serde(rename = "title")]
@@ -124,6 +153,9 @@ pub struct RawRecordalInfo {
     /// 主办单位名称
     #[serde(rename = "recordalEntityName")]
     pub recordal_entity_name: String,
+    /// 没声明的字段
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 fn hot_default() -> String {
@@ -147,7 +179,7 @@ fn main_device_codes_default() -> Vec<String> {
 }
 
 /// 1. 原始 JSON 数据直接映射
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RawJsonData {
     #[serde(rename = "appId")]
     pub app_id: String,
@@ -164,14 +196,16 @@ pub struct RawJsonData {
     pub dev_en_name: String,
     #[serde(rename = "supplier")]
     pub supplier: String,
-    #[serde(rename = "kindId")]
-    pub kind_id: String,
+    /// 上游是字符串, 解析失败按 0 处理
+    #[serde(rename = "kindId", with = "string_i32")]
+    pub kind_id: i32,
     #[serde(rename = "kindName")]
     pub kind_name: String,
     #[serde(rename = "tagName")]
     pub tag_name: Option<String>,
-    #[serde(rename = "kindTypeId")]
-    pub kind_type_id: String,
+    /// 上游是字符串, 解析失败按 0 处理
+    #[serde(rename = "kindTypeId", with = "string_i32")]
+    pub kind_type_id: i32,
     #[serde(rename = "kindTypeName")]
     pub kind_type_name: String,
     #[serde(rename = "icon")]
@@ -183,19 +217,19 @@ pub struct RawJsonData {
     #[serde(rename = "privacyUrl")]
     pub privacy_url: String,
     #[serde(rename = "ctype")]
-    pub ctype: i32,
+    pub ctype: CType,
     #[serde(rename = "detailId")]
     pub detail_id: String,
     #[serde(rename = "appLevel")]
-    pub app_level: i32,
+    pub app_level: AppLevel,
     #[serde(rename = "jocatId")]
     pub jocat_id: i32,
     pub iap: i32,
     pub hms: i32,
     #[serde(rename = "tariffType")]
-    pub tariff_type: String,
+    pub tariff_type: TariffType,
     #[serde(rename = "packingType")]
-    pub packing_type: i32,
+    pub packing_type: PackingType,
     #[serde(rename = "orderApp", default)]
     pub order_app: bool,
     #[serde(rename = "denpendGms", default)]
@@ -213,7 +247,7 @@ pub struct RawJsonData {
     #[serde(rename = "isShelves")]
     pub is_shelves: i32,
     #[serde(rename = "submitType")]
-    pub submit_type: i32,
+    pub submit_type: SubmitType,
     #[serde(rename = "deleteArchive")]
     pub delete_archive: i32,
     #[serde(rename = "charging")]
@@ -225,7 +259,7 @@ pub struct RawJsonData {
     #[serde(rename = "freeDays")]
     pub free_days: i32,
     #[serde(rename = "payInstallType")]
-    pub pay_install_type: i32,
+    pub pay_install_type: PayInstallType,
     #[serde(rename = "version")]
     pub version: String,
     #[serde(rename = "versionCode")]
@@ -278,4 +312,30 @@ pub struct RawJsonData {
     /// 发布的国家
     #[serde(rename = "releaseCountries")]
     pub release_countries: Vec<String>,
+    /// 没声明的字段
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+impl RawJsonData {
+    /// 不认识的代码取值, (字段名, 原始值)
+    pub fn unknown_codes(&self) -> Vec<(&'static str, String)> {
+        let mut codes = Vec::new();
+        let mut check = |field: &'static str, known: bool, raw: String| {
+            if !known {
+                codes.push((field, raw));
+            }
+        };
+        check("ctype", self.ctype.is_known(), self.ctype.code().to_string());
+        check("appLevel", self.app_level.is_known(), self.app_level.code().to_string());
+        check("tariffType", self.tariff_type.is_known(), self.tariff_type.to_string());
+        check("packingType", self.packing_type.is_known(), self.packing_type.code().to_string());
+        check("submitType", self.submit_type.is_known(), self.submit_type.code().to_string());
+        check(
+            "payInstallType",
+            self.pay_install_type.is_known(),
+            self.pay_install_type.code().to_string(),
+        );
+        codes
+    }
 }
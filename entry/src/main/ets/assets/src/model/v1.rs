@@ -0,0 +1,418 @@
+//! `/api/v1` 的返回结构
+//!
+//! v0 直接把 `app_full_info` 的行序列化出去, 表结构一改 (包括 `denpend_gms` 这种拼写) 接口就跟着变.
+//! v1 单独定义一套结构, 按开发者 / 版本 / 指标 / 评分 / 备案分组, 命名统一成 snake_case 全拼,
+//! 只通过这里的 `From` 从数据库模型转换过来. 改表的时候只要改转换, 接口保持不动
+
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::model::{
+    FullAppInfo, FullSubstanceInfo, ShortAppInfo, ShortSubstanceInfo,
+    codes::{AppLevel, CType, PackingType, PayInstallType, SubmitType, TariffType},
+};
+
+/// 代码字段: 原始取值 + 中文名
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct CodeV1 {
+    /// 上游的原始取值
+    pub code: i32,
+    /// 中文名, 不认识的取值为 "未知"
+    pub label: String,
+}
+
+/// 资费类型, 上游直接给字符串
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct TariffV1 {
+    pub code: String,
+    pub label: String,
+    pub is_free: bool,
+}
+
+/// 应用完整信息
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct AppV1 {
+    pub app_id: String,
+    pub alliance_app_id: String,
+    pub pkg_name: String,
+    pub name: String,
+    pub icon_url: String,
+    pub brief_desc: String,
+    pub description: String,
+    pub privacy_url: String,
+    pub developer: DeveloperV1,
+    pub category: CategoryV1,
+    pub classification: ClassificationV1,
+    pub flags: AppFlagsV1,
+    pub release: ReleaseV1,
+    pub metrics: MetricsV1,
+    /// 没抓到评分时为 null
+    pub rating: Option<RatingV1>,
+    /// 没有备案信息时为 null
+    pub recordal: Option<RecordalV1>,
+    pub release_countries: Vec<String>,
+    pub main_device_codes: Vec<String>,
+    /// 投稿时附带的备注
+    pub comment: Option<serde_json::Value>,
+    /// 第一次入库时间
+    pub created_at: DateTime<Local>,
+    /// 上架时间
+    pub listed_at: DateTime<Local>,
+    /// 最后一次更新时间
+    pub updated_at: DateTime<Local>,
+}
+
+/// 开发者
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct DeveloperV1 {
+    pub id: String,
+    pub name: String,
+    pub en_name: String,
+    pub supplier: String,
+}
+
+/// 分类
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct CategoryV1 {
+    pub kind_id: i32,
+    pub kind_name: String,
+    pub kind_type_id: i32,
+    pub kind_type_name: String,
+    pub tag_name: Option<String>,
+}
+
+/// 各种代码字段
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct ClassificationV1 {
+    /// 应用 / 原子化服务
+    pub client_type: CodeV1,
+    pub app_level: CodeV1,
+    pub packing_type: CodeV1,
+    pub submit_type: CodeV1,
+    pub pay_install_type: CodeV1,
+    pub tariff_type: TariffV1,
+    pub detail_id: String,
+    pub jocat_id: i32,
+    pub img_tag: String,
+}
+
+/// 布尔标记
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct AppFlagsV1 {
+    pub in_app_purchase: bool,
+    pub hms: bool,
+    pub order_app: bool,
+    pub depends_gms: bool,
+    pub depends_hms: bool,
+    pub force_update: bool,
+    pub is_pay: bool,
+    pub is_disciplined: bool,
+    pub is_shelves: bool,
+    pub delete_archive: bool,
+    pub charging: bool,
+    pub button_grey: bool,
+    pub app_gift: bool,
+    pub free_days: i32,
+}
+
+/// 当前版本
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct ReleaseV1 {
+    pub version: String,
+    pub version_code: i64,
+    pub size_bytes: i64,
+    pub sha256: String,
+    /// 发布时间 (毫秒时间戳)
+    pub release_date: i64,
+    pub new_features: String,
+    pub upgrade_msg: String,
+    pub target_sdk: i32,
+    pub min_sdk: i32,
+    pub compile_sdk: i32,
+    pub min_hmos_api_level: i32,
+    pub api_release_type: String,
+}
+
+/// 下载量等指标
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct MetricsV1 {
+    pub download_count: i64,
+    /// 详情页上的评分
+    pub info_score: Decimal,
+    pub info_rate_count: i64,
+    pub price: Decimal,
+    /// 这份指标的采集时间
+    pub collected_at: DateTime<Local>,
+}
+
+/// 评分
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct RatingV1 {
+    pub average: Decimal,
+    pub full_average: Option<Decimal>,
+    pub total_count: Option<i32>,
+    pub only_star_count: Option<i32>,
+    pub my_star: Option<i32>,
+    /// 一星到五星的数量
+    pub star_counts: StarCountsV1,
+    pub source_type: Option<String>,
+    /// 这份评分的采集时间
+    pub collected_at: Option<DateTime<Local>>,
+}
+
+/// 各星级数量
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct StarCountsV1 {
+    pub star_1: Option<i32>,
+    pub star_2: Option<i32>,
+    pub star_3: Option<i32>,
+    pub star_4: Option<i32>,
+    pub star_5: Option<i32>,
+}
+
+/// 备案信息
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct RecordalV1 {
+    pub title: Option<String>,
+    /// 备案号
+    pub recordal_info: Option<String>,
+    pub entity_title: Option<String>,
+    pub entity_name: Option<String>,
+}
+
+/// 应用简略信息, 用于列表
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct AppSummaryV1 {
+    pub app_id: String,
+    pub pkg_name: String,
+    pub name: String,
+    pub icon_url: String,
+    pub created_at: DateTime<Local>,
+}
+
+/// 单个应用查询的结果
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct AppLookupV1 {
+    pub app: AppV1,
+    pub sync: SyncStateV1,
+}
+
+/// 这次查询时的同步情况
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct SyncStateV1 {
+    /// 是否从华为拿到了最新数据, false 表示返回的是数据库里的现有数据
+    pub fetched: bool,
+    pub new_app: bool,
+    pub info_changed: bool,
+    pub metrics_changed: bool,
+    pub rating_changed: bool,
+}
+
+/// 专题完整信息
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct SubstanceV1 {
+    pub substance_id: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub name: Option<String>,
+    pub comment: Option<serde_json::Value>,
+    pub created_at: DateTime<Local>,
+    pub apps: Vec<AppSummaryV1>,
+}
+
+/// 专题简略信息
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct SubstanceSummaryV1 {
+    pub substance_id: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub created_at: DateTime<Local>,
+}
+
+fn code<T: Into<i32> + Copy>(value: T, label: &str) -> CodeV1 {
+    CodeV1 {
+        code: value.into(),
+        label: label.to_string(),
+    }
+}
+
+impl From<&FullAppInfo> for AppV1 {
+    fn from(info: &FullAppInfo) -> Self {
+        let ctype = CType::from_code(info.ctype);
+        let app_level = AppLevel::from_code(info.app_level);
+        let packing_type = PackingType::from_code(info.packing_type);
+        let submit_type = SubmitType::from_code(info.submit_type);
+        let pay_install_type = PayInstallType::from_code(info.pay_install_type);
+        let tariff_type = TariffType::from_raw(&info.tariff_type);
+
+        let rating = info.average_rating.map(|average| RatingV1 {
+            average,
+            full_average: info.full_average_rating,
+            total_count: info.total_star_rating_count,
+            only_star_count: info.only_star_count,
+            my_star: info.my_star_rating,
+            star_counts: StarCountsV1 {
+                star_1: info.star_1_rating_count,
+                star_2: info.star_2_rating_count,
+                star_3: info.star_3_rating_count,
+                star_4: info.star_4_rating_count,
+                star_5: info.star_5_rating_count,
+            },
+            source_type: info.source_type.clone(),
+            collected_at: info.rating_created_at,
+        });
+
+        let recordal = RecordalV1 {
+            title: info.title.clone(),
+            recordal_info: info.app_recordal_info.clone(),
+            entity_title: info.recordal_entity_title.clone(),
+            entity_name: info.recordal_entity_name.clone(),
+        };
+        let recordal = (recordal.title.is_some()
+            || recordal.recordal_info.is_some()
+            || recordal.entity_title.is_some()
+            || recordal.entity_name.is_some())
+        .then_some(recordal);
+
+        Self {
+            app_id: info.app_id.clone(),
+            alliance_app_id: info.alliance_app_id.clone(),
+            pkg_name: info.pkg_name.clone(),
+            name: info.name.clone(),
+            icon_url: info.icon_url.clone(),
+            brief_desc: info.brief_desc.clone(),
+            description: info.description.clone(),
+            privacy_url: info.privacy_url.clone(),
+            developer: DeveloperV1 {
+                id: info.dev_id.clone(),
+                name: info.developer_name.clone(),
+                en_name: info.dev_en_name.clone(),
+                supplier: info.supplier.clone(),
+            },
+            category: CategoryV1 {
+                kind_id: info.kind_id,
+                kind_name: info.kind_name.clone(),
+                kind_type_id: info.kind_type_id,
+                kind_type_name: info.kind_type_name.clone(),
+                tag_name: info.tag_name.clone(),
+            },
+            classification: ClassificationV1 {
+                client_type: code(ctype, ctype.label()),
+                app_level: code(app_level, app_level.label()),
+                packing_type: code(packing_type, packing_type.label()),
+                submit_type: code(submit_type, submit_type.label()),
+                pay_install_type: code(pay_install_type, pay_install_type.label()),
+                tariff_type: TariffV1 {
+                    code: tariff_type.as_str().to_string(),
+                    label: tariff_type.label().to_string(),
+                    is_free: tariff_type == TariffType::Free,
+                },
+                detail_id: info.detail_id.clone(),
+                jocat_id: info.jocat_id,
+                img_tag: info.img_tag.clone(),
+            },
+            flags: AppFlagsV1 {
+                in_app_purchase: info.iap,
+                hms: info.hms,
+                order_app: info.order_app,
+                depends_gms: info.denpend_gms,
+                depends_hms: info.denpend_hms,
+                force_update: info.force_update,
+                is_pay: info.is_pay,
+                is_disciplined: info.is_disciplined,
+                is_shelves: info.is_shelves,
+                delete_archive: info.delete_archive,
+                charging: info.charging,
+                button_grey: info.button_grey,
+                app_gift: info.app_gift,
+                free_days: info.free_days,
+            },
+            release: ReleaseV1 {
+                version: info.version.clone(),
+                version_code: info.version_code,
+                size_bytes: info.size_bytes,
+                sha256: info.sha256.clone(),
+                release_date: info.release_date,
+                new_features: info.new_features.clone(),
+                upgrade_msg: info.upgrade_msg.clone(),
+                target_sdk: info.target_sdk,
+                min_sdk: info.minsdk,
+                compile_sdk: info.compile_sdk_version,
+                min_hmos_api_level: info.min_hmos_api_level,
+                api_release_type: info.api_release_type.clone(),
+            },
+            metrics: MetricsV1 {
+                download_count: info.download_count,
+                info_score: info.info_score,
+                info_rate_count: info.info_rate_count,
+                price: info.price,
+                collected_at: info.metrics_created_at,
+            },
+            rating,
+            recordal,
+            release_countries: info.release_countries.clone(),
+            main_device_codes: info.main_device_codes.clone(),
+            comment: info.comment.clone(),
+            created_at: info.created_at,
+            listed_at: info.listed_at,
+            updated_at: info.updated_at,
+        }
+    }
+}
+
+impl From<FullAppInfo> for AppV1 {
+    fn from(info: FullAppInfo) -> Self {
+        Self::from(&info)
+    }
+}
+
+impl From<&FullAppInfo> for AppSummaryV1 {
+    fn from(info: &FullAppInfo) -> Self {
+        Self {
+            app_id: info.app_id.clone(),
+            pkg_name: info.pkg_name.clone(),
+            name: info.name.clone(),
+            icon_url: info.icon_url.clone(),
+            created_at: info.created_at,
+        }
+    }
+}
+
+impl From<ShortAppInfo> for AppSummaryV1 {
+    fn from(info: ShortAppInfo) -> Self {
+        Self {
+            app_id: info.app_id,
+            pkg_name: info.pkg_name,
+            name: info.name,
+            icon_url: info.icon_url,
+            created_at: info.create_at,
+        }
+    }
+}
+
+impl From<FullSubstanceInfo> for SubstanceV1 {
+    fn from(info: FullSubstanceInfo) -> Self {
+        Self {
+            substance_id: info.substance_id,
+            title: info.title,
+            subtitle: info.subtitle,
+            name: info.name,
+            comment: info.comment,
+            created_at: info.created_at,
+            apps: info.apps.into_iter().map(AppSummaryV1::from).collect(),
+        }
+    }
+}
+
+impl From<ShortSubstanceInfo> for SubstanceSummaryV1 {
+    fn from(info: ShortSubstanceInfo) -> Self {
+        Self {
+            substance_id: info.substance_id,
+            title: info.title,
+            subtitle: info.subtitle,
+            created_at: info.created_at,
+        }
+    }
+}
@@ -28,7 +28,9 @@ async fn async_main() -> anyhow::Result<()> {
     // 加载配置
     let config = config::Config::load().with_context(|| "无法加载配置文件")?;
     event!(Level::INFO, "connecting to db");
-    let db = db::Database::new(config.database_url(), config.db_max_connect()).await?;
+    let db: db::storage::SharedStorage = std::sync::Arc::new(
+        db::Database::new(config.database_url(), config.db_max_connect()).await?,
+    );
     event!(Level::INFO, "connected to db");
     let client = reqwest::ClientBuilder::new()
         .build()
@@ -0,0 +1,570 @@
+//! 管理接口处理器
+//!
+//! 数据维护相关的接口: app_full_info 一致性检查 / 修复, 数据导出, 投稿审核
+
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use axum_client_ip::ClientIp;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+use std::{
+    net::IpAddr,
+    sync::{Arc, LazyLock},
+};
+use tokio::sync::Semaphore;
+use tracing::{Level, event};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    db::{
+        consistency::ConsistencyReport,
+        export::ExportParams,
+        moderation::{
+            ModerationAction, ModerationOutcome, ModerationRequest, ReviewMetadata, ReviewStatus,
+        },
+        submission::SubmissionKind,
+    },
+    server::{
+        auth::ApiKeyId,
+        cache,
+        error::{ApiError, ApiResult},
+        state::{ApiResponse, AppState},
+    },
+};
+
+/// 一致性检查参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct ConsistencyQuery {
+    /// 最多返回多少条不一致记录, 默认 100 (summary 始终是完整的)
+    pub limit: Option<usize>,
+    /// 修复时每批重建多少个应用, 默认使用 sync_batch_size
+    pub batch_size: Option<usize>,
+}
+
+/// 管理接口只接受带 key 的请求
+///
+/// 鉴权中间件之外再检查一次, 路由挂载出错时这些接口也不会对匿名请求开放
+fn admin_key(key_id: Option<Extension<ApiKeyId>>) -> ApiResult<i64> {
+    key_id
+        .map(|Extension(ApiKeyId(id))| id)
+        .ok_or_else(|| ApiError::unauthorized("需要带有 admin 权限的 API key"))
+}
+
+/// 同时进行的导出数量, 导出会长时间占用一个数据库连接
+const MAX_CONCURRENT_EXPORTS: usize = 1;
+
+static EXPORT_PERMITS: LazyLock<Arc<Semaphore>> =
+    LazyLock::new(|| Arc::new(Semaphore::new(MAX_CONCURRENT_EXPORTS)));
+
+fn truncate(mut report: ConsistencyReport, limit: Option<usize>) -> ConsistencyReport {
+    report.issues.truncate(limit.unwrap_or(100));
+    report
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/admin/consistency",
+    params(ConsistencyQuery),
+    responses(
+        (status = 200, description = "app_full_info 与 app_info / app_metrics / app_rating / app_record 的差异，data 为 ConsistencyReport", body = ApiResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 检查 app_full_info 一致性
+///
+/// 同时检查没有指标的应用和孤立的专题映射, 只读
+pub async fn check_consistency(
+    State(state): State<Arc<AppState>>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Query(query): Query<ConsistencyQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    admin_key(key_id)?;
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let report = db.check_full_info_consistency().await.map_err(|e| {
+        event!(Level::WARN, "app_full_info 一致性检查失败: {e}");
+        ApiError::database("一致性检查失败")
+    })?;
+    Ok(Json(ApiResponse::success(truncate(report, query.limit), None, None)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/admin/consistency/repair",
+    params(ConsistencyQuery),
+    responses(
+        (status = 200, description = "修复前的检查结果和修复数量，data 为 ConsistencyReport", body = ApiResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 修复 app_full_info
+///
+/// 按批用源表重建不一致的 app_full_info, 删除孤立的专题映射.
+/// 只会重写派生数据, 重复执行是安全的
+pub async fn repair_consistency(
+    State(state): State<Arc<AppState>>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Query(query): Query<ConsistencyQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let key_id = admin_key(key_id)?;
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let batch_size = query.batch_size.unwrap_or_else(|| state.cfg.sync_batch_size());
+    let report = db.repair_full_info(batch_size).await.map_err(|e| {
+        event!(Level::WARN, "app_full_info 修复失败: {e}");
+        ApiError::database("一致性修复失败")
+    })?;
+    event!(
+        Level::INFO,
+        "API key {key_id} 重建了 {} 个应用的 app_full_info, 删除 {} 条孤立的专题映射",
+        report.repaired_apps,
+        report.removed_substance_maps
+    );
+    if report.repaired_apps > 0 || report.removed_substance_maps > 0 {
+        cache::invalidate();
+    }
+    Ok(Json(ApiResponse::success(truncate(report, query.limit), None, None)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/admin/export",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "导出文件 (流式), 格式由 format 决定; 参数错误时返回 ApiErrorResponse"),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse),
+        (status = 429, description = "已有导出正在进行", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 导出数据集
+///
+/// 支持 Parquet / CSV / NDJSON, 可以选列、按 created_at 限定时间范围、
+/// 用应用列表的 filter 过滤应用. 边查边发, 不会把整个数据集读进内存.
+/// 导出中途出错时连接会被中断, 下载到的文件不完整.
+/// 同一时间只允许一个导出, 其余请求返回 429
+pub async fn export_dataset(
+    State(state): State<Arc<AppState>>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let key_id = match admin_key(key_id) {
+        Ok(key_id) => key_id,
+        Err(e) => return e.into_response(),
+    };
+    let Some(db) = state.postgres() else {
+        return ApiError::unsupported_backend().into_response();
+    };
+    let options = match params.into_options() {
+        Ok(options) => options,
+        Err(e) => return ApiError::bad_request(e).into_response(),
+    };
+    let Ok(permit) = EXPORT_PERMITS.clone().try_acquire_owned() else {
+        return ApiError::rate_limited("已有导出正在进行, 请稍后再试").into_response();
+    };
+    event!(Level::INFO, "API key {key_id} 导出 {}", options.dataset.name());
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<Vec<u8>>>(4);
+    let db = db.clone();
+    let file_name = options.file_name();
+    let format = options.format;
+    tokio::spawn(async move {
+        // 导出结束 (或客户端断开) 之后才释放
+        let _permit = permit;
+        let result = db
+            .export(&options, |chunk| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(Ok(chunk))
+                        .await
+                        .map_err(|_| anyhow::anyhow!("客户端已断开"))
+                }
+            })
+            .await;
+        if let Err(e) = result {
+            event!(Level::WARN, "导出 {} 失败: {e}", options.dataset.name());
+            let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_page_size() -> u32 {
+    50
+}
+
+/// 审核列表参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct ReviewListQuery {
+    /// 只看某个状态, 不填返回全部
+    pub status: Option<ReviewStatus>,
+    /// 页码, 从 1 开始
+    #[serde(default = "default_page")]
+    pub page: u32,
+    /// 每页数量, 最多 200
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+/// 审核记录参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct ModerationLogQuery {
+    /// 只看某条审核的记录
+    pub review_id: Option<i64>,
+    /// 页码, 从 1 开始
+    #[serde(default = "default_page")]
+    pub page: u32,
+    /// 每页数量, 最多 200
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+}
+
+/// 修改待审核的元数据, 整体替换 (不填的字段会被清空)
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReviewEditBody {
+    pub comment: Option<JsonValue>,
+    /// 上架时间, 只有应用投稿可以填
+    pub listed_at: Option<DateTime<Utc>>,
+    /// 操作备注, 记到审核记录里
+    pub note: Option<String>,
+}
+
+/// 通过 / 驳回
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct ReviewDecisionBody {
+    /// 审核意见
+    pub note: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/admin/reviews",
+    params(ReviewListQuery),
+    responses(
+        (status = 200, description = "投稿审核列表, 按 ID 倒序, data 为 SubmissionReview 数组, total 为总数", body = ApiResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 投稿审核列表
+pub async fn list_reviews(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReviewListQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let page_size = query.page_size.clamp(1, 200);
+    let (reviews, total) = state
+        .db
+        .list_submission_reviews(query.status, query.page.max(1), page_size)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询投稿审核列表失败: {e:?}");
+            ApiError::database("查询投稿审核失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        reviews,
+        Some(total as u32),
+        Some(page_size),
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/admin/reviews/{review_id}",
+    params(
+        ("review_id" = i64, Path, description = "审核ID")
+    ),
+    responses(
+        (status = 200, description = "data 为 {review, log}, log 是这条审核的全部操作记录", body = ApiResponse),
+        (status = 404, description = "审核不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 查询一条投稿审核和它的操作记录
+pub async fn get_review(
+    State(state): State<Arc<AppState>>,
+    Path(review_id): Path<i64>,
+) -> ApiResult<Json<ApiResponse>> {
+    let review = state
+        .db
+        .get_submission_review(review_id)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询投稿审核 {review_id} 失败: {e:?}");
+            ApiError::database("查询投稿审核失败")
+        })?
+        .ok_or_else(|| ApiError::not_found(format!("投稿审核 {review_id} 不存在")))?;
+    // 单条审核的操作不会很多, 一页取完
+    let (log, _) = state
+        .db
+        .list_moderation_log(Some(review_id), 1, 200)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询投稿审核 {review_id} 的操作记录失败: {e:?}");
+            ApiError::database("查询审核记录失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        json!({"review": review, "log": log}),
+        None,
+        None,
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/admin/reviews/{review_id}/edit",
+    params(
+        ("review_id" = i64, Path, description = "审核ID")
+    ),
+    request_body = ReviewEditBody,
+    responses(
+        (status = 200, description = "修改后的审核, data 为 SubmissionReview", body = ApiResponse),
+        (status = 400, description = "元数据不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 404, description = "审核不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 409, description = "审核已经处理过, detail 为当前的审核", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 修改待审核的元数据
+///
+/// 只能修改待审核的投稿, 请求体整体替换原来的 comment / listed_at
+pub async fn edit_review(
+    State(state): State<Arc<AppState>>,
+    Path(review_id): Path<i64>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    Json(body): Json<ReviewEditBody>,
+) -> ApiResult<Json<ApiResponse>> {
+    let metadata = ReviewMetadata {
+        comment: body.comment,
+        listed_at: body.listed_at,
+    };
+    if metadata.is_empty() {
+        return Err(ApiError::bad_request(
+            "comment 和 listed_at 不能都为空, 不需要写入元数据请直接驳回",
+        ));
+    }
+    if metadata.listed_at.is_some_and(|listed_at| listed_at > Utc::now()) {
+        return Err(ApiError::bad_request("listed_at 不能晚于当前时间"));
+    }
+    if metadata.listed_at.is_some() {
+        let review = state
+            .db
+            .get_submission_review(review_id)
+            .await
+            .map_err(|e| {
+                event!(Level::WARN, "查询投稿审核 {review_id} 失败: {e:?}");
+                ApiError::database("查询投稿审核失败")
+            })?
+            .ok_or_else(|| ApiError::not_found(format!("投稿审核 {review_id} 不存在")))?;
+        if review.kind == SubmissionKind::Substance {
+            return Err(ApiError::bad_request("专题投稿没有上架时间"));
+        }
+    }
+
+    let request = ModerationRequest {
+        action: ModerationAction::Edit,
+        metadata: Some(metadata),
+        note: body.note,
+        actor_key_id: key_id.map(|Extension(ApiKeyId(id))| id),
+        actor_ip: Some(ip),
+    };
+    moderate(&state, review_id, request).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/admin/reviews/{review_id}/approve",
+    params(
+        ("review_id" = i64, Path, description = "审核ID")
+    ),
+    request_body(content = Option<ReviewDecisionBody>, description = "可以不带请求体"),
+    responses(
+        (status = 200, description = "通过后的审核, data 为 SubmissionReview", body = ApiResponse),
+        (status = 404, description = "审核不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 409, description = "审核已经处理过 (detail 为当前的审核), 或者应用 / 专题还没有收录", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 通过投稿审核
+///
+/// 把元数据写入应用 / 专题, 只写不为空的字段
+pub async fn approve_review(
+    State(state): State<Arc<AppState>>,
+    Path(review_id): Path<i64>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    body: Option<Json<ReviewDecisionBody>>,
+) -> ApiResult<Json<ApiResponse>> {
+    let request = decision(ModerationAction::Approve, key_id, ip, body);
+    moderate(&state, review_id, request).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/admin/reviews/{review_id}/reject",
+    params(
+        ("review_id" = i64, Path, description = "审核ID")
+    ),
+    request_body(content = Option<ReviewDecisionBody>, description = "可以不带请求体"),
+    responses(
+        (status = 200, description = "驳回后的审核, data 为 SubmissionReview", body = ApiResponse),
+        (status = 404, description = "审核不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 409, description = "审核已经处理过, detail 为当前的审核", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 驳回投稿审核, 元数据不会写入
+pub async fn reject_review(
+    State(state): State<Arc<AppState>>,
+    Path(review_id): Path<i64>,
+    key_id: Option<Extension<ApiKeyId>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    body: Option<Json<ReviewDecisionBody>>,
+) -> ApiResult<Json<ApiResponse>> {
+    let request = decision(ModerationAction::Reject, key_id, ip, body);
+    moderate(&state, review_id, request).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/admin/moderation_log",
+    params(ModerationLogQuery),
+    responses(
+        (status = 200, description = "审核操作记录, 按 ID 倒序, data 为 ModerationLogEntry 数组, total 为总数", body = ApiResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 admin 权限", body = crate::server::error::ApiErrorResponse)
+    ),
+    security(("api_key" = [])),
+    tag = "管理"
+)]
+/// 审核操作记录
+///
+/// 每条记录包含操作人、操作前后的审核内容和备注
+pub async fn list_moderation_log(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ModerationLogQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let page_size = query.page_size.clamp(1, 200);
+    let (entries, total) = state
+        .db
+        .list_moderation_log(query.review_id, query.page.max(1), page_size)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询审核记录失败: {e:?}");
+            ApiError::database("查询审核记录失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        entries,
+        Some(total as u32),
+        Some(page_size),
+    )))
+}
+
+fn decision(
+    action: ModerationAction,
+    key_id: Option<Extension<ApiKeyId>>,
+    ip: IpAddr,
+    body: Option<Json<ReviewDecisionBody>>,
+) -> ModerationRequest {
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    ModerationRequest {
+        action,
+        metadata: None,
+        note: body.note,
+        actor_key_id: key_id.map(|Extension(ApiKeyId(id))| id),
+        actor_ip: Some(ip),
+    }
+}
+
+/// 执行审核操作, 把结果转换成响应
+async fn moderate(
+    state: &AppState,
+    review_id: i64,
+    request: ModerationRequest,
+) -> ApiResult<Json<ApiResponse>> {
+    let action = request.action;
+    let outcome = state
+        .db
+        .moderate_submission_review(review_id, &request)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "投稿审核 {review_id} {} 失败: {e:?}", action.name());
+            ApiError::database("审核操作失败")
+        })?;
+    match outcome {
+        ModerationOutcome::Done(review) => {
+            event!(
+                Level::INFO,
+                review_id,
+                actor_key_id = request.actor_key_id,
+                "投稿审核 {} {}: {}",
+                action.name(),
+                review.kind.name(),
+                review.target
+            );
+            if action == ModerationAction::Approve {
+                cache::invalidate();
+            }
+            Ok(Json(ApiResponse::success(review, None, None)))
+        }
+        ModerationOutcome::NotFound => {
+            Err(ApiError::not_found(format!("投稿审核 {review_id} 不存在")))
+        }
+        ModerationOutcome::NotPending(review) => Err(ApiError::conflict(format!(
+            "投稿审核 {review_id} 已经是 {} 状态",
+            review.status.name()
+        ))
+        .with_detail(serde_json::to_value(&review).unwrap_or_default())),
+        ModerationOutcome::TargetMissing => Err(ApiError::conflict(
+            "应用/专题还没有收录, 等投稿任务完成后再审核",
+        )),
+    }
+}
@@ -0,0 +1,313 @@
+//! API key 校验
+//!
+//! 投稿 / 管理 / 访问日志接口按 scope 校验 key, 在 routes 里用 `route_layer` 挂到对应路由上.
+//! key 放在 `x-api-key` 头或 `Authorization: Bearer <key>` 里;
+//! `serve.anonymous_scopes` 里的 scope 不带 key 也放行, `admin` 无论怎么配置都必须带 key.
+//! 限流中间件按 key 计数时已经查过一次库, 结果通过请求的 extensions 传过来, 不再重复查.
+//! 查库结果 (包括无效的 key) 缓存 [`KEY_CACHE_TTL`]. Postgres 后端吊销 key 时会发 NOTIFY,
+//! [`start_revocation_listener`] 收到后立即清掉缓存; SQLite 没有通知, 吊销最多
+//! [`KEY_CACHE_TTL`] 之后生效
+
+use std::{
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde_json::json;
+use tracing::{Level, event};
+
+use crate::{
+    db::{
+        Database,
+        api_key::{ApiKey, ApiScope, hash_secret},
+    },
+    server::{error::ApiError, state::AppState},
+};
+
+/// API key 请求头
+pub static API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// 校验通过的 key, 同时放进请求和响应的 extensions,
+/// 统计中间件从响应里读出来记进访问日志
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiKeyId(pub i64);
+
+/// 限流中间件查过的 key
+#[derive(Debug, Clone)]
+struct ResolvedKey(Result<Option<ApiKey>, ApiError>);
+
+/// key 查询结果的缓存时间
+const KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+/// 缓存最多记多少个 key, 满了先清过期的, 还是满的就全部清掉
+const KEY_CACHE_MAX_ENTRIES: usize = 10_000;
+
+/// key 哈希 -> (查询时间, key), 无效的 key 记为 None
+static KEY_CACHE: LazyLock<DashMap<String, (Instant, Option<ApiKey>)>> =
+    LazyLock::new(DashMap::new);
+
+fn cached_key(hash: &str, now: Instant) -> Option<Option<ApiKey>> {
+    let entry = KEY_CACHE.get(hash)?;
+    let (cached_at, key) = entry.value();
+    (now.saturating_duration_since(*cached_at) < KEY_CACHE_TTL).then(|| key.clone())
+}
+
+fn cache_key(hash: String, key: Option<ApiKey>, now: Instant) {
+    if KEY_CACHE.len() >= KEY_CACHE_MAX_ENTRIES {
+        KEY_CACHE.retain(|_, (cached_at, _)| {
+            now.saturating_duration_since(*cached_at) < KEY_CACHE_TTL
+        });
+        if KEY_CACHE.len() >= KEY_CACHE_MAX_ENTRIES {
+            KEY_CACHE.clear();
+        }
+    }
+    KEY_CACHE.insert(hash, (now, key));
+}
+
+/// 清掉指定 key 的缓存, 吊销之后调用
+pub fn evict_cached_key(id: i64) {
+    KEY_CACHE.retain(|_, (_, key)| key.as_ref().is_none_or(|key| key.id != id));
+}
+
+/// 启动 key 吊销通知的监听任务
+///
+/// 连接断开期间可能漏掉通知, 重连后把整个缓存清掉
+pub fn start_revocation_listener(db: Database) -> tokio::task::JoinHandle<()> {
+    event!(Level::INFO, "启动 API key 吊销通知监听");
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match db.listen_api_key_revocations().await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    event!(Level::WARN, "监听 API key 吊销通知失败: {e}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            KEY_CACHE.clear();
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => match notification.payload().parse() {
+                        Ok(id) => {
+                            event!(Level::INFO, "API key #{id} 已吊销, 清掉缓存");
+                            evict_cached_key(id);
+                        }
+                        Err(_) => KEY_CACHE.clear(),
+                    },
+                    // 连接断了, 下次 try_recv 会自动重连, 期间的通知收不到
+                    Ok(None) => KEY_CACHE.clear(),
+                    Err(e) => {
+                        event!(Level::WARN, "接收 API key 吊销通知失败: {e}");
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    })
+}
+
+/// 从请求头里取 key 明文
+fn extract_key(headers: &HeaderMap) -> Option<&str> {
+    let from_header = headers
+        .get(&API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok());
+    let from_bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    [from_header, from_bearer]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|key| !key.is_empty())
+}
+
+/// 查请求带的 key, 没带时为 `Ok(None)`
+async fn lookup_key(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<ApiKey>, ApiError> {
+    let Some(secret) = extract_key(headers) else {
+        return Ok(None);
+    };
+    let hash = hash_secret(secret);
+    let now = Instant::now();
+    let found = match cached_key(&hash, now) {
+        Some(key) => Ok(key),
+        None => state.db.find_api_key(&hash).await.inspect(|key| {
+            cache_key(hash, key.clone(), now);
+        }),
+    };
+    match found {
+        Ok(Some(key)) => Ok(Some(key)),
+        Ok(None) => Err(ApiError::unauthorized("API key 无效或已吊销")),
+        Err(e) => {
+            event!(Level::WARN, "查询 API key 失败: {e}");
+            Err(ApiError::database("API key 校验失败"))
+        }
+    }
+}
+
+/// 请求带了 key 且缓存里没有, 校验时需要查库
+///
+/// 限流中间件据此在查库前先按 IP 扣令牌, 带随机 key 刷接口的请求在占用连接之前就会被限流
+pub fn needs_key_lookup(headers: &HeaderMap) -> bool {
+    extract_key(headers)
+        .is_some_and(|secret| cached_key(&hash_secret(secret), Instant::now()).is_none())
+}
+
+/// 限流用: 返回有效 key 的 id, 查询结果留给之后的鉴权
+pub async fn resolve_key_id(state: &AppState, request: &mut Request) -> Option<i64> {
+    let resolved = lookup_key(state, request.headers()).await;
+    let id = resolved.as_ref().ok().and_then(Option::as_ref).map(|key| key.id);
+    request.extensions_mut().insert(ResolvedKey(resolved));
+    id
+}
+
+/// 不带 key 的请求能不能访问需要 `scope` 的接口
+///
+/// 配置加载时已经拒绝了匿名 admin, 这里再兜一次底, 管理接口永远不对匿名请求开放
+fn anonymous_allowed(anonymous: &[ApiScope], scope: ApiScope) -> bool {
+    scope != ApiScope::Admin && anonymous.contains(&scope)
+}
+
+async fn authorize(
+    state: &AppState,
+    scope: ApiScope,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let resolved = match request.extensions_mut().remove::<ResolvedKey>() {
+        Some(ResolvedKey(resolved)) => resolved,
+        None => lookup_key(state, request.headers()).await,
+    };
+    let key = match resolved {
+        Ok(key) => key,
+        Err(e) => return e.into_response(),
+    };
+    match &key {
+        Some(key) if !key.allows(scope) => {
+            return ApiError::forbidden(format!("API key {} 没有 {} 权限", key.name, scope.name()))
+                .with_detail(json!({ "required_scope": scope }))
+                .into_response();
+        }
+        None if !anonymous_allowed(&state.cfg.anonymous_scopes(), scope) => {
+            return ApiError::unauthorized(format!("需要带有 {} 权限的 API key", scope.name()))
+                .with_detail(json!({ "required_scope": scope }))
+                .into_response();
+        }
+        _ => {}
+    }
+
+    let key_id = key.map(|key| ApiKeyId(key.id));
+    if let Some(key_id) = key_id {
+        request.extensions_mut().insert(key_id);
+    }
+    let mut response = next.run(request).await;
+    if let Some(key_id) = key_id {
+        response.extensions_mut().insert(key_id);
+    }
+    response
+}
+
+/// 投稿接口: 需要 `submit`
+pub async fn require_submit(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    authorize(&state, ApiScope::Submit, request, next).await
+}
+
+/// 管理接口: 需要 `admin`
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    authorize(&state, ApiScope::Admin, request, next).await
+}
+
+/// 访问日志: 需要 `stats:read`
+pub async fn require_stats_read(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    authorize(&state, ApiScope::StatsRead, request, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn key_from_either_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(extract_key(&headers), None);
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer hmk_b"));
+        assert_eq!(extract_key(&headers), Some("hmk_b"));
+
+        // 两个都有时用 x-api-key
+        headers.insert(API_KEY_HEADER.clone(), HeaderValue::from_static(" hmk_a "));
+        assert_eq!(extract_key(&headers), Some("hmk_a"));
+
+        // 空值等于没带
+        headers.insert(API_KEY_HEADER.clone(), HeaderValue::from_static(""));
+        assert_eq!(extract_key(&headers), Some("hmk_b"));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(extract_key(&headers), None);
+    }
+
+    #[test]
+    fn invalid_keys_are_cached() {
+        let mut headers = HeaderMap::new();
+        assert!(!needs_key_lookup(&headers));
+        headers.insert(API_KEY_HEADER.clone(), HeaderValue::from_static("hmk_cache_test"));
+        assert!(needs_key_lookup(&headers));
+
+        let now = Instant::now();
+        cache_key(hash_secret("hmk_cache_test"), None, now);
+        assert!(!needs_key_lookup(&headers));
+        assert!(matches!(cached_key(&hash_secret("hmk_cache_test"), now), Some(None)));
+        // 过期之后重新查库
+        assert!(cached_key(&hash_secret("hmk_cache_test"), now + KEY_CACHE_TTL).is_none());
+    }
+
+    #[test]
+    fn revoked_key_evicted() {
+        let key = ApiKey {
+            id: 9_001,
+            name: "revoke".to_string(),
+            key_prefix: "hmk_revoke".to_string(),
+            scopes: vec![ApiScope::Submit],
+            created_at: chrono::Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        let now = Instant::now();
+        let hash = hash_secret("hmk_revoke_test");
+        cache_key(hash.clone(), Some(key), now);
+        assert!(matches!(cached_key(&hash, now), Some(Some(_))));
+        // 吊销之后立即重新查库
+        evict_cached_key(9_001);
+        assert!(cached_key(&hash, now).is_none());
+    }
+
+    #[test]
+    fn admin_never_anonymous() {
+        let anonymous = [ApiScope::Submit, ApiScope::Admin];
+        assert!(anonymous_allowed(&anonymous, ApiScope::Submit));
+        assert!(!anonymous_allowed(&anonymous, ApiScope::Admin));
+        assert!(!anonymous_allowed(&anonymous, ApiScope::StatsRead));
+    }
+}
@@ -0,0 +1,254 @@
+//! 看板查询结果缓存
+//!
+//! `market_info`、`/charts/*`、`/rankings/*` 这些接口每次都要跑一遍聚合,
+//! 而数据只在同步写入时才会变化. 这里按 "接口 + 查询参数" 缓存结果,
+//! 条目有 TTL 和数量上限, 同步写入了新数据时整体失效
+//!
+//! 失效用代数 (generation) 实现: 查询开始前记下代数, 写回时代数变了就不写,
+//! 避免失效前发起的慢查询把旧结果塞回缓存
+
+use std::{
+    any::Any,
+    future::Future,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use serde::Serialize;
+use tracing::{Level, event};
+use utoipa::ToSchema;
+
+/// 全局查询缓存 (由 config.toml 初始化, 未初始化时不缓存)
+pub static QUERY_CACHE: OnceLock<QueryCache> = OnceLock::new();
+
+struct CacheEntry {
+    value: Arc<dyn Any + Send + Sync>,
+    expires_at: Instant,
+}
+
+pub struct QueryCache {
+    entries: DashMap<String, CacheEntry>,
+    ttl: Duration,
+    max_entries: usize,
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+/// 缓存命中统计
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStats {
+    /// 当前条目数
+    pub entries: usize,
+    /// 条目上限
+    pub max_entries: usize,
+    /// 条目有效期 (秒)
+    pub ttl_seconds: u64,
+    /// 命中次数
+    pub hits: u64,
+    /// 未命中次数
+    pub misses: u64,
+    /// 命中率
+    pub hit_rate: f64,
+    /// 因同步写入失效的次数
+    pub invalidations: u64,
+}
+
+impl QueryCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+            max_entries,
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_entries > 0
+    }
+
+    fn get<T: Clone + Send + Sync + 'static>(&self, key: &str) -> Option<T> {
+        let entry = self.entries.get(key)?;
+        if entry.expires_at <= Instant::now() {
+            drop(entry);
+            self.entries.remove(key);
+            return None;
+        }
+        entry.value.downcast_ref::<T>().cloned()
+    }
+
+    fn insert<T: Send + Sync + 'static>(&self, key: String, value: T, generation: u64) {
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            self.evict();
+        }
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value: Arc::new(value),
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+    }
+
+    /// 腾出位置: 先清过期条目, 还满的话丢掉最早过期的一个
+    fn evict(&self) {
+        let now = Instant::now();
+        self.entries.retain(|_, entry| entry.expires_at > now);
+        if self.entries.len() < self.max_entries {
+            return;
+        }
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|entry| entry.expires_at)
+            .map(|entry| entry.key().clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// 取缓存, 没有就执行 `load` 并缓存成功的结果
+    pub async fn get_or_load<T, E, F, Fut>(&self, key: String, load: F) -> Result<T, E>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if !self.enabled() {
+            return load().await;
+        }
+        if let Some(value) = self.get::<T>(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::Acquire);
+        let value = load().await?;
+        self.insert(key, value.clone(), generation);
+        Ok(value)
+    }
+
+    /// 清空缓存
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let total = hits + misses;
+        CacheStats {
+            entries: self.entries.len(),
+            max_entries: self.max_entries,
+            ttl_seconds: self.ttl.as_secs(),
+            hits,
+            misses,
+            hit_rate: if total == 0 {
+                0.0
+            } else {
+                hits as f64 / total as f64
+            },
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 由接口名和查询参数生成缓存 key
+pub fn cache_key(name: &str, params: &impl Serialize) -> String {
+    format!("{name}?{}", serde_json::to_string(params).unwrap_or_default())
+}
+
+/// 通过全局缓存查询, 缓存未初始化时直接执行 `load`
+pub async fn cached<T, E, F, Fut>(key: String, load: F) -> Result<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    match QUERY_CACHE.get() {
+        Some(cache) => cache.get_or_load(key, load).await,
+        None => load().await,
+    }
+}
+
+/// 同步写入了新数据, 让缓存失效
+pub fn invalidate() {
+    if let Some(cache) = QUERY_CACHE.get()
+        && cache.enabled()
+    {
+        event!(Level::DEBUG, "数据有更新, 查询缓存失效");
+        cache.invalidate();
+    }
+}
+
+/// 当前缓存统计, 缓存未初始化时返回 None
+pub fn stats() -> Option<CacheStats> {
+    QUERY_CACHE.get().map(QueryCache::stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_on<F: Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    #[test]
+    fn test_hit_and_invalidate() {
+        block_on(async {
+            let cache = QueryCache::new(Duration::from_secs(60), 10);
+            let load = |v: u32| async move { Ok::<_, ()>(v) };
+
+            assert_eq!(cache.get_or_load("a".to_string(), || load(1)).await, Ok(1));
+            // 命中缓存, 不会执行 load
+            assert_eq!(cache.get_or_load("a".to_string(), || load(2)).await, Ok(1));
+            assert_eq!(cache.stats().hits, 1);
+            assert_eq!(cache.stats().misses, 1);
+
+            cache.invalidate();
+            assert_eq!(cache.get_or_load("a".to_string(), || load(3)).await, Ok(3));
+        });
+    }
+
+    #[test]
+    fn test_bounded_and_errors_not_cached() {
+        block_on(async {
+            let cache = QueryCache::new(Duration::from_secs(60), 2);
+            for i in 0..5u32 {
+                let _ = cache
+                    .get_or_load(i.to_string(), || async move { Ok::<_, ()>(i) })
+                    .await;
+            }
+            assert!(cache.stats().entries <= 2);
+
+            // 失败结果不缓存
+            let _ = cache
+                .get_or_load("err".to_string(), || async { Err::<u32, _>("db") })
+                .await;
+            assert_eq!(
+                cache
+                    .get_or_load("err".to_string(), || async { Ok::<u32, &str>(7) })
+                    .await,
+                Ok(7)
+            );
+        });
+    }
+}
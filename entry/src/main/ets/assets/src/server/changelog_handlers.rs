@@ -0,0 +1,181 @@
+//! 变更记录 HTTP 接口处理器
+//!
+//! 单个应用 / 专题的变更记录, 以及全市场的最近变更
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{Level, event};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    db::{
+        changelog::{ChangelogFilter, ChangelogKind},
+    },
+    server::{
+        error::{ApiError, ApiResult},
+        state::{ApiResponse, AppState},
+    },
+};
+
+/// 单个应用 / 专题的变更记录查询参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct ChangelogQuery {
+    /// 页码，从 1 开始
+    pub page: Option<u32>,
+    /// 每页大小
+    pub page_size: Option<u32>,
+    /// 只看这些顶层字段，逗号分隔，例如 `versionName,labels`
+    pub fields: Option<String>,
+    /// 是否包含第一个快照，默认 true
+    pub include_initial: Option<bool>,
+}
+
+/// 最近变更查询参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct RecentChangesQuery {
+    /// 只看应用或专题，不传则都返回
+    pub kind: Option<ChangelogKind>,
+    /// 页码，从 1 开始
+    pub page: Option<u32>,
+    /// 每页大小
+    pub page_size: Option<u32>,
+    /// 只看这些顶层字段，逗号分隔
+    pub fields: Option<String>,
+    /// 是否包含新上架 (第一个快照)，默认 false
+    pub include_new: Option<bool>,
+    /// 只看这个时间之后的变更 (RFC3339)
+    #[param(value_type = Option<String>)]
+    #[schema(value_type = Option<String>)]
+    pub since: Option<DateTime<Local>>,
+}
+
+fn split_fields(fields: Option<&str>) -> Vec<String> {
+    fields
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+async fn changelog(
+    state: Arc<AppState>,
+    kind: ChangelogKind,
+    target_id: String,
+    query: ChangelogQuery,
+) -> ApiResult<Json<ApiResponse>> {
+    let filter = ChangelogFilter {
+        fields: split_fields(query.fields.as_deref()),
+        include_initial: query.include_initial.unwrap_or(true),
+        since: None,
+    };
+    let page_size = query.page_size.unwrap_or(20);
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let (entries, total) = db
+        .get_changelog(
+            kind,
+            &target_id,
+            &filter,
+            query.page.unwrap_or(1),
+            page_size,
+        )
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询 {target_id} 的变更记录失败: {e}");
+            ApiError::database("获取变更记录失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        entries,
+        Some(total as u32),
+        Some(page_size),
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/apps/changelog/{app_id}",
+    params(
+        ("app_id" = String, Path, description = "应用ID"),
+        ChangelogQuery
+    ),
+    responses(
+        (status = 200, description = "应用快照的变更记录，新的在前，data 为 ChangelogEntry 列表", body = ApiResponse)
+    ),
+    tag = "变更记录"
+)]
+/// 获取应用的变更记录
+///
+/// 每条记录是相邻两次原始数据快照之间的差异 (新增、删除、修改的字段及新旧值)
+pub async fn app_changelog(
+    State(state): State<Arc<AppState>>,
+    Path(app_id): Path<String>,
+    Query(query): Query<ChangelogQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    changelog(state, ChangelogKind::App, app_id, query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/substance/changelog/{substance_id}",
+    params(
+        ("substance_id" = String, Path, description = "专题ID"),
+        ChangelogQuery
+    ),
+    responses(
+        (status = 200, description = "专题快照的变更记录，新的在前，data 为 ChangelogEntry 列表", body = ApiResponse)
+    ),
+    tag = "变更记录"
+)]
+/// 获取专题的变更记录
+pub async fn substance_changelog(
+    State(state): State<Arc<AppState>>,
+    Path(substance_id): Path<String>,
+    Query(query): Query<ChangelogQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    changelog(state, ChangelogKind::Substance, substance_id, query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/changes/recent",
+    params(RecentChangesQuery),
+    responses(
+        (status = 200, description = "全市场的最近变更，新的在前，data 为 ChangelogEntry 列表", body = ApiResponse)
+    ),
+    tag = "变更记录"
+)]
+/// 全市场最近变更
+pub async fn recent_changes(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RecentChangesQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let filter = ChangelogFilter {
+        fields: split_fields(query.fields.as_deref()),
+        include_initial: query.include_new.unwrap_or(false),
+        since: query.since,
+    };
+    let page_size = query.page_size.unwrap_or(20);
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let (entries, total) = db
+        .recent_changes(query.kind, &filter, query.page.unwrap_or(1), page_size)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询最近变更失败: {e}");
+            ApiError::database("获取最近变更失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        entries,
+        Some(total as u32),
+        Some(page_size),
+    )))
+}
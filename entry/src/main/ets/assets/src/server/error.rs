@@ -0,0 +1,226 @@
+//! 接口错误
+//!
+//! 所有 handler 的错误都走 [`ApiError`]: HTTP 状态码和 `code` 一一对应, 客户端按 `code` 判断,
+//! 不用再去匹配提示文字. 响应体外层结构和 [`ApiResponse`](crate::server::state::ApiResponse) 一样,
+//! `data.error` 仍然是提示文字, 老客户端读这个字段不受影响
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+
+use crate::{db::storage::UNSUPPORTED_BACKEND, server::middle::current_request_id};
+
+/// 稳定的错误码, 只增不改
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 参数不合法 (400)
+    InvalidParameter,
+    /// 没有带 API key 或 key 无效 (401)
+    Unauthorized,
+    /// API key 没有所需的 scope (403)
+    Forbidden,
+    /// 应用 / 专题等资源不存在 (404)
+    NotFound,
+    /// 与现有数据冲突 (409)
+    Conflict,
+    /// 请求过于频繁 (429)
+    RateLimited,
+    /// 华为接口请求失败 (502)
+    UpstreamError,
+    /// 数据库不可用或查询失败 (503)
+    DatabaseError,
+    /// 当前存储后端不支持该功能 (501)
+    UnsupportedBackend,
+    /// 其它内部错误 (500)
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::InvalidParameter => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::UpstreamError => StatusCode::BAD_GATEWAY,
+            Self::DatabaseError => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UnsupportedBackend => StatusCode::NOT_IMPLEMENTED,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// 没有给提示文字时使用的默认提示
+    pub fn default_message(self) -> &'static str {
+        match self {
+            Self::InvalidParameter => "请求参数不合法",
+            Self::Unauthorized => "需要有效的 API key",
+            Self::Forbidden => "API key 没有访问该接口的权限",
+            Self::NotFound => "请求的资源不存在",
+            Self::Conflict => "与现有数据冲突",
+            Self::RateLimited => "请求过于频繁, 请稍后再试",
+            Self::UpstreamError => "华为应用市场接口请求失败",
+            Self::DatabaseError => "数据库暂时不可用",
+            Self::UnsupportedBackend => UNSUPPORTED_BACKEND,
+            Self::Internal => "服务器内部错误",
+        }
+    }
+}
+
+/// 接口错误
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    /// 提示文字, 不给时用 [`ErrorCode::default_message`]
+    pub message: Option<String>,
+    /// 附加的结构化信息, 例如冲突的记录
+    pub detail: Option<JsonValue>,
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            code,
+            message: None,
+            detail: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: impl ToString) -> Self {
+        self.message = Some(message.to_string());
+        self
+    }
+
+    pub fn with_detail(mut self, detail: JsonValue) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    pub fn bad_request(message: impl ToString) -> Self {
+        Self::new(ErrorCode::InvalidParameter).with_message(message)
+    }
+
+    pub fn unauthorized(message: impl ToString) -> Self {
+        Self::new(ErrorCode::Unauthorized).with_message(message)
+    }
+
+    pub fn forbidden(message: impl ToString) -> Self {
+        Self::new(ErrorCode::Forbidden).with_message(message)
+    }
+
+    pub fn not_found(message: impl ToString) -> Self {
+        Self::new(ErrorCode::NotFound).with_message(message)
+    }
+
+    pub fn conflict(message: impl ToString) -> Self {
+        Self::new(ErrorCode::Conflict).with_message(message)
+    }
+
+    pub fn rate_limited(message: impl ToString) -> Self {
+        Self::new(ErrorCode::RateLimited).with_message(message)
+    }
+
+    pub fn upstream(message: impl ToString) -> Self {
+        Self::new(ErrorCode::UpstreamError).with_message(message)
+    }
+
+    pub fn database(message: impl ToString) -> Self {
+        Self::new(ErrorCode::DatabaseError).with_message(message)
+    }
+
+    pub fn unsupported_backend() -> Self {
+        Self::new(ErrorCode::UnsupportedBackend)
+    }
+
+    pub fn internal(message: impl ToString) -> Self {
+        Self::new(ErrorCode::Internal).with_message(message)
+    }
+
+    pub fn message(&self) -> &str {
+        self.message
+            .as_deref()
+            .unwrap_or_else(|| self.code.default_message())
+    }
+
+    fn into_body(self) -> ApiErrorResponse {
+        ApiErrorResponse {
+            success: false,
+            data: ErrorBody {
+                code: self.code,
+                error: self.message().to_string(),
+                request_id: current_request_id(),
+                detail: self.detail,
+            },
+            timestamp: chrono::Utc::now(),
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.code.status();
+        (status, Json(self.into_body())).into_response()
+    }
+}
+
+/// 错误响应
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApiErrorResponse {
+    /// 固定为 false
+    pub success: bool,
+    pub data: ErrorBody,
+    /// 响应生成时间戳（UTC）
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// 错误详情
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// 机器可读的错误码
+    pub code: ErrorCode,
+    /// 提示文字 (中文)
+    pub error: String,
+    /// 请求 ID, 与响应头 `x-request-id` 一致, 反馈问题时带上
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<JsonValue>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_body_keeps_legacy_error_field() {
+        let body = ApiError::not_found("没有这个应用").into_body();
+        let value = serde_json::to_value(&body).unwrap();
+        assert_eq!(value["success"], false);
+        assert_eq!(value["data"]["code"], "not_found");
+        // 老客户端读的是 data.error
+        assert_eq!(value["data"]["error"], "没有这个应用");
+        // 不在请求里时没有 request id
+        assert!(value["data"]["request_id"].is_null());
+        assert!(value["data"].get("detail").is_none());
+
+        let error = ApiError::new(ErrorCode::RateLimited);
+        assert_eq!(error.code.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(error.message(), "请求过于频繁, 请稍后再试");
+    }
+}
@@ -0,0 +1,780 @@
+//! 飞书多维表格数据连接器
+//!
+//! 飞书先读 `meta.json` 拿到接口地址, 再调用 `table_meta` 取字段定义, 最后反复调用 `records`
+//! 分页拉取数据, 直到 `hasMore` 为 false. 请求体里的 `params` 是 JSON 字符串,
+//! 其中 `datasourceConfig` 是数据源配置 (同样是 JSON 字符串), 例如
+//! `{"table": "rankings", "exclude_atomic": true}`, 可选的表见 [`FeishuTable`].
+//!
+//! `table_meta` / `records` 必须带签名, 没有配置 `serve.feishu.secret` 时一律拒绝:
+//! `X-Base-Signature = hex(sha256(timestamp + nonce + secret + body))`,
+//! timestamp (秒) 和 nonce 分别来自 `X-Base-Request-Timestamp` 和 `X-Base-Request-Nonce`.
+//! 签名有效期内用过的 nonce 会记下来, 同一个 nonce 再来视为重放
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+use dashmap::{DashMap, mapref::entry::Entry};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, LazyLock};
+use tracing::{Level, event};
+use utoipa::ToSchema;
+
+use crate::{
+    config::FeishuConfig,
+    db::query::get_max_limit,
+    model::{FullAppInfo, ShortSubstanceInfo},
+    server::state::AppState,
+};
+
+const TIMESTAMP_HEADER: &str = "x-base-request-timestamp";
+const NONCE_HEADER: &str = "x-base-request-nonce";
+const SIGNATURE_HEADER: &str = "x-base-signature";
+
+/// 飞书要求接口始终返回 HTTP 200, 用 code 区分错误, msg 会展示给用户
+const CODE_OK: i64 = 0;
+const CODE_CONFIG_ERROR: i64 = 1254400;
+const CODE_AUTH_FAILED: i64 = 1254403;
+const CODE_INTERNAL_ERROR: i64 = 1254500;
+
+/// 记录的 nonce 超过这个数量时先清掉过期的
+const NONCE_PRUNE_THRESHOLD: usize = 1_024;
+
+/// 签名有效期内见过的 nonce -> 过期时间 (unix 秒)
+static SEEN_NONCES: LazyLock<DashMap<String, i64>> = LazyLock::new(DashMap::new);
+
+/// 每页最多返回多少条记录, 同时受 `database.max_limit` 限制
+const MAX_PAGE_SIZE: u32 = 200;
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+/// 可以同步到飞书的表
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeishuTable {
+    /// 应用基本信息
+    #[default]
+    Apps,
+    /// 每个应用最新的一条指标
+    Metrics,
+    /// 按下载量排名
+    Rankings,
+    /// 专题
+    Substances,
+}
+
+impl FeishuTable {
+    pub const ALL: [FeishuTable; 4] = [Self::Apps, Self::Metrics, Self::Rankings, Self::Substances];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Apps => "apps",
+            Self::Metrics => "metrics",
+            Self::Rankings => "rankings",
+            Self::Substances => "substances",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|table| table.name() == name)
+    }
+
+    /// 飞书里显示的表名
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Apps => "鸿蒙应用",
+            Self::Metrics => "应用指标",
+            Self::Rankings => "下载量排行",
+            Self::Substances => "专题",
+        }
+    }
+
+    /// 字段定义, 第一列是主键
+    fn columns(&self) -> &'static [Column] {
+        match self {
+            Self::Apps => APP_COLUMNS,
+            Self::Metrics => METRIC_COLUMNS,
+            Self::Rankings => RANKING_COLUMNS,
+            Self::Substances => SUBSTANCE_COLUMNS,
+        }
+    }
+}
+
+/// 飞书的字段类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Text,
+    Number,
+    SingleSelect,
+    MultiSelect,
+    /// 值为毫秒时间戳
+    Date,
+    Checkbox,
+}
+
+impl FieldType {
+    fn code(self) -> u8 {
+        match self {
+            Self::Text => 1,
+            Self::Number => 2,
+            Self::SingleSelect => 3,
+            Self::MultiSelect => 4,
+            Self::Date => 5,
+            Self::Checkbox => 7,
+        }
+    }
+
+    fn property(self) -> JsonValue {
+        match self {
+            Self::Date => json!({"formatter": "yyyy/MM/dd HH:mm"}),
+            _ => json!({}),
+        }
+    }
+}
+
+struct Column {
+    id: &'static str,
+    name: &'static str,
+    field_type: FieldType,
+}
+
+const fn column(id: &'static str, name: &'static str, field_type: FieldType) -> Column {
+    Column {
+        id,
+        name,
+        field_type,
+    }
+}
+
+const APP_COLUMNS: &[Column] = &[
+    column("app_id", "应用ID", FieldType::Text),
+    column("name", "应用名", FieldType::Text),
+    column("pkg_name", "包名", FieldType::Text),
+    column("developer_name", "开发者", FieldType::Text),
+    column("dev_id", "开发者ID", FieldType::Text),
+    column("kind_name", "分类", FieldType::SingleSelect),
+    column("kind_type_name", "类型", FieldType::SingleSelect),
+    column("tag_name", "标签", FieldType::Text),
+    column("tariff_type", "付费类型", FieldType::SingleSelect),
+    column("brief_desc", "简介", FieldType::Text),
+    column("icon_url", "图标", FieldType::Text),
+    column("is_pay", "付费", FieldType::Checkbox),
+    column("release_countries", "发布地区", FieldType::MultiSelect),
+    column("main_device_codes", "设备类型", FieldType::MultiSelect),
+    column("listed_at", "上架时间", FieldType::Date),
+    column("created_at", "收录时间", FieldType::Date),
+];
+
+const METRIC_COLUMNS: &[Column] = &[
+    column("app_id", "应用ID", FieldType::Text),
+    column("name", "应用名", FieldType::Text),
+    column("pkg_name", "包名", FieldType::Text),
+    column("version", "版本", FieldType::Text),
+    column("version_code", "版本号", FieldType::Number),
+    column("size_bytes", "大小 (字节)", FieldType::Number),
+    column("download_count", "下载量", FieldType::Number),
+    column("info_score", "评分", FieldType::Number),
+    column("info_rate_count", "评分人数", FieldType::Number),
+    column("price", "价格", FieldType::Number),
+    column("target_sdk", "目标 SDK", FieldType::Number),
+    column("minsdk", "最低 SDK", FieldType::Number),
+    column("compile_sdk_version", "编译 SDK", FieldType::Number),
+    column("api_release_type", "API 发布类型", FieldType::SingleSelect),
+    column("metrics_created_at", "指标时间", FieldType::Date),
+];
+
+const RANKING_COLUMNS: &[Column] = &[
+    column("app_id", "应用ID", FieldType::Text),
+    column("rank", "排名", FieldType::Number),
+    column("name", "应用名", FieldType::Text),
+    column("pkg_name", "包名", FieldType::Text),
+    column("developer_name", "开发者", FieldType::Text),
+    column("kind_name", "分类", FieldType::SingleSelect),
+    column("download_count", "下载量", FieldType::Number),
+    column("average_rating", "平均星级", FieldType::Number),
+    column("total_star_rating_count", "星级评分人数", FieldType::Number),
+];
+
+const SUBSTANCE_COLUMNS: &[Column] = &[
+    column("substance_id", "专题ID", FieldType::Text),
+    column("title", "标题", FieldType::Text),
+    column("subtitle", "副标题", FieldType::Text),
+    column("created_at", "收录时间", FieldType::Date),
+];
+
+fn decimal(value: &Decimal) -> Option<f64> {
+    value.to_f64()
+}
+
+/// 一个应用在应用 / 指标 / 排行表里对应的记录, `rank` 只有排行表用到
+fn app_record(table: FeishuTable, app: &FullAppInfo, rank: u32) -> JsonValue {
+    let data = match table {
+        FeishuTable::Metrics => json!({
+            "app_id": app.app_id,
+            "name": app.name,
+            "pkg_name": app.pkg_name,
+            "version": app.version,
+            "version_code": app.version_code,
+            "size_bytes": app.size_bytes,
+            "download_count": app.download_count,
+            "info_score": decimal(&app.info_score),
+            "info_rate_count": app.info_rate_count,
+            "price": decimal(&app.price),
+            "target_sdk": app.target_sdk,
+            "minsdk": app.minsdk,
+            "compile_sdk_version": app.compile_sdk_version,
+            "api_release_type": app.api_release_type,
+            "metrics_created_at": app.metrics_created_at.timestamp_millis(),
+        }),
+        FeishuTable::Rankings => json!({
+            "app_id": app.app_id,
+            "rank": rank,
+            "name": app.name,
+            "pkg_name": app.pkg_name,
+            "developer_name": app.developer_name,
+            "kind_name": app.kind_name,
+            "download_count": app.download_count,
+            "average_rating": app.average_rating.as_ref().and_then(decimal),
+            "total_star_rating_count": app.total_star_rating_count,
+        }),
+        // 专题表不会走到这里
+        FeishuTable::Apps | FeishuTable::Substances => json!({
+            "app_id": app.app_id,
+            "name": app.name,
+            "pkg_name": app.pkg_name,
+            "developer_name": app.developer_name,
+            "dev_id": app.dev_id,
+            "kind_name": app.kind_name,
+            "kind_type_name": app.kind_type_name,
+            "tag_name": app.tag_name,
+            "tariff_type": app.tariff_type,
+            "brief_desc": app.brief_desc,
+            "icon_url": app.icon_url,
+            "is_pay": app.is_pay,
+            "release_countries": app.release_countries,
+            "main_device_codes": app.main_device_codes,
+            "listed_at": app.listed_at.timestamp_millis(),
+            "created_at": app.created_at.timestamp_millis(),
+        }),
+    };
+    json!({"primaryID": app.app_id, "data": data})
+}
+
+fn substance_record(substance: &ShortSubstanceInfo) -> JsonValue {
+    json!({
+        "primaryID": substance.substance_id,
+        "data": {
+            "substance_id": substance.substance_id,
+            "title": substance.title,
+            "subtitle": substance.subtitle,
+            "created_at": substance.created_at.timestamp_millis(),
+        },
+    })
+}
+
+/// 连接器错误, 按飞书的约定以 HTTP 200 + code 返回
+#[derive(Debug)]
+struct FeishuError {
+    code: i64,
+    msg: String,
+}
+
+impl FeishuError {
+    fn config(msg: impl ToString) -> Self {
+        Self {
+            code: CODE_CONFIG_ERROR,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn auth(msg: impl ToString) -> Self {
+        Self {
+            code: CODE_AUTH_FAILED,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn internal(msg: impl ToString) -> Self {
+        Self {
+            code: CODE_INTERNAL_ERROR,
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for FeishuError {
+    fn into_response(self) -> Response {
+        Json(json!({"code": self.code, "msg": self.msg})).into_response()
+    }
+}
+
+type FeishuResult = Result<Json<JsonValue>, FeishuError>;
+
+fn success(data: JsonValue) -> FeishuResult {
+    Ok(Json(json!({"code": CODE_OK, "msg": "", "data": data})))
+}
+
+/// 飞书发来的请求体
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct FeishuRequest {
+    /// JSON 字符串, 包含 datasourceConfig / transactionID / pageToken / maxPageSize
+    pub params: String,
+    /// JSON 字符串, 多维表格和租户信息, 这里不使用
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestParams {
+    #[serde(default)]
+    datasource_config: Option<String>,
+    /// 同一次同步的请求共用一个
+    #[serde(default, rename = "transactionID")]
+    transaction_id: Option<String>,
+    #[serde(default)]
+    page_token: Option<String>,
+    #[serde(default)]
+    max_page_size: Option<u32>,
+}
+
+/// 数据源配置
+#[derive(Debug, Default, Deserialize)]
+struct DatasourceConfig {
+    #[serde(default)]
+    table: FeishuTable,
+    /// 排除华为自家应用
+    #[serde(default)]
+    exclude_huawei: bool,
+    /// 排除元服务
+    #[serde(default)]
+    exclude_atomic: bool,
+}
+
+impl RequestParams {
+    /// 实际的每页条数
+    ///
+    /// 数据库查询最多返回 `max_limit` 条, 页大小超过它时翻页会跳过每页多出来的部分
+    fn page_size(&self, max_limit: u32) -> u32 {
+        self.max_page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE.min(max_limit).max(1))
+    }
+
+    fn parse(body: &[u8]) -> Result<Self, FeishuError> {
+        let request: FeishuRequest = serde_json::from_slice(body)
+            .map_err(|e| FeishuError::config(format!("请求体格式不正确: {e}")))?;
+        serde_json::from_str(&request.params)
+            .map_err(|e| FeishuError::config(format!("params 格式不正确: {e}")))
+    }
+
+    /// 没有配置时同步应用表
+    fn config(&self) -> Result<DatasourceConfig, FeishuError> {
+        match self.datasource_config.as_deref().map(str::trim) {
+            None | Some("") => Ok(DatasourceConfig::default()),
+            Some(text) => serde_json::from_str(text).map_err(|e| {
+                FeishuError::config(format!(
+                    "datasourceConfig 格式不正确: {e} (table 可选 apps / metrics / rankings / substances)"
+                ))
+            }),
+        }
+    }
+}
+
+/// 分页游标, 格式为 `<表名>:<页码>`, 换了数据源配置后旧游标不能继续用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PageToken {
+    table: FeishuTable,
+    page: u32,
+}
+
+impl PageToken {
+    fn encode(&self) -> String {
+        format!("{}:{}", self.table.name(), self.page)
+    }
+
+    /// 没有游标时从第一页开始
+    fn decode(token: Option<&str>, table: FeishuTable) -> Result<Self, FeishuError> {
+        let Some(token) = token.filter(|token| !token.is_empty()) else {
+            return Ok(Self { table, page: 1 });
+        };
+        let parsed = token
+            .split_once(':')
+            .and_then(|(name, page)| Some((FeishuTable::from_name(name)?, page.parse().ok()?)))
+            .filter(|(_, page)| *page >= 1);
+        match parsed {
+            Some((token_table, page)) if token_table == table => Ok(Self { table, page }),
+            Some(_) => Err(FeishuError::config("pageToken 和数据源配置的表不一致, 请重新同步")),
+            None => Err(FeishuError::config(format!("无法识别的 pageToken: {token}"))),
+        }
+    }
+}
+
+/// 校验签名, `now` 为当前的 unix 时间戳 (秒)
+///
+/// 签名通过后把 nonce 记进 `seen`, 过期之前同一个 nonce 再来就拒绝
+fn verify_signature(
+    secret: &str,
+    max_age_seconds: u64,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: i64,
+    seen: &DashMap<String, i64>,
+) -> Result<(), FeishuError> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| FeishuError::auth(format!("缺少请求头 {name}")))
+    };
+    let timestamp = header(TIMESTAMP_HEADER)?;
+    let nonce = header(NONCE_HEADER)?;
+    let signature = header(SIGNATURE_HEADER)?;
+
+    let sent_at: i64 = timestamp
+        .parse()
+        .map_err(|_| FeishuError::auth("请求时间戳格式不正确"))?;
+    if now.abs_diff(sent_at) > max_age_seconds {
+        return Err(FeishuError::auth("请求已过期"));
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(timestamp.as_bytes());
+    hasher.update(nonce.as_bytes());
+    hasher.update(secret.as_bytes());
+    hasher.update(body);
+    let expected = format!("{:x}", hasher.finalize());
+    let signature = signature.to_ascii_lowercase();
+    // 逐字节比较完, 不提前返回
+    let matched = expected.len() == signature.len()
+        && expected
+            .bytes()
+            .zip(signature.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !matched {
+        return Err(FeishuError::auth("签名校验失败"));
+    }
+
+    if seen.len() >= NONCE_PRUNE_THRESHOLD {
+        seen.retain(|_, expires_at| *expires_at >= now);
+    }
+    let expires_at = sent_at.saturating_add(max_age_seconds as i64);
+    match seen.entry(nonce.to_string()) {
+        Entry::Occupied(entry) if *entry.get() >= now => {
+            Err(FeishuError::auth("请求重复 (nonce 已使用)"))
+        }
+        entry => {
+            entry.insert(expires_at);
+            Ok(())
+        }
+    }
+}
+
+/// 校验签名并解析请求参数
+fn accept(
+    config: &FeishuConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<RequestParams, FeishuError> {
+    let Some(secret) = config.secret.as_deref().filter(|secret| !secret.is_empty()) else {
+        event!(Level::WARN, "没有配置 serve.feishu.secret, 拒绝飞书请求");
+        return Err(FeishuError::auth("服务端没有配置飞书签名密钥, 不提供数据"));
+    };
+    let now = chrono::Utc::now().timestamp();
+    let max_age = config.signature_max_age_seconds;
+    if let Err(e) = verify_signature(secret, max_age, headers, body, now, &SEEN_NONCES) {
+        event!(Level::WARN, "飞书请求签名校验失败: {}", e.msg);
+        return Err(e);
+    }
+    RequestParams::parse(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/feishu/meta.json",
+    responses(
+        (status = 200, description = "数据连接器元信息, 包含 table_meta / records 的地址", body = serde_json::Value)
+    ),
+    tag = "飞书集成"
+)]
+/// 飞书数据连接器元信息
+pub async fn feishu_meta(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    event!(Level::INFO, "Feishu 正在获取元信息");
+    let mut meta = json!({
+        "schemaVersion": 1,
+        "version": env!("CARGO_PKG_VERSION"),
+        "type": "data_connector",
+        "protocol": {
+            "type": "http",
+            "httpProtocol": {
+                "uris": [
+                    {"type": "tableMeta", "uri": "/api/v0/feishu/table_meta"},
+                    {"type": "records", "uri": "/api/v0/feishu/records"},
+                ],
+            },
+        },
+    });
+    if let Some(url) = &state.cfg.feishu().config_ui_url {
+        meta["extraData"] = json!({"dataSourceConfigUiUri": url});
+    }
+    Json(meta)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/feishu/table_meta",
+    request_body = FeishuRequest,
+    responses(
+        (status = 200, description = "表名和字段定义; 出错时 code 不为 0, msg 为原因", body = serde_json::Value)
+    ),
+    tag = "飞书集成"
+)]
+/// 飞书表格字段定义
+///
+/// 按数据源配置里的 `table` 返回表名和字段, 第一个字段是主键
+pub async fn feishu_table_meta(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> FeishuResult {
+    let params = accept(state.cfg.feishu(), &headers, &body)?;
+    let table = params.config()?.table;
+    event!(Level::INFO, "Feishu 正在获取表格元信息: {}", table.name());
+
+    let fields: Vec<JsonValue> = table
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            json!({
+                "fieldId": column.id,
+                "fieldName": column.name,
+                "fieldType": column.field_type.code(),
+                "isPrimary": index == 0,
+                "description": "",
+                "property": column.field_type.property(),
+            })
+        })
+        .collect();
+    success(json!({"tableName": table.title(), "fields": fields}))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/feishu/records",
+    request_body = FeishuRequest,
+    responses(
+        (status = 200, description = "一页记录和 nextPageToken / hasMore; 出错时 code 不为 0, msg 为原因", body = serde_json::Value)
+    ),
+    tag = "飞书集成"
+)]
+/// 飞书分页拉取记录
+///
+/// 应用和指标表按收录时间正序翻页, 同步过程中新收录的应用会排在最后, 不会漏掉;
+/// 排行表按下载量倒序, 排名为同步时的名次
+pub async fn feishu_records(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> FeishuResult {
+    let params = accept(state.cfg.feishu(), &headers, &body)?;
+    let config = params.config()?;
+    let token = PageToken::decode(params.page_token.as_deref(), config.table)?;
+    let page_size = params.page_size(get_max_limit());
+    event!(
+        Level::INFO,
+        transaction_id = params.transaction_id.as_deref().unwrap_or_default(),
+        "Feishu 正在获取记录: {} 第 {} 页",
+        config.table.name(),
+        token.page
+    );
+
+    let (records, total_pages) = match config.table {
+        FeishuTable::Substances => {
+            // 专题列表的页码从 0 开始
+            let substances = state
+                .db
+                .get_substance_list_paged(token.page - 1, page_size, "created_at", false)
+                .await
+                .map_err(|e| {
+                    event!(Level::WARN, "飞书同步获取专题失败: {e}");
+                    FeishuError::internal("获取专题失败")
+                })?;
+            let records = substances.data.iter().map(substance_record).collect();
+            (records, substances.total_pages)
+        }
+        table => {
+            let (sort_key, desc) = match table {
+                FeishuTable::Rankings => ("download_count", true),
+                _ => ("created_at", false),
+            };
+            let apps = state
+                .db
+                .get_app_list_paged(
+                    token.page,
+                    page_size,
+                    sort_key,
+                    desc,
+                    None,
+                    None,
+                    config.exclude_huawei,
+                    config.exclude_atomic,
+                )
+                .await
+                .map_err(|e| {
+                    event!(Level::WARN, "飞书同步获取应用失败: {e}");
+                    FeishuError::internal("获取应用失败")
+                })?;
+            let offset = (token.page - 1) * page_size;
+            let records = apps
+                .data
+                .iter()
+                .zip(offset + 1..)
+                .map(|(app, rank)| app_record(table, app, rank))
+                .collect::<Vec<_>>();
+            (records, apps.total_pages)
+        }
+    };
+
+    let has_more = token.page < total_pages;
+    let next_page_token = if has_more {
+        PageToken {
+            table: config.table,
+            page: token.page + 1,
+        }
+        .encode()
+    } else {
+        String::new()
+    };
+    success(json!({
+        "nextPageToken": next_page_token,
+        "hasMore": has_more,
+        "records": records,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn signed_headers(secret: &str, timestamp: i64, body: &[u8]) -> HeaderMap {
+        let mut hasher = Sha256::new();
+        hasher.update(timestamp.to_string().as_bytes());
+        hasher.update(b"nonce");
+        hasher.update(secret.as_bytes());
+        hasher.update(body);
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+        headers.insert(NONCE_HEADER, HeaderValue::from_static("nonce"));
+        headers.insert(
+            SIGNATURE_HEADER,
+            HeaderValue::from_str(&format!("{:x}", hasher.finalize())).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn signature() {
+        let body = br#"{"params":"{}"}"#;
+        let headers = signed_headers("secret", 1_000, body);
+        let seen = DashMap::new();
+        assert!(verify_signature("secret", 300, &headers, body, 1_100, &seen).is_ok());
+        // 密钥 / 请求体不对, 或者过期
+        assert!(verify_signature("other", 300, &headers, body, 1_100, &seen).is_err());
+        assert!(verify_signature("secret", 300, &headers, b"{}", 1_100, &seen).is_err());
+        assert!(verify_signature("secret", 300, &headers, body, 1_400, &seen).is_err());
+        let missing = HeaderMap::new();
+        assert!(verify_signature("secret", 300, &missing, body, 1_000, &seen).is_err());
+    }
+
+    #[test]
+    fn replayed_nonce() {
+        let body = br#"{"params":"{}"}"#;
+        let seen = DashMap::new();
+        let headers = signed_headers("secret", 1_000, body);
+        assert!(verify_signature("secret", 300, &headers, body, 1_000, &seen).is_ok());
+        // 有效期内同一个请求再发一次
+        assert!(verify_signature("secret", 300, &headers, body, 1_010, &seen).is_err());
+        // 签名不对的请求不占用 nonce
+        let other = signed_headers("secret", 2_000, body);
+        assert!(verify_signature("other", 300, &other, body, 2_000, &seen).is_err());
+        assert!(verify_signature("secret", 300, &other, body, 2_000, &seen).is_ok());
+    }
+
+    #[test]
+    fn page_token() {
+        let token = PageToken {
+            table: FeishuTable::Rankings,
+            page: 3,
+        };
+        let decoded = PageToken::decode(Some(&token.encode()), FeishuTable::Rankings);
+        assert_eq!(decoded.unwrap(), token);
+        assert_eq!(PageToken::decode(None, FeishuTable::Apps).unwrap().page, 1);
+        assert!(PageToken::decode(Some("rankings:3"), FeishuTable::Apps).is_err());
+        assert!(PageToken::decode(Some("apps:0"), FeishuTable::Apps).is_err());
+        assert!(PageToken::decode(Some("garbage"), FeishuTable::Apps).is_err());
+    }
+
+    #[test]
+    fn params() {
+        let body = json!({
+            "params": json!({
+                "datasourceConfig": r#"{"table": "metrics", "exclude_atomic": true}"#,
+                "transactionID": "t1",
+                "pageToken": "metrics:2",
+                "maxPageSize": 50,
+            })
+            .to_string(),
+        })
+        .to_string();
+        let params = RequestParams::parse(body.as_bytes()).unwrap();
+        let config = params.config().unwrap();
+        assert_eq!(config.table, FeishuTable::Metrics);
+        assert!(config.exclude_atomic && !config.exclude_huawei);
+        assert_eq!(params.max_page_size, Some(50));
+        assert_eq!(RequestParams::default().config().unwrap().table, FeishuTable::Apps);
+    }
+
+    #[test]
+    fn page_size_respects_max_limit() {
+        let params = |max_page_size| RequestParams {
+            max_page_size,
+            ..Default::default()
+        };
+        assert_eq!(params(Some(50)).page_size(100), 50);
+        // 超过 database.max_limit 时按 max_limit 分页, 否则每页超出的部分永远同步不到
+        assert_eq!(params(Some(200)).page_size(100), 100);
+        assert_eq!(params(Some(500)).page_size(1000), MAX_PAGE_SIZE);
+        assert_eq!(params(None).page_size(20), 20);
+        assert_eq!(params(Some(0)).page_size(100), 1);
+        assert_eq!(params(Some(10)).page_size(0), 1);
+    }
+
+    #[test]
+    fn columns_match_records() {
+        for table in FeishuTable::ALL {
+            let columns = table.columns();
+            assert_eq!(columns[0].field_type, FieldType::Text, "{} 的主键", table.name());
+            let mut ids: Vec<_> = columns.iter().map(|column| column.id).collect();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), columns.len(), "{} 的字段重复", table.name());
+        }
+        let record = substance_record(&ShortSubstanceInfo {
+            substance_id: "s1".to_string(),
+            title: "专题".to_string(),
+            subtitle: None,
+            created_at: chrono::Local::now(),
+        });
+        let data = record["data"].as_object().unwrap();
+        assert_eq!(data.len(), SUBSTANCE_COLUMNS.len());
+        assert!(SUBSTANCE_COLUMNS.iter().all(|column| data.contains_key(column.id)));
+    }
+}
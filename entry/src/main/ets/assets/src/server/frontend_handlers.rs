@@ -45,3 +45,16 @@ const SWAGGER_UI_HTML: &str = include_str!("../../assets/html/swagger_ui.html");
 pub async fn serve_swagger_ui() -> impl IntoResponse {
     Html(SWAGGER_UI_HTML).into_response()
 }
+
+/// GraphiQL 页面, 调试 `/api/graphql`
+pub async fn serve_graphiql() -> impl IntoResponse {
+    static GRAPHIQL_HTML: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+    let html = GRAPHIQL_HTML.get_or_init(|| {
+        async_graphql::http::GraphiQLSource::build()
+            .endpoint("/api/graphql")
+            .title("鸿蒙应用市场 GraphQL")
+            .finish()
+    });
+    Html(html.as_str()).into_response()
+}
@@ -0,0 +1,1038 @@
+//! GraphQL 接口 (`/api/graphql`)
+//!
+//! 查询逻辑建立在 [`Storage`](crate::db::storage::Storage) 已有的查询上, 只读.
+//! 应用的指标 / 专题、专题里的应用等关联数据通过 [`DbLoader`] 批量加载,
+//! 一页应用的关联数据各只查一次, 不会按应用逐个查询 (SQLite 没有批量查询, 退化为逐个查本地文件).
+//!
+//! 列表字段的复杂度按请求的数量放大, 超过 `serve.graphql_max_complexity` 或
+//! `serve.graphql_max_depth` 的查询在执行前就会被拒绝
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, InputObject, Json as GqlJson, Object,
+    Result as GqlResult, Schema, SimpleObject,
+    dataloader::{DataLoader, Loader},
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{Extension, extract::State};
+use chrono::{DateTime, Local};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use tracing::{Level, event};
+
+use crate::{
+    db::{
+        DbSearch,
+        expand::Include,
+        filter::AppFilter,
+        query::get_max_limit,
+    },
+    model::{AppMetric, AppQuery, FullAppInfo, FullSubstanceInfo, ShortSubstanceInfo},
+    server::state::AppState,
+};
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// 分页 / 排行 / 指标条数的上限
+const MAX_PAGE_SIZE: u32 = 100;
+/// 批量查应用时每次最多带多少个 app_id, 另外不超过 `database.max_limit`
+const APP_BATCH_SIZE: usize = 100;
+/// 数量不固定的列表 (专题里的应用、应用所在的专题) 按这么多项估算复杂度
+const LIST_COMPLEXITY: usize = 20;
+
+pub fn build_schema(max_complexity: usize, max_depth: usize) -> ApiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_complexity(max_complexity)
+        .limit_depth(max_depth)
+        .finish()
+}
+
+/// 执行 GraphQL 请求, 每个请求单独创建 loader
+pub async fn graphql_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<ApiSchema>,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let loader = DataLoader::new(
+        DbLoader {
+            state: state.clone(),
+        },
+        tokio::spawn,
+    );
+    schema
+        .execute(request.into_inner().data(state).data(loader))
+        .await
+        .into()
+}
+
+fn app_state<'a>(ctx: &Context<'a>) -> &'a Arc<AppState> {
+    ctx.data_unchecked::<Arc<AppState>>()
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<DbLoader> {
+    ctx.data_unchecked::<DataLoader<DbLoader>>()
+}
+
+/// 记下数据库错误, 返回给客户端的只有笼统的原因
+fn db_error(what: &'static str) -> impl FnOnce(anyhow::Error) -> async_graphql::Error {
+    move |e| {
+        event!(Level::WARN, "GraphQL {what}失败: {e:?}");
+        async_graphql::Error::new(format!("{what}失败"))
+    }
+}
+
+fn decimal(value: &Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+// ---- 批量加载 ----
+
+/// 按 app_id 加载应用
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AppKey(String);
+
+/// 加载应用最近 `limit` 条指标, SQLite 按包名查
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MetricsKey {
+    app_id: String,
+    pkg_name: String,
+    limit: u32,
+}
+
+/// 加载收录了应用的专题
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AppSubstancesKey(String);
+
+/// 加载专题包含的 app_id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubstanceAppsKey(String);
+
+pub struct DbLoader {
+    state: Arc<AppState>,
+}
+
+impl Loader<AppKey> for DbLoader {
+    type Value = FullAppInfo;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[AppKey]) -> Result<HashMap<AppKey, FullAppInfo>, Self::Error> {
+        let db = &self.state.db;
+        let mut apps = HashMap::new();
+        let Some(pg) = self.state.postgres() else {
+            for key in keys {
+                let query = AppQuery::app_id(&key.0);
+                if db.app_exists(&query).await {
+                    let app = db.get_full_app_info(&query).await.map_err(db_error("查询应用"))?;
+                    apps.insert(key.clone(), app);
+                }
+            }
+            return Ok(apps);
+        };
+
+        let batch_size = APP_BATCH_SIZE.min(get_max_limit() as usize).max(1);
+        for chunk in keys.chunks(batch_size) {
+            let app_ids: Vec<String> = chunk.iter().map(|key| key.0.clone()).collect();
+            let batch = pg
+                .get_full_app_infos(&app_ids)
+                .await
+                .map_err(db_error("批量查询应用"))?;
+            apps.extend(batch.into_iter().map(|app| (AppKey(app.app_id.clone()), app)));
+        }
+        Ok(apps)
+    }
+}
+
+impl Loader<MetricsKey> for DbLoader {
+    type Value = Vec<AppMetric>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[MetricsKey],
+    ) -> Result<HashMap<MetricsKey, Vec<AppMetric>>, Self::Error> {
+        let mut result = HashMap::new();
+        let Some(db) = self.state.postgres() else {
+            for key in keys {
+                let mut metrics = self
+                    .state
+                    .db
+                    .get_app_metrics_by_pkg_id(&key.pkg_name)
+                    .await
+                    .map_err(db_error("查询应用指标"))?;
+                metrics.truncate(key.limit as usize);
+                result.insert(key.clone(), metrics);
+            }
+            return Ok(result);
+        };
+
+        // 同一个查询里 limit 一般都一样, 按 limit 分组各查一次
+        let mut by_limit: HashMap<u32, Vec<&MetricsKey>> = HashMap::new();
+        for key in keys {
+            by_limit.entry(key.limit).or_default().push(key);
+        }
+        for (limit, keys) in by_limit {
+            let app_ids: Vec<String> = keys.iter().map(|key| key.app_id.clone()).collect();
+            let mut included = db
+                .get_app_includes(&app_ids, &[Include::Metrics], limit)
+                .await
+                .map_err(db_error("批量查询应用指标"))?;
+            for key in keys {
+                let metrics = included
+                    .remove(&key.app_id)
+                    .and_then(|included| included.metrics)
+                    .unwrap_or_default();
+                result.insert(key.clone(), metrics);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Loader<AppSubstancesKey> for DbLoader {
+    type Value = Vec<ShortSubstanceInfo>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[AppSubstancesKey],
+    ) -> Result<HashMap<AppSubstancesKey, Vec<ShortSubstanceInfo>>, Self::Error> {
+        let Some(db) = self.state.postgres() else {
+            return Err(async_graphql::Error::new("查询应用所在的专题只有 Postgres 支持"));
+        };
+        let app_ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let mut included = db
+            .get_app_includes(&app_ids, &[Include::Substances], 0)
+            .await
+            .map_err(db_error("批量查询应用所在的专题"))?;
+        Ok(keys
+            .iter()
+            .map(|key| {
+                let substances = included
+                    .remove(&key.0)
+                    .and_then(|included| included.substances)
+                    .unwrap_or_default();
+                (key.clone(), substances)
+            })
+            .collect())
+    }
+}
+
+impl Loader<SubstanceAppsKey> for DbLoader {
+    type Value = Vec<String>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[SubstanceAppsKey],
+    ) -> Result<HashMap<SubstanceAppsKey, Vec<String>>, Self::Error> {
+        let mut result = HashMap::new();
+        let Some(db) = self.state.postgres() else {
+            for key in keys {
+                let substance = self
+                    .state
+                    .db
+                    .get_substance_by_id(&key.0)
+                    .await
+                    .map_err(db_error("查询专题"))?;
+                let app_ids = substance
+                    .map(|substance| substance.apps.into_iter().map(|app| app.app_id).collect())
+                    .unwrap_or_default();
+                result.insert(key.clone(), app_ids);
+            }
+            return Ok(result);
+        };
+
+        let substance_ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let mut app_ids = db
+            .get_substance_app_ids(&substance_ids)
+            .await
+            .map_err(db_error("批量查询专题里的应用"))?;
+        for key in keys {
+            result.insert(key.clone(), app_ids.remove(&key.0).unwrap_or_default());
+        }
+        Ok(result)
+    }
+}
+
+/// 按顺序加载一组应用, 数据库里没有的跳过
+async fn load_apps(ctx: &Context<'_>, app_ids: Vec<String>) -> GqlResult<Vec<App>> {
+    let mut apps = loader(ctx)
+        .load_many(app_ids.iter().cloned().map(AppKey))
+        .await?;
+    Ok(app_ids
+        .into_iter()
+        .filter_map(|app_id| apps.remove(&AppKey(app_id)))
+        .map(App)
+        .collect())
+}
+
+// ---- 类型 ----
+
+/// 应用列表的排序字段
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq, Default)]
+pub enum AppSort {
+    #[default]
+    CreatedAt,
+    ListedAt,
+    DownloadCount,
+    AverageRating,
+    TotalStarRatingCount,
+    Price,
+    SizeBytes,
+    VersionCode,
+    MetricsCreatedAt,
+}
+
+impl AppSort {
+    fn column(self) -> &'static str {
+        match self {
+            Self::CreatedAt => "created_at",
+            Self::ListedAt => "listed_at",
+            Self::DownloadCount => "download_count",
+            Self::AverageRating => "average_rating",
+            Self::TotalStarRatingCount => "total_star_rating_count",
+            Self::Price => "price",
+            Self::SizeBytes => "size_bytes",
+            Self::VersionCode => "version_code",
+            Self::MetricsCreatedAt => "metrics_created_at",
+        }
+    }
+}
+
+/// 可以搜索的字段
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
+pub enum SearchField {
+    Name,
+    PkgName,
+    AppId,
+    DevId,
+    DeveloperName,
+    KindName,
+    KindTypeName,
+    TagName,
+}
+
+impl SearchField {
+    fn column(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::PkgName => "pkg_name",
+            Self::AppId => "app_id",
+            Self::DevId => "dev_id",
+            Self::DeveloperName => "developer_name",
+            Self::KindName => "kind_name",
+            Self::KindTypeName => "kind_type_name",
+            Self::TagName => "tag_name",
+        }
+    }
+}
+
+/// 搜索条件, 和 v0 应用列表的 search_key / search_value 一样
+#[derive(Debug, InputObject)]
+pub struct AppSearch {
+    pub field: SearchField,
+    pub value: String,
+    /// 精确匹配, 默认模糊匹配
+    #[graphql(default)]
+    pub exact: bool,
+}
+
+/// 应用列表的查询条件
+struct AppListArgs {
+    page: u32,
+    page_size: u32,
+    sort: AppSort,
+    desc: bool,
+    search: Option<DbSearch>,
+    filter: Option<AppFilter>,
+    exclude_huawei: bool,
+    exclude_atomic: bool,
+}
+
+async fn list_apps(state: &AppState, args: AppListArgs) -> GqlResult<AppPage> {
+    if args.filter.is_some() && state.postgres().is_none() {
+        return Err(async_graphql::Error::new("filter 只有 Postgres 支持"));
+    }
+    let page = state
+        .db
+        .get_app_list_paged(
+            args.page.max(1),
+            args.page_size.clamp(1, MAX_PAGE_SIZE),
+            args.sort.column(),
+            args.desc,
+            args.search,
+            args.filter.as_ref(),
+            args.exclude_huawei,
+            args.exclude_atomic,
+        )
+        .await
+        .map_err(db_error("查询应用列表"))?;
+    Ok(AppPage {
+        total: page.total_count,
+        page: page.page,
+        page_size: page.page_size,
+        total_pages: page.total_pages,
+        items: page.data.into_iter().map(App).collect(),
+    })
+}
+
+/// 一页应用
+#[derive(SimpleObject)]
+pub struct AppPage {
+    pub total: u32,
+    pub page: u32,
+    pub page_size: u32,
+    pub total_pages: u32,
+    pub items: Vec<App>,
+}
+
+/// 一页专题
+#[derive(SimpleObject)]
+pub struct SubstancePage {
+    pub total: u32,
+    /// 从 1 开始
+    pub page: u32,
+    pub page_size: u32,
+    pub total_pages: u32,
+    pub items: Vec<Substance>,
+}
+
+/// 应用, 指标和评分为最新一次的数据
+pub struct App(FullAppInfo);
+
+#[Object]
+impl App {
+    async fn app_id(&self) -> &str {
+        &self.0.app_id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn pkg_name(&self) -> &str {
+        &self.0.pkg_name
+    }
+
+    async fn developer(&self) -> Developer {
+        Developer {
+            id: self.0.dev_id.clone(),
+            name: self.0.developer_name.clone(),
+            en_name: Some(self.0.dev_en_name.clone()).filter(|name| !name.is_empty()),
+            app_count: None,
+        }
+    }
+
+    async fn kind_name(&self) -> &str {
+        &self.0.kind_name
+    }
+
+    async fn kind_type_name(&self) -> &str {
+        &self.0.kind_type_name
+    }
+
+    async fn tag_name(&self) -> Option<&str> {
+        self.0.tag_name.as_deref()
+    }
+
+    async fn icon_url(&self) -> &str {
+        &self.0.icon_url
+    }
+
+    async fn brief_desc(&self) -> &str {
+        &self.0.brief_desc
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn tariff_type(&self) -> &str {
+        &self.0.tariff_type
+    }
+
+    async fn is_pay(&self) -> bool {
+        self.0.is_pay
+    }
+
+    async fn release_countries(&self) -> &[String] {
+        &self.0.release_countries
+    }
+
+    async fn main_device_codes(&self) -> &[String] {
+        &self.0.main_device_codes
+    }
+
+    async fn listed_at(&self) -> DateTime<Local> {
+        self.0.listed_at
+    }
+
+    /// 收录时间
+    async fn created_at(&self) -> DateTime<Local> {
+        self.0.created_at
+    }
+
+    async fn version(&self) -> &str {
+        &self.0.version
+    }
+
+    async fn version_code(&self) -> i64 {
+        self.0.version_code
+    }
+
+    async fn size_bytes(&self) -> i64 {
+        self.0.size_bytes
+    }
+
+    async fn download_count(&self) -> i64 {
+        self.0.download_count
+    }
+
+    async fn price(&self) -> f64 {
+        decimal(&self.0.price)
+    }
+
+    async fn target_sdk(&self) -> i32 {
+        self.0.target_sdk
+    }
+
+    async fn minsdk(&self) -> i32 {
+        self.0.minsdk
+    }
+
+    /// 最新的星级评分, 没有评分数据时为 null
+    async fn rating(&self) -> Option<Rating> {
+        Rating::from_app(&self.0)
+    }
+
+    /// 最近 `limit` 条指标, 新的在前, 最多 100 条
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn metrics(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10)] limit: u32,
+    ) -> GqlResult<Vec<MetricPoint>> {
+        let key = MetricsKey {
+            app_id: self.0.app_id.clone(),
+            pkg_name: self.0.pkg_name.clone(),
+            limit: limit.clamp(1, MAX_PAGE_SIZE),
+        };
+        let metrics = loader(ctx).load_one(key).await?.unwrap_or_default();
+        Ok(metrics.iter().map(MetricPoint::from).collect())
+    }
+
+    /// 收录了这个应用的专题 (只有 Postgres 支持)
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn substances(&self, ctx: &Context<'_>) -> GqlResult<Vec<Substance>> {
+        let key = AppSubstancesKey(self.0.app_id.clone());
+        let substances = loader(ctx).load_one(key).await?.unwrap_or_default();
+        Ok(substances.into_iter().map(Substance::from).collect())
+    }
+}
+
+/// 开发者
+pub struct Developer {
+    id: String,
+    name: String,
+    en_name: Option<String>,
+    /// 已经知道的应用数量, 没有时查询
+    app_count: Option<i64>,
+}
+
+impl Developer {
+    fn app_list_args(&self, page: u32, page_size: u32, sort: AppSort, desc: bool) -> AppListArgs {
+        AppListArgs {
+            page,
+            page_size,
+            sort,
+            desc,
+            search: Some(DbSearch::new(
+                "dev_id".to_string(),
+                self.id.clone(),
+                true,
+                true,
+            )),
+            filter: None,
+            exclude_huawei: false,
+            exclude_atomic: false,
+        }
+    }
+}
+
+#[Object]
+impl Developer {
+    async fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn en_name(&self) -> Option<&str> {
+        self.en_name.as_deref()
+    }
+
+    async fn app_count(&self, ctx: &Context<'_>) -> GqlResult<i64> {
+        if let Some(count) = self.app_count {
+            return Ok(count);
+        }
+        let args = self.app_list_args(1, 1, AppSort::default(), false);
+        Ok(list_apps(app_state(ctx), args).await?.total as i64)
+    }
+
+    /// 开发者的应用
+    #[graphql(complexity = "page_size as usize * child_complexity")]
+    async fn apps(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] page_size: u32,
+        #[graphql(default)] sort: AppSort,
+        #[graphql(default)] desc: bool,
+    ) -> GqlResult<AppPage> {
+        list_apps(app_state(ctx), self.app_list_args(page, page_size, sort, desc)).await
+    }
+}
+
+/// 专题
+pub struct Substance {
+    id: String,
+    title: String,
+    subtitle: Option<String>,
+    name: Option<String>,
+    created_at: DateTime<Local>,
+    /// 查专题详情时已经带了应用列表, 列表查询时需要再加载
+    app_ids: Option<Vec<String>>,
+}
+
+impl From<FullSubstanceInfo> for Substance {
+    fn from(substance: FullSubstanceInfo) -> Self {
+        Self {
+            id: substance.substance_id,
+            title: substance.title,
+            subtitle: substance.subtitle,
+            name: substance.name,
+            created_at: substance.created_at,
+            app_ids: Some(substance.apps.into_iter().map(|app| app.app_id).collect()),
+        }
+    }
+}
+
+impl From<ShortSubstanceInfo> for Substance {
+    fn from(substance: ShortSubstanceInfo) -> Self {
+        Self {
+            id: substance.substance_id,
+            title: substance.title,
+            subtitle: substance.subtitle,
+            name: None,
+            created_at: substance.created_at,
+            app_ids: None,
+        }
+    }
+}
+
+#[Object]
+impl Substance {
+    async fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn title(&self) -> &str {
+        &self.title
+    }
+
+    async fn subtitle(&self) -> Option<&str> {
+        self.subtitle.as_deref()
+    }
+
+    async fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// 收录时间
+    async fn created_at(&self) -> DateTime<Local> {
+        self.created_at
+    }
+
+    /// 专题里的应用, 按 app_id 排序
+    #[graphql(complexity = "LIST_COMPLEXITY * child_complexity")]
+    async fn apps(&self, ctx: &Context<'_>) -> GqlResult<Vec<App>> {
+        let app_ids = match &self.app_ids {
+            Some(app_ids) => app_ids.clone(),
+            None => loader(ctx)
+                .load_one(SubstanceAppsKey(self.id.clone()))
+                .await?
+                .unwrap_or_default(),
+        };
+        load_apps(ctx, app_ids).await
+    }
+}
+
+/// 一条指标记录
+#[derive(SimpleObject)]
+pub struct MetricPoint {
+    pub version: String,
+    pub version_code: i64,
+    pub size_bytes: i64,
+    pub download_count: i64,
+    pub info_score: f64,
+    pub info_rate_count: i64,
+    pub price: f64,
+    pub target_sdk: i32,
+    pub minsdk: i32,
+    pub compile_sdk_version: i32,
+    pub api_release_type: String,
+    pub created_at: DateTime<Local>,
+}
+
+impl From<&AppMetric> for MetricPoint {
+    fn from(metric: &AppMetric) -> Self {
+        Self {
+            version: metric.version.clone(),
+            version_code: metric.version_code,
+            size_bytes: metric.size_bytes,
+            download_count: metric.download_count,
+            info_score: decimal(&metric.info_score),
+            info_rate_count: metric.info_rate_count,
+            price: decimal(&metric.price),
+            target_sdk: metric.target_sdk,
+            minsdk: metric.minsdk,
+            compile_sdk_version: metric.compile_sdk_version,
+            api_release_type: metric.api_release_type.clone(),
+            created_at: metric.created_at,
+        }
+    }
+}
+
+/// 星级评分
+#[derive(SimpleObject)]
+pub struct Rating {
+    pub average: f64,
+    pub full_average: Option<f64>,
+    pub star_1_count: i32,
+    pub star_2_count: i32,
+    pub star_3_count: i32,
+    pub star_4_count: i32,
+    pub star_5_count: i32,
+    pub total_count: i32,
+    pub only_star_count: i32,
+    pub source_type: Option<String>,
+    pub created_at: DateTime<Local>,
+}
+
+impl Rating {
+    fn from_app(app: &FullAppInfo) -> Option<Self> {
+        Some(Self {
+            average: app.average_rating.as_ref().map(decimal).unwrap_or_default(),
+            full_average: app.full_average_rating.as_ref().map(decimal),
+            star_1_count: app.star_1_rating_count.unwrap_or_default(),
+            star_2_count: app.star_2_rating_count.unwrap_or_default(),
+            star_3_count: app.star_3_rating_count.unwrap_or_default(),
+            star_4_count: app.star_4_rating_count.unwrap_or_default(),
+            star_5_count: app.star_5_rating_count.unwrap_or_default(),
+            total_count: app.total_star_rating_count.unwrap_or_default(),
+            only_star_count: app.only_star_count.unwrap_or_default(),
+            source_type: app.source_type.clone(),
+            created_at: app.rating_created_at?,
+        })
+    }
+}
+
+/// 排行榜类型
+#[derive(Debug, Enum, Copy, Clone, Eq, PartialEq)]
+pub enum RankingKind {
+    /// 按平均星级, value 为平均星级
+    Rating,
+    /// 最近更新, 没有 value
+    Recent,
+    /// 按下载量, value 为下载量
+    Downloads,
+}
+
+/// 排行榜里的一项
+#[derive(SimpleObject)]
+pub struct Ranking {
+    /// 从 1 开始
+    pub rank: u32,
+    pub value: Option<f64>,
+    pub app: App,
+}
+
+fn ranked(apps: impl IntoIterator<Item = (App, Option<f64>)>) -> Vec<Ranking> {
+    apps.into_iter()
+        .zip(1..)
+        .map(|((app, value), rank)| Ranking { rank, value, app })
+        .collect()
+}
+
+// ---- 查询入口 ----
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 按应用ID或包名查询应用, 二选一. 只查数据库, 没有收录时返回 null
+    async fn app(
+        &self,
+        ctx: &Context<'_>,
+        app_id: Option<String>,
+        pkg_name: Option<String>,
+    ) -> GqlResult<Option<App>> {
+        let query = match (app_id, pkg_name) {
+            (Some(app_id), None) => AppQuery::app_id(app_id),
+            (None, Some(pkg_name)) => AppQuery::pkg_name(pkg_name),
+            _ => return Err(async_graphql::Error::new("appId 和 pkgName 需要且只能给一个")),
+        };
+        let state = app_state(ctx);
+        if !state.db.app_exists(&query).await {
+            return Ok(None);
+        }
+        let app = state
+            .db
+            .get_full_app_info(&query)
+            .await
+            .map_err(db_error("查询应用"))?;
+        Ok(Some(App(app)))
+    }
+
+    /// 分页查询应用
+    ///
+    /// `filter` 和 v0 应用列表的 filter 一样是一棵 JSON 过滤树, 只有 Postgres 支持
+    #[graphql(complexity = "page_size as usize * child_complexity")]
+    #[allow(clippy::too_many_arguments)]
+    async fn apps(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] page_size: u32,
+        #[graphql(default)] sort: AppSort,
+        #[graphql(default)] desc: bool,
+        search: Option<AppSearch>,
+        filter: Option<GqlJson<AppFilter>>,
+        #[graphql(default)] exclude_huawei: bool,
+        #[graphql(default)] exclude_atomic: bool,
+    ) -> GqlResult<AppPage> {
+        let filter = filter.map(|GqlJson(filter)| filter);
+        if let Some(filter) = &filter {
+            filter.validate().map_err(|e| async_graphql::Error::new(e.to_string()))?;
+        }
+        let search = search.map(|search| {
+            DbSearch::new(
+                search.field.column().to_string(),
+                search.value,
+                search.exact,
+                true,
+            )
+        });
+        let args = AppListArgs {
+            page,
+            page_size,
+            sort,
+            desc,
+            search,
+            filter,
+            exclude_huawei,
+            exclude_atomic,
+        };
+        list_apps(app_state(ctx), args).await
+    }
+
+    /// 按开发者ID查询, 没有收录这个开发者的应用时返回 null
+    async fn developer(&self, ctx: &Context<'_>, id: String) -> GqlResult<Option<Developer>> {
+        let probe = Developer {
+            id,
+            name: String::new(),
+            en_name: None,
+            app_count: None,
+        };
+        let args = probe.app_list_args(1, 1, AppSort::default(), false);
+        let page = list_apps(app_state(ctx), args).await?;
+        Ok(page.items.into_iter().next().map(|App(app)| Developer {
+            id: probe.id,
+            name: app.developer_name,
+            en_name: Some(app.dev_en_name).filter(|name| !name.is_empty()),
+            app_count: Some(page.total as i64),
+        }))
+    }
+
+    /// 应用数量最多的开发者
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn top_developers(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] limit: u32,
+    ) -> GqlResult<Vec<Developer>> {
+        let developers = app_state(ctx)
+            .db
+            .get_top_developers(limit.clamp(1, MAX_PAGE_SIZE))
+            .await
+            .map_err(db_error("查询开发者排行"))?;
+        Ok(developers
+            .into_iter()
+            .map(|(id, name, app_count)| Developer {
+                id,
+                name,
+                en_name: None,
+                app_count: Some(app_count),
+            })
+            .collect())
+    }
+
+    /// 查询专题, 只查数据库, 没有收录时返回 null
+    async fn substance(&self, ctx: &Context<'_>, id: String) -> GqlResult<Option<Substance>> {
+        let substance = app_state(ctx)
+            .db
+            .get_substance_by_id(&id)
+            .await
+            .map_err(db_error("查询专题"))?;
+        Ok(substance.map(Substance::from))
+    }
+
+    /// 分页查询专题, 按收录时间排序, 页码从 1 开始
+    #[graphql(complexity = "page_size as usize * child_complexity")]
+    async fn substances(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: u32,
+        #[graphql(default = 20)] page_size: u32,
+        #[graphql(default)] desc: bool,
+    ) -> GqlResult<SubstancePage> {
+        let page = page.max(1);
+        // 专题列表的页码从 0 开始
+        let substances = app_state(ctx)
+            .db
+            .get_substance_list_paged(
+                page - 1,
+                page_size.clamp(1, MAX_PAGE_SIZE),
+                "created_at",
+                desc,
+            )
+            .await
+            .map_err(db_error("查询专题列表"))?;
+        Ok(SubstancePage {
+            total: substances.total_count,
+            page,
+            page_size: substances.page_size,
+            total_pages: substances.total_pages,
+            items: substances.data.into_iter().map(Substance::from).collect(),
+        })
+    }
+
+    /// 排行榜
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn ranking(
+        &self,
+        ctx: &Context<'_>,
+        kind: RankingKind,
+        #[graphql(default = 20)] limit: u32,
+    ) -> GqlResult<Vec<Ranking>> {
+        let state = app_state(ctx);
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        match kind {
+            RankingKind::Rating => {
+                let top = state
+                    .db
+                    .get_top_rated_apps(limit)
+                    .await
+                    .map_err(db_error("查询评分排行"))?;
+                let values: HashMap<String, f64> = top
+                    .iter()
+                    .map(|app| (app.app_id.clone(), decimal(&app.average_rating)))
+                    .collect();
+                let apps = load_apps(ctx, top.into_iter().map(|app| app.app_id).collect()).await?;
+                Ok(ranked(apps.into_iter().map(|app| {
+                    let value = values.get(&app.0.app_id).copied();
+                    (app, value)
+                })))
+            }
+            RankingKind::Recent => {
+                let apps = state
+                    .db
+                    .get_recently_updated_apps(limit)
+                    .await
+                    .map_err(db_error("查询最近更新排行"))?;
+                Ok(ranked(apps.into_iter().map(|app| (App(app), None))))
+            }
+            RankingKind::Downloads => {
+                let args = AppListArgs {
+                    page: 1,
+                    page_size: limit,
+                    sort: AppSort::DownloadCount,
+                    desc: true,
+                    search: None,
+                    filter: None,
+                    exclude_huawei: false,
+                    exclude_atomic: false,
+                };
+                let page = list_apps(state, args).await?;
+                Ok(ranked(page.items.into_iter().map(|app| {
+                    let value = app.0.download_count as f64;
+                    (app, Some(value))
+                })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::state::AppListQuery;
+
+    #[test]
+    fn sort_and_search_columns_are_whitelisted() {
+        let sorts = [
+            AppSort::CreatedAt,
+            AppSort::ListedAt,
+            AppSort::DownloadCount,
+            AppSort::AverageRating,
+            AppSort::TotalStarRatingCount,
+            AppSort::Price,
+            AppSort::SizeBytes,
+            AppSort::VersionCode,
+            AppSort::MetricsCreatedAt,
+        ];
+        for sort in sorts {
+            let query = AppListQuery {
+                sort: Some(sort.column().to_string()),
+                ..Default::default()
+            };
+            assert!(query.is_valid_sort(), "{}", sort.column());
+        }
+        let fields = [
+            SearchField::Name,
+            SearchField::PkgName,
+            SearchField::AppId,
+            SearchField::DevId,
+            SearchField::DeveloperName,
+            SearchField::KindName,
+            SearchField::KindTypeName,
+            SearchField::TagName,
+        ];
+        for field in fields {
+            let query = AppListQuery {
+                search_key: Some(field.column().to_string()),
+                ..Default::default()
+            };
+            assert!(query.is_valid_search(), "{}", field.column());
+        }
+    }
+
+    #[test]
+    fn complexity_limit() {
+        let schema = build_schema(1000, 10);
+        // 100 个应用 * 每个 100 条指标, 在执行前就被拒绝, 不会碰数据库
+        let response = futures::executor::block_on(schema.execute(
+            "{ apps(pageSize: 100) { items { metrics(limit: 100) { version downloadCount } } } }",
+        ));
+        assert!(
+            response
+                .errors
+                .iter()
+                .any(|e| e.message.contains("complex")),
+            "{:?}",
+            response.errors
+        );
+    }
+}
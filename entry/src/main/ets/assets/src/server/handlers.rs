@@ -1,101 +1,164 @@
 use axum::{
-    Json,
+    Extension, Json,
     extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::{
         IntoResponse,
         sse::{Event, Sse},
     },
 };
 
-use chrono::{DateTime, Local};
+use axum_client_ip::ClientIp;
+use chrono::{DateTime, Local, Utc};
 use serde_json::{Value as JsonValue, json};
-use std::{convert::Infallible, str::FromStr, sync::Arc, time::Duration};
+use std::{convert::Infallible, net::IpAddr, str::FromStr, sync::Arc, time::Duration};
 use tracing::{Level, event};
 
 use crate::{
-    db::AppCounts,
-    model::{AppQuery, FullAppInfo, ShortAppInfo},
-    server::state::{
-        ApiResponse, AppListQuery, AppQueryParam, AppState, IntervalParams, RankingQuery,
-        SubstanceListQuery,
+    db::{
+        AppCounts, PageInfo,
+        expand::{Include, attach_includes, project_fields},
+        filter::AppFilter,
+        moderation::NewReview,
+        submission::{NewSubmission, SubmissionJob},
+    },
+    model::{AppQuery, FullAppInfo, FullSubstanceInfo, ShortAppInfo, codes::CodeLabels},
+    server::{
+        auth::ApiKeyId,
+        cache,
+        error::{ApiError, ApiResult},
+        queue,
+        state::{
+            ApiResponse, AppExpandQuery, AppListQuery, AppQueryParam, AppState, IntervalParams,
+            RankingQuery, SubstanceListQuery,
+        },
     },
 };
 
+/// 单个应用的查询结果, v0 / v1 共用
 #[derive(Debug, serde::Serialize)]
-struct Response {
-    full_info: FullAppInfo,
-    new_app: bool,
-    new_info: bool,
-    new_metric: bool,
-    new_rating: bool,
-    get_data: bool,
+pub(crate) struct AppLookup {
+    pub(crate) full_info: FullAppInfo,
+    /// `ctype` 等代码字段的中文名, 例如 `ctype: 2` 对应 "原子化服务"
+    pub(crate) code_labels: CodeLabels,
+    pub(crate) new_app: bool,
+    pub(crate) new_info: bool,
+    pub(crate) new_metric: bool,
+    pub(crate) new_rating: bool,
+    pub(crate) get_data: bool,
 }
 
-pub async fn query_app(
-    state: Arc<AppState>,
-    query: AppQuery,
+/// 在线获取应用信息, 失败时退回数据库里的现有数据
+///
+/// 两边都没有时: 华为接口请求失败返回 502, 否则返回 404
+pub(crate) async fn lookup_app(
+    state: &AppState,
+    query: &AppQuery,
     listed_at: Option<DateTime<Local>>,
     comment: Option<JsonValue>,
-) -> Json<ApiResponse> {
+) -> ApiResult<AppLookup> {
     // 检查是否是新的应用
-    let exists = state.db.app_exists(&query).await;
+    let exists = state.db.app_exists(query).await;
 
     match crate::sync::sync_app(
         &state.client,
         &state.db,
         state.cfg.api_url(),
-        &query,
+        query,
         listed_at,
         comment,
     )
     .await
     {
-        Ok((new_info, new_metric, new_rating, full_info)) => Json(ApiResponse::success(
-            Response {
-                full_info,
-                new_app: !exists,
-                new_info,
-                new_metric,
-                new_rating,
-                get_data: true,
-            },
-            None,
-            None,
-        )),
-        Err(e) => {
+        Ok((new_info, new_metric, new_rating, full_info)) => Ok(AppLookup {
+            code_labels: full_info.code_labels(),
+            full_info,
+            new_app: !exists,
+            new_info,
+            new_metric,
+            new_rating,
+            get_data: true,
+        }),
+        Err(sync_err) => {
             event!(
                 Level::WARN,
-                "http服务获取 appid: {query:?} 的信息失败: {e}, 尝试获取现有数据"
+                "http服务获取 appid: {query:?} 的信息失败: {sync_err}, 尝试获取现有数据"
             );
-            match state.db.get_full_app_info(&query).await {
-                Ok(full_info) => Json(ApiResponse::success(
-                    Response {
-                        full_info,
-                        new_app: false,
-                        new_info: false,
-                        new_metric: false,
-                        new_rating: false,
-                        get_data: false,
-                    },
-                    None,
-                    None,
-                )),
-                Err(e) => {
+            match state.db.get_full_app_info(query).await {
+                Ok(full_info) => Ok(AppLookup {
+                    code_labels: full_info.code_labels(),
+                    full_info,
+                    new_app: false,
+                    new_info: false,
+                    new_metric: false,
+                    new_rating: false,
+                    get_data: false,
+                }),
+                Err(e) if is_row_not_found(&e) => {
                     event!(Level::WARN, "数据库里也没有 {query} 的数据: {e}");
-                    Json(ApiResponse::error(
-                        "对不起, 数据库里并没有这个应用的完整信息",
-                    ))
+                    if crate::sync::is_upstream_unavailable(&sync_err) {
+                        Err(ApiError::upstream(
+                            "华为应用市场接口请求失败, 数据库里也没有这个应用的数据",
+                        ))
+                    } else {
+                        Err(ApiError::not_found(format!("没有找到应用 {query}")))
+                    }
+                }
+                Err(e) => {
+                    event!(Level::WARN, "查询 {query} 的现有数据失败: {e}");
+                    Err(ApiError::database("查询应用信息失败"))
                 }
             }
         }
     }
 }
 
+/// 查询不到记录 (而不是数据库本身出错)
+pub(crate) fn is_row_not_found(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::RowNotFound))
+}
+
+/// 带 `fields` / `include` 的单个应用查询, 两个参数都没传时和 [`query_app`] 完全一样
+async fn query_app_expanded(
+    state: Arc<AppState>,
+    query: AppQuery,
+    expand: AppExpandQuery,
+) -> ApiResult<Json<ApiResponse>> {
+    let (fields, includes) = parse_expand(&state, &expand)?;
+    if fields.is_none() && includes.is_empty() {
+        return query_app(state, query, None, None).await;
+    }
+    let lookup = lookup_app(&state, &query, None, None).await?;
+    let mut data = serde_json::to_value(&lookup).unwrap_or_default();
+    let mut items = vec![data["full_info"].take()];
+    expand_items(
+        &state,
+        &mut items,
+        fields.as_deref(),
+        &includes,
+        expand.metrics_limit(),
+    )
+    .await?;
+    data["full_info"] = items.pop().unwrap_or_default();
+    Ok(Json(ApiResponse::success(data, None, None)))
+}
+
+pub async fn query_app(
+    state: Arc<AppState>,
+    query: AppQuery,
+    listed_at: Option<DateTime<Local>>,
+    comment: Option<JsonValue>,
+) -> ApiResult<Json<ApiResponse>> {
+    let lookup = lookup_app(&state, &query, listed_at, comment).await?;
+    Ok(Json(ApiResponse::success(lookup, None, None)))
+}
+
 #[utoipa::path(
     get,
     path = "/api/v0/apps/pkg_name/{pkg_name}",
     params(
-        ("pkg_name" = String, Path, description = "应用包名，例如：com.huawei.music")
+        ("pkg_name" = String, Path, description = "应用包名，例如：com.huawei.music"),
+        AppExpandQuery
     ),
     responses(
         (status = 200, description = "成功返回应用完整信息，包括基础信息、评分、下载量等", body = crate::server::state::ApiResponse),
@@ -107,23 +170,26 @@ pub async fn query_app(
 ///
 /// 该接口会优先从华为应用市场获取最新数据，如果获取失败则返回数据库中的历史数据。
 /// 返回的数据包括：应用基础信息、版本信息、评分、下载量、开发者信息等。
+/// 可以用 `fields` 只取部分字段, 用 `include` 内嵌指标历史 / 评分 / 专题 / 备案信息。
 pub async fn query_pkg(
     State(state): State<Arc<AppState>>,
     Path(pkg_name): Path<String>,
-) -> Json<ApiResponse> {
+    Query(expand): Query<AppExpandQuery>,
+) -> ApiResult<Json<ApiResponse>> {
     event!(
         Level::DEBUG,
         "http 服务正在尝试通过 pkg name 获取 {pkg_name} 的信息"
     );
     let query = AppQuery::pkg_name(&pkg_name);
-    query_app(state, query, None, None).await
+    query_app_expanded(state, query, expand).await
 }
 
 #[utoipa::path(
     get,
     path = "/api/v0/apps/app_id/{app_id}",
     params(
-        ("app_id" = String, Path, description = "华为应用市场的应用ID，例如：C10084839")
+        ("app_id" = String, Path, description = "华为应用市场的应用ID，例如：C10084839"),
+        AppExpandQuery
     ),
     responses(
         (status = 200, description = "成功返回应用完整信息，包括基础信息、评分、下载量等", body = crate::server::state::ApiResponse),
@@ -138,13 +204,14 @@ pub async fn query_pkg(
 pub async fn query_app_id(
     State(state): State<Arc<AppState>>,
     Path(app_id): Path<String>,
-) -> Json<ApiResponse> {
+    Query(expand): Query<AppExpandQuery>,
+) -> ApiResult<Json<ApiResponse>> {
     event!(
         Level::DEBUG,
         "http 服务正在尝试通过 appid 获取 {app_id} 的信息"
     );
     let query = AppQuery::app_id(&app_id);
-    query_app(state, query, None, None).await
+    query_app_expanded(state, query, expand).await
 }
 
 #[utoipa::path(
@@ -166,7 +233,7 @@ pub async fn query_app_id(
 /// - sync_status: 当前同步状态
 /// - crate_version: 服务版本号
 /// - user_agent: 请求华为API使用的User-Agent
-pub async fn market_info(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn market_info(State(state): State<Arc<AppState>>) -> ApiResult<Json<ApiResponse>> {
     event!(Level::DEBUG, "http 服务正在尝试获取应用列表信息");
     #[derive(serde::Deserialize, serde::Serialize)]
     struct MarketInfo {
@@ -178,27 +245,21 @@ pub async fn market_info(State(state): State<Arc<AppState>>) -> impl IntoRespons
         crate_version: String,
         user_agent: String,
     }
-    let app_count = if let Ok(app_count) = state.db.count_apps().await {
-        app_count
-    } else {
-        return Json(ApiResponse::error(
-            json!({"error": "Database error, faild to get app count"}),
-        ));
-    };
-    let developer_count = if let Ok(developer_count) = state.db.count_developers().await {
-        developer_count
-    } else {
-        return Json(ApiResponse::error(
-            json!({"error": "Database error, faild to get dev count"}),
-        ));
-    };
-    let substance_count = if let Ok(substance_count) = state.db.count_substances().await {
-        substance_count
-    } else {
-        return Json(ApiResponse::error(
-            json!({"error": "Database error, faild to get substance count"}),
-        ));
-    };
+    // 同步状态是实时的, 只缓存三个计数
+    let counts = cache::cached("market_info".to_string(), || async {
+        let Ok(app_count) = state.db.count_apps().await else {
+            return Err("Database error, faild to get app count");
+        };
+        let Ok(developer_count) = state.db.count_developers().await else {
+            return Err("Database error, faild to get dev count");
+        };
+        let Ok(substance_count) = state.db.count_substances().await else {
+            return Err("Database error, faild to get substance count");
+        };
+        Ok((app_count, developer_count, substance_count))
+    })
+    .await;
+    let (app_count, developer_count, substance_count) = counts.map_err(ApiError::database)?;
     let sync_status = crate::sync::get_sync_status();
     let data = MarketInfo {
         app_count,
@@ -209,7 +270,7 @@ pub async fn market_info(State(state): State<Arc<AppState>>) -> impl IntoRespons
         crate_version: env!("CARGO_PKG_VERSION").to_string(),
         user_agent: crate::sync::USER_AGENT.to_string(),
     };
-    Json(ApiResponse::success(data, None, None))
+    Ok(Json(ApiResponse::success(data, None, None)))
 }
 
 #[utoipa::path(
@@ -236,94 +297,225 @@ pub async fn market_info(State(state): State<Arc<AppState>>) -> impl IntoRespons
 /// - search_key: 搜索字段，支持：name（应用名）、pkg_name（包名）、developer（开发者）等
 /// - exclude_huawei: 是否排除华为官方应用
 /// - exclude_atomic: 是否排除原子化服务
+/// - filter: 组合过滤条件（JSON 字符串），支持 and/or/not、eq/ne、contains、range、in、is_null/not_null
+/// - fields: 只返回这些字段（app_full_info 列名，逗号分隔），在 SQL 里只查这些列，指定后忽略 detail
+/// - include: 内嵌关联数据，逗号分隔：metrics（最近 metrics_limit 条指标）、rating、substances、recordal
 pub async fn app_list_paged(
     State(state): State<Arc<AppState>>,
     Path(page): Path<String>,
     Query(query): Query<AppListQuery>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
+    app_list(state, page, query).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v0/apps/list/{page_count}",
+    params(
+        ("page_count" = String, Path, description = "页码，从0开始"),
+    ),
+    request_body = AppListQuery,
+    responses(
+        (status = 200, description = "成功返回分页的应用列表", body = crate::server::state::ApiResponse),
+    ),
+    tag = "应用查询"
+)]
+/// 分页获取应用列表，参数放在 JSON body 中
+///
+/// 参数与 GET 版本相同，`filter` 可以直接传过滤条件对象，方便构造复杂条件
+pub async fn app_list_paged_body(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<String>,
+    Json(query): Json<AppListQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    app_list(state, page, query).await
+}
+
+async fn app_list(
+    state: Arc<AppState>,
+    page: String,
+    query: AppListQuery,
+) -> ApiResult<Json<ApiResponse>> {
+    let expand = query.expand();
+    let (fields, includes) = parse_expand(&state, &expand)?;
+    if fields.is_some() || !includes.is_empty() {
+        let detail = query.detail();
+        let present = |app: FullAppInfo| {
+            let value = if detail {
+                serde_json::to_value(app)
+            } else {
+                serde_json::to_value(ShortAppInfo::from(app))
+            };
+            value.unwrap_or_default()
+        };
+        let apps = fetch_app_list_expanded(
+            &state,
+            &page,
+            &query,
+            fields.as_deref(),
+            &includes,
+            expand.metrics_limit(),
+            present,
+        )
+        .await?;
+        let total_count = apps.total_count;
+        return Ok(Json(ApiResponse::success(
+            apps,
+            Some(total_count),
+            Some(query.page_size()),
+        )));
+    }
+
+    let apps = fetch_app_list(&state, &page, &query).await?;
+    let total_count = apps.total_count;
+    let data = if query.detail() {
+        serde_json::to_value(apps)
+    } else {
+        serde_json::to_value(PageInfo {
+            data: apps
+                .data
+                .into_iter()
+                .map(ShortAppInfo::from)
+                .collect::<Vec<_>>(),
+            total_count: apps.total_count,
+            page: apps.page,
+            page_size: apps.page_size,
+            total_pages: apps.total_pages,
+        })
+    };
+    Ok(Json(ApiResponse::success(
+        data.unwrap_or_default(),
+        Some(total_count),
+        Some(query.page_size()),
+    )))
+}
+
+/// 校验分页参数, 返回页码和过滤条件
+fn validate_list_query(page: &str, query: &AppListQuery) -> ApiResult<(u32, Option<AppFilter>)> {
     if !query.is_valid_sort()
         && let Some(sort_key) = query.raw_sort_key()
     {
-        return Json(ApiResponse::error(format!(
-            "你在想什么, 不许按照 {} 排序",
-            sort_key
-        )));
+        return Err(ApiError::bad_request(format!("不支持按 {} 排序", sort_key)));
     }
     if !query.is_valid_search()
         && let Some(search_key) = query.raw_search_key()
     {
-        return Json(ApiResponse::error(format!(
-            "你在想什么, 不许按照 {} 搜索",
-            search_key
-        )));
+        return Err(ApiError::bad_request(format!("不支持按 {} 搜索", search_key)));
     }
-    match page.parse::<u32>() {
-        Ok(page) => {
-            if query.detail() {
-                match state
-                    .db
-                    .get_app_info_paginated_enhanced::<FullAppInfo>(
-                        page,
-                        query.page_size(),
-                        &query.sort_key(),
-                        query.desc.unwrap_or_default(),
-                        query.search_option(),
-                        query.exclude_huawei(),
-                        query.exclude_atomic(),
-                    )
-                    .await
-                {
-                    Ok(apps) => {
-                        let total_count = apps.total_count;
-                        Json(ApiResponse::success(
-                            apps,
-                            Some(total_count),
-                            Some(query.page_size()),
-                        ))
-                    }
-                    Err(e) => {
-                        event!(Level::WARN, "http服务获取分页应用信息失败: {e}");
-                        Json(ApiResponse::error(
-                            "Database error, faild to get paged info",
-                        ))
-                    }
-                }
-            } else {
-                match state
-                    .db
-                    .get_app_info_paginated_enhanced::<ShortAppInfo>(
-                        page,
-                        query.page_size(),
-                        &query.sort_key(),
-                        query.desc.unwrap_or_default(),
-                        query.search_option(),
-                        query.exclude_huawei(),
-                        query.exclude_atomic(),
-                    )
-                    .await
-                {
-                    Ok(apps) => {
-                        let total_count = apps.total_count;
-                        Json(ApiResponse::success(
-                            apps,
-                            Some(total_count),
-                            Some(query.page_size()),
-                        ))
-                    }
-                    Err(e) => {
-                        event!(Level::WARN, "http服务获取分页应用信息失败: {e}");
-                        Json(ApiResponse::error(
-                            "Database error, faild to get paged info",
-                        ))
-                    }
-                }
-            }
+    let filter = query.filter_option().map_err(ApiError::bad_request)?;
+    let page = page
+        .parse::<u32>()
+        .map_err(|e| ApiError::bad_request(format!("页码解析失败: {}", e)))?;
+    Ok((page, filter))
+}
+
+/// 校验分页参数并查询, v0 / v1 共用
+pub(crate) async fn fetch_app_list(
+    state: &AppState,
+    page: &str,
+    query: &AppListQuery,
+) -> ApiResult<PageInfo<FullAppInfo>> {
+    let (page, filter) = validate_list_query(page, query)?;
+    state
+        .db
+        .get_app_list_paged(
+            page,
+            query.page_size(),
+            &query.sort_key(),
+            query.desc.unwrap_or_default(),
+            query.search_option(),
+            filter.as_ref(),
+            query.exclude_huawei(),
+            query.exclude_atomic(),
+        )
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "http服务获取分页应用信息失败: {e}");
+            ApiError::database("获取分页应用信息失败")
+        })
+}
+
+/// 带 `fields` / `include` 的分页查询, v0 / v1 共用
+///
+/// Postgres 下 `fields` 直接在 SQL 里只查这些列; 其它后端查完整数据后再裁剪.
+/// 没指定 `fields` 时每一项由 `present` 决定输出结构
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn fetch_app_list_expanded(
+    state: &AppState,
+    page: &str,
+    query: &AppListQuery,
+    fields: Option<&[&'static str]>,
+    includes: &[Include],
+    metrics_limit: u32,
+    present: impl FnMut(FullAppInfo) -> JsonValue,
+) -> ApiResult<PageInfo<JsonValue>> {
+    let mut apps = match (fields, state.postgres()) {
+        (Some(fields), Some(db)) => {
+            let (page, filter) = validate_list_query(page, query)?;
+            db.get_app_list_fields(
+                page,
+                query.page_size(),
+                &query.sort_key(),
+                query.desc.unwrap_or_default(),
+                query.search_option(),
+                filter.as_ref(),
+                query.exclude_huawei(),
+                query.exclude_atomic(),
+                fields,
+            )
+            .await
+            .map_err(|e| {
+                event!(Level::WARN, "http服务按字段获取分页应用信息失败: {e}");
+                ApiError::database("获取分页应用信息失败")
+            })?
+        }
+        (Some(_), None) => fetch_app_list(state, page, query)
+            .await?
+            .map(|app| serde_json::to_value(app).unwrap_or_default()),
+        (None, _) => fetch_app_list(state, page, query).await?.map(present),
+    };
+    expand_items(state, &mut apps.data, fields, includes, metrics_limit).await?;
+    Ok(apps)
+}
+
+/// 解析 `fields` / `include`, 在真正查询之前把不支持的情况挡掉
+pub(crate) fn parse_expand(
+    state: &AppState,
+    expand: &AppExpandQuery,
+) -> ApiResult<(Option<Vec<&'static str>>, Vec<Include>)> {
+    let fields = expand.fields().map_err(ApiError::bad_request)?;
+    let includes = expand.includes().map_err(ApiError::bad_request)?;
+    if !includes.is_empty() && state.postgres().is_none() {
+        return Err(ApiError::unsupported_backend());
+    }
+    Ok((fields, includes))
+}
+
+/// 按 `fields` 裁剪并挂上 `include` 的关联数据, 每一项都要有 `app_id`
+pub(crate) async fn expand_items(
+    state: &AppState,
+    items: &mut [JsonValue],
+    fields: Option<&[&str]>,
+    includes: &[Include],
+    metrics_limit: u32,
+) -> ApiResult<()> {
+    if let Some(fields) = fields {
+        for item in items.iter_mut() {
+            *item = project_fields(item.take(), fields);
         }
-        Err(e) => Json(ApiResponse::error(format!(
-            "Failed to parse page: {} what the fuck did you commit",
-            e
-        ))),
     }
+    if includes.is_empty() {
+        return Ok(());
+    }
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    attach_includes(db, items, includes, metrics_limit)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "http服务获取应用关联数据失败: {e}");
+            ApiError::database("获取应用关联数据失败")
+        })
 }
 
 #[utoipa::path(
@@ -344,22 +536,19 @@ pub async fn app_list_paged(
 pub async fn get_rating_ranking(
     State(state): State<Arc<AppState>>,
     Query(query): Query<RankingQuery>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     let limit = query.limit.unwrap_or(10);
     event!(Level::DEBUG, "获取评分排行，限制: {}", limit);
 
-    match state.db.get_top_rated_apps(limit).await {
-        Ok(apps) => {
-            let total_count = apps.len() as u32;
-            Json(ApiResponse::success(apps, Some(total_count), Some(limit)))
-        }
-        Err(e) => {
+    let key = cache::cache_key("rankings/ratings", &query);
+    let apps = cache::cached(key, || state.db.get_top_rated_apps(limit))
+        .await
+        .map_err(|e| {
             event!(Level::WARN, "获取评分排行失败: {e}");
-            Json(ApiResponse::error(
-                "Database error, faild to get rating ranking",
-            ))
-        }
-    }
+            ApiError::database("获取评分排行失败")
+        })?;
+    let total_count = apps.len() as u32;
+    Ok(Json(ApiResponse::success(apps, Some(total_count), Some(limit))))
 }
 
 #[utoipa::path(
@@ -380,22 +569,22 @@ pub async fn get_rating_ranking(
 pub async fn get_recent_ranking(
     State(state): State<Arc<AppState>>,
     Query(query): Query<RankingQuery>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     let limit = query.limit.unwrap_or(10);
     event!(Level::DEBUG, "获取最近更新排行，限制: {}", limit);
 
-    match state.db.get_recently_updated_apps(limit).await {
-        Ok(apps) => {
-            let all_count = state.db.count_apps().await.map(|c| c.apps as u32).ok();
-            Json(ApiResponse::success(apps, all_count, Some(limit)))
-        }
-        Err(e) => {
-            event!(Level::WARN, "获取最近更新排行失败: {e}");
-            Json(ApiResponse::error(
-                "Database error, faild to get recently updated ranking",
-            ))
-        }
-    }
+    let key = cache::cache_key("rankings/recent", &query);
+    let ranking = cache::cached(key, || async {
+        let apps = state.db.get_recently_updated_apps(limit).await?;
+        let all_count = state.db.count_apps().await.map(|c| c.apps as u32).ok();
+        anyhow::Ok((apps, all_count))
+    })
+    .await;
+    let (apps, all_count) = ranking.map_err(|e| {
+        event!(Level::WARN, "获取最近更新排行失败: {e}");
+        ApiError::database("获取最近更新排行失败")
+    })?;
+    Ok(Json(ApiResponse::success(apps, all_count, Some(limit))))
 }
 
 #[utoipa::path(
@@ -417,24 +606,23 @@ pub async fn get_recent_ranking(
 pub async fn get_developer_ranking(
     State(state): State<Arc<AppState>>,
     Query(query): Query<RankingQuery>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     let limit = query.limit.unwrap_or(10);
     event!(Level::DEBUG, "获取开发者排行，限制: {}", limit);
 
-    match state.db.get_top_developers(limit).await {
-        Ok(developers) => {
-            let total_count = developers.len() as u32;
-            Json(ApiResponse::success(
-                developers,
-                Some(total_count),
-                Some(limit),
-            ))
-        }
-        Err(e) => {
+    let key = cache::cache_key("rankings/developers", &query);
+    let developers = cache::cached(key, || state.db.get_top_developers(limit))
+        .await
+        .map_err(|e| {
             event!(Level::WARN, "获取开发者排行失败: {e}");
-            Json(ApiResponse::error("Database error".to_string()))
-        }
-    }
+            ApiError::database("获取开发者排行失败")
+        })?;
+    let total_count = developers.len() as u32;
+    Ok(Json(ApiResponse::success(
+        developers,
+        Some(total_count),
+        Some(limit),
+    )))
 }
 
 #[utoipa::path(
@@ -450,19 +638,22 @@ pub async fn get_developer_ranking(
 ///
 /// 返回数据库中所有应用的星级评分分布情况，统计1星到5星各个评分区间的应用数量。
 /// 用于生成评分分布图表或进行数据分析。
-pub async fn get_rating_distribution(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn get_rating_distribution(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<ApiResponse>> {
     event!(Level::DEBUG, "http 服务正在尝试获取星级分布");
-    match state.db.get_star_distribution().await {
-        Ok((star_1, star_2, star_3, star_4, star_5)) => Json(ApiResponse::success(
-            json!({"star_1": star_1, "star_2": star_2, "star_3": star_3, "star_4": star_4, "star_5": star_5}),
-            None,
-            None,
-        )),
-        Err(e) => {
-            event!(Level::WARN, "http服务获取星级分布失败: {e}");
-            Json(ApiResponse::error(json!({"error": "Database error"})))
-        }
-    }
+    let (star_1, star_2, star_3, star_4, star_5) =
+        cache::cached("charts/rating".to_string(), || state.db.get_star_distribution())
+            .await
+            .map_err(|e| {
+                event!(Level::WARN, "http服务获取星级分布失败: {e}");
+                ApiError::database("获取星级分布失败")
+            })?;
+    Ok(Json(ApiResponse::success(
+        json!({"star_1": star_1, "star_2": star_2, "star_3": star_3, "star_4": star_4, "star_5": star_5}),
+        None,
+        None,
+    )))
 }
 
 #[utoipa::path(
@@ -478,15 +669,17 @@ pub async fn get_rating_distribution(State(state): State<Arc<AppState>>) -> impl
 /// 返回数据库中所有应用的最低支持SDK版本（minSdkVersion）分布情况。
 /// 统计数据以SDK版本号为键，应用数量为值。
 /// 用于了解开发者对不同Android版本的支持情况。
-pub async fn get_min_sdk_distribution(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn get_min_sdk_distribution(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<ApiResponse>> {
     event!(Level::DEBUG, "http 服务正在尝试获取最小支持SDK分布");
-    match state.db.count_min_sdk().await {
-        Ok(distribution) => Json(ApiResponse::success(distribution, None, None)),
-        Err(e) => {
+    let distribution = cache::cached("charts/min_sdk".to_string(), || state.db.count_min_sdk())
+        .await
+        .map_err(|e| {
             event!(Level::WARN, "http服务获取最小支持SDK分布失败: {e}");
-            Json(ApiResponse::error(json!({"error": "Database error"})))
-        }
-    }
+            ApiError::database("获取最小支持SDK分布失败")
+        })?;
+    Ok(Json(ApiResponse::success(distribution, None, None)))
 }
 
 #[utoipa::path(
@@ -502,15 +695,18 @@ pub async fn get_min_sdk_distribution(State(state): State<Arc<AppState>>) -> imp
 /// 返回数据库中所有应用的目标SDK版本（targetSdkVersion）分布情况。
 /// 统计数据以SDK版本号为键，应用数量为值。
 /// 用于了解开发者针对的Android目标版本趋势。
-pub async fn get_target_sdk_distribution(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn get_target_sdk_distribution(
+    State(state): State<Arc<AppState>>,
+) -> ApiResult<Json<ApiResponse>> {
     event!(Level::DEBUG, "http 服务正在尝试获取目标支持SDK分布");
-    match state.db.count_target_sdk().await {
-        Ok(distribution) => Json(ApiResponse::success(distribution, None, None)),
-        Err(e) => {
-            event!(Level::WARN, "http服务获取目标支持SDK分布失败: {e}");
-            Json(ApiResponse::error(json!({"error": "Database error"})))
-        }
-    }
+    let distribution =
+        cache::cached("charts/target_sdk".to_string(), || state.db.count_target_sdk())
+            .await
+            .map_err(|e| {
+                event!(Level::WARN, "http服务获取目标支持SDK分布失败: {e}");
+                ApiError::database("获取目标支持SDK分布失败")
+            })?;
+    Ok(Json(ApiResponse::success(distribution, None, None)))
 }
 
 #[utoipa::path(
@@ -532,23 +728,25 @@ pub async fn get_target_sdk_distribution(State(state): State<Arc<AppState>>) ->
 pub async fn get_app_download_history(
     State(state): State<Arc<AppState>>,
     Path(pkg_name): Path<String>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     event!(
         Level::DEBUG,
         "http 服务正在尝试获取应用 {} 的下载量历史数据",
         pkg_name
     );
-    match state.db.get_app_metrics_by_pkg_id(&pkg_name).await {
-        Ok(metrics) => Json(ApiResponse::success(metrics, None, None)),
-        Err(e) => {
+    let metrics = state
+        .db
+        .get_app_metrics_by_pkg_id(&pkg_name)
+        .await
+        .map_err(|e| {
             event!(
                 Level::WARN,
                 "http服务获取应用 {} 下载量历史失败: {e}",
                 pkg_name
             );
-            Json(ApiResponse::error("Database error"))
-        }
-    }
+            ApiError::database("获取下载量历史失败")
+        })?;
+    Ok(Json(ApiResponse::success(metrics, None, None)))
 }
 
 #[utoipa::path(
@@ -563,118 +761,96 @@ pub async fn get_app_download_history(
         example = json!({"comment": "用户提交的备注信息"})
     ),
     responses(
-        (status = 200, description = "成功获取并保存专题及其关联的应用信息", body = crate::server::state::ApiResponse),
-        (status = 200, description = "返回专题数据、是否为新记录、关联应用数量")
+        (status = 202, description = "已加入投稿队列, data.job 为投稿任务, 同一专题已在队列中时 data.deduplicated 为 true", body = crate::server::state::ApiResponse),
+        (status = 400, description = "专题ID为空", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 submit 权限", body = crate::server::error::ApiErrorResponse)
     ),
+    security(("api_key" = [])),
     tag = "应用提交"
 )]
 /// 提交专题信息
 ///
-/// 从华为应用市场获取指定专题的信息，并自动获取其关联的所有应用数据。
-/// 如果数据库中不存在该专题，会将其保存到数据库。
-/// 同时会同步所有关联的应用信息。
+/// 专题加入投稿队列后立即返回任务, 由后台 worker 从华为应用市场获取专题,
+/// 同步其关联的所有应用并保存专题。任务状态通过 `/api/v0/submissions/{job_id}` 查询,
+/// 完成后 `result` 里是专题数据、是否为新记录以及应用同步成功 / 失败的数量。
+/// `comment` 需要管理员审核通过后才会写入, 待审核记录在 `data.review` 里。
 pub async fn submit_substance(
     State(state): State<Arc<AppState>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    key_id: Option<Extension<ApiKeyId>>,
+    headers: HeaderMap,
     Path(substance_id): Path<String>,
     Json(data): Json<JsonValue>,
-) -> impl IntoResponse {
-    event!(Level::INFO, "http 服务正在尝试提交专题 {}", substance_id);
-
-    let comment = data.get("comment").cloned();
-
-    match crate::sync::get_app_from_substance(&state.client, state.cfg.api_url(), &substance_id)
-        .await
-    {
-        Ok((substance, raw_value)) => {
-            for query in substance.data.iter() {
-                match crate::sync::sync_app(
-                    &state.client,
-                    &state.db,
-                    state.cfg.api_url(),
-                    query,
-                    None,
-                    None,
-                )
-                .await
-                {
-                    Ok((_new_info, _new_metric, _new_rating, _full_info)) => {
-                        event!(
-                            Level::INFO,
-                            "专题 {query} ({}) 对应的应用数据保存成功",
-                            substance.say_my_name()
-                        );
-                    }
-                    Err(e) => {
-                        event!(
-                            Level::WARN,
-                            "http服务获取并保存专题 {} 对应的应用信息失败: {e}",
-                            substance_id
-                        );
-                    }
-                }
-            }
-            // 保存专题数据
-            let is_new = match state
-                .db
-                .save_substance(&substance, &raw_value, comment)
-                .await
-            {
-                Ok(b) => b,
-                Err(e) => {
-                    event!(Level::WARN, "专题 {} 对应的数据保存失败: {e}", substance_id);
-                    return Json(ApiResponse::error("Database error"));
-                }
-            };
-            let len = substance.data.len();
-            Json(ApiResponse::success(
-                json!({"data": substance, "is_new": is_new}),
-                Some(len as u32),
-                None,
-            ))
-        }
-        Err(e) => {
-            event!(Level::WARN, "http服务获取专题 {} 失败: {e}", substance_id);
-            Json(ApiResponse::error("Failed to get substance"))
-        }
+) -> ApiResult<(StatusCode, Json<ApiResponse>)> {
+    let substance_id = substance_id.trim();
+    if substance_id.is_empty() {
+        return Err(ApiError::bad_request("专题ID不能为空"));
     }
+    event!(Level::INFO, "http 服务收到专题 {} 的投稿", substance_id);
+
+    let mut submission = NewSubmission::substance(substance_id);
+    submission.comment = data.get("comment").cloned();
+    submission.api_key_id = key_id.map(|Extension(ApiKeyId(id))| id);
+    enqueue(&state, submission, ip, &headers).await
 }
 
 #[utoipa::path(
     post,
     path = "/api/v0/submit",
-    request_body = serde_json::Value,
+    request_body(
+        content = serde_json::Value,
+        description = "app_id 和 pkg_name 二选一, 可选 listed_at (RFC3339) 和 comment",
+        example = json!({"pkg_name": "com.huawei.music", "comment": "用户提交的备注信息"})
+    ),
     responses(
-        (status = 200, description = "提交应用信息，需要提供app_id或pkg_name", body = crate::server::state::ApiResponse)
+        (status = 202, description = "已加入投稿队列, data.job 为投稿任务, 同一应用已在队列中时 data.deduplicated 为 true", body = crate::server::state::ApiResponse),
+        (status = 400, description = "app_id 和 pkg_name 没给或者都给了, 或者 listed_at 格式不对 / 晚于当前时间", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 submit 权限", body = crate::server::error::ApiErrorResponse)
     ),
+    security(("api_key" = [])),
     tag = "应用提交"
 )]
+/// 提交应用信息
+///
+/// 应用加入投稿队列后立即返回任务, 完成后任务的 `result` 和查询应用接口的 `data` 一样。
+/// `listed_at` 和 `comment` 需要管理员审核通过后才会写入, 待审核记录在 `data.review` 里。
 pub async fn submit_app(
     State(state): State<Arc<AppState>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    key_id: Option<Extension<ApiKeyId>>,
+    headers: HeaderMap,
     Json(data): Json<JsonValue>,
-) -> Json<ApiResponse> {
+) -> ApiResult<(StatusCode, Json<ApiResponse>)> {
     // 获取 app_id 或者 pkg_name
     let app_id = data.get("app_id").and_then(|v| v.as_str());
     let pkg_name = data.get("pkg_name").and_then(|v| v.as_str());
     if app_id.is_none() && pkg_name.is_none() {
-        return Json(ApiResponse::error(
-            "app_id 和 pkg_name 至少给一个, 总不能一个不给吧".to_string(),
-        ));
+        return Err(ApiError::bad_request("app_id 和 pkg_name 需要给一个"));
     } else if app_id.is_some() && pkg_name.is_some() {
-        return Json(ApiResponse::error(
-            "app_id 和 pkg_name 至多给一个, 你两个都给是什么意思".to_string(),
-        ));
+        return Err(ApiError::bad_request("app_id 和 pkg_name 只能给一个"));
     }
     let query = match (app_id, pkg_name) {
         (Some(id), None) => AppQuery::app_id(id),
         (None, Some(name)) => AppQuery::pkg_name(name),
         _ => unreachable!(),
     };
-    let app_exists = state.db.app_exists(&query).await;
 
-    let listed_at: Option<DateTime<Local>> = data
-        .get("listed_at")
-        .and_then(|v| v.as_str())
-        .and_then(|d| DateTime::from_str(d).ok());
+    // 上架时间要审核, 格式不对或者晚于现在的直接拒绝
+    let listed_at: Option<DateTime<Local>> = match data.get("listed_at") {
+        None | Some(JsonValue::Null) => None,
+        Some(value) => {
+            let listed_at = value
+                .as_str()
+                .and_then(|d| DateTime::from_str(d).ok())
+                .ok_or_else(|| ApiError::bad_request("listed_at 需要是 RFC3339 格式的时间"))?;
+            if listed_at > Local::now() {
+                return Err(ApiError::bad_request("listed_at 不能晚于当前时间"));
+            }
+            Some(listed_at)
+        }
+    };
 
     let comment = data.get("comment");
 
@@ -686,54 +862,97 @@ pub async fn submit_app(
         "接收到投稿 data: query: {:?}, listed_at: {:?}, comment: {:?}",
         query, listed_at, comment_str
     );
-    if app_exists {
-        query_app(state, query, None, None).await
+
+    let mut submission = NewSubmission::app(&query);
+    submission.comment = comment.cloned();
+    submission.listed_at = listed_at.map(|time| time.with_timezone(&Utc));
+    submission.api_key_id = key_id.map(|Extension(ApiKeyId(id))| id);
+    enqueue(&state, submission, ip, &headers).await
+}
+
+/// 入队并唤醒 worker, 返回 202 和任务
+///
+/// 带了备注 / 上架时间的投稿另外记一条待审核, 审核通过后才写入
+async fn enqueue(
+    state: &AppState,
+    submission: NewSubmission,
+    ip: IpAddr,
+    headers: &HeaderMap,
+) -> ApiResult<(StatusCode, Json<ApiResponse>)> {
+    let (job, created) = state
+        .db
+        .enqueue_submission(&submission)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "投稿 {} 入队失败: {e:?}", submission.target);
+            ApiError::database("投稿入队失败")
+        })?;
+    if created {
+        queue::notify();
     } else {
-        query_app(state, query, listed_at, comment.cloned()).await
+        event!(
+            Level::INFO,
+            job_id = job.id,
+            "{} 已经在投稿队列中, 返回已有任务",
+            submission.target
+        );
     }
-}
 
-#[utoipa::path(
-    get,
-    path = "/api/v0/feishu/meta.json",
-    responses(
-        (status = 200, description = "获取飞书数据连接器元信息", body = serde_json::Value)
-    ),
-    tag = "飞书集成"
-)]
-pub async fn feishu_meta(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    event!(Level::INFO, "Feishu 正在获取元信息");
-    Json(serde_json::json! {{
-      "schemaVersion": 1,
-      "version": env!("CARGO_PKG_VERSION"),
-      "type": "data_connector",
-    }})
-}
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|ua| ua.to_str().ok())
+        .map(str::to_string);
+    let review = match NewReview::from_submission(&submission, job.id, ip, user_agent) {
+        Some(review) => {
+            let review = state.db.create_submission_review(&review).await.map_err(|e| {
+                event!(Level::WARN, "投稿 {} 的审核记录保存失败: {e:?}", submission.target);
+                ApiError::database("投稿审核记录保存失败")
+            })?;
+            Some(review)
+        }
+        None => None,
+    };
 
-#[utoipa::path(
-    post,
-    path = "/api/v0/feishu/table_meta",
-    responses(
-        (status = 200, description = "获取飞书表格元信息", body = serde_json::Value)
-    ),
-    tag = "飞书集成"
-)]
-pub async fn feishu_table_meta(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    event!(Level::INFO, "Feishu 正在获取表格元信息");
-    Json(serde_json::json! {{}})
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::success(
+            json!({"job": job, "deduplicated": !created, "review": review}),
+            None,
+            None,
+        )),
+    ))
 }
 
 #[utoipa::path(
-    post,
-    path = "/api/v0/feishu/records",
+    get,
+    path = "/api/v0/submissions/{job_id}",
+    params(
+        ("job_id" = i64, Path, description = "投稿接口返回的任务ID")
+    ),
     responses(
-        (status = 200, description = "获取飞书记录", body = serde_json::Value)
+        (status = 200, description = "投稿任务, status 为 succeeded 时 result 是执行结果, failed 时 error 是失败原因", body = SubmissionJob),
+        (status = 404, description = "任务不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 401, description = "没有带 API key 或 key 无效", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 submit 权限", body = crate::server::error::ApiErrorResponse)
     ),
-    tag = "飞书集成"
+    security(("api_key" = [])),
+    tag = "应用提交"
 )]
-pub async fn feishu_records(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    event!(Level::INFO, "Feishu 正在获取记录");
-    Json(serde_json::json! {{}})
+/// 查询投稿任务状态
+pub async fn get_submission_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<i64>,
+) -> ApiResult<Json<ApiResponse>> {
+    let job = state
+        .db
+        .get_submission_job(job_id)
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "查询投稿任务 {job_id} 失败: {e:?}");
+            ApiError::database("查询投稿任务失败")
+        })?
+        .ok_or_else(|| ApiError::not_found(format!("投稿任务 {job_id} 不存在")))?;
+    Ok(Json(ApiResponse::success(job, None, None)))
 }
 
 #[utoipa::path(
@@ -750,13 +969,16 @@ pub async fn feishu_records(State(_state): State<Arc<AppState>>) -> impl IntoRes
 pub async fn download_increase(
     State(state): State<Arc<AppState>>,
     Query(interval): Query<IntervalParams>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     // event!(Level::INFO, "正在计算应用下载量增长数据");
     let pg_interval = interval.to_pg_interval();
     let limit = interval.limit();
-    match state
-        .db
-        .calculate_download_increase(
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let key = cache::cache_key("rankings/download_increase", &interval);
+    let increase = cache::cached(key, || {
+        db.calculate_download_increase(
             pg_interval,
             limit,
             interval.page,
@@ -764,14 +986,13 @@ pub async fn download_increase(
             interval.exclude_atomic(),
             interval.listed_interval(),
         )
-        .await
-    {
-        Ok((data, total)) => Json(ApiResponse::success(data, Some(total as u32), Some(limit))),
-        Err(e) => {
-            event!(Level::WARN, "计算应用下载量增长数据失败: {e}");
-            Json(ApiResponse::error("Database error".to_string()))
-        }
-    }
+    })
+    .await;
+    let (data, total) = increase.map_err(|e| {
+        event!(Level::WARN, "计算应用下载量增长数据失败: {e}");
+        ApiError::database("计算应用下载量增长数据失败")
+    })?;
+    Ok(Json(ApiResponse::success(data, Some(total as u32), Some(limit))))
 }
 
 #[utoipa::path(
@@ -788,16 +1009,14 @@ pub async fn download_increase(
 pub async fn get_app_icon(
     State(state): State<Arc<AppState>>,
     Query(app_query): Query<AppQueryParam>,
-) -> impl IntoResponse {
-    if let Some(query) = app_query.as_query() {
-        if let Some(icon_url) = state.db.get_app_icon(&query).await {
-            Json(ApiResponse::success(icon_url, Some(1), Some(1)))
-        } else {
-            Json(ApiResponse::error("App icon not found"))
-        }
-    } else {
-        Json(ApiResponse::error("app_id or pkg_name is required"))
-    }
+) -> ApiResult<Json<ApiResponse>> {
+    let Some(query) = app_query.as_query() else {
+        return Err(ApiError::bad_request("app_id 和 pkg_name 需要给一个"));
+    };
+    let Some(icon_url) = state.db.get_app_icon(&query).await else {
+        return Err(ApiError::not_found(format!("没有找到应用 {query} 的图标")));
+    };
+    Ok(Json(ApiResponse::success(icon_url, Some(1), Some(1))))
 }
 
 #[utoipa::path(
@@ -857,9 +1076,27 @@ pub async fn sync_status_stream() -> impl IntoResponse {
 pub async fn query_substance(
     State(state): State<Arc<AppState>>,
     Path(substance_id): Path<String>,
-) -> impl IntoResponse {
-    match state.db.get_substance_by_id(&substance_id).await {
-        Ok(Some(substance)) => Json(ApiResponse::success(substance, Some(1), Some(1))),
+) -> ApiResult<Json<ApiResponse>> {
+    let substance = lookup_substance(&state, &substance_id).await?;
+    Ok(Json(ApiResponse::success(substance, Some(1), Some(1))))
+}
+
+/// 从华为那边拉专题失败: 接口请求失败算 502, 其它 (例如返回里没有这个专题) 算 404
+fn substance_fetch_error(e: &anyhow::Error) -> ApiError {
+    if crate::sync::is_upstream_unavailable(e) {
+        ApiError::upstream("从华为应用市场获取专题失败")
+    } else {
+        ApiError::not_found("专题不存在")
+    }
+}
+
+/// 查询专题, 数据库里没有就去华为那边拉一份存下来, v0 / v1 共用
+pub(crate) async fn lookup_substance(
+    state: &AppState,
+    substance_id: &str,
+) -> ApiResult<FullSubstanceInfo> {
+    match state.db.get_substance_by_id(substance_id).await {
+        Ok(Some(substance)) => Ok(substance),
         Ok(None) => {
             // 数据库不存在，尝试从华为服务器获取
             event!(
@@ -870,47 +1107,49 @@ pub async fn query_substance(
             match crate::sync::substance::get_app_from_substance(
                 &state.client,
                 state.cfg.api_url(),
-                &substance_id,
+                substance_id,
             )
             .await
             {
                 Ok((substance_data, raw_data)) => {
                     // 保存到数据库
-                    if let Err(e) = state
+                    match state
                         .db
                         .save_substance(&substance_data, &raw_data, None)
                         .await
                     {
-                        event!(Level::WARN, "保存专题 {} 到数据库失败: {}", substance_id, e);
-                        return Json(ApiResponse::error("专题保存到数据库失败"));
+                        Ok((_, true)) => cache::invalidate(),
+                        Ok((_, false)) => {}
+                        Err(e) => {
+                            event!(Level::WARN, "保存专题 {} 到数据库失败: {}", substance_id, e);
+                            return Err(ApiError::database("专题保存到数据库失败"));
+                        }
                     }
 
                     // 重新从数据库查询
-                    match state.db.get_substance_by_id(&substance_id).await {
-                        Ok(Some(substance)) => {
-                            Json(ApiResponse::success(substance, Some(1), Some(1)))
-                        }
-                        Ok(None) => Json(ApiResponse::error("专题获取成功但查询失败")),
+                    match state.db.get_substance_by_id(substance_id).await {
+                        Ok(Some(substance)) => Ok(substance),
+                        Ok(None) => Err(ApiError::internal("专题获取成功但查询失败")),
                         Err(e) => {
                             event!(Level::WARN, "查询新保存的专题信息失败: {}", e);
-                            Json(ApiResponse::error("数据库查询错误"))
+                            Err(ApiError::database("数据库查询错误"))
                         }
                     }
                 }
                 Err(e) => {
                     event!(
                         Level::WARN,
-                        "从华为服务器获取专题 {} 失败: {}",
+                        "从华为服务器获取专题 {} 失败: {:#}",
                         substance_id,
                         e
                     );
-                    Json(ApiResponse::error("专题不存在"))
+                    Err(substance_fetch_error(&e))
                 }
             }
         }
         Err(e) => {
             event!(Level::WARN, "查询专题信息失败: {}", e);
-            Json(ApiResponse::error("数据库查询错误"))
+            Err(ApiError::database("数据库查询错误"))
         }
     }
 }
@@ -932,45 +1171,32 @@ pub async fn substance_list_paged(
     State(state): State<Arc<AppState>>,
     Path(page): Path<String>,
     Query(query): Query<SubstanceListQuery>,
-) -> impl IntoResponse {
+) -> ApiResult<Json<ApiResponse>> {
     if !query.is_valid_sort()
         && let Some(sort_key) = query.raw_sort_key()
     {
-        return Json(ApiResponse::error(format!(
-            "你在想什么, 不许按照 {} 排序",
-            sort_key
-        )));
-    }
-
-    match page.parse::<u32>() {
-        Ok(page) => {
-            match state
-                .db
-                .get_substance_list_paged(
-                    page,
-                    query.page_size(),
-                    &query.sort_key(),
-                    query.desc.unwrap_or_default(),
-                )
-                .await
-            {
-                Ok(substances) => {
-                    let total_count = substances.data.len() as u32;
-                    Json(ApiResponse::success(
-                        substances,
-                        Some(total_count),
-                        Some(query.page_size()),
-                    ))
-                }
-                Err(e) => {
-                    event!(Level::WARN, "http服务获取分页专题信息失败: {}", e);
-                    Json(ApiResponse::error("数据库错误，获取分页信息失败"))
-                }
-            }
-        }
-        Err(e) => Json(ApiResponse::error(format!(
-            "页码解析失败: {} 你提交的是什么玩意",
-            e
-        ))),
+        return Err(ApiError::bad_request(format!("不支持按 {} 排序", sort_key)));
     }
+    let page = page
+        .parse::<u32>()
+        .map_err(|e| ApiError::bad_request(format!("页码解析失败: {}", e)))?;
+    let substances = state
+        .db
+        .get_substance_list_paged(
+            page,
+            query.page_size(),
+            &query.sort_key(),
+            query.desc.unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "http服务获取分页专题信息失败: {}", e);
+            ApiError::database("获取分页专题信息失败")
+        })?;
+    let total_count = substances.data.len() as u32;
+    Ok(Json(ApiResponse::success(
+        substances,
+        Some(total_count),
+        Some(query.page_size()),
+    )))
 }
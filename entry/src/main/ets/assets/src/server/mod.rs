@@ -1,10 +1,21 @@
+pub mod admin_handlers;
+pub mod auth;
+pub mod cache;
+pub mod changelog_handlers;
+pub mod error;
+pub mod feishu_handlers;
 pub mod frontend_handlers;
+pub mod graphql;
 pub mod handlers;
 pub mod middle;
+pub mod queue;
+pub mod rate_limit;
 pub mod routes;
+pub mod search_handlers;
 pub mod state;
 pub mod statistics;
 pub mod statistics_handlers;
+pub mod v1_handlers;
 
 use std::{net::SocketAddr, sync::Arc};
 
@@ -14,7 +25,7 @@ use tracing::{Level, event};
 
 use crate::{
     config::{Config, get_config},
-    db::Database,
+    db::storage::{SharedStorage, open_storage},
     sync::code::GLOBAL_CODE_MANAGER,
 };
 
@@ -25,9 +36,12 @@ pub use routes::create_router;
 /// Web服务器工作线程
 pub async fn worker(mut waiter: tokio::sync::oneshot::Receiver<()>) -> anyhow::Result<()> {
     let config = get_config();
-    event!(Level::INFO, "connecting to db");
-    let db = crate::db::Database::new(config.database_url(), config.db_max_connect()).await?;
-    event!(Level::INFO, "connected to db");
+    let db = open_storage(config).await?;
+
+    // schema 不兼容就直接拒绝启动
+    db.prepare(config.db_auto_migrate())
+        .await
+        .with_context(|| "数据库 schema 检查失败, 拒绝启动")?;
 
     #[cfg(not(feature = "no_sync"))]
     let client = reqwest::ClientBuilder::new()
@@ -37,6 +51,44 @@ pub async fn worker(mut waiter: tokio::sync::oneshot::Receiver<()>) -> anyhow::R
 
     let _ = GLOBAL_CODE_MANAGER.update_token().await;
 
+    let rollup_interval = config.metrics_rollup_interval();
+    // 降采样只有 Postgres 支持
+    let rollup_part = db.as_postgres().filter(|_| rollup_interval > 0).map(|pg| {
+        crate::db::rollup::start_rollup_task(
+            pg.clone(),
+            config.metrics_raw_days() as i32,
+            config.metrics_daily_days() as i32,
+            rollup_interval,
+        )
+    });
+
+    let replica_part = db.as_postgres().filter(|pg| pg.has_replicas()).map(|pg| {
+        crate::db::replica::start_replica_monitor(
+            pg.clone(),
+            config.db_replica_max_lag(),
+            config.db_replica_check_interval(),
+        )
+    });
+
+    // 吊销通知只有 Postgres 有
+    let revocation_part = db
+        .as_postgres()
+        .map(|pg| crate::server::auth::start_revocation_listener(pg.clone()));
+
+    let maintenance_interval = config.statistics_maintenance_interval();
+    // 统计表分区只有 Postgres 有
+    let partition_part = db
+        .as_postgres()
+        .filter(|_| maintenance_interval > 0)
+        .map(|pg| {
+            crate::db::partition::start_partition_maintenance_task(
+                pg.clone(),
+                config.access_log_retention_days(),
+                config.hourly_statistics_retention_days(),
+                maintenance_interval,
+            )
+        });
+
     let interval = config.api_interval();
     let web_part = tokio::spawn(web_main(config.clone(), db.clone()));
 
@@ -62,10 +114,22 @@ pub async fn worker(mut waiter: tokio::sync::oneshot::Receiver<()>) -> anyhow::R
 
     event!(Level::INFO, "正在关闭 Web 服务器...");
     web_part.abort();
+    if let Some(rollup_part) = rollup_part {
+        rollup_part.abort();
+    }
+    if let Some(replica_part) = replica_part {
+        replica_part.abort();
+    }
+    if let Some(partition_part) = partition_part {
+        partition_part.abort();
+    }
+    if let Some(revocation_part) = revocation_part {
+        revocation_part.abort();
+    }
 
     // 优雅关闭统计系统
     event!(Level::INFO, "正在关闭统计系统...");
-    if let Err(e) = statistics::shutdown_statistics(&db).await {
+    if let Err(e) = statistics::shutdown_statistics(db.as_ref()).await {
         event!(Level::WARN, "关闭统计系统时出错: {:?}", e);
     }
 
@@ -73,10 +137,10 @@ pub async fn worker(mut waiter: tokio::sync::oneshot::Receiver<()>) -> anyhow::R
 }
 
 /// Web服务器主函数
-pub async fn web_main(config: Config, db: Database) -> anyhow::Result<()> {
+pub async fn web_main(config: Config, db: SharedStorage) -> anyhow::Result<()> {
     // 初始化统计系统
     let enable_logs = config.statistics_enable_detailed_logs();
-    statistics::initialize_statistics(&db, enable_logs).await?;
+    statistics::initialize_statistics(db.as_ref(), enable_logs).await?;
 
     // 启动统计同步任务
     let sync_interval = config.statistics_sync_interval();
@@ -93,6 +157,21 @@ pub async fn web_main(config: Config, db: Database) -> anyhow::Result<()> {
         cfg: config.clone(),
     });
 
+    // 投稿队列 worker, 任务有租约, 多实例可以同时开启
+    let workers = config.submission_workers();
+    let _worker_handles = if workers > 0 {
+        queue::start_submission_workers(
+            app_state.clone(),
+            workers,
+            config.submission_poll_interval(),
+            config.submission_lease(),
+        )
+        .await?
+    } else {
+        event!(Level::INFO, "submission_workers 为 0, 这个实例不处理投稿队列");
+        Vec::new()
+    };
+
     let router = routes::create_router(app_state);
 
     let listener = tokio::net::TcpListener::bind((config.serve_url(), config.serve_port())).await?;
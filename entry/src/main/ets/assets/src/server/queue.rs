@@ -0,0 +1,251 @@
+//! 投稿队列 worker
+//!
+//! 投稿接口只负责入队 (见 [`crate::db::submission`]), 这里的 worker 取出任务请求华为接口并写库.
+//! 同一实例入队后通过 [`notify`] 立即唤醒 worker, 别的实例入队的任务靠定时轮询取到.
+//!
+//! 执行中的任务按租约续约, 每个实例定时把租约过期的任务放回队列, 多个实例可以同时开启 worker
+
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures::{StreamExt, stream};
+use serde_json::{Value as JsonValue, json};
+use tokio::sync::Notify;
+use tracing::{Level, event};
+
+use crate::{
+    db::submission::{SubmissionJob, SubmissionKind},
+    model::AppQuery,
+    server::{cache, error::ApiResult, handlers::lookup_app, state::AppState},
+};
+
+/// 同一个专题里的应用同时同步的数量
+const SUBSTANCE_SYNC_CONCURRENCY: usize = 4;
+
+static WAKEUP: OnceLock<Notify> = OnceLock::new();
+
+fn wakeup() -> &'static Notify {
+    WAKEUP.get_or_init(Notify::new)
+}
+
+/// 有新任务入队, 唤醒一个空闲的 worker
+pub fn notify() {
+    wakeup().notify_one();
+}
+
+/// 这个实例的 ID, 记在它执行中的任务上
+///
+/// 每次启动都不一样, 重启前没执行完的任务要等租约过期后重新排队
+fn instance_id() -> &'static str {
+    static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+    INSTANCE_ID.get_or_init(|| {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "local".to_string());
+        let started = chrono::Utc::now().timestamp_micros();
+        format!("{host}-{}-{started:x}", std::process::id())
+    })
+}
+
+/// 启动投稿队列 worker
+///
+/// 启动 `workers` 个 worker, 另外启动一个定时把租约过期的任务放回队列的任务
+pub async fn start_submission_workers(
+    state: Arc<AppState>,
+    workers: usize,
+    poll_interval_seconds: u64,
+    lease_seconds: u64,
+) -> anyhow::Result<Vec<tokio::task::JoinHandle<()>>> {
+    requeue_stale(&state, lease_seconds)
+        .await
+        .with_context(|| "重置租约过期的投稿任务失败")?;
+    event!(
+        Level::INFO,
+        workers,
+        poll_interval_seconds,
+        lease_seconds,
+        instance = instance_id(),
+        "启动投稿队列 worker"
+    );
+
+    let poll_interval = Duration::from_secs(poll_interval_seconds);
+    let lease = Duration::from_secs(lease_seconds);
+    let mut handles: Vec<_> = (0..workers)
+        .map(|worker| tokio::spawn(worker_loop(state.clone(), worker, poll_interval, lease)))
+        .collect();
+    handles.push(tokio::spawn(reaper_loop(state, lease_seconds)));
+    Ok(handles)
+}
+
+async fn requeue_stale(state: &AppState, lease_seconds: u64) -> anyhow::Result<()> {
+    let requeued = state.db.requeue_stale_submissions(lease_seconds).await?;
+    if requeued > 0 {
+        event!(Level::WARN, "{requeued} 个租约过期的投稿任务已重新排队");
+        notify();
+    }
+    Ok(())
+}
+
+/// 每半个租约检查一次租约过期的任务
+async fn reaper_loop(state: Arc<AppState>, lease_seconds: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(lease_seconds / 2));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // 第一次在启动时已经执行过了
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Err(e) = requeue_stale(&state, lease_seconds).await {
+            event!(Level::WARN, "重置租约过期的投稿任务失败: {e:?}");
+        }
+    }
+}
+
+async fn worker_loop(
+    state: Arc<AppState>,
+    worker: usize,
+    poll_interval: Duration,
+    lease: Duration,
+) {
+    let worker_id = instance_id();
+    loop {
+        match state.db.claim_submission_job(worker_id).await {
+            Ok(Some(job)) => {
+                event!(
+                    Level::INFO,
+                    worker,
+                    job_id = job.id,
+                    "开始执行投稿任务 {} {}",
+                    job.kind.name(),
+                    job.target
+                );
+                let result = run_with_heartbeat(&state, &job, worker_id, lease).await;
+                if let Err(e) = &result {
+                    event!(Level::WARN, job_id = job.id, "投稿任务执行失败: {e}");
+                }
+                match state.db.finish_submission_job(job.id, worker_id, result).await {
+                    Ok(true) => {}
+                    Ok(false) => event!(
+                        Level::WARN,
+                        job_id = job.id,
+                        "投稿任务的租约已过期并被重新排队, 这次的结果不再记录"
+                    ),
+                    Err(e) => event!(Level::WARN, job_id = job.id, "记录投稿任务结果失败: {e:?}"),
+                }
+                // 可能还有排队的任务, 不等待直接取下一个
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => event!(Level::WARN, worker, "取投稿任务失败: {e:?}"),
+        }
+
+        tokio::select! {
+            _ = wakeup().notified() => {}
+            _ = tokio::time::sleep(poll_interval) => {}
+        }
+    }
+}
+
+/// 执行任务, 期间每隔租约的 1/3 续约一次
+async fn run_with_heartbeat(
+    state: &AppState,
+    job: &SubmissionJob,
+    worker_id: &str,
+    lease: Duration,
+) -> Result<JsonValue, String> {
+    let run = run_job(state, job);
+    tokio::pin!(run);
+    let mut heartbeat = tokio::time::interval(lease / 3);
+    heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // 取任务时已经续过约了
+    heartbeat.tick().await;
+    loop {
+        tokio::select! {
+            result = &mut run => return result,
+            _ = heartbeat.tick() => {
+                match state.db.heartbeat_submission_job(job.id, worker_id).await {
+                    Ok(true) => {}
+                    // 继续执行完, 结果不会写入
+                    Ok(false) => event!(Level::WARN, job_id = job.id, "投稿任务的租约已经丢失"),
+                    Err(e) => event!(Level::WARN, job_id = job.id, "投稿任务续约失败: {e:?}"),
+                }
+            }
+        }
+    }
+}
+
+/// 执行任务, 失败原因存到任务的 `error` 里
+async fn run_job(state: &AppState, job: &SubmissionJob) -> Result<JsonValue, String> {
+    match job.kind {
+        SubmissionKind::App => {
+            let query = job
+                .app_query()
+                .ok_or_else(|| format!("无法识别的应用投稿目标: {}", job.target))?;
+            run_app(state, &query).await.map_err(|e| e.to_string())
+        }
+        SubmissionKind::Substance => run_substance(state, job).await.map_err(|e| format!("{e:#}")),
+    }
+}
+
+/// 应用投稿, 结果和查询应用接口的 `data` 一样
+///
+/// 投稿附带的备注和上架时间要等审核通过才写入 (见 [`crate::db::moderation`]), 这里不传
+async fn run_app(state: &AppState, query: &AppQuery) -> ApiResult<JsonValue> {
+    let lookup = lookup_app(state, query, None, None).await?;
+    Ok(serde_json::to_value(&lookup).unwrap_or_default())
+}
+
+/// 专题投稿: 同步专题里的应用后保存专题, 备注同样要等审核
+async fn run_substance(state: &AppState, job: &SubmissionJob) -> anyhow::Result<JsonValue> {
+    let substance_id = &job.target;
+    let (substance, raw_value) =
+        crate::sync::get_app_from_substance(&state.client, state.cfg.api_url(), substance_id)
+            .await
+            .with_context(|| format!("从华为应用市场获取专题 {substance_id} 失败"))?;
+
+    let results: Vec<bool> = stream::iter(substance.data.iter())
+        .map(|query| async move {
+            match crate::sync::sync_app(
+                &state.client,
+                &state.db,
+                state.cfg.api_url(),
+                query,
+                None,
+                None,
+            )
+            .await
+            {
+                Ok(_) => true,
+                Err(e) => {
+                    event!(Level::WARN, "专题 {substance_id} 对应的应用 {query} 同步失败: {e}");
+                    false
+                }
+            }
+        })
+        .buffer_unordered(SUBSTANCE_SYNC_CONCURRENCY)
+        .collect()
+        .await;
+    let synced = results.iter().filter(|ok| **ok).count();
+    event!(
+        Level::INFO,
+        "专题 {} ({}) 对应的应用同步完成: 成功 {synced} 个, 失败 {} 个",
+        substance_id,
+        substance.say_my_name(),
+        results.len() - synced
+    );
+
+    let (is_new, changed) = state
+        .db
+        .save_substance(&substance, &raw_value, None)
+        .await
+        .with_context(|| format!("专题 {substance_id} 的数据保存失败"))?;
+    if changed {
+        cache::invalidate();
+    }
+    Ok(json!({
+        "data": substance,
+        "is_new": is_new,
+        "synced": synced,
+        "failed": results.len() - synced,
+    }))
+}
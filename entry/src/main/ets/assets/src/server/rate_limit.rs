@@ -0,0 +1,442 @@
+//! 按客户端限流
+//!
+//! 令牌桶, 按 (客户端, 预算) 计数. 客户端是请求带的有效 API key, 没带或无效时是 IP.
+//! 校验 key 需要查库时先按 IP 扣令牌, 随机 key 不能绕过 IP 预算占用数据库连接.
+//! `/submit`、`/rankings/download_increase`、`/apps/list` 这类会打到华为接口或者跑大查询的接口
+//! 有单独的预算, 其余 `/api` 接口共用 default 预算; 看板页面和静态资源不限流
+//!
+//! 响应带 `RateLimit-Limit` / `RateLimit-Remaining` / `RateLimit-Reset` / `RateLimit-Policy`,
+//! 被限流时返回 429 和 `Retry-After`, 次数记进每小时 IP 统计
+
+use std::{
+    net::IpAddr,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Extension,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_client_ip::ClientIp;
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{Level, event};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{RateBudget, RateLimitConfig, RouteRateLimit},
+    server::{auth, error::ApiError, state::AppState, statistics},
+};
+
+/// 全局限流器 (由 config.toml 初始化, 未初始化时不限流)
+pub static RATE_LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+static RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+static RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+static RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+static RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// 只限这个前缀下的接口
+const API_PREFIX: &str = "/api/";
+
+/// 满了之后最多每隔这么久清理一次令牌桶
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 其余 `/api` 接口的默认预算
+pub fn default_budget() -> RateBudget {
+    RateBudget {
+        burst: 120,
+        per_minute: 120,
+    }
+}
+
+/// 内置的单独预算, 配置里同名的会覆盖
+fn builtin_routes() -> Vec<RouteRateLimit> {
+    let route = |name: &str, paths: &[&str], burst, per_minute| RouteRateLimit {
+        name: name.to_string(),
+        paths: paths.iter().map(|path| path.to_string()).collect(),
+        burst,
+        per_minute,
+    };
+    vec![
+        // 每次都会请求华为接口; 前缀同时覆盖 /submit_substance
+        route("submit", &["/api/v0/submit"], 10, 10),
+        // 要扫一遍指标表
+        route("download_increase", &["/api/v0/rankings/download_increase"], 10, 6),
+        route("app_list", &["/api/v0/apps/list", "/api/v1/apps/list"], 30, 30),
+    ]
+}
+
+/// 计数对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    ApiKey(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Budget {
+    name: String,
+    paths: Vec<String>,
+    burst: u32,
+    per_minute: u32,
+    throttled: AtomicU64,
+}
+
+impl Budget {
+    fn new(name: &str, paths: Vec<String>, burst: u32, per_minute: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            paths,
+            burst,
+            per_minute,
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// 每秒补充的令牌数
+    fn rate(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    /// 补到 `now` 时的令牌数
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate()).min(self.burst as f64)
+    }
+}
+
+/// 一次检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    /// 令牌补满还要多少秒
+    reset: u64,
+    /// 被限流时, 下一个令牌还要多少秒
+    retry_after: u64,
+    /// 补满所需的秒数, 即 `RateLimit-Policy` 里的窗口
+    window: u64,
+}
+
+impl Decision {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let mut insert = |name: &HeaderName, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name.clone(), value);
+            }
+        };
+        insert(&RATELIMIT_LIMIT, self.limit.to_string());
+        insert(&RATELIMIT_REMAINING, self.remaining.to_string());
+        insert(&RATELIMIT_RESET, self.reset.to_string());
+        insert(&RATELIMIT_POLICY, format!("{};w={}", self.limit, self.window));
+        if !self.allowed {
+            insert(&header::RETRY_AFTER, self.retry_after.to_string());
+        }
+    }
+}
+
+pub struct RateLimiter {
+    /// 第 0 个是 default 预算
+    budgets: Vec<Budget>,
+    buckets: DashMap<(Client, usize), Bucket>,
+    by_api_key: bool,
+    max_clients: usize,
+    last_prune: Mutex<Instant>,
+}
+
+/// 单个预算的配置和限流次数
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BudgetStats {
+    /// 预算名, `default` 为其余 `/api` 接口共用
+    pub name: String,
+    /// 匹配的路径前缀
+    pub paths: Vec<String>,
+    /// 令牌桶容量
+    pub burst: u32,
+    /// 每分钟补充的令牌数
+    pub per_minute: u32,
+    /// 进程启动以来被限流的请求数
+    pub throttled: u64,
+}
+
+/// 限流统计
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RateLimitStats {
+    /// 带有效 API key 时是否按 key 计数
+    pub by_api_key: bool,
+    /// 内存里的令牌桶数量
+    pub tracked_buckets: usize,
+    pub budgets: Vec<BudgetStats>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let mut routes = builtin_routes();
+        for route in &config.routes {
+            match routes.iter_mut().find(|builtin| builtin.name == route.name) {
+                Some(builtin) => *builtin = route.clone(),
+                None => routes.push(route.clone()),
+            }
+        }
+        let default = Budget::new(
+            "default",
+            vec![API_PREFIX.to_string()],
+            config.default.burst,
+            config.default.per_minute,
+        );
+        let budgets = std::iter::once(default)
+            .chain(routes.into_iter().map(|route| {
+                Budget::new(&route.name, route.paths, route.burst, route.per_minute)
+            }))
+            .collect();
+        Self {
+            budgets,
+            buckets: DashMap::new(),
+            by_api_key: config.by_api_key,
+            max_clients: config.max_clients,
+            last_prune: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 路径对应的预算, 最长前缀优先; 不在 `/api` 下返回 None
+    fn budget_for(&self, path: &str) -> Option<usize> {
+        if !path.starts_with(API_PREFIX) {
+            return None;
+        }
+        let matched = self
+            .budgets
+            .iter()
+            .enumerate()
+            .skip(1)
+            .flat_map(|(index, budget)| budget.paths.iter().map(move |prefix| (index, prefix)))
+            .filter(|(_, prefix)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(_, prefix)| prefix.len())
+            .map(|(index, _)| index);
+        Some(matched.unwrap_or(0))
+    }
+
+    /// 取一个令牌
+    fn check(&self, client: Client, index: usize, now: Instant) -> Decision {
+        let budget = &self.budgets[index];
+        let key = (client, index);
+        if self.buckets.len() >= self.max_clients && !self.buckets.contains_key(&key) {
+            self.prune(now);
+        }
+
+        let burst = budget.burst as f64;
+        let rate = budget.rate();
+        let (allowed, tokens) = {
+            let mut bucket = self.buckets.entry(key).or_insert(Bucket {
+                tokens: burst,
+                updated_at: now,
+            });
+            bucket.tokens = budget.refilled(&bucket, now);
+            bucket.updated_at = now;
+            let allowed = bucket.tokens >= 1.0;
+            if allowed {
+                bucket.tokens -= 1.0;
+            }
+            (allowed, bucket.tokens)
+        };
+        if !allowed {
+            budget.throttled.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Decision {
+            allowed,
+            limit: budget.burst,
+            remaining: tokens.floor() as u32,
+            reset: ((burst - tokens) / rate).ceil() as u64,
+            retry_after: if allowed {
+                0
+            } else {
+                ((1.0 - tokens) / rate).ceil().max(1.0) as u64
+            },
+            window: (burst / rate).ceil() as u64,
+        }
+    }
+
+    /// 清掉已经补满的令牌桶, 它们和新建的没有区别
+    fn prune(&self, now: Instant) {
+        let Ok(mut last_prune) = self.last_prune.try_lock() else {
+            return;
+        };
+        if now.saturating_duration_since(*last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+        *last_prune = now;
+        let before = self.buckets.len();
+        self.buckets.retain(|(_, index), bucket| {
+            let budget = &self.budgets[*index];
+            budget.refilled(bucket, now) < budget.burst as f64
+        });
+        event!(
+            Level::DEBUG,
+            removed = before - self.buckets.len(),
+            "清理已补满的令牌桶"
+        );
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            by_api_key: self.by_api_key,
+            tracked_buckets: self.buckets.len(),
+            budgets: self
+                .budgets
+                .iter()
+                .map(|budget| BudgetStats {
+                    name: budget.name.clone(),
+                    paths: budget.paths.clone(),
+                    burst: budget.burst,
+                    per_minute: budget.per_minute,
+                    throttled: budget.throttled.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 当前限流统计, 未启用时为 None
+pub fn stats() -> Option<RateLimitStats> {
+    RATE_LIMITER.get().map(RateLimiter::stats)
+}
+
+/// 限流中间件, 放在统计中间件里面, 被限流的请求也会记进访问统计
+pub async fn rate_limit_middleware(
+    State(state): State<Arc<AppState>>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = RATE_LIMITER.get() else {
+        return next.run(request).await;
+    };
+    let Some(index) = limiter.budget_for(request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let now = Instant::now();
+    let (client, decision) = if limiter.by_api_key {
+        // 缓存里没有的 key 要查库, 先按 IP 扣一个令牌, 查库的请求也受 IP 预算限制
+        let ip_decision = if auth::needs_key_lookup(request.headers()) {
+            let decision = limiter.check(Client::Ip(ip), index, now);
+            if !decision.allowed {
+                return throttled(limiter, index, Client::Ip(ip), ip, &decision);
+            }
+            Some(decision)
+        } else {
+            None
+        };
+        let client = auth::resolve_key_id(&state, &mut request)
+            .await
+            .map_or(Client::Ip(ip), Client::ApiKey);
+        let decision = match (client, ip_decision) {
+            // 已经按 IP 扣过了
+            (Client::Ip(_), Some(decision)) => decision,
+            _ => limiter.check(client, index, now),
+        };
+        (client, decision)
+    } else {
+        (Client::Ip(ip), limiter.check(Client::Ip(ip), index, now))
+    };
+
+    if !decision.allowed {
+        return throttled(limiter, index, client, ip, &decision);
+    }
+
+    let mut response = next.run(request).await;
+    decision.write_headers(response.headers_mut());
+    response
+}
+
+/// 被限流时的 429 响应
+fn throttled(
+    limiter: &RateLimiter,
+    index: usize,
+    client: Client,
+    ip: IpAddr,
+    decision: &Decision,
+) -> Response {
+    let budget = &limiter.budgets[index].name;
+    statistics::record_throttled(ip, Utc::now());
+    event!(Level::DEBUG, ?client, budget = budget.as_str(), "请求被限流");
+    let mut response = ApiError::rate_limited(format!(
+        "请求过于频繁, 请 {} 秒后再试",
+        decision.retry_after
+    ))
+    .with_detail(json!({ "budget": budget, "retry_after": decision.retry_after }))
+    .into_response();
+    decision.write_headers(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(routes: Vec<RouteRateLimit>) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            default: RateBudget {
+                burst: 2,
+                per_minute: 60,
+            },
+            routes,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn budget_by_longest_prefix() {
+        let limiter = limiter(vec![RouteRateLimit {
+            name: "submit".to_string(),
+            paths: vec!["/api/v0/submit".to_string(), "/api/v0/submit_substance".to_string()],
+            burst: 1,
+            per_minute: 1,
+        }]);
+        let name = |path| limiter.budget_for(path).map(|i| limiter.budgets[i].name.as_str());
+        assert_eq!(name("/dashboard"), None);
+        assert_eq!(name("/api/v0/market_info"), Some("default"));
+        assert_eq!(name("/api/v1/apps/list/1"), Some("app_list"));
+        // 配置里同名的覆盖内置的, 不会多出一个 submit
+        assert_eq!(name("/api/v0/submit_substance/abc"), Some("submit"));
+        assert_eq!(limiter.budgets.iter().filter(|b| b.name == "submit").count(), 1);
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let limiter = limiter(Vec::new());
+        let client = Client::Ip("127.0.0.1".parse().unwrap());
+        let start = Instant::now();
+
+        let first = limiter.check(client, 0, start);
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining, first.window), (2, 1, 2));
+        assert!(limiter.check(client, 0, start).allowed);
+
+        // 桶空了, 每秒补一个
+        let denied = limiter.check(client, 0, start);
+        assert!(!denied.allowed);
+        assert_eq!((denied.remaining, denied.retry_after, denied.reset), (0, 1, 2));
+        assert_eq!(limiter.stats().budgets[0].throttled, 1);
+
+        assert!(limiter.check(client, 0, start + Duration::from_secs(1)).allowed);
+        // 别的客户端不受影响
+        assert!(limiter.check(Client::ApiKey(1), 0, start).allowed);
+    }
+}
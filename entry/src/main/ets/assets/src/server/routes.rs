@@ -1,5 +1,5 @@
 use axum::{
-    Json, Router,
+    Extension, Json, Router,
     http::StatusCode,
     middleware,
     response::IntoResponse,
@@ -7,24 +7,31 @@ use axum::{
 };
 use tower_http::compression::CompressionLayer;
 use tower_http::services::ServeDir;
-use utoipa::OpenApi;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+};
 
 use std::sync::Arc;
 
 use crate::server::statistics::{get_statistics, middle_response};
-use crate::server::{frontend_handlers, handlers, statistics_handlers};
 use crate::server::{
-    middle::client_ip_middleware,
-    state::{ApiResponse, AppState},
+    admin_handlers, auth, changelog_handlers, feishu_handlers, frontend_handlers, graphql,
+    handlers, rate_limit, search_handlers, statistics_handlers, v1_handlers,
+};
+use crate::server::{
+    error::ApiError,
+    middle::{client_ip_middleware, request_id_middleware},
+    state::AppState,
 };
 
 pub type AppRouter = Router<Arc<AppState>>;
 
 pub fn feishu_router(app_state: Arc<AppState>) -> AppRouter {
     Router::new()
-        .route("/meta.json", get(handlers::feishu_meta))
-        .route("/table_meta", post(handlers::feishu_table_meta))
-        .route("/records", post(handlers::feishu_records))
+        .route("/meta.json", get(feishu_handlers::feishu_meta))
+        .route("/table_meta", post(feishu_handlers::feishu_table_meta))
+        .route("/records", post(feishu_handlers::feishu_records))
         .with_state(app_state)
 }
 
@@ -42,10 +49,51 @@ pub fn statistics_router(app_state: Arc<AppState>) -> AppRouter {
         .route("/history", get(statistics_handlers::get_history_statistics))
         // 获取每小时统计趋势
         .route("/hourly", get(statistics_handlers::get_hourly_statistics))
-        // 获取访问日志 不行, 这玩意不给看
-        // .route("/logs", get(statistics_handlers::get_access_logs))
+        // 获取访问日志, 需要 stats:read
+        .route(
+            "/logs",
+            get(statistics_handlers::get_access_logs).route_layer(
+                middleware::from_fn_with_state(app_state.clone(), auth::require_stats_read),
+            ),
+        )
         // 获取统计概览
         .route("/summary", get(statistics_handlers::get_statistics_summary))
+        // 获取查询缓存命中情况
+        .route("/cache", get(statistics_handlers::get_cache_statistics))
+        // 获取限流统计
+        .route("/rate_limit", get(statistics_handlers::get_rate_limit_statistics))
+        .with_state(app_state)
+}
+
+pub fn admin_router(app_state: Arc<AppState>) -> AppRouter {
+    Router::new()
+        // app_full_info 一致性检查 / 修复
+        .route("/consistency", get(admin_handlers::check_consistency))
+        .route("/consistency/repair", post(admin_handlers::repair_consistency))
+        // 数据导出
+        .route("/export", get(admin_handlers::export_dataset))
+        // 投稿审核
+        .route("/reviews", get(admin_handlers::list_reviews))
+        .route("/reviews/{review_id}", get(admin_handlers::get_review))
+        .route("/reviews/{review_id}/edit", post(admin_handlers::edit_review))
+        .route("/reviews/{review_id}/approve", post(admin_handlers::approve_review))
+        .route("/reviews/{review_id}/reject", post(admin_handlers::reject_review))
+        .route("/moderation_log", get(admin_handlers::list_moderation_log))
+        // 管理接口全部需要 admin
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth::require_admin))
+        .with_state(app_state)
+}
+
+/// 投稿, 需要 submit (可以通过 `serve.anonymous_scopes` 对匿名请求开放)
+pub fn submit_router(app_state: Arc<AppState>) -> AppRouter {
+    Router::new()
+        .route("/submit", post(handlers::submit_app))
+        .route(
+            "/submit_substance/{substance_id}",
+            post(handlers::submit_substance),
+        )
+        .route("/submissions/{job_id}", get(handlers::get_submission_job))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), auth::require_submit))
         .with_state(app_state)
 }
 
@@ -60,7 +108,10 @@ pub fn api_router(app_state: Arc<AppState>) -> AppRouter {
         // 根据应用ID查询应用信息
         .route("/apps/app_id/{app_id}", get(handlers::query_app_id))
         // 获取分页的应用信息
-        .route("/apps/list/{page_count}", get(handlers::app_list_paged))
+        .route(
+            "/apps/list/{page_count}",
+            get(handlers::app_list_paged).post(handlers::app_list_paged_body),
+        )
         // 获取应用图标URL
         .route("/apps/icon", get(handlers::get_app_icon))
         // 获取应用下载量历史数据
@@ -68,6 +119,16 @@ pub fn api_router(app_state: Arc<AppState>) -> AppRouter {
             "/apps/metrics/{pkg_id}",
             get(handlers::get_app_download_history),
         )
+        // 应用变更记录
+        .route(
+            "/apps/changelog/{app_id}",
+            get(changelog_handlers::app_changelog),
+        )
+        // 全市场最近变更
+        .route("/changes/recent", get(changelog_handlers::recent_changes))
+        // 搜索
+        .route("/search", get(search_handlers::search_apps))
+        .route("/search/suggest", get(search_handlers::search_suggest))
         // 专题查询相关路由
         // 根据专题ID查询专题信息
         .route("/substance/{substance_id}", get(handlers::query_substance))
@@ -76,6 +137,11 @@ pub fn api_router(app_state: Arc<AppState>) -> AppRouter {
             "/substance/list/{page}",
             get(handlers::substance_list_paged),
         )
+        // 专题变更记录
+        .route(
+            "/substance/changelog/{substance_id}",
+            get(changelog_handlers::substance_changelog),
+        )
         // 新增排行API路由
         // 下载量增量排行榜
         .route(
@@ -96,18 +162,45 @@ pub fn api_router(app_state: Arc<AppState>) -> AppRouter {
             get(handlers::get_target_sdk_distribution),
         )
         // 投稿
-        .route("/submit", post(handlers::submit_app))
-        .route(
-            "/submit_substance/{substance_id}",
-            post(handlers::submit_substance),
-        )
+        .merge(submit_router(app_state.clone()))
         .nest("/feishu", feishu_router(app_state.clone()))
         .nest("/temp", temp_router(app_state.clone()))
         .nest("/statistics", statistics_router(app_state.clone()))
+        .nest("/admin", admin_router(app_state.clone()))
         .fallback(api_not_found)
         .with_state(app_state.clone())
 }
 
+/// `/api/v1`: 返回结构和数据库表解耦, 见 `model::v1`
+pub fn api_v1_router(app_state: Arc<AppState>) -> AppRouter {
+    Router::new()
+        .route("/apps/pkg_name/{pkg_name}", get(v1_handlers::query_pkg))
+        .route("/apps/app_id/{app_id}", get(v1_handlers::query_app_id))
+        .route(
+            "/apps/list/{page_count}",
+            get(v1_handlers::app_list_paged).post(v1_handlers::app_list_paged_body),
+        )
+        .route("/substance/{substance_id}", get(v1_handlers::query_substance))
+        .route(
+            "/substance/list/{page}",
+            get(v1_handlers::substance_list_paged),
+        )
+        .fallback(api_not_found)
+        .with_state(app_state)
+}
+
+/// `/api/graphql`: 只读查询, 复杂度和深度上限见配置
+pub fn graphql_router(app_state: Arc<AppState>) -> AppRouter {
+    let schema = graphql::build_schema(
+        app_state.cfg.graphql_max_complexity(),
+        app_state.cfg.graphql_max_depth(),
+    );
+    Router::new()
+        .route("/", post(graphql::graphql_handler))
+        .layer(Extension(schema))
+        .with_state(app_state)
+}
+
 /// 静态资源处理
 async fn static_handler() -> impl IntoResponse {
     Json(get_statistics().await)
@@ -142,13 +235,19 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
         // API 文档页面
         .route("/swagger-ui", get(frontend_handlers::serve_swagger_ui))
         .route("/docs", get(frontend_handlers::serve_swagger_ui))
+        .route("/graphiql", get(frontend_handlers::serve_graphiql))
         .nest_service("/js", ServeDir::new("assets/js"))
         .nest("/api/v0", api_router(app_state.clone()))
+        .nest("/api/v1", api_v1_router(app_state.clone()))
+        .nest("/api/graphql", graphql_router(app_state.clone()))
         .fallback(frontend_handlers::serve_not_found)
-        .with_state(app_state)
+        .with_state(app_state.clone())
         .layer(CompressionLayer::new())
+        // 在统计中间件里面, 被限流的请求同样计入访问统计
+        .layer(middleware::from_fn_with_state(app_state, rate_limit::rate_limit_middleware))
         .layer(middleware::from_fn(middle_response))
         .layer(middleware::from_fn(client_ip_middleware))
+        .layer(middleware::from_fn(request_id_middleware))
 }
 
 #[derive(OpenApi)]
@@ -167,8 +266,16 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
         handlers::query_pkg,
         handlers::query_app_id,
         handlers::app_list_paged,
+        handlers::app_list_paged_body,
         handlers::get_app_icon,
         handlers::get_app_download_history,
+        // 搜索
+        search_handlers::search_apps,
+        search_handlers::search_suggest,
+        // 变更记录
+        changelog_handlers::app_changelog,
+        changelog_handlers::substance_changelog,
+        changelog_handlers::recent_changes,
         // 市场信息
         handlers::market_info,
         handlers::sync_status_stream,
@@ -184,29 +291,79 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
         // 应用提交
         handlers::submit_app,
         handlers::submit_substance,
+        handlers::get_submission_job,
         // 专题查询
         handlers::query_substance,
         handlers::substance_list_paged,
         // 飞书集成
-        handlers::feishu_meta,
-        handlers::feishu_table_meta,
-        handlers::feishu_records,
+        feishu_handlers::feishu_meta,
+        feishu_handlers::feishu_table_meta,
+        feishu_handlers::feishu_records,
         // 访问统计
         statistics_handlers::get_current_statistics,
         statistics_handlers::get_history_statistics,
         statistics_handlers::get_hourly_statistics,
-        // statistics_handlers::get_access_logs,
+        statistics_handlers::get_access_logs,
         statistics_handlers::get_statistics_summary,
+        statistics_handlers::get_cache_statistics,
+        statistics_handlers::get_rate_limit_statistics,
+        // 管理
+        admin_handlers::check_consistency,
+        admin_handlers::repair_consistency,
+        admin_handlers::export_dataset,
+        admin_handlers::list_reviews,
+        admin_handlers::get_review,
+        admin_handlers::edit_review,
+        admin_handlers::approve_review,
+        admin_handlers::reject_review,
+        admin_handlers::list_moderation_log,
+        // v1
+        v1_handlers::query_pkg,
+        v1_handlers::query_app_id,
+        v1_handlers::app_list_paged,
+        v1_handlers::app_list_paged_body,
+        v1_handlers::query_substance,
+        v1_handlers::substance_list_paged,
     ),
     components(
         schemas(
             // API 基础类型
             crate::server::state::ApiResponse,
+            crate::server::error::ApiErrorResponse,
+            crate::server::error::ErrorBody,
+            crate::server::error::ErrorCode,
+            crate::db::api_key::ApiScope,
             crate::server::state::AppListQuery,
+            crate::server::state::AppExpandQuery,
+            crate::db::expand::Include,
             crate::server::state::AppQueryParam,
             crate::server::state::IntervalParams,
             crate::server::state::RankingQuery,
             crate::server::state::SubstanceListQuery,
+            // 过滤条件
+            crate::db::filter::AppFilter,
+            crate::db::filter::FilterField,
+            crate::db::filter::FilterValue,
+            // 搜索
+            crate::server::search_handlers::SearchQuery,
+            crate::server::search_handlers::SuggestQuery,
+            crate::db::search::SearchHit,
+            crate::db::search::Suggestion,
+            crate::db::search::SuggestionKind,
+            // 变更记录
+            crate::server::changelog_handlers::ChangelogQuery,
+            crate::server::changelog_handlers::RecentChangesQuery,
+            crate::db::changelog::ChangelogKind,
+            crate::db::changelog::ChangelogEntry,
+            crate::db::changelog::FieldChange,
+            crate::db::changelog::ChangeOp,
+            // 投稿队列
+            crate::db::submission::SubmissionJob,
+            crate::db::submission::SubmissionKind,
+            crate::db::submission::JobStatus,
+            // 飞书集成
+            crate::server::feishu_handlers::FeishuRequest,
+            crate::server::feishu_handlers::FeishuTable,
             // 应用模型
             crate::model::FullAppInfo,
             crate::model::ShortAppInfo,
@@ -222,23 +379,81 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
             crate::server::statistics_handlers::HourlyQueryParams,
             crate::server::statistics_handlers::AccessLogQueryParams,
             crate::server::statistics_handlers::StatisticsSummary,
+            crate::server::cache::CacheStats,
+            crate::server::rate_limit::RateLimitStats,
+            crate::server::rate_limit::BudgetStats,
+            // 管理
+            crate::server::admin_handlers::ConsistencyQuery,
+            crate::db::consistency::ConsistencyReport,
+            crate::db::consistency::ConsistencyIssue,
+            crate::db::consistency::IssueKind,
+            crate::db::export::Dataset,
+            crate::db::export::ExportFormat,
+            // 投稿审核
+            crate::server::admin_handlers::ReviewListQuery,
+            crate::server::admin_handlers::ModerationLogQuery,
+            crate::server::admin_handlers::ReviewEditBody,
+            crate::server::admin_handlers::ReviewDecisionBody,
+            crate::db::moderation::SubmissionReview,
+            crate::db::moderation::ReviewStatus,
+            crate::db::moderation::ModerationAction,
+            crate::db::moderation::ReviewMetadata,
+            crate::db::moderation::ModerationLogEntry,
+            // v1 返回结构
+            crate::model::v1::AppLookupV1,
+            crate::model::v1::SyncStateV1,
+            crate::model::v1::AppV1,
+            crate::model::v1::AppSummaryV1,
+            crate::model::v1::DeveloperV1,
+            crate::model::v1::CategoryV1,
+            crate::model::v1::ClassificationV1,
+            crate::model::v1::CodeV1,
+            crate::model::v1::TariffV1,
+            crate::model::v1::AppFlagsV1,
+            crate::model::v1::ReleaseV1,
+            crate::model::v1::MetricsV1,
+            crate::model::v1::RatingV1,
+            crate::model::v1::StarCountsV1,
+            crate::model::v1::RecordalV1,
+            crate::model::v1::SubstanceV1,
+            crate::model::v1::SubstanceSummaryV1,
         )
     ),
+    modifiers(&SecurityAddon),
     tags(
         (name = "应用查询", description = "应用信息查询相关接口"),
+        (name = "搜索", description = "应用全文搜索与自动补全"),
+        (name = "变更记录", description = "应用 / 专题原始数据的变更记录"),
         (name = "市场信息", description = "市场统计信息和同步状态"),
         (name = "排行榜", description = "各类应用排行榜"),
         (name = "统计图表", description = "数据分布统计图表"),
         (name = "应用提交", description = "应用信息提交接口"),
         (name = "专题查询", description = "专题信息查询相关接口"),
-        (name = "飞书集成", description = "飞书数据连接器集成(目前未实现)"),
+        (name = "飞书集成", description = "飞书多维表格数据连接器"),
         (name = "访问统计", description = "API访问统计分析"),
+        (name = "管理", description = "数据维护和投稿审核接口"),
+        (name = "应用查询 v1", description = "v1 应用 / 专题查询, 返回结构与数据库表解耦"),
     )
 )]
 struct ApiDocs;
 
+/// 注册 API key 鉴权方式, 需要鉴权的接口用 `security(("api_key" = []))` 标注
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(
+                    auth::API_KEY_HEADER.as_str(),
+                ))),
+            );
+        }
+    }
+}
+
 /// API-specific 404 handler returning JSON error
-async fn api_not_found() -> impl IntoResponse {
-    let payload = ApiResponse::error("Api Not Found");
-    (StatusCode::NOT_FOUND, Json(payload))
+async fn api_not_found() -> ApiError {
+    ApiError::not_found("Api Not Found")
 }
@@ -0,0 +1,124 @@
+//! 搜索 HTTP 接口处理器
+//!
+//! 提供按相关度排序的应用搜索和应用名 / 开发者名自动补全
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{Level, event};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::server::{
+    error::{ApiError, ApiResult},
+    state::{ApiResponse, AppState},
+};
+
+/// 搜索关键词最大长度 (字符)
+const MAX_TERM_CHARS: usize = 64;
+
+/// 搜索查询参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct SearchQuery {
+    /// 搜索关键词
+    pub q: String,
+    /// 页码，从 1 开始
+    pub page: Option<u32>,
+    /// 每页大小
+    pub page_size: Option<u32>,
+    /// 是否排除原子化应用
+    pub exclude_atomic: Option<bool>,
+}
+
+/// 自动补全查询参数
+#[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
+pub struct SuggestQuery {
+    /// 已输入的前缀
+    pub q: String,
+    /// 返回数量，默认 10
+    pub limit: Option<u32>,
+}
+
+/// 清理关键词, 不合法时返回错误信息
+fn clean_term(raw: &str) -> ApiResult<&str> {
+    let term = raw.trim();
+    if term.is_empty() {
+        return Err(ApiError::bad_request("搜索关键词不能为空"));
+    }
+    if term.chars().count() > MAX_TERM_CHARS {
+        return Err(ApiError::bad_request(format!(
+            "搜索关键词太长了, 最多 {MAX_TERM_CHARS} 个字符"
+        )));
+    }
+    Ok(term)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "按相关度排序的搜索结果，data 为 SearchHit 列表", body = ApiResponse)
+    ),
+    tag = "搜索"
+)]
+/// 搜索应用
+///
+/// 同时匹配应用名、简介、描述、开发者名和分类名，支持错别字容错，
+/// 结果中的 snippet 用 `<mark>` 标出命中部分
+pub async fn search_apps(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let term = clean_term(&query.q)?;
+    let page_size = query.page_size.unwrap_or(20);
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let (hits, total) = db
+        .search_apps(
+            term,
+            query.page.unwrap_or(1),
+            page_size,
+            query.exclude_atomic.unwrap_or(false),
+        )
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "搜索 {term} 失败: {e}");
+            ApiError::database("搜索失败")
+        })?;
+    Ok(Json(ApiResponse::success(
+        hits,
+        Some(total as u32),
+        Some(page_size),
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/search/suggest",
+    params(SuggestQuery),
+    responses(
+        (status = 200, description = "自动补全候选，data 为 Suggestion 列表", body = ApiResponse)
+    ),
+    tag = "搜索"
+)]
+/// 应用名 / 开发者名自动补全
+pub async fn search_suggest(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SuggestQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    let term = clean_term(&query.q)?;
+    let limit = query.limit.unwrap_or(10);
+    let Some(db) = state.postgres() else {
+        return Err(ApiError::unsupported_backend());
+    };
+    let suggestions = db.suggest(term, limit).await.map_err(|e| {
+        event!(Level::WARN, "自动补全 {term} 失败: {e}");
+        ApiError::database("自动补全失败")
+    })?;
+    let total = suggestions.len() as u32;
+    Ok(Json(ApiResponse::success(suggestions, Some(total), Some(limit))))
+}
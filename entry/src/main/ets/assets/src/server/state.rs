@@ -5,23 +5,33 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     config::Config,
-    db::{Database, DbSearch},
+    db::{
+        Database, DbSearch,
+        expand::{DEFAULT_INCLUDE_METRICS, Include, parse_fields, parse_includes},
+        filter::AppFilter,
+        storage::SharedStorage,
+    },
     model::AppQuery,
 };
 
 /// 应用状态，包含数据库连接、HTTP客户端和配置
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Database,
+    pub db: SharedStorage,
     pub client: Client,
     pub cfg: Config,
 }
 
 impl AppState {
     /// 创建新的应用状态
-    pub fn new(db: Database, client: Client, cfg: Config) -> Self {
+    pub fn new(db: SharedStorage, client: Client, cfg: Config) -> Self {
         Self { db, client, cfg }
     }
+
+    /// Postgres 专有功能 (搜索、变更记录、下载增量) 使用, 其它后端返回 None
+    pub fn postgres(&self) -> Option<&Database> {
+        self.db.as_postgres()
+    }
 }
 
 /// 用于API响应的统一格式
@@ -117,6 +127,26 @@ pub struct AppListQuery {
     pub exclude_huawei: Option<bool>,
     /// 是否排除原子化应用
     pub exclude_atomic: Option<bool>,
+    /// 组合过滤条件
+    ///
+    /// query string 中传 JSON 字符串, POST body 中直接传对象
+    #[param(value_type = Option<String>)]
+    #[schema(value_type = Option<AppFilter>)]
+    pub filter: Option<FilterParam>,
+    /// 只返回这些字段 (app_full_info 的列名, 逗号分隔), 指定后忽略 detail
+    pub fields: Option<String>,
+    /// 内嵌关联数据, 逗号分隔: metrics / rating / substances / recordal
+    pub include: Option<String>,
+    /// include=metrics 时每个应用带几条, 默认 10
+    pub metrics_limit: Option<u32>,
+}
+
+/// 过滤条件参数, 兼容 JSON 字符串和 JSON 对象两种写法
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum FilterParam {
+    Tree(AppFilter),
+    Json(String),
 }
 
 impl AppListQuery {
@@ -195,6 +225,28 @@ impl AppListQuery {
         self.search_key.clone()
     }
 
+    /// 解析并校验过滤条件
+    pub fn filter_option(&self) -> Result<Option<AppFilter>, String> {
+        let filter = match &self.filter {
+            None => return Ok(None),
+            Some(FilterParam::Tree(filter)) => filter.clone(),
+            Some(FilterParam::Json(raw)) if raw.trim().is_empty() => return Ok(None),
+            Some(FilterParam::Json(raw)) => serde_json::from_str::<AppFilter>(raw)
+                .map_err(|e| format!("filter 解析失败: {e}"))?,
+        };
+        filter.validate().map_err(|e| e.to_string())?;
+        Ok(Some(filter))
+    }
+
+    /// `fields` / `include` 相关参数
+    pub fn expand(&self) -> AppExpandQuery {
+        AppExpandQuery {
+            fields: self.fields.clone(),
+            include: self.include.clone(),
+            metrics_limit: self.metrics_limit,
+        }
+    }
+
     pub fn search_option(&self) -> Option<DbSearch> {
         if !self.is_valid_search() {
             return None;
@@ -211,6 +263,39 @@ impl AppListQuery {
     }
 }
 
+/// 单个应用接口的 `fields` / `include` 参数
+#[derive(Deserialize, Serialize, Clone, Debug, Default, ToSchema, IntoParams)]
+pub struct AppExpandQuery {
+    /// 只返回这些字段 (app_full_info 的列名, 逗号分隔)
+    pub fields: Option<String>,
+    /// 内嵌关联数据, 逗号分隔: metrics / rating / substances / recordal
+    pub include: Option<String>,
+    /// include=metrics 时带几条, 默认 10
+    pub metrics_limit: Option<u32>,
+}
+
+impl AppExpandQuery {
+    /// 解析并校验 `fields`, 没传返回 None
+    pub fn fields(&self) -> Result<Option<Vec<&'static str>>, String> {
+        match &self.fields {
+            Some(raw) => parse_fields(raw).map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 解析并校验 `include`
+    pub fn includes(&self) -> Result<Vec<Include>, String> {
+        match &self.include {
+            Some(raw) => parse_includes(raw).map_err(|e| e.to_string()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn metrics_limit(&self) -> u32 {
+        self.metrics_limit.unwrap_or(DEFAULT_INCLUDE_METRICS)
+    }
+}
+
 // 定义一个结构体来接收 URL 查询参数
 #[derive(Debug, Deserialize, Serialize, ToSchema, IntoParams)]
 pub struct IntervalParams {
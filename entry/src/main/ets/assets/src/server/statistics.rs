@@ -9,7 +9,13 @@ use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, event};
 
-use crate::db::{Database, statistics::AccessLog};
+use crate::{
+    db::{
+        statistics::AccessLog,
+        storage::{SharedStorage, Storage},
+    },
+    server::auth::ApiKeyId,
+};
 
 /// 统计条目结构体 - 存储完整的统计信息
 #[derive(Debug, Clone)]
@@ -37,6 +43,12 @@ static UA_HOURLY_COUNTS: OnceLock<DashMap<(String, DateTime<Utc>), HourlyEntry>>
 /// IP 每小时统计 - key: (ip_address, hour_timestamp)
 static IP_HOURLY_COUNTS: OnceLock<DashMap<(IpAddr, DateTime<Utc>), HourlyEntry>> = OnceLock::new();
 
+/// IP 每小时被限流次数 - key: (ip_address, hour_timestamp), 同步后清空
+static IP_HOURLY_THROTTLED: OnceLock<DashMap<(IpAddr, DateTime<Utc>), u64>> = OnceLock::new();
+
+/// API key 最后使用时间 - key: api_key_id, 同步后清空
+static API_KEY_LAST_USED: OnceLock<DashMap<i64, DateTime<Utc>>> = OnceLock::new();
+
 // 统计条目的最大数量配置（由 config.toml 初始化）
 pub static MAX_UA_ENTRIES: OnceLock<usize> = OnceLock::new();
 pub static MAX_IP_ENTRIES: OnceLock<usize> = OnceLock::new();
@@ -76,7 +88,7 @@ fn get_hour_timestamp(now: DateTime<Utc>) -> DateTime<Utc> {
 /// 初始化统计系统
 ///
 /// 从数据库加载历史统计数据到内存
-pub async fn initialize_statistics(db: &Database, enable_logs: bool) -> anyhow::Result<()> {
+pub async fn initialize_statistics(db: &dyn Storage, enable_logs: bool) -> anyhow::Result<()> {
     event!(Level::INFO, "初始化统计系统...");
 
     ENABLE_DETAILED_LOGS.get_or_init(|| enable_logs);
@@ -125,7 +137,7 @@ pub async fn initialize_statistics(db: &Database, enable_logs: bool) -> anyhow::
 ///
 /// 定期将内存中的统计数据同步到数据库
 pub fn start_statistics_sync_task(
-    db: Database,
+    db: SharedStorage,
     interval_seconds: u64,
 ) -> tokio::task::JoinHandle<()> {
     event!(
@@ -146,7 +158,8 @@ pub fn start_statistics_sync_task(
     tokio::spawn(async move {
         let mut sync_interval =
             tokio::time::interval(std::time::Duration::from_secs(interval_seconds));
-        // let mut cleanup_interval = tokio::time::interval(std::time::Duration::from_secs(3600)); // 每小时清理一次
+        // 每小时清理一次内存里的旧每小时统计, 数据库里的由分区维护任务负责
+        let mut cleanup_interval = tokio::time::interval(std::time::Duration::from_secs(3600));
         let mut access_logs_buffer = Vec::new();
 
         loop {
@@ -169,10 +182,10 @@ pub fn start_statistics_sync_task(
                     }
                     access_logs_buffer.clear();
                 }
-                // _ = cleanup_interval.tick() => {
-                //     // 清理旧的每小时统计数据（保留最近 7 天）
-                //     cleanup_old_hourly_data();
-                // }
+                _ = cleanup_interval.tick() => {
+                    // 清理旧的每小时统计数据（保留最近 7 天）
+                    cleanup_old_hourly_data();
+                }
                 Some(log) = rx.recv() => {
                     // 收集访问日志
                     access_logs_buffer.push(log);
@@ -193,7 +206,7 @@ pub fn start_statistics_sync_task(
 }
 
 /// 同步统计数据到数据库
-async fn sync_statistics_to_db(db: &Database, access_logs: &[AccessLog]) -> anyhow::Result<()> {
+async fn sync_statistics_to_db(db: &dyn Storage, access_logs: &[AccessLog]) -> anyhow::Result<()> {
     event!(Level::INFO, "正在同步访问日志");
 
     // 1. 批量插入访问日志
@@ -296,7 +309,40 @@ async fn sync_statistics_to_db(db: &Database, access_logs: &[AccessLog]) -> anyh
         }
     }
 
-    // 5. 同步 IP 每小时统计
+    // 5. 同步 API key 最后使用时间
+    let key_map = API_KEY_LAST_USED.get_or_init(DashMap::new);
+    let key_data: Vec<(i64, DateTime<Utc>)> = key_map
+        .iter()
+        .map(|entry| (*entry.key(), *entry.value()))
+        .collect();
+    if !key_data.is_empty() {
+        let affected = db.touch_api_keys(&key_data).await?;
+        event!(Level::INFO, api_key_affected = affected, "同步 API key 使用时间完成");
+        // 只删掉已经同步的值, 期间又被使用过的留到下次
+        for (id, used_at) in key_data {
+            key_map.remove_if(&id, |_, last| *last == used_at);
+        }
+    }
+
+    // 6. 同步 IP 每小时被限流次数
+    let throttled_map = IP_HOURLY_THROTTLED.get_or_init(DashMap::new);
+    let throttled_data: Vec<(IpAddr, DateTime<Utc>, u64)> = throttled_map
+        .iter()
+        .map(|entry| (entry.key().0, entry.key().1, *entry.value()))
+        .collect();
+    if !throttled_data.is_empty() {
+        let affected = db.batch_add_ip_hourly_throttled(&throttled_data).await?;
+        event!(Level::INFO, throttled_affected = affected, "同步限流次数完成");
+        // 减掉已经同步的次数, 期间新增的留到下次
+        for (ip, hour_timestamp, count) in throttled_data {
+            if let Some(mut entry) = throttled_map.get_mut(&(ip, hour_timestamp)) {
+                *entry -= count;
+            }
+        }
+        throttled_map.retain(|_, count| *count > 0);
+    }
+
+    // 7. 同步 IP 每小时统计
     // 先收集数据，避免在异步操作中持有锁
     let ip_hourly_map = IP_HOURLY_COUNTS.get_or_init(DashMap::new);
     // 只收集有增量变化的条目
@@ -374,8 +420,7 @@ pub async fn get_statistics_with_timestamps() -> StatisticsWithTimestamps {
     StatisticsWithTimestamps { ua, ip }
 }
 
-#[allow(unused)]
-/// 清理旧的每小时统计数据（保留最近 7 天）
+/// 清理内存中旧的每小时统计数据（保留最近 7 天）
 fn cleanup_old_hourly_data() {
     let now = Utc::now();
     let cutoff = now - Duration::days(7);
@@ -442,7 +487,7 @@ fn cleanup_old_hourly_data() {
 /// 优雅关闭统计系统
 ///
 /// 确保所有统计数据都已同步到数据库
-pub async fn shutdown_statistics(db: &Database) -> anyhow::Result<()> {
+pub async fn shutdown_statistics(db: &dyn Storage) -> anyhow::Result<()> {
     event!(Level::INFO, "关闭统计系统，正在发送关闭信号...");
 
     // 发送取消信号
@@ -461,6 +506,14 @@ pub async fn shutdown_statistics(db: &Database) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 记录一次被限流的请求, 随统计数据一起写入 `ip_hourly_statistics.throttled_count`
+pub fn record_throttled(ip: IpAddr, now: DateTime<Utc>) {
+    *IP_HOURLY_THROTTLED
+        .get_or_init(DashMap::new)
+        .entry((ip, get_hour_timestamp(now)))
+        .or_insert(0) += 1;
+}
+
 pub async fn middle_response(
     Extension(ClientIp(ip)): Extension<ClientIp>,
     req: Request<Body>,
@@ -479,6 +532,17 @@ pub async fn middle_response(
     let request_method = req.method().to_string();
     let request_path = req.uri().path().to_string();
 
+    // 鉴权在路由里做, 请求使用的 key 要等处理完从响应里拿
+    let response = next.run(req).await;
+    let api_key_id = response.extensions().get::<ApiKeyId>().map(|id| id.0);
+    if let Some(id) = api_key_id {
+        API_KEY_LAST_USED
+            .get_or_init(DashMap::new)
+            .entry(id)
+            .and_modify(|last| *last = (*last).max(now))
+            .or_insert(now);
+    }
+
     // 更新 UA 统计
     let ua_map = UA_STATS.get_or_init(DashMap::new);
     let max_ua = MAX_UA_ENTRIES.get().copied().unwrap_or(10000);
@@ -568,6 +632,7 @@ pub async fn middle_response(
             user_agent,
             request_method,
             request_path,
+            api_key_id,
         };
 
         if let Some(sender) = ACCESS_LOG_SENDER.get()
@@ -577,5 +642,5 @@ pub async fn middle_response(
         }
     }
 
-    next.run(req).await
+    response
 }
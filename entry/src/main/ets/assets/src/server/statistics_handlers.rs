@@ -5,7 +5,6 @@
 use axum::{
     Json,
     extract::{Query, State},
-    http::StatusCode,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -13,7 +12,14 @@ use std::{net::IpAddr, sync::Arc};
 use tracing::error;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::{db::statistics::AccessLogRecord, server::state::AppState};
+use crate::{
+    db::statistics::AccessLogRecord,
+    server::{
+        error::{ApiError, ApiResult},
+        rate_limit::RateLimitStats,
+        state::AppState,
+    },
+};
 
 use super::statistics;
 
@@ -118,6 +124,9 @@ pub struct AccessLogQueryParams {
     /// 路径过滤
     #[serde(default)]
     pub path: Option<String>,
+    /// 只看某个 API key 的请求
+    #[serde(default)]
+    pub api_key_id: Option<i64>,
 }
 
 #[utoipa::path(
@@ -179,7 +188,7 @@ pub async fn get_current_statistics() -> Json<CurrentStatisticsResponse> {
 pub async fn get_history_statistics(
     State(state): State<Arc<AppState>>,
     Query(params): Query<HistoryQueryParams>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> ApiResult<Json<serde_json::Value>> {
     let page = params.page.max(1);
     let page_size = params.page_size.clamp(1, 1000);
 
@@ -191,10 +200,7 @@ pub async fn get_history_statistics(
                 .await
                 .map_err(|e| {
                     error!("查询 UA 历史统计失败: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("查询失败: {}", e),
-                    )
+                    ApiError::database("查询失败")
                 })?;
 
             let total_pages = ((total as f64) / (page_size as f64)).ceil() as u32;
@@ -214,10 +220,7 @@ pub async fn get_history_statistics(
                 .await
                 .map_err(|e| {
                     error!("查询 IP 历史统计失败: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("查询失败: {}", e),
-                    )
+                    ApiError::database("查询失败")
                 })?;
 
             let total_pages = ((total as f64) / (page_size as f64)).ceil() as u32;
@@ -230,9 +233,8 @@ pub async fn get_history_statistics(
                 total_pages,
             })))
         }
-        _ => Err((
-            StatusCode::BAD_REQUEST,
-            "stat_type 参数必须是 'ua' 或 'ip'".to_string(),
+        _ => Err(ApiError::bad_request(
+            "stat_type 参数必须是 'ua' 或 'ip'",
         )),
     }
 }
@@ -254,12 +256,12 @@ pub async fn get_history_statistics(
 pub async fn get_hourly_statistics(
     State(state): State<Arc<AppState>>,
     Query(params): Query<HourlyQueryParams>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> ApiResult<Json<serde_json::Value>> {
     // 解析时间范围，默认最近 24 小时
     let end_time = if let Some(end_str) = params.end_time {
         DateTime::parse_from_rfc3339(&end_str)
             .map(|dt| dt.with_timezone(&Utc))
-            .map_err(|e| (StatusCode::BAD_REQUEST, format!("end_time 格式错误: {}", e)))?
+            .map_err(|e| ApiError::bad_request(format!("end_time 格式错误: {}", e)))?
     } else {
         Utc::now()
     };
@@ -267,12 +269,7 @@ pub async fn get_hourly_statistics(
     let start_time = if let Some(start_str) = params.start_time {
         DateTime::parse_from_rfc3339(&start_str)
             .map(|dt| dt.with_timezone(&Utc))
-            .map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    format!("start_time 格式错误: {}", e),
-                )
-            })?
+            .map_err(|e| ApiError::bad_request(format!("start_time 格式错误: {}", e)))?
     } else {
         end_time - chrono::Duration::hours(24)
     };
@@ -285,10 +282,7 @@ pub async fn get_hourly_statistics(
                 .await
                 .map_err(|e| {
                     error!("查询 UA 每小时统计失败: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("查询失败: {}", e),
-                    )
+                    ApiError::database("查询失败")
                 })?;
 
             Ok(Json(serde_json::json!({
@@ -303,7 +297,7 @@ pub async fn get_hourly_statistics(
             let ip_addr: IpAddr = params
                 .target
                 .parse()
-                .map_err(|e| (StatusCode::BAD_REQUEST, format!("IP 地址格式错误: {}", e)))?;
+                .map_err(|e| ApiError::bad_request(format!("IP 地址格式错误: {}", e)))?;
 
             let data = state
                 .db
@@ -311,10 +305,7 @@ pub async fn get_hourly_statistics(
                 .await
                 .map_err(|e| {
                     error!("查询 IP 每小时统计失败: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("查询失败: {}", e),
-                    )
+                    ApiError::database("查询失败")
                 })?;
 
             Ok(Json(serde_json::json!({
@@ -325,9 +316,8 @@ pub async fn get_hourly_statistics(
                 "data": data,
             })))
         }
-        _ => Err((
-            StatusCode::BAD_REQUEST,
-            "stat_type 参数必须是 'ua' 或 'ip'".to_string(),
+        _ => Err(ApiError::bad_request(
+            "stat_type 参数必须是 'ua' 或 'ip'",
         )),
     }
 }
@@ -339,17 +329,22 @@ pub async fn get_hourly_statistics(
         AccessLogQueryParams
     ),
     responses(
-        (status = 200, description = "获取访问详细日志，支持按IP、UA、路径过滤")
+        (status = 200, description = "获取访问详细日志，支持按IP、UA、路径、API key 过滤"),
+        (status = 401, description = "没有带 API key", body = crate::server::error::ApiErrorResponse),
+        (status = 403, description = "API key 没有 stats:read 权限", body = crate::server::error::ApiErrorResponse)
     ),
+    security(("api_key" = [])),
     tag = "访问统计"
 )]
 /// 获取访问详细日志
 ///
-/// GET /api/statistics/logs?page=1&page_size=50&ip=...&ua=...&path=...
+/// GET /api/statistics/logs?page=1&page_size=50&ip=...&ua=...&path=...&api_key_id=...
+///
+/// 需要 `stats:read`
 pub async fn get_access_logs(
     State(state): State<Arc<AppState>>,
     Query(params): Query<AccessLogQueryParams>,
-) -> Result<Json<HistoryStatisticsResponse<AccessLogRecord>>, (StatusCode, String)> {
+) -> ApiResult<Json<HistoryStatisticsResponse<AccessLogRecord>>> {
     let page = params.page.max(1);
     let page_size = params.page_size.clamp(1, 1000);
 
@@ -357,7 +352,7 @@ pub async fn get_access_logs(
         Some(
             ip_str
                 .parse::<IpAddr>()
-                .map_err(|e| (StatusCode::BAD_REQUEST, format!("IP 地址格式错误: {}", e)))?,
+                .map_err(|e| ApiError::bad_request(format!("IP 地址格式错误: {}", e)))?,
         )
     } else {
         None
@@ -365,14 +360,18 @@ pub async fn get_access_logs(
 
     let (data, total) = state
         .db
-        .query_access_logs(page, page_size, ip_filter, params.ua, params.path)
+        .query_access_logs(
+            page,
+            page_size,
+            ip_filter,
+            params.ua,
+            params.path,
+            params.api_key_id,
+        )
         .await
         .map_err(|e| {
             error!("查询访问日志失败: {:?}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("查询失败: {}", e),
-            )
+            ApiError::database("查询失败")
         })?;
 
     let total_pages = ((total as f64) / (page_size as f64)).ceil() as u32;
@@ -438,3 +437,27 @@ pub async fn get_statistics_summary() -> Json<StatisticsSummary> {
         most_active_ip,
     })
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/statistics/cache",
+    responses(
+        (status = 200, description = "看板查询缓存的命中统计, 缓存未启用时为 null", body = Option<crate::server::cache::CacheStats>)
+    ),
+    tag = "访问统计"
+)]
+pub async fn get_cache_statistics() -> Json<Option<crate::server::cache::CacheStats>> {
+    Json(crate::server::cache::stats())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v0/statistics/rate_limit",
+    responses(
+        (status = 200, description = "各限流预算的配置和进程启动以来的限流次数, 限流未启用时为 null; 每个 IP 每小时的限流次数见 /statistics/hourly", body = Option<RateLimitStats>)
+    ),
+    tag = "访问统计"
+)]
+pub async fn get_rate_limit_statistics() -> Json<Option<RateLimitStats>> {
+    Json(crate::server::rate_limit::stats())
+}
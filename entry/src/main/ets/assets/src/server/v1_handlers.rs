@@ -0,0 +1,290 @@
+//! `/api/v1` 接口
+//!
+//! 查询逻辑和 v0 共用 (见 `handlers` 里的 `lookup_app` / `fetch_app_list` / `lookup_substance`),
+//! 区别只在返回结构: 这里统一转换成 `model::v1` 里的结构再输出
+//!
+//! `include` 和 v0 一样; `fields` 选的是 app_full_info 的列名, 和 v1 的分组结构对不上, 这里不支持
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use std::sync::Arc;
+use tracing::{Level, event};
+
+use crate::{
+    db::expand::Include,
+    model::{
+        AppQuery, FullAppInfo,
+        v1::{AppLookupV1, AppSummaryV1, AppV1, SubstanceSummaryV1, SubstanceV1, SyncStateV1},
+    },
+    server::{
+        handlers::{
+            AppLookup, expand_items, fetch_app_list, fetch_app_list_expanded, lookup_app,
+            lookup_substance, parse_expand,
+        },
+        error::{ApiError, ApiResult},
+        state::{ApiResponse, AppExpandQuery, AppListQuery, AppState, SubstanceListQuery},
+    },
+};
+
+impl From<AppLookup> for AppLookupV1 {
+    fn from(lookup: AppLookup) -> Self {
+        Self {
+            app: AppV1::from(lookup.full_info),
+            sync: SyncStateV1 {
+                fetched: lookup.get_data,
+                new_app: lookup.new_app,
+                info_changed: lookup.new_info,
+                metrics_changed: lookup.new_metric,
+                rating_changed: lookup.new_rating,
+            },
+        }
+    }
+}
+
+const FIELDS_UNSUPPORTED: &str = "v1 按分组返回, 不支持 fields, 需要裁剪字段请使用 v0";
+
+/// 解析 `include`, 传了 `fields` 时直接拒绝
+fn parse_includes(state: &AppState, expand: &AppExpandQuery) -> ApiResult<Vec<Include>> {
+    match parse_expand(state, expand)? {
+        (None, includes) => Ok(includes),
+        (Some(_), _) => Err(ApiError::bad_request(FIELDS_UNSUPPORTED)),
+    }
+}
+
+async fn query_app(
+    state: Arc<AppState>,
+    query: AppQuery,
+    expand: AppExpandQuery,
+) -> ApiResult<Json<ApiResponse>> {
+    let includes = parse_includes(&state, &expand)?;
+    let lookup = AppLookupV1::from(lookup_app(&state, &query, None, None).await?);
+    if includes.is_empty() {
+        return Ok(Json(ApiResponse::success(lookup, None, None)));
+    }
+    let mut data = serde_json::to_value(&lookup).unwrap_or_default();
+    let mut items = vec![data["app"].take()];
+    expand_items(&state, &mut items, None, &includes, expand.metrics_limit()).await?;
+    data["app"] = items.pop().unwrap_or_default();
+    Ok(Json(ApiResponse::success(data, None, None)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/apps/pkg_name/{pkg_name}",
+    params(
+        ("pkg_name" = String, Path, description = "应用包名，例如：com.huawei.music"),
+        AppExpandQuery
+    ),
+    responses(
+        (status = 200, description = "data 为 AppLookupV1", body = ApiResponse),
+        (status = 400, description = "参数不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 404, description = "应用不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 502, description = "华为接口请求失败且数据库里没有这个应用", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 根据应用包名查询应用详细信息
+///
+/// 和 v0 一样优先在线获取, 失败时返回数据库里的现有数据 (`sync.fetched` 为 false).
+/// `include` 的关联数据挂在 `app` 下
+pub async fn query_pkg(
+    State(state): State<Arc<AppState>>,
+    Path(pkg_name): Path<String>,
+    Query(expand): Query<AppExpandQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    event!(Level::DEBUG, "v1 正在通过 pkg name 获取 {pkg_name} 的信息");
+    query_app(state, AppQuery::pkg_name(&pkg_name), expand).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/apps/app_id/{app_id}",
+    params(
+        ("app_id" = String, Path, description = "华为应用市场的应用ID，例如：C10084839"),
+        AppExpandQuery
+    ),
+    responses(
+        (status = 200, description = "data 为 AppLookupV1", body = ApiResponse),
+        (status = 400, description = "参数不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 404, description = "应用不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 502, description = "华为接口请求失败且数据库里没有这个应用", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 根据应用ID查询应用详细信息
+pub async fn query_app_id(
+    State(state): State<Arc<AppState>>,
+    Path(app_id): Path<String>,
+    Query(expand): Query<AppExpandQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    event!(Level::DEBUG, "v1 正在通过 appid 获取 {app_id} 的信息");
+    query_app(state, AppQuery::app_id(&app_id), expand).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/apps/list/{page_count}",
+    params(
+        ("page_count" = String, Path, description = "页码，从0开始"),
+        AppListQuery
+    ),
+    responses(
+        (status = 200, description = "detail=true 时为 AppV1 列表, 否则为 AppSummaryV1", body = ApiResponse),
+        (status = 400, description = "参数不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 503, description = "数据库查询失败", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 分页获取应用列表
+///
+/// 参数和 v0 一致, 但不支持 `fields`
+pub async fn app_list_paged(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<String>,
+    Query(query): Query<AppListQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    app_list(state, page, query).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/apps/list/{page_count}",
+    params(
+        ("page_count" = String, Path, description = "页码，从0开始"),
+    ),
+    request_body = AppListQuery,
+    responses(
+        (status = 200, description = "同 GET 版本", body = ApiResponse),
+        (status = 400, description = "参数不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 503, description = "数据库查询失败", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 分页获取应用列表，参数放在 JSON body 中
+pub async fn app_list_paged_body(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<String>,
+    Json(query): Json<AppListQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    app_list(state, page, query).await
+}
+
+async fn app_list(
+    state: Arc<AppState>,
+    page: String,
+    query: AppListQuery,
+) -> ApiResult<Json<ApiResponse>> {
+    let expand = query.expand();
+    let includes = parse_includes(&state, &expand)?;
+    let limit = Some(query.page_size());
+    if !includes.is_empty() {
+        let detail = query.detail();
+        let present = |app: FullAppInfo| {
+            let value = if detail {
+                serde_json::to_value(AppV1::from(app))
+            } else {
+                serde_json::to_value(AppSummaryV1::from(&app))
+            };
+            value.unwrap_or_default()
+        };
+        let apps = fetch_app_list_expanded(
+            &state,
+            &page,
+            &query,
+            None,
+            &includes,
+            expand.metrics_limit(),
+            present,
+        )
+        .await?;
+        let total_count = apps.total_count;
+        return Ok(Json(ApiResponse::success(apps, Some(total_count), limit)));
+    }
+
+    let apps = fetch_app_list(&state, &page, &query).await?;
+    let total_count = apps.total_count;
+    if query.detail() {
+        let page = apps.map(AppV1::from);
+        Ok(Json(ApiResponse::success(page, Some(total_count), limit)))
+    } else {
+        let page = apps.map(|app| AppSummaryV1::from(&app));
+        Ok(Json(ApiResponse::success(page, Some(total_count), limit)))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/substance/{substance_id}",
+    params(
+        ("substance_id" = String, Path, description = "专题ID")
+    ),
+    responses(
+        (status = 200, description = "data 为 SubstanceV1", body = ApiResponse),
+        (status = 404, description = "专题不存在", body = crate::server::error::ApiErrorResponse),
+        (status = 502, description = "华为接口请求失败", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 根据专题ID查询专题信息
+pub async fn query_substance(
+    State(state): State<Arc<AppState>>,
+    Path(substance_id): Path<String>,
+) -> ApiResult<Json<ApiResponse>> {
+    let substance = lookup_substance(&state, &substance_id).await?;
+    Ok(Json(ApiResponse::success(
+        SubstanceV1::from(substance),
+        Some(1),
+        Some(1),
+    )))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/substance/list/{page}",
+    params(
+        ("page" = u32, Path, description = "页码（从0开始）"),
+        SubstanceListQuery
+    ),
+    responses(
+        (status = 200, description = "data.data 为 SubstanceSummaryV1 列表", body = ApiResponse),
+        (status = 400, description = "参数不合法", body = crate::server::error::ApiErrorResponse),
+        (status = 503, description = "数据库查询失败", body = crate::server::error::ApiErrorResponse)
+    ),
+    tag = "应用查询 v1"
+)]
+/// 分页获取专题列表
+pub async fn substance_list_paged(
+    State(state): State<Arc<AppState>>,
+    Path(page): Path<String>,
+    Query(query): Query<SubstanceListQuery>,
+) -> ApiResult<Json<ApiResponse>> {
+    if !query.is_valid_sort()
+        && let Some(sort_key) = query.raw_sort_key()
+    {
+        return Err(ApiError::bad_request(format!("不支持按 {} 排序", sort_key)));
+    }
+    let page = page
+        .parse::<u32>()
+        .map_err(|e| ApiError::bad_request(format!("页码解析失败: {}", e)))?;
+    let substances = state
+        .db
+        .get_substance_list_paged(
+            page,
+            query.page_size(),
+            &query.sort_key(),
+            query.desc.unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| {
+            event!(Level::WARN, "v1 获取分页专题信息失败: {}", e);
+            ApiError::database("获取分页专题信息失败")
+        })?;
+    let total_count = substances.total_count;
+    Ok(Json(ApiResponse::success(
+        substances.map(SubstanceSummaryV1::from),
+        Some(total_count),
+        Some(query.page_size()),
+    )))
+}
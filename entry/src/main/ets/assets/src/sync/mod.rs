@@ -8,7 +8,10 @@ use serde_json::Value as JsonValue;
 use tracing::{Level, event};
 
 use crate::{
-    db::Database,
+    db::{
+        batch::PendingApp,
+        storage::{SharedStorage, Storage},
+    },
     model::{
         AppQuery, FullAppInfo, RawJsonData, RawRatingData,
         raw::{RawAppData, RawRecordalInfo},
@@ -21,13 +24,15 @@ pub const TOKEN_UPDATE_INTERVAL: Duration = Duration::from_secs(600);
 pub mod code;
 pub mod status;
 pub mod substance;
+pub mod unknown;
 
 pub use substance::{SubstanceData, get_app_from_substance};
 
 // 重新导出状态管理相关的公共接口
 pub use status::{
-    SyncStatusInfo, end_sync_all, get_sync_status, reset_sync_status, start_sync_all,
-    update_sync_progress,
+    SyncFailure, SyncPhase, SyncStatusInfo, end_sync_all, end_sync_phase, get_sync_status,
+    record_sync_failure, reset_sync_status, start_sync_all, start_sync_phase,
+    update_phase_progress, update_sync_batch, update_sync_progress,
 };
 
 /// UA
@@ -57,7 +62,7 @@ pub static USER_AGENT: LazyLock<String> = LazyLock::new(|| {
 /// 5. 统计并输出结果
 pub async fn sync_all(
     client: &Client,
-    db: &crate::db::Database,
+    db: &SharedStorage,
     config: &crate::config::Config,
 ) -> Result<()> {
     let mut packages = config.packages().to_vec();
@@ -110,42 +115,78 @@ pub async fn sync_all(
     // 按批次处理包
     for chunk in packages.chunks(batch_size) {
         batch_count += 1;
+        update_sync_batch(batch_count, total_batches);
         let mut join_set = tokio::task::JoinSet::new();
 
-        // 为批次中的每个包创建异步任务
+        // 为批次中的每个包创建异步任务, 只负责拉取数据
         for package in chunk {
             let client = client.clone();
-            let db = db.clone();
             let api_url = config.api_url().to_string();
             let package = package.clone();
 
             join_set.spawn(async move {
-                match sync_app(
-                    &client,
-                    &db,
-                    &api_url,
-                    &AppQuery::pkg_name(&package),
-                    None,
-                    None,
-                )
-                .await
-                {
-                    Ok(inserted) => Ok((package, inserted)),
-                    Err(e) => Err((package, e)),
-                }
+                let result = query_app(&client, &api_url, &AppQuery::pkg_name(&package)).await;
+                (package, result)
             });
         }
 
-        // 等待批次中的所有任务完成
         let mut batch_processed = 0;
         let mut batch_inserted = 0;
         let mut batch_skipped = 0;
         let mut batch_failed = 0;
 
+        // 等待批次中的所有任务完成
+        let mut fetched = Vec::with_capacity(chunk.len());
         while let Some(result) = join_set.join_next().await {
             match result {
-                Ok(Ok((package, inserted))) => {
+                Ok((package, Ok(data))) => fetched.push((package, data)),
+                Ok((package, Err(e))) => {
+                    batch_processed += 1;
+                    batch_failed += 1;
+                    event!(Level::WARN, "包 {} 同步失败: {:#}", package, e);
+                    record_sync_failure(&package, format!("{e:#}"));
+                }
+                Err(e) => {
                     batch_processed += 1;
+                    batch_failed += 1;
+                    event!(Level::WARN, "任务执行失败: {:#}", e);
+                    record_sync_failure("<task>", e);
+                }
+            }
+        }
+
+        // 整批在一个事务里保存, 失败会整批回滚, 再用已经拉到的数据逐个保存找出是哪个包的问题,
+        // 不重新请求上游
+        let (packages_fetched, pending): (Vec<_>, Vec<_>) = fetched
+            .into_iter()
+            .map(|(package, data)| (package, PendingApp::new(data, None, None)))
+            .unzip();
+        let saved = match db.save_app_data_batch(pending.clone()).await {
+            Ok(saved) => packages_fetched
+                .into_iter()
+                .zip(saved)
+                .map(|(package, inserted)| (package, Ok(inserted)))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                event!(Level::WARN, "批次 {batch_count} 批量保存失败, 改为逐个保存: {e:#}");
+                let mut saved = Vec::with_capacity(packages_fetched.len());
+                for (package, app) in packages_fetched.into_iter().zip(pending) {
+                    let result = db
+                        .save_app_data(app.data, app.listed_at, app.comment)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("保存包 {package} 的数据失败: {e:#}"));
+                    saved.push((package, result));
+                }
+                saved
+            }
+        };
+
+        let mut any_changed = false;
+        for (package, result) in saved {
+            batch_processed += 1;
+            match result {
+                Ok(inserted) => {
+                    any_changed |= inserted.0 || inserted.1 || inserted.2;
                     if inserted.0 || inserted.1 {
                         if inserted.0 {
                             event!(Level::DEBUG, "已将 {package} 的数据插入数据库");
@@ -160,18 +201,16 @@ pub async fn sync_all(
                         event!(Level::DEBUG, "包 {} 处理完成 (数据相同，已跳过)", package);
                     }
                 }
-                Ok(Err((package, e))) => {
-                    batch_processed += 1;
-                    batch_failed += 1;
-                    event!(Level::WARN, "包 {} 同步失败: {:#}", package, e);
-                }
                 Err(e) => {
-                    batch_processed += 1;
                     batch_failed += 1;
-                    event!(Level::WARN, "任务执行失败: {:#}", e);
+                    event!(Level::WARN, "包 {} 同步失败: {:#}", package, e);
+                    record_sync_failure(&package, format!("{e:#}"));
                 }
             }
         }
+        if any_changed {
+            crate::server::cache::invalidate();
+        }
 
         // 更新全局统计
         total_processed += batch_processed;
@@ -188,11 +227,11 @@ pub async fn sync_all(
             total_failed,
         );
 
-        // 打印批次进度
+        // 打印批次进度 (剩余时间与 sync_status 使用同一个吞吐量估算)
         let total_elapsed = start_time.elapsed();
-        let avg_time_per_batch = total_elapsed / batch_count;
-        let estimated_total_time = avg_time_per_batch * total_batches as u32;
-        let remaining_time = estimated_total_time.saturating_sub(total_elapsed);
+        let remaining_time = get_sync_status()
+            .estimated_remaining_time
+            .unwrap_or_default();
 
         print!(
             "\r[批次 {}/{}] 已处理 {} 个包，总耗时 {:?}，预计剩余 {:?}",
@@ -206,6 +245,7 @@ pub async fn sync_all(
 
     // 结束全局同步状态
     end_sync_all();
+    unknown::report();
 
     println!("{}", "所有包处理完成！".green());
 
@@ -244,7 +284,7 @@ pub async fn sync_all(
 /// 4. 返回插入状态
 pub async fn sync_app(
     client: &reqwest::Client,
-    db: &Database,
+    db: &dyn Storage,
     api_url: &str,
     app_query: &AppQuery,
     listed_at: Option<DateTime<Local>>,
@@ -264,10 +304,21 @@ pub async fn sync_app(
         .save_app_data(app_data, listed_at, comment)
         .await
         .map_err(|e| anyhow::anyhow!("保存包 {} 的数据失败: {:#}", app_query, e))?;
+    // 有新数据写入, 看板的缓存结果就过时了
+    if inserted.0 || inserted.1 || inserted.2 {
+        crate::server::cache::invalidate();
+    }
 
     Ok(inserted)
 }
 
+/// 错误是不是华为接口本身出了问题 (连接失败 / 超时 / 非 2xx 状态码)
+///
+/// 用来区分 "上游挂了" 和 "上游说没有这个应用"
+pub fn is_upstream_unavailable(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| cause.is::<reqwest::Error>())
+}
+
 /// 查询单个应用的完整数据
 ///
 /// # 参数
@@ -286,7 +337,10 @@ async fn query_app(
 ) -> Result<RawAppData> {
     let data = get_app_data(client, api_url, app_query)
         .await
-        .map_err(|e| anyhow::anyhow!("获取包 {} 的数据失败: {:#}", app_query, e))?;
+        .map_err(|e| {
+            let message = format!("获取包 {} 的数据失败: {:#}", app_query, e);
+            e.context(message)
+        })?;
 
     let (raw_data, data) = (
         data.clone(),
@@ -312,6 +366,8 @@ async fn query_app(
         }
     }
 
+    unknown::record(&raw_data);
+
     // event!(
     //     Level::DEBUG,
     //     app_id = data.app_id,
@@ -362,11 +418,11 @@ pub async fn get_app_data(
         .await?;
 
     // 检查响应状态码
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
+    if let Err(e) = response.error_for_status_ref() {
+        return Err(anyhow::Error::new(e).context(format!(
             "HTTP请求失败,状态码: {}",
             response.status()
-        ));
+        )));
     }
 
     // 检查响应体是否为空
@@ -1,18 +1,112 @@
 use std::{
-    sync::LazyLock,
-    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering},
+    sync::{LazyLock, Mutex},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+/// 最多保留的最近失败记录数量
+pub const MAX_RECENT_FAILURES: usize = 50;
+
+/// 同步阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPhase {
+    /// 空闲
+    Idle,
+    /// 同步应用 (sync_all)
+    Apps,
+    /// 拉取和保存专题 (sync_substance)
+    Substances,
+    /// 同步专题里发现的应用
+    Discovery,
+}
+
+impl SyncPhase {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Apps,
+            2 => Self::Substances,
+            3 => Self::Discovery,
+            _ => Self::Idle,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            Self::Idle => 0,
+            Self::Apps => 1,
+            Self::Substances => 2,
+            Self::Discovery => 3,
+        }
+    }
+}
+
+/// 单条同步失败记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncFailure {
+    /// 失败时所处阶段
+    pub phase: SyncPhase,
+    /// 失败的目标 (包名 / app_id / 专题 id)
+    pub target: String,
+    /// 错误信息
+    pub error: String,
+    /// 失败时间
+    pub at: DateTime<Local>,
+}
+
+/// 定长的失败记录环形缓冲区, 满了就丢掉最旧的
+#[derive(Debug)]
+pub struct FailureRing {
+    capacity: usize,
+    items: VecDeque<SyncFailure>,
+}
+
+impl FailureRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, failure: SyncFailure) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.items.len() >= self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(failure);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// 按时间倒序 (最新的在前) 返回
+    pub fn snapshot(&self) -> Vec<SyncFailure> {
+        self.items.iter().rev().cloned().collect()
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
 /// 全局同步状态管理器
 pub struct GlobalSyncStatus {
     /// 是否正在执行 sync_all
     pub is_syncing_all: AtomicBool,
-    /// 当前进度 - 已完成数量
+    /// sync_all 当前进度 - 已完成数量, 其他阶段不会改它
     pub current_progress: AtomicUsize,
-    /// 总数量
+    /// sync_all 总数量
     pub total_packages: AtomicUsize,
     /// 统计信息
     pub total_processed: AtomicUsize,
@@ -23,6 +117,20 @@ pub struct GlobalSyncStatus {
     pub start_time_nanos: AtomicU64,
     /// 上次同步完成时间 (使用 AtomicU64 存储时间戳)
     pub last_complete_time_nanos: AtomicU64,
+    /// 当前阶段 (SyncPhase)
+    pub phase: AtomicU8,
+    /// 当前阶段开始时间, 用于计算吞吐量
+    pub phase_start_nanos: AtomicU64,
+    /// 当前阶段已完成数量
+    pub phase_progress: AtomicUsize,
+    /// 当前阶段总数量
+    pub phase_total: AtomicUsize,
+    /// 当前批次 (从 1 开始)
+    pub current_batch: AtomicUsize,
+    /// 总批次
+    pub total_batches: AtomicUsize,
+    /// 最近的失败记录
+    pub recent_failures: Mutex<FailureRing>,
 }
 
 impl GlobalSyncStatus {
@@ -37,26 +145,74 @@ impl GlobalSyncStatus {
             total_failed: AtomicUsize::new(0),
             start_time_nanos: AtomicU64::new(0),
             last_complete_time_nanos: AtomicU64::new(0),
+            phase: AtomicU8::new(SyncPhase::Idle.as_u8()),
+            phase_start_nanos: AtomicU64::new(0),
+            phase_progress: AtomicUsize::new(0),
+            phase_total: AtomicUsize::new(0),
+            current_batch: AtomicUsize::new(0),
+            total_batches: AtomicUsize::new(0),
+            recent_failures: Mutex::new(FailureRing::new(MAX_RECENT_FAILURES)),
         }
     }
 
     fn set_start_time(&self) {
-        let now = SystemTime::now();
-        let nanos = now
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64;
-        self.start_time_nanos.store(nanos, Ordering::Relaxed);
+        self.start_time_nanos.store(now_nanos(), Ordering::Relaxed);
     }
 
     fn set_complete_time(&self) {
-        let now = SystemTime::now();
-        let nanos = now
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64;
         self.last_complete_time_nanos
-            .store(nanos, Ordering::Relaxed);
+            .store(now_nanos(), Ordering::Relaxed);
+    }
+
+    /// 当前阶段
+    pub fn phase(&self) -> SyncPhase {
+        SyncPhase::from_u8(self.phase.load(Ordering::Relaxed))
+    }
+
+    fn set_phase(&self, phase: SyncPhase, total: usize) {
+        self.phase.store(phase.as_u8(), Ordering::Relaxed);
+        self.phase_start_nanos.store(now_nanos(), Ordering::Relaxed);
+        self.phase_progress.store(0, Ordering::Relaxed);
+        self.phase_total.store(total, Ordering::Relaxed);
+        self.current_batch.store(0, Ordering::Relaxed);
+        self.total_batches.store(0, Ordering::Relaxed);
+    }
+
+    /// 结束专题 / 发现阶段
+    ///
+    /// sync_all 还在跑的时候回到 Apps 阶段, 进度接着 sync_all 的算, 否则回到空闲
+    fn end_phase(&self) {
+        if !self.is_syncing_all.load(Ordering::Relaxed) {
+            self.phase.store(SyncPhase::Idle.as_u8(), Ordering::Relaxed);
+            return;
+        }
+        self.set_phase(SyncPhase::Apps, self.total_packages.load(Ordering::Relaxed));
+        self.phase_start_nanos.store(
+            self.start_time_nanos.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.phase_progress.store(
+            self.current_progress.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// 根据当前阶段的吞吐量 (个/秒) 计算剩余时间
+    fn throughput(&self) -> (Option<f64>, Option<Duration>) {
+        let start_nanos = self.phase_start_nanos.load(Ordering::Relaxed);
+        let current = self.phase_progress.load(Ordering::Relaxed);
+        let total = self.phase_total.load(Ordering::Relaxed);
+        if self.phase() == SyncPhase::Idle || start_nanos == 0 || current == 0 {
+            return (None, None);
+        }
+        let start_time = SystemTime::UNIX_EPOCH + Duration::from_nanos(start_nanos);
+        let elapsed = start_time.elapsed().unwrap_or_default().as_secs_f64();
+        if elapsed <= 0.0 {
+            return (None, None);
+        }
+        let rate = current as f64 / elapsed;
+        let remaining = total.saturating_sub(current) as f64 / rate;
+        (Some(rate), Some(Duration::from_secs_f64(remaining)))
     }
 }
 
@@ -73,6 +229,7 @@ pub static GLOBAL_SYNC_STATUS: LazyLock<GlobalSyncStatus> = LazyLock::new(Global
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncStatusInfo {
     pub is_syncing_all: bool,
+    /// sync_all 的进度, 专题等阶段不影响它
     pub progress: (usize, usize),
     pub total_processed: usize,
     pub total_inserted: usize,
@@ -81,6 +238,18 @@ pub struct SyncStatusInfo {
     pub elapsed_time: Option<Duration>,
     pub estimated_total_time: Option<Duration>,
     pub next_sync_countdown: Option<Duration>,
+    /// 当前阶段
+    pub phase: SyncPhase,
+    /// 当前阶段的进度 (已完成, 总数)
+    pub phase_progress: (usize, usize),
+    /// 批次进度 (当前批次, 总批次)
+    pub batch: (usize, usize),
+    /// 当前阶段吞吐量 (个/秒)
+    pub throughput: Option<f64>,
+    /// 按吞吐量估算的当前阶段剩余时间
+    pub estimated_remaining_time: Option<Duration>,
+    /// 最近的失败记录, 最新的在前
+    pub recent_failures: Vec<SyncFailure>,
 }
 
 /// 获取当前同步状态
@@ -146,6 +315,13 @@ pub fn get_sync_status() -> SyncStatusInfo {
         None // 正在同步中
     };
 
+    let (throughput, estimated_remaining_time) = status.throughput();
+    let recent_failures = status
+        .recent_failures
+        .lock()
+        .map(|ring| ring.snapshot())
+        .unwrap_or_default();
+
     SyncStatusInfo {
         is_syncing_all: status.is_syncing_all.load(Ordering::Relaxed),
         progress: (current, total),
@@ -156,6 +332,18 @@ pub fn get_sync_status() -> SyncStatusInfo {
         elapsed_time: elapsed,
         estimated_total_time: estimated,
         next_sync_countdown,
+        phase: status.phase(),
+        phase_progress: (
+            status.phase_progress.load(Ordering::Relaxed),
+            status.phase_total.load(Ordering::Relaxed),
+        ),
+        batch: (
+            status.current_batch.load(Ordering::Relaxed),
+            status.total_batches.load(Ordering::Relaxed),
+        ),
+        throughput,
+        estimated_remaining_time,
+        recent_failures,
     }
 }
 
@@ -171,20 +359,28 @@ pub fn reset_sync_status() {
     status.total_failed.store(0, Ordering::Relaxed);
     status.start_time_nanos.store(0, Ordering::Relaxed);
     status.last_complete_time_nanos.store(0, Ordering::Relaxed);
+    status.set_phase(SyncPhase::Idle, 0);
+    status.phase_start_nanos.store(0, Ordering::Relaxed);
+    if let Ok(mut ring) = status.recent_failures.lock() {
+        ring.clear();
+    }
 }
 
 /// 开始 sync_all
 pub fn start_sync_all(total_packages: usize) {
     let status = &*GLOBAL_SYNC_STATUS;
     status.is_syncing_all.store(true, Ordering::Relaxed);
-    status
-        .total_packages
-        .store(total_packages, Ordering::Relaxed);
+    status.set_phase(SyncPhase::Apps, total_packages);
     status.current_progress.store(0, Ordering::Relaxed);
+    status.total_packages.store(total_packages, Ordering::Relaxed);
     status.total_processed.store(0, Ordering::Relaxed);
     status.total_inserted.store(0, Ordering::Relaxed);
     status.total_skipped.store(0, Ordering::Relaxed);
     status.total_failed.store(0, Ordering::Relaxed);
+    // 新一轮同步, 清掉上一轮的失败记录
+    if let Ok(mut ring) = status.recent_failures.lock() {
+        ring.clear();
+    }
 
     // 设置开始时间
     status.set_start_time();
@@ -200,6 +396,10 @@ pub fn update_sync_progress(
 ) {
     let status = &*GLOBAL_SYNC_STATUS;
     status.current_progress.store(current, Ordering::Relaxed);
+    // 专题 / 发现阶段进行中时不覆盖它们的进度, 阶段结束时会接回来
+    if status.phase() == SyncPhase::Apps {
+        status.phase_progress.store(current, Ordering::Relaxed);
+    }
     status.total_processed.store(processed, Ordering::Relaxed);
     status.total_inserted.store(inserted, Ordering::Relaxed);
     status.total_skipped.store(skipped, Ordering::Relaxed);
@@ -210,6 +410,125 @@ pub fn update_sync_progress(
 pub fn end_sync_all() {
     let status = &*GLOBAL_SYNC_STATUS;
     status.is_syncing_all.store(false, Ordering::Relaxed);
+    // 专题 / 发现阶段还没结束的话保留它
+    let _ = status.phase.compare_exchange(
+        SyncPhase::Apps.as_u8(),
+        SyncPhase::Idle.as_u8(),
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    );
     // 记录同步完成时间
     status.set_complete_time();
 }
+
+/// 开始 sync_all 以外的阶段 (专题 / 发现)
+///
+/// 只重置当前阶段的进度, 不影响 sync_all 的进度和统计
+pub fn start_sync_phase(phase: SyncPhase, total: usize) {
+    GLOBAL_SYNC_STATUS.set_phase(phase, total);
+}
+
+/// 更新当前阶段的进度
+pub fn update_phase_progress(current: usize) {
+    GLOBAL_SYNC_STATUS
+        .phase_progress
+        .store(current, Ordering::Relaxed);
+}
+
+/// 结束当前阶段, sync_all 还在跑的话回到 Apps 阶段
+pub fn end_sync_phase() {
+    GLOBAL_SYNC_STATUS.end_phase();
+}
+
+/// 更新批次进度
+pub fn update_sync_batch(current: usize, total: usize) {
+    let status = &*GLOBAL_SYNC_STATUS;
+    status.current_batch.store(current, Ordering::Relaxed);
+    status.total_batches.store(total, Ordering::Relaxed);
+}
+
+/// 记录一次同步失败
+pub fn record_sync_failure(target: impl ToString, error: impl ToString) {
+    let status = &*GLOBAL_SYNC_STATUS;
+    let failure = SyncFailure {
+        phase: status.phase(),
+        target: target.to_string(),
+        error: error.to_string(),
+        at: Local::now(),
+    };
+    if let Ok(mut ring) = status.recent_failures.lock() {
+        ring.push(failure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(target: &str) -> SyncFailure {
+        SyncFailure {
+            phase: SyncPhase::Apps,
+            target: target.to_string(),
+            error: "boom".to_string(),
+            at: Local::now(),
+        }
+    }
+
+    #[test]
+    fn test_failure_ring_bounded() {
+        let mut ring = FailureRing::new(3);
+        for i in 0..5 {
+            ring.push(failure(&i.to_string()));
+        }
+        let snapshot = ring.snapshot();
+        // 只保留最新的 3 条, 最新的在前
+        let targets: Vec<_> = snapshot.iter().map(|f| f.target.as_str()).collect();
+        assert_eq!(targets, vec!["4", "3", "2"]);
+    }
+
+    #[test]
+    fn test_phase_roundtrip() {
+        for phase in [
+            SyncPhase::Idle,
+            SyncPhase::Apps,
+            SyncPhase::Substances,
+            SyncPhase::Discovery,
+        ] {
+            assert_eq!(SyncPhase::from_u8(phase.as_u8()), phase);
+        }
+    }
+    #[test]
+    fn test_phase_keeps_sync_all_progress() {
+        let status = GlobalSyncStatus::new();
+        status.current_progress.store(10, Ordering::Relaxed);
+        status.total_packages.store(20, Ordering::Relaxed);
+        status.set_phase(SyncPhase::Discovery, 5);
+        status.phase_progress.store(3, Ordering::Relaxed);
+
+        // 专题 / 发现阶段不会把 sync_all 的进度清零
+        assert_eq!(status.current_progress.load(Ordering::Relaxed), 10);
+        assert_eq!(status.total_packages.load(Ordering::Relaxed), 20);
+        assert_eq!(status.phase_total.load(Ordering::Relaxed), 5);
+        assert_eq!(status.phase(), SyncPhase::Discovery);
+    }
+
+    #[test]
+    fn test_end_phase_restores_sync_all() {
+        let status = GlobalSyncStatus::new();
+        status.is_syncing_all.store(true, Ordering::Relaxed);
+        status.current_progress.store(10, Ordering::Relaxed);
+        status.total_packages.store(20, Ordering::Relaxed);
+        status.set_phase(SyncPhase::Substances, 5);
+        status.end_phase();
+
+        // sync_all 没结束, 回到 Apps 阶段并接上它的进度
+        assert_eq!(status.phase(), SyncPhase::Apps);
+        assert_eq!(status.phase_progress.load(Ordering::Relaxed), 10);
+        assert_eq!(status.phase_total.load(Ordering::Relaxed), 20);
+
+        status.is_syncing_all.store(false, Ordering::Relaxed);
+        status.set_phase(SyncPhase::Substances, 5);
+        status.end_phase();
+        assert_eq!(status.phase(), SyncPhase::Idle);
+    }
+}
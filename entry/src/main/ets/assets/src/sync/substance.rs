@@ -6,7 +6,10 @@ use tracing::{Level, event};
 
 use crate::{
     model::AppQuery,
-    sync::{USER_AGENT, code},
+    sync::{
+        SyncPhase, USER_AGENT, code, end_sync_phase, record_sync_failure, start_sync_phase,
+        update_phase_progress,
+    },
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -271,7 +274,7 @@ pub async fn get_app_from_substance(
 /// 同步专题
 pub async fn sync_substance(
     client: &Client,
-    db: &crate::db::Database,
+    db: &dyn crate::db::storage::Storage,
     config: &crate::config::Config,
 ) -> anyhow::Result<()> {
     let substances = db.get_all_substance_id().await?;
@@ -279,26 +282,47 @@ pub async fn sync_substance(
     let mut query_apps = Vec::with_capacity(substances.len() * 3);
     let mut raw_datas = Vec::with_capacity(substances.len());
 
-    for substance_id in substances {
-        let apps = get_app_from_substance(client, config.api_url(), substance_id).await?;
+    start_sync_phase(SyncPhase::Substances, substances.len());
+    for (idx, substance_id) in substances.into_iter().enumerate() {
+        let apps = match get_app_from_substance(client, config.api_url(), &substance_id).await {
+            Ok(apps) => apps,
+            Err(e) => {
+                record_sync_failure(&substance_id, format!("{e:#}"));
+                end_sync_phase();
+                return Err(e);
+            }
+        };
         query_apps.extend(apps.0.data.clone());
         raw_datas.push(apps);
+        update_phase_progress(idx + 1);
     }
 
     query_apps.sort();
     query_apps.dedup();
 
-    // 使用 sync_all
-    for app in query_apps.iter() {
+    // 专题里发现的应用单独算一个阶段
+    start_sync_phase(SyncPhase::Discovery, query_apps.len());
+    for (idx, app) in query_apps.iter().enumerate() {
         if let Err(e) = crate::sync::sync_app(client, db, config.api_url(), app, None, None).await {
-            event!(Level::WARN, "保存 app 时错误 {e}")
+            event!(Level::WARN, "保存 app 时错误 {e}");
+            record_sync_failure(app, format!("{e:#}"));
         }
+        update_phase_progress(idx + 1);
     }
 
-    for (substance, raw_substance) in raw_datas {
-        if let Err(e) = db.save_substance(&substance, &raw_substance, None).await {
-            event!(Level::WARN, "保存 substance 时错误 {e}")
+    start_sync_phase(SyncPhase::Substances, raw_datas.len());
+    for (idx, (substance, raw_substance)) in raw_datas.into_iter().enumerate() {
+        match db.save_substance(&substance, &raw_substance, None).await {
+            Ok((_, true)) => crate::server::cache::invalidate(),
+            Ok((_, false)) => {}
+            Err(e) => {
+                event!(Level::WARN, "保存 substance 时错误 {e}");
+                record_sync_failure(&substance.id, format!("{e:#}"));
+            }
         }
+        update_phase_progress(idx + 1);
     }
+    end_sync_phase();
+    crate::sync::unknown::report();
     Ok(())
 }
@@ -0,0 +1,136 @@
+//! 上游新增字段 / 新取值的收集
+//!
+//! 原始模型里没声明的字段会进 `extra`, 代码字段不认识的取值会落到 `Unknown`.
+//! 同步过程中记在这里, 每轮同步结束时打一次日志, 这样华为加了字段能第一时间发现
+
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+};
+
+use serde_json::Value as JsonValue;
+use tracing::{Level, event};
+
+use crate::model::raw::RawAppData;
+
+/// 示例值最多保留多长
+const SAMPLE_LEN: usize = 200;
+
+/// 一个没见过的字段 / 取值
+#[derive(Debug, Clone)]
+pub struct UnknownEntry {
+    /// 出现次数
+    pub count: usize,
+    /// 第一次出现的包名
+    pub first_pkg: String,
+    /// 第一次出现时的值 (截断过)
+    pub sample: String,
+}
+
+#[derive(Debug, Default)]
+struct Collected {
+    /// 没声明的字段, key 为 `info.xxx` / `rating.xxx` / `record.xxx`
+    fields: BTreeMap<String, UnknownEntry>,
+    /// 不认识的代码取值, key 为 (字段名, 原始值)
+    codes: BTreeMap<(&'static str, String), UnknownEntry>,
+}
+
+static COLLECTED: LazyLock<Mutex<Collected>> = LazyLock::new(Default::default);
+
+fn sample(value: &JsonValue) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(SAMPLE_LEN) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+fn bump<K: Ord>(map: &mut BTreeMap<K, UnknownEntry>, key: K, pkg: &str, sample: String) {
+    map.entry(key)
+        .and_modify(|entry| entry.count += 1)
+        .or_insert_with(|| UnknownEntry {
+            count: 1,
+            first_pkg: pkg.to_string(),
+            sample,
+        });
+}
+
+/// 记下一个应用里没声明的字段和不认识的代码取值
+pub fn record(data: &RawAppData) {
+    let fields = data.unknown_fields();
+    let codes = data.app_info.unknown_codes();
+    if fields.is_empty() && codes.is_empty() {
+        return;
+    }
+    let pkg = &data.app_info.pkg_name;
+    let mut collected = COLLECTED.lock().unwrap_or_else(|e| e.into_inner());
+    for (key, value) in fields {
+        bump(&mut collected.fields, key, pkg, sample(value));
+    }
+    for (field, raw) in codes {
+        let text = raw.clone();
+        bump(&mut collected.codes, (field, raw), pkg, text);
+    }
+}
+
+/// 取出目前收集到的内容并清空
+///
+/// 返回 (没声明的字段, 不认识的代码取值)
+pub fn take() -> (
+    BTreeMap<String, UnknownEntry>,
+    BTreeMap<(&'static str, String), UnknownEntry>,
+) {
+    let mut collected = COLLECTED.lock().unwrap_or_else(|e| e.into_inner());
+    let taken = std::mem::take(&mut *collected);
+    (taken.fields, taken.codes)
+}
+
+/// 一轮同步结束时调用, 把收集到的内容打到日志里并清空
+pub fn report() {
+    let (fields, codes) = take();
+    if fields.is_empty() && codes.is_empty() {
+        event!(Level::DEBUG, "本轮同步没有发现未知字段");
+        return;
+    }
+    for (key, entry) in fields.iter() {
+        event!(
+            Level::WARN,
+            field = key.as_str(),
+            count = entry.count,
+            pkg = entry.first_pkg.as_str(),
+            "发现未声明的上游字段, 示例值: {}",
+            entry.sample
+        );
+    }
+    for ((field, raw), entry) in codes.iter() {
+        event!(
+            Level::WARN,
+            field = *field,
+            count = entry.count,
+            pkg = entry.first_pkg.as_str(),
+            "发现不认识的代码取值: {raw}"
+        );
+    }
+    event!(
+        Level::WARN,
+        "本轮同步发现 {} 个未声明字段, {} 个不认识的代码取值",
+        fields.len(),
+        codes.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_is_truncated_on_char_boundary() {
+        let short = JsonValue::String("原子化服务".to_string());
+        assert_eq!(sample(&short), "\"原子化服务\"");
+
+        let long = JsonValue::String("字".repeat(SAMPLE_LEN * 2));
+        let text = sample(&long);
+        assert!(text.ends_with("..."));
+        assert_eq!(text.chars().count(), SAMPLE_LEN + 3);
+    }
+}